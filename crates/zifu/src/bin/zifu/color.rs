@@ -0,0 +1,161 @@
+#[cfg(feature = "color")]
+pub use ansi_term::{ANSIGenericString, Colour, Style};
+use once_cell::sync::OnceCell;
+#[cfg(not(feature = "color"))]
+pub use plain::{ANSIGenericString, Colour, Style};
+
+/// Stand-ins for the styles of `ansi_term` that paint nothing (without the `color` feature)
+#[cfg(not(feature = "color"))]
+mod plain {
+    use std::borrow::Cow;
+
+    /// Painted string, which is the string as it is
+    pub type ANSIGenericString<'a, S> = Cow<'a, S>;
+
+    /// Colors of the messages (ignored)
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Colour {
+        Red,
+        Green,
+        Yellow,
+        Blue,
+        Cyan,
+    }
+
+    impl Colour {
+        /// Returns the plain style
+        pub fn bold(self) -> Style {
+            return Style;
+        }
+
+        /// Returns the plain style
+        pub fn normal(self) -> Style {
+            return Style;
+        }
+    }
+
+    /// Style that paints nothing
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct Style;
+
+    impl Style {
+        /// Returns the plain style
+        pub fn new() -> Style {
+            return Style;
+        }
+
+        /// Returns the plain style
+        pub fn dimmed(self) -> Style {
+            return Style;
+        }
+
+        /// Returns the string as it is
+        pub fn paint<'a, I: Into<Cow<'a, str>>>(self, input: I) -> ANSIGenericString<'a, str> {
+            return input.into();
+        }
+    }
+}
+
+/// Stream the messages are written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// When to color the messages (`--color`)
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Only the streams connected to terminals
+    Auto,
+    /// Even if redirected to files or pipes
+    Always,
+    /// Never
+    Never,
+}
+
+impl ColorChoice {
+    /// Returns the choice given by the environment variables (`None` if they don't decide it)
+    ///
+    /// `ZIFU_COLOR` (`always`, `never`, or `auto`) comes first.  Then non-empty `NO_COLOR` disables colors,
+    /// `CLICOLOR_FORCE` other than `0` forces them, and `CLICOLOR=0` disables them.
+    ///
+    /// # Arguments
+    ///
+    /// * `var` - returns the value of an environment variable (e.g. `std::env::var(name).ok()`)
+    pub fn from_env<F: Fn(&str) -> Option<String>>(var: F) -> Option<ColorChoice> {
+        if let Some(choice) =
+            var("ZIFU_COLOR").and_then(|value| clap::ValueEnum::from_str(&value, true).ok())
+        {
+            return Some(choice);
+        }
+        if var("NO_COLOR").is_some_and(|value| !value.is_empty()) {
+            return Some(ColorChoice::Never);
+        }
+        if var("CLICOLOR_FORCE").is_some_and(|value| !value.is_empty() && value != "0") {
+            return Some(ColorChoice::Always);
+        }
+        if var("CLICOLOR").as_deref() == Some("0") {
+            return Some(ColorChoice::Never);
+        }
+        return None;
+    }
+}
+
+static COLOR_CHOICE: OnceCell<ColorChoice> = OnceCell::new();
+
+/// Sets when to color the messages (only the first call takes effect; `auto` if never called)
+///
+/// # Arguments
+///
+/// * `choice` - `--color` or the environment variables (see `ColorChoice::from_env`)
+pub fn set_color_choice(choice: ColorChoice) {
+    let _ = COLOR_CHOICE.set(choice);
+}
+
+/// Returns `true` if the messages written to `stream` are colored
+///
+/// stdout and stderr are checked separately, so `zifu -l a.zip > list.txt` still colors the warnings on the terminal.
+///
+/// # Arguments
+///
+/// * `stream` - stream the messages are written to
+#[cfg(feature = "color")]
+pub fn uses_color(stream: Stream) -> bool {
+    return match COLOR_CHOICE.get().copied().unwrap_or(ColorChoice::Auto) {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => atty::is(match stream {
+            Stream::Stdout => atty::Stream::Stdout,
+            Stream::Stderr => atty::Stream::Stderr,
+        }),
+    };
+}
+
+/// Returns `false` (the messages are never colored without the `color` feature)
+///
+/// # Arguments
+///
+/// * `stream` - stream the messages are written to
+#[cfg(not(feature = "color"))]
+pub fn uses_color(_stream: Stream) -> bool {
+    return false;
+}
+
+/// Returns the style as it is if stdout is colored, otherwise the plain one
+pub fn stdout_style(style: Style) -> Style {
+    return if uses_color(Stream::Stdout) {
+        style
+    } else {
+        Style::new()
+    };
+}
+
+/// Returns the style as it is if stderr is colored, otherwise the plain one
+pub fn stderr_style(style: Style) -> Style {
+    return if uses_color(Stream::Stderr) {
+        style
+    } else {
+        Style::new()
+    };
+}
@@ -0,0 +1,138 @@
+use zifu_core::collisions::NameCollisionError;
+use zifu_core::encryption::UnsupportedEncryptionError;
+use zifu_core::feature_gate::UnsupportedFeatureError;
+
+use crate::{ArchiveError, InvalidArgument};
+
+/// Exit status of zifu (also listed in README)
+///
+/// The values must not be changed because wrappers branch on them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// The conversion is done or the archive is universal (`-c`)
+    Ok = 0,
+    /// An error not covered by the other statuses occurred (e.g. I/O errors)
+    Failure = 1,
+    /// The archive is not universal (`-c`) or some entries would be renamed (`--dry-run`)
+    NeedsFix = 2,
+    /// The input is not a valid ZIP archive
+    ParseError = 3,
+    /// The archive uses a feature not supported by the operation (e.g. encryption)
+    UnsupportedFeature = 4,
+    /// The encoding of file names could not be detected or the names could not be decoded
+    DetectionFailure = 5,
+    /// The archive does not have to be converted
+    NotApplied = 6,
+    /// The user rejected the conversion
+    Rejected = 7,
+    /// The conversion would give multiple entries the same name (`--on-collision abort`)
+    NameCollision = 8,
+    /// Invalid command line arguments (`EX_USAGE` of sysexits.h)
+    Usage = 64,
+}
+
+impl ExitStatus {
+    /// Returns the exit code of the process
+    pub fn code(self) -> i32 {
+        return self as i32;
+    }
+
+    /// Returns the name of the status for machines (`--summary json`)
+    pub fn name(self) -> &'static str {
+        return match self {
+            ExitStatus::Ok => "ok",
+            ExitStatus::Failure => "failure",
+            ExitStatus::NeedsFix => "needs_fix",
+            ExitStatus::ParseError => "parse_error",
+            ExitStatus::UnsupportedFeature => "unsupported_feature",
+            ExitStatus::DetectionFailure => "detection_failure",
+            ExitStatus::NotApplied => "not_applied",
+            ExitStatus::Rejected => "rejected",
+            ExitStatus::NameCollision => "name_collision",
+            ExitStatus::Usage => "usage",
+        };
+    }
+
+    /// Returns the status of a failed archive
+    ///
+    /// # Arguments
+    ///
+    /// * `error` - error returned while processing the archive
+    pub fn from_error(error: &anyhow::Error) -> Self {
+        if error.is::<InvalidArgument>() {
+            return ExitStatus::Usage;
+        }
+        if error.is::<UnsupportedFeatureError>() || error.is::<UnsupportedEncryptionError>() {
+            return ExitStatus::UnsupportedFeature;
+        }
+        if error.is::<NameCollisionError>() {
+            return ExitStatus::NameCollision;
+        }
+        return match error.downcast_ref::<ArchiveError>() {
+            Some(ArchiveError::Parse(_)) => ExitStatus::ParseError,
+            Some(_) => ExitStatus::DetectionFailure,
+            None => ExitStatus::Failure,
+        };
+    }
+
+    /// Returns `true` if the status means that the archive could not be processed
+    pub fn is_error(self) -> bool {
+        return !matches!(
+            self,
+            ExitStatus::Ok | ExitStatus::NeedsFix | ExitStatus::NotApplied
+        );
+    }
+
+    /// Describes the status for humans (batch summary & `--log`)
+    ///
+    /// # Arguments
+    ///
+    /// * `check` - `true` if the archive is checked (`-c`)
+    pub fn describe(self, check: bool) -> &'static str {
+        return match self {
+            ExitStatus::Ok if check => "universal",
+            ExitStatus::Ok => "ok",
+            ExitStatus::NeedsFix if check => "not universal",
+            ExitStatus::NeedsFix => "would change",
+            ExitStatus::NotApplied => "skipped (no need to apply)",
+            ExitStatus::Rejected => "cancelled",
+            _ => "failed",
+        };
+    }
+
+    /// Combines statuses of archives in batch mode
+    ///
+    /// Errors > rejected > needs fix > not applied > ok.  Different errors are combined into `Failure`.
+    ///
+    /// # Arguments
+    ///
+    /// * `statuses` - statuses of the archives
+    pub fn combine(statuses: &[ExitStatus]) -> Self {
+        let severity = |status: ExitStatus| {
+            return match status {
+                ExitStatus::Ok => 0,
+                ExitStatus::NotApplied => 1,
+                ExitStatus::NeedsFix => 2,
+                ExitStatus::Rejected => 3,
+                _ => 4,
+            };
+        };
+        let worst = match statuses
+            .iter()
+            .copied()
+            .max_by_key(|&status| severity(status))
+        {
+            Some(worst) => worst,
+            None => return ExitStatus::Ok,
+        };
+        if worst.is_error()
+            && worst != ExitStatus::Rejected
+            && statuses
+                .iter()
+                .any(|&status| severity(status) == severity(worst) && status != worst)
+        {
+            return ExitStatus::Failure;
+        }
+        return worst;
+    }
+}
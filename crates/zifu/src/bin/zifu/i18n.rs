@@ -0,0 +1,1028 @@
+use std::cell::Cell;
+
+use once_cell::sync::OnceCell;
+
+/// Language of the messages (`--lang`)
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    /// English
+    En,
+    /// Japanese
+    Ja,
+    /// Simplified Chinese
+    #[clap(name = "zh-hans")]
+    ZhHans,
+}
+
+impl Language {
+    /// Returns the language of the locale name (e.g. `ja_JP.UTF-8` or `zh-CN`; English if not translated)
+    ///
+    /// Chinese in Taiwan, Hong Kong, and Macau (Traditional Chinese) is not translated yet.
+    ///
+    /// # Arguments
+    ///
+    /// * `locale` - locale name in POSIX (`ll_CC.encoding`) or BCP 47 (`ll-CC`) form
+    pub fn from_locale_name(locale: &str) -> Language {
+        let locale = locale.to_ascii_lowercase().replace('_', "-");
+        let mut parts = locale.split(['-', '.', '@']);
+        return match parts.next() {
+            Some("ja") => Language::Ja,
+            Some("zh") if !parts.any(|part| matches!(part, "tw" | "hk" | "mo" | "hant")) => {
+                Language::ZhHans
+            }
+            _ => Language::En,
+        };
+    }
+
+    /// Returns the language of the user's locale (`LC_ALL`, `LC_MESSAGES`, or `LANG` first, then that of the OS)
+    ///
+    /// Without the `locale` feature, the language of the OS is not detected (English).
+    pub fn detect() -> Language {
+        for name in ["LC_ALL", "LC_MESSAGES", "LANG"] {
+            match std::env::var(name) {
+                Ok(value) if !value.is_empty() => return Language::from_locale_name(&value),
+                _ => {}
+            }
+        }
+        #[cfg(feature = "locale")]
+        return Language::from_locale_name(locale_config::Locale::user_default().as_ref());
+        #[cfg(not(feature = "locale"))]
+        return Language::En;
+    }
+}
+
+static LANGUAGE: OnceCell<Language> = OnceCell::new();
+
+/// Sets the language of the messages (only the first call takes effect)
+///
+/// # Arguments
+///
+/// * `language` - language of the messages
+pub fn set_language(language: Language) {
+    let _ = LANGUAGE.set(language);
+}
+
+thread_local! {
+    /// Whether the messages are kept in English regardless of the language (see `untranslated`)
+    static UNTRANSLATED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Returns the language set by `set_language` (English if not set or in `untranslated`)
+pub fn language() -> Language {
+    if UNTRANSLATED.with(Cell::get) {
+        return Language::En;
+    }
+    return LANGUAGE.get().copied().unwrap_or(Language::En);
+}
+
+/// Returns the result of `f` with the messages kept in English (e.g. errors written to the session log)
+///
+/// # Arguments
+///
+/// * `f` - function that formats the messages
+pub fn untranslated<T>(f: impl FnOnce() -> T) -> T {
+    let previous = UNTRANSLATED.with(|untranslated| untranslated.replace(true));
+    let result = f();
+    UNTRANSLATED.with(|untranslated| untranslated.set(previous));
+    return result;
+}
+
+/// Japanese translations (keyed by the English messages)
+const JA: &[(&str, &str)] = &[
+    (
+        "All file names are encoded in ASCII or explicitly in UTF-8.",
+        "すべてのファイル名がASCIIまたは明示的にUTF-8でエンコードされています。",
+    ),
+    (
+        "Some files are encoded implicitly in a multibyte encoding.",
+        "一部のファイル名が暗黙的にマルチバイトエンコーディングでエンコードされています。",
+    ),
+    (
+        "Some file names use irregular unicode normalization.",
+        "一部のファイル名が標準的でないUnicode正規化形式を使っています。",
+    ),
+    (
+        "Some files use irregular unicode normalization and others are encoded implicitly in a multibyte encoding.",
+        "一部のファイル名が標準的でないUnicode正規化形式を使っており、他のファイル名は暗黙的にマルチバイトエンコーディングでエンコードされています。",
+    ),
+    (
+        "Almost all devices can decode its file names correctly.",
+        "ほぼすべての環境でファイル名を正しく読めます。",
+    ),
+    (
+        "Apply this tool, or the receiver may not be able to see the correct file names.",
+        "このツールを適用しないと、受け取った人が正しいファイル名を見られないおそれがあります。",
+    ),
+    (
+        "Apply this tool, or the receiver may not deal with the pericular file name normalization.",
+        "このツールを適用しないと、受け取った人が特殊な正規化形式のファイル名を扱えないおそれがあります。",
+    ),
+    (
+        "You do not have to apply this tool.",
+        "このツールを適用する必要はありません。",
+    ),
+    ("Existing.", "終了します。"),
+    ("Warning: {}", "警告: {}"),
+    ("Error: {}", "エラー: {}"),
+    ("Hint: {}", "ヒント: {}"),
+    ("Origin: {}.", "作成元: {}。"),
+    (
+        "Are these file names correct? [Y/n]: ",
+        "これらのファイル名は正しいですか？ [Y/n]: ",
+    ),
+    (
+        "Are these file names correct? [Y/n/e(dit)]: ",
+        "これらのファイル名は正しいですか？ [Y/n/e(編集)]: ",
+    ),
+    (
+        "Detected encoding: {} (confidence: {}%)",
+        "判定したエンコーディング: {}（確信度: {}%）",
+    ),
+    (
+        "The detection is ambiguous.  Other candidates (use -e to choose one; --compare-encodings shows how names look in each):",
+        "判定があいまいです。他の候補（-eで選べます。--compare-encodingsでそれぞれのファイル名を比べられます）:",
+    ),
+    (
+        "Warning: some file names can escape the extraction directory (e.g. ../a.txt). Convert with --sanitize-paths to rewrite them.",
+        "警告: 展開先のフォルダーの外を指すファイル名（例: ../a.txt）があります。--sanitize-pathsを指定して変換すると書き換えられます。",
+    ),
+    (
+        "Warning: some file names cannot be extracted in Windows (e.g. aux.txt). Convert with --fix-reserved-names to rewrite them.",
+        "警告: Windowsで展開できないファイル名（例: aux.txt）があります。--fix-reserved-namesを指定して変換すると書き換えられます。",
+    ),
+    (
+        "Warning: some file names end with a dot or a space, which Windows strips (e.g. a.). Convert with --trim-trailing-dots to trim them.",
+        "警告: Windowsでは取り除かれる、ドットや空白で終わるファイル名（例: a.）があります。--trim-trailing-dotsを指定して変換すると取り除けます。",
+    ),
+    (
+        "Warning: some file names are duplicated (possibly except for case). Convert with --dedupe to remove the duplicates.",
+        "警告: （大文字・小文字の違いを除いて）重複したファイル名があります。--dedupeを指定して変換すると重複を取り除けます。",
+    ),
+    (
+        "Warning: some UTF-8 flags are set only in the local file headers, so extractors that follow the central directory garble the names. Convert to set them in both.",
+        "警告: UTF-8フラグがローカルファイルヘッダーにしか設定されていないため、セントラルディレクトリーを読む展開ソフトではファイル名が文字化けします。変換すると両方に設定されます。",
+    ),
+    (
+        "All the file names are in ASCII or explicitly in UTF-8.",
+        "すべてのファイル名がASCIIまたは明示的にUTF-8です。",
+    ),
+    ("The archive would not change.", "ZIPファイルは変更されません。"),
+    (
+        "{} of {} entries would be renamed.",
+        "{}個のエントリーの名前が変更されます（全{}個中）。",
+    ),
+    (
+        "Extracted {} entries into {}.",
+        "{}個のエントリーを{}に展開しました。",
+    ),
+    (
+        "Created {} with {} entries.",
+        "{}を{}個のエントリーで作成しました。",
+    ),
+    ("No encodings match `{}`.", "`{}`に一致するエンコーディングはありません。"),
+    ("Summary:", "結果:"),
+    (
+        "yes",
+        "はい",
+    ),
+    (
+        "no",
+        "いいえ",
+    ),
+    (
+        "ZIP64: input {}, output {}",
+        "ZIP64: 入力 {}、出力 {}",
+    ),
+    (
+        "Encrypted entries (ZipCrypto / AES) pass-through: {}",
+        "暗号化されたエントリー（ZipCrypto / AES）のそのままのコピー: {}",
+    ),
+    (
+        "Strong / central directory encryption: {}",
+        "Strong Encryption・セントラルディレクトリーの暗号化: {}",
+    ),
+    (
+        "Spanned archives: input {}, output {}",
+        "分割されたZIPファイル: 入力 {}、出力 {}",
+    ),
+    (
+        "Async I/O: {}",
+        "非同期I/O: {}",
+    ),
+    (
+        "Parallel processing: {}",
+        "並列処理: {}",
+    ),
+    (
+        "Features: {}",
+        "機能: {}",
+    ),
+    (
+        "(none)",
+        "（なし）",
+    ),
+    (
+        "Encodings: {}",
+        "エンコーディング: {}",
+    ),
+    (
+        "{} (confidence: {}%)",
+        "{}（確信度: {}%）",
+    ),
+    (
+        "Decoder scores:",
+        "デコーダーのスコア:",
+    ),
+    (
+        "{} ({}; lossy)",
+        "{}（{}、情報が失われます）",
+    ),
+    (
+        "(left out)",
+        "（除外）",
+    ),
+    (
+        "Entry #{}",
+        "エントリー #{}",
+    ),
+    (
+        "raw:",
+        "元のバイト列:",
+    ),
+    (
+        "explicit UTF-8",
+        "明示的なUTF-8",
+    ),
+    (
+        "implicit",
+        "暗黙的",
+    ),
+    (
+        "changed",
+        "変更あり",
+    ),
+    (
+        "unchanged",
+        "変更なし",
+    ),
+    (
+        "final:",
+        "最終結果:",
+    ),
+    (
+        "loss:",
+        "欠落:",
+    ),
+    (
+        "universal",
+        "ユニバーサル",
+    ),
+    (
+        "ok",
+        "成功",
+    ),
+    (
+        "not universal",
+        "ユニバーサルではありません",
+    ),
+    (
+        "would change",
+        "変更されます",
+    ),
+    (
+        "skipped (no need to apply)",
+        "スキップ（適用不要）",
+    ),
+    (
+        "cancelled",
+        "キャンセル",
+    ),
+    (
+        "failed",
+        "失敗",
+    ),
+    (
+        "failed: {}",
+        "失敗: {}",
+    ),
+    (
+        "Fixed the offset of #{}: {} -> {}",
+        "#{}のオフセットを修正しました: {} -> {}",
+    ),
+    (
+        "Recovered {} entries missing from the central directory.",
+        "セントラルディレクトリーにない{}個のエントリーを復元しました。",
+    ),
+    (
+        "Repaired {}",
+        "修復しました: {}",
+    ),
+    (
+        "Encryption: {} entry is {} (passed through untouched when converted).",
+        "暗号化: {}個のエントリーが{}です（変換時はそのままコピーされます）。",
+    ),
+    (
+        "Encryption: {} entries are {} (passed through untouched when converted).",
+        "暗号化: {}個のエントリーが{}です（変換時はそのままコピーされます）。",
+    ),
+    (
+        "Unix permissions of {} entries were normalized.",
+        "{}個のエントリーのUnixパーミッションを正規化しました。",
+    ),
+    (
+        "Rename an entry by \"<index> <new name>\" (empty: done, n: cancel): ",
+        "「<番号> <新しい名前>」でエントリーの名前を変更します（空: 完了、n: キャンセル）: ",
+    ),
+    (
+        "No entry #{}",
+        "エントリー #{} はありません",
+    ),
+    (
+        "Invalid input: {}",
+        "無効な入力: {}",
+    ),
+    (
+        "#{} has different names in the central directory ({}) and the local file header ({}) (use --name-mismatch to choose one)",
+        "#{}の名前がセントラルディレクトリー（{}）とローカルファイルヘッダー（{}）で違います（--name-mismatchでどちらを使うか選べます）",
+    ),
+    (
+        "#{} {} cannot be fully represented in {} (the UTF-8 name is kept in the Unicode Path extra field)",
+        "#{} {}は{}で完全には表せません（UTF-8の名前はUnicode Path拡張フィールドに残します）",
+    ),
+    (
+        "#{} {} is a symbolic link renamed to {} (its target is kept as it is, and links pointing to it may break)",
+        "#{} {}は{}に名前が変わるシンボリックリンクです（リンク先はそのままなので、これを指すリンクが壊れるおそれがあります）",
+    ),
+    (
+        "#{} {} is not extracted: {}",
+        "#{} {}は展開されませんでした: {}",
+    ),
+    (
+        "#{} {}: forbidden characters {} in {} (use --substitute-forbidden-chars to rewrite it)",
+        "#{} {}: 使えない文字{}があります（対象: {}）（--substitute-forbidden-charsを指定すると書き換えられます）",
+    ),
+    (
+        "#{} {}: {} (use {} to rewrite it)",
+        "#{} {}: {}（{}を指定すると書き換えられます）",
+    ),
+    (
+        "--post-check cannot be used for compressed output.",
+        "--post-checkは圧縮した出力には使えません。",
+    ),
+    (
+        "--post-check cannot be used for the output to stdout.",
+        "--post-checkは標準出力への出力には使えません。",
+    ),
+    (
+        "--span-size cannot be used with {}.",
+        "--span-sizeは{}と一緒に使えません。",
+    ),
+    (
+        "--summary cannot be used with the output to stdout.",
+        "--summaryは標準出力への出力と一緒に使えません。",
+    ),
+    (
+        "--tui cannot be used for the output to stdout.",
+        "--tuiは標準出力への出力には使えません。",
+    ),
+    (
+        "a UNIX-like OS",
+        "UNIX系OS",
+    ),
+    (
+        "cannot recover the streamed entry at {} ({})",
+        "{}にあるストリーム形式のエントリー（{}）を復元できません",
+    ),
+    (
+        "cannot repair #{} ({})",
+        "#{}を修復できません（{}）",
+    ),
+    (
+        "created by an unknown archiver",
+        "不明なアーカイバーで作成",
+    ),
+    (
+        "created by ~{}",
+        "~{}で作成",
+    ),
+    (
+        "created by ~{} on {}",
+        "~{}で作成（{}）",
+    ),
+    (
+        "created on ~{}",
+        "~{}で作成",
+    ),
+    (
+        "file names & comments are not encoded in UTF-8 or {}.  Try with -e <another encoding> option.",
+        "ファイル名とコメントがUTF-8でも{}でもエンコードされていません。-e <別のエンコーディング>を指定してやり直してください。",
+    ),
+    (
+        "failed to detect the encoding of file names.  Try with -e <encoding> option.",
+        "ファイル名のエンコーディングを判定できませんでした。-e <エンコーディング>を指定してやり直してください。",
+    ),
+    (
+        "invalid glob pattern: {}",
+        "無効なglobパターン: {}",
+    ),
+    (
+        "left out #{} {} as a duplicate",
+        "重複している#{} {}を除外しました",
+    ),
+    (
+        "likely created on Windows (MS-DOS host)",
+        "おそらくWindowsで作成（MS-DOSホスト）",
+    ),
+    (
+        "likely created on Windows NTFS",
+        "おそらくWindowsのNTFSで作成",
+    ),
+    (
+        "likely created on Windows NTFS at {}",
+        "おそらく{}にWindowsのNTFSで作成",
+    ),
+    (
+        "likely created on a UNIX-like OS",
+        "おそらくUNIX系OSで作成",
+    ),
+    (
+        "no argument <{}> was passed",
+        "引数<{}>が指定されていません",
+    ),
+    (
+        "no such entry in the archive: {}",
+        "アーカイブにないエントリーです: {}",
+    ),
+    (
+        "renamed {} to {} to avoid a case-insensitive duplicate",
+        "大文字・小文字の違いだけの重複を避けるため、{}を{}に名前変更しました",
+    ),
+    (
+        "renamed {} to {} to avoid a collision",
+        "衝突を避けるため、{}を{}に名前変更しました",
+    ),
+    (
+        "some file names cannot be decoded in {} and are shown as raw bytes.  Try with -e <another encoding> option.",
+        "一部のファイル名を{}でデコードできないため、生のバイト列で表示します。-e <別のエンコーディング>を指定してやり直してください。",
+    ),
+    (
+        "specify -i or --output-dir to convert multiple archives.",
+        "複数のアーカイブを変換するには-iまたは--output-dirを指定してください。",
+    ),
+    (
+        "stdin and stdout (-) cannot be used in batch mode.",
+        "一括処理では標準入力と標準出力（-）は使えません。",
+    ),
+    (
+        "the entries are not in a common top-level directory (--strip-top-dir is ignored)",
+        "エントリーが共通の最上位フォルダーに入っていません（--strip-top-dirは無視されます）",
+    ),
+    (
+        "the local header of #{} is not found (recorded at {})",
+        "#{}のローカルヘッダーが見つかりません（記録された位置: {}）",
+    ),
+    (
+        "the span size must be at least {}.",
+        "分割サイズは{}以上にしてください。",
+    ),
+    (
+        "unknown encoding name: {}",
+        "不明なエンコーディング名: {}",
+    ),
+    (
+        "you cannot specify the same file for input and output files.",
+        "入力と出力に同じファイルは指定できません。",
+    ),
+    (
+        "{} (version made by: {})",
+        "{}（version made by: {}）",
+    ),
+    (
+        "{} bytes of junk follow the end of central directory record (ignored and dropped from the output)",
+        "セントラルディレクトリー終端レコードの後に{}バイトの不要なデータがあります（無視して出力から取り除きます）",
+    ),
+    (
+        "{} cannot be used with --to-encoding.",
+        "{}は--to-encodingと一緒に使えません。",
+    ),
+    (
+        "{} is not a directory.",
+        "{}はフォルダーではありません。",
+    ),
+    (
+        "{} is not archived: {}",
+        "{}はアーカイブに追加されませんでした: {}",
+    ),
+    (
+        "{} is not fully supported",
+        "{}は完全には対応していません",
+    ),
+    (
+        "{} {} are duplicated (use --dedupe to remove the duplicates)",
+        "{} {}が重複しています（--dedupeを指定すると重複を取り除けます）",
+    ),
+    (
+        "{} {} are duplicated except for case (use --dedupe to remove the duplicates)",
+        "{} {}が大文字・小文字の違いを除いて重複しています（--dedupeを指定すると重複を取り除けます）",
+    ),
+];
+
+/// Simplified Chinese translations (keyed by the English messages)
+const ZH_HANS: &[(&str, &str)] = &[
+    (
+        "All file names are encoded in ASCII or explicitly in UTF-8.",
+        "所有文件名均以 ASCII 或显式的 UTF-8 编码。",
+    ),
+    (
+        "Some files are encoded implicitly in a multibyte encoding.",
+        "部分文件名以隐式的多字节编码编码。",
+    ),
+    (
+        "Some file names use irregular unicode normalization.",
+        "部分文件名使用了非常规的 Unicode 规范化形式。",
+    ),
+    (
+        "Some files use irregular unicode normalization and others are encoded implicitly in a multibyte encoding.",
+        "部分文件名使用了非常规的 Unicode 规范化形式，其他文件名以隐式的多字节编码编码。",
+    ),
+    (
+        "Almost all devices can decode its file names correctly.",
+        "几乎所有设备都能正确解码其文件名。",
+    ),
+    (
+        "Apply this tool, or the receiver may not be able to see the correct file names.",
+        "请使用本工具处理，否则接收者可能无法看到正确的文件名。",
+    ),
+    (
+        "Apply this tool, or the receiver may not deal with the pericular file name normalization.",
+        "请使用本工具处理，否则接收者可能无法处理特殊规范化形式的文件名。",
+    ),
+    ("You do not have to apply this tool.", "无需使用本工具处理。"),
+    ("Existing.", "退出。"),
+    ("Warning: {}", "警告：{}"),
+    ("Error: {}", "错误：{}"),
+    ("Hint: {}", "提示：{}"),
+    ("Origin: {}.", "来源：{}。"),
+    (
+        "Are these file names correct? [Y/n]: ",
+        "这些文件名正确吗？[Y/n]: ",
+    ),
+    (
+        "Are these file names correct? [Y/n/e(dit)]: ",
+        "这些文件名正确吗？[Y/n/e(编辑)]: ",
+    ),
+    (
+        "Detected encoding: {} (confidence: {}%)",
+        "检测到的编码：{}（置信度：{}%）",
+    ),
+    (
+        "The detection is ambiguous.  Other candidates (use -e to choose one; --compare-encodings shows how names look in each):",
+        "检测结果不确定。其他候选（可用 -e 选择；--compare-encodings 可比较各编码下的文件名）：",
+    ),
+    (
+        "Warning: some file names can escape the extraction directory (e.g. ../a.txt). Convert with --sanitize-paths to rewrite them.",
+        "警告：部分文件名会指向解压目录之外（例如 ../a.txt）。使用 --sanitize-paths 转换即可改写。",
+    ),
+    (
+        "Warning: some file names cannot be extracted in Windows (e.g. aux.txt). Convert with --fix-reserved-names to rewrite them.",
+        "警告：部分文件名无法在 Windows 中解压（例如 aux.txt）。使用 --fix-reserved-names 转换即可改写。",
+    ),
+    (
+        "Warning: some file names end with a dot or a space, which Windows strips (e.g. a.). Convert with --trim-trailing-dots to trim them.",
+        "警告：部分文件名以点或空格结尾，Windows 会将其删除（例如 a.）。使用 --trim-trailing-dots 转换即可删除。",
+    ),
+    (
+        "Warning: some file names are duplicated (possibly except for case). Convert with --dedupe to remove the duplicates.",
+        "警告：存在重复的文件名（可能仅大小写不同）。使用 --dedupe 转换即可删除重复项。",
+    ),
+    (
+        "Warning: some UTF-8 flags are set only in the local file headers, so extractors that follow the central directory garble the names. Convert to set them in both.",
+        "警告：部分 UTF-8 标志仅设置在本地文件头中，依据中央目录解压的软件会显示乱码。转换后两处均会设置。",
+    ),
+    (
+        "All the file names are in ASCII or explicitly in UTF-8.",
+        "所有文件名均为 ASCII 或显式的 UTF-8。",
+    ),
+    ("The archive would not change.", "压缩文件不会改变。"),
+    (
+        "{} of {} entries would be renamed.",
+        "将重命名 {} 个条目（共 {} 个）。",
+    ),
+    ("Extracted {} entries into {}.", "已将 {} 个条目解压到 {}。"),
+    ("Created {} with {} entries.", "已创建 {}，包含 {} 个条目。"),
+    ("No encodings match `{}`.", "没有与 `{}` 匹配的编码。"),
+    ("Summary:", "结果："),
+    (
+        "yes",
+        "是",
+    ),
+    (
+        "no",
+        "否",
+    ),
+    (
+        "ZIP64: input {}, output {}",
+        "ZIP64：输入 {}，输出 {}",
+    ),
+    (
+        "Encrypted entries (ZipCrypto / AES) pass-through: {}",
+        "加密条目（ZipCrypto / AES）原样保留：{}",
+    ),
+    (
+        "Strong / central directory encryption: {}",
+        "强加密 / 中央目录加密：{}",
+    ),
+    (
+        "Spanned archives: input {}, output {}",
+        "分卷压缩文件：输入 {}，输出 {}",
+    ),
+    (
+        "Async I/O: {}",
+        "异步 I/O：{}",
+    ),
+    (
+        "Parallel processing: {}",
+        "并行处理：{}",
+    ),
+    (
+        "Features: {}",
+        "功能：{}",
+    ),
+    (
+        "(none)",
+        "（无）",
+    ),
+    (
+        "Encodings: {}",
+        "编码：{}",
+    ),
+    (
+        "{} (confidence: {}%)",
+        "{}（置信度：{}%）",
+    ),
+    (
+        "Decoder scores:",
+        "解码器得分：",
+    ),
+    (
+        "{} ({}; lossy)",
+        "{}（{}，有损）",
+    ),
+    (
+        "(left out)",
+        "（省略）",
+    ),
+    (
+        "Entry #{}",
+        "条目 #{}",
+    ),
+    (
+        "raw:",
+        "原始字节：",
+    ),
+    (
+        "explicit UTF-8",
+        "显式 UTF-8",
+    ),
+    (
+        "implicit",
+        "隐式",
+    ),
+    (
+        "changed",
+        "已更改",
+    ),
+    (
+        "unchanged",
+        "未更改",
+    ),
+    (
+        "final:",
+        "最终结果：",
+    ),
+    (
+        "loss:",
+        "损失：",
+    ),
+    (
+        "universal",
+        "通用",
+    ),
+    (
+        "ok",
+        "成功",
+    ),
+    (
+        "not universal",
+        "不通用",
+    ),
+    (
+        "would change",
+        "将会更改",
+    ),
+    (
+        "skipped (no need to apply)",
+        "已跳过（无需处理）",
+    ),
+    (
+        "cancelled",
+        "已取消",
+    ),
+    (
+        "failed",
+        "失败",
+    ),
+    (
+        "failed: {}",
+        "失败：{}",
+    ),
+    (
+        "Fixed the offset of #{}: {} -> {}",
+        "已修正 #{} 的偏移量：{} -> {}",
+    ),
+    (
+        "Recovered {} entries missing from the central directory.",
+        "已恢复 {} 个中央目录中缺失的条目。",
+    ),
+    (
+        "Repaired {}",
+        "已修复 {}",
+    ),
+    (
+        "Encryption: {} entry is {} (passed through untouched when converted).",
+        "加密：{} 个条目为 {}（转换时原样保留）。",
+    ),
+    (
+        "Encryption: {} entries are {} (passed through untouched when converted).",
+        "加密：{} 个条目为 {}（转换时原样保留）。",
+    ),
+    (
+        "Unix permissions of {} entries were normalized.",
+        "已规范化 {} 个条目的 Unix 权限。",
+    ),
+    (
+        "Rename an entry by \"<index> <new name>\" (empty: done, n: cancel): ",
+        "输入“<序号> <新名称>”重命名条目（留空：完成，n：取消）：",
+    ),
+    (
+        "No entry #{}",
+        "没有条目 #{}",
+    ),
+    (
+        "Invalid input: {}",
+        "无效的输入：{}",
+    ),
+    (
+        "#{} has different names in the central directory ({}) and the local file header ({}) (use --name-mismatch to choose one)",
+        "#{} 在中央目录（{}）和本地文件头（{}）中的名称不同（可用 --name-mismatch 选择其一）",
+    ),
+    (
+        "#{} {} cannot be fully represented in {} (the UTF-8 name is kept in the Unicode Path extra field)",
+        "#{} {} 无法完全用 {} 表示（UTF-8 名称保留在 Unicode Path 扩展字段中）",
+    ),
+    (
+        "#{} {} is a symbolic link renamed to {} (its target is kept as it is, and links pointing to it may break)",
+        "#{} {} 是被重命名为 {} 的符号链接（其目标保持不变，指向它的链接可能失效）",
+    ),
+    (
+        "#{} {} is not extracted: {}",
+        "#{} {} 未解压：{}",
+    ),
+    (
+        "#{} {}: forbidden characters {} in {} (use --substitute-forbidden-chars to rewrite it)",
+        "#{} {}：包含禁止字符 {}（目标：{}）（使用 --substitute-forbidden-chars 即可改写）",
+    ),
+    (
+        "#{} {}: {} (use {} to rewrite it)",
+        "#{} {}：{}（使用 {} 即可改写）",
+    ),
+    (
+        "--post-check cannot be used for compressed output.",
+        "--post-check 不能用于压缩输出。",
+    ),
+    (
+        "--post-check cannot be used for the output to stdout.",
+        "--post-check 不能用于输出到标准输出。",
+    ),
+    (
+        "--span-size cannot be used with {}.",
+        "--span-size 不能与 {} 同时使用。",
+    ),
+    (
+        "--summary cannot be used with the output to stdout.",
+        "--summary 不能与输出到标准输出同时使用。",
+    ),
+    (
+        "--tui cannot be used for the output to stdout.",
+        "--tui 不能用于输出到标准输出。",
+    ),
+    (
+        "a UNIX-like OS",
+        "类 UNIX 操作系统",
+    ),
+    (
+        "cannot recover the streamed entry at {} ({})",
+        "无法恢复位于 {} 的流式条目（{}）",
+    ),
+    (
+        "cannot repair #{} ({})",
+        "无法修复 #{}（{}）",
+    ),
+    (
+        "created by an unknown archiver",
+        "由未知的压缩软件创建",
+    ),
+    (
+        "created by ~{}",
+        "由 ~{} 创建",
+    ),
+    (
+        "created by ~{} on {}",
+        "由 ~{} 在 {} 上创建",
+    ),
+    (
+        "created on ~{}",
+        "在 ~{} 上创建",
+    ),
+    (
+        "file names & comments are not encoded in UTF-8 or {}.  Try with -e <another encoding> option.",
+        "文件名和注释既不是 UTF-8 编码也不是 {} 编码。请使用 -e <其他编码> 选项重试。",
+    ),
+    (
+        "failed to detect the encoding of file names.  Try with -e <encoding> option.",
+        "无法检测文件名的编码。请使用 -e <编码> 选项重试。",
+    ),
+    (
+        "invalid glob pattern: {}",
+        "无效的 glob 模式：{}",
+    ),
+    (
+        "left out #{} {} as a duplicate",
+        "已排除重复的 #{} {}",
+    ),
+    (
+        "likely created on Windows (MS-DOS host)",
+        "可能创建于 Windows（MS-DOS 主机）",
+    ),
+    (
+        "likely created on Windows NTFS",
+        "可能创建于 Windows NTFS",
+    ),
+    (
+        "likely created on Windows NTFS at {}",
+        "可能于 {} 在 Windows NTFS 上创建",
+    ),
+    (
+        "likely created on a UNIX-like OS",
+        "可能创建于类 UNIX 操作系统",
+    ),
+    (
+        "no argument <{}> was passed",
+        "未传入参数 <{}>",
+    ),
+    (
+        "no such entry in the archive: {}",
+        "压缩文件中没有该条目：{}",
+    ),
+    (
+        "renamed {} to {} to avoid a case-insensitive duplicate",
+        "为避免仅大小写不同的重复，已将 {} 重命名为 {}",
+    ),
+    (
+        "renamed {} to {} to avoid a collision",
+        "为避免冲突，已将 {} 重命名为 {}",
+    ),
+    (
+        "some file names cannot be decoded in {} and are shown as raw bytes.  Try with -e <another encoding> option.",
+        "部分文件名无法用 {} 解码，已显示为原始字节。请使用 -e <其他编码> 选项重试。",
+    ),
+    (
+        "specify -i or --output-dir to convert multiple archives.",
+        "要转换多个压缩文件，请指定 -i 或 --output-dir。",
+    ),
+    (
+        "stdin and stdout (-) cannot be used in batch mode.",
+        "批量模式下不能使用标准输入和标准输出（-）。",
+    ),
+    (
+        "the entries are not in a common top-level directory (--strip-top-dir is ignored)",
+        "条目不在同一个顶层目录中（--strip-top-dir 被忽略）",
+    ),
+    (
+        "the local header of #{} is not found (recorded at {})",
+        "未找到 #{} 的本地文件头（记录位置为 {}）",
+    ),
+    (
+        "the span size must be at least {}.",
+        "分卷大小至少为 {}。",
+    ),
+    (
+        "unknown encoding name: {}",
+        "未知的编码名称：{}",
+    ),
+    (
+        "you cannot specify the same file for input and output files.",
+        "不能将同一个文件同时指定为输入和输出文件。",
+    ),
+    (
+        "{} (version made by: {})",
+        "{}（version made by：{}）",
+    ),
+    (
+        "{} bytes of junk follow the end of central directory record (ignored and dropped from the output)",
+        "中央目录结束记录之后有 {} 字节的多余数据（已忽略并从输出中删除）",
+    ),
+    (
+        "{} cannot be used with --to-encoding.",
+        "{} 不能与 --to-encoding 同时使用。",
+    ),
+    (
+        "{} is not a directory.",
+        "{} 不是目录。",
+    ),
+    (
+        "{} is not archived: {}",
+        "{} 未添加到压缩文件：{}",
+    ),
+    (
+        "{} is not fully supported",
+        "{} 尚未完全支持",
+    ),
+    (
+        "{} {} are duplicated (use --dedupe to remove the duplicates)",
+        "{} {} 重复（使用 --dedupe 即可删除重复项）",
+    ),
+    (
+        "{} {} are duplicated except for case (use --dedupe to remove the duplicates)",
+        "{} {} 重复（仅大小写不同）（使用 --dedupe 即可删除重复项）",
+    ),
+];
+
+/// Returns the translations into `language` keyed by the English messages (empty for English)
+///
+/// # Arguments
+///
+/// * `language` - language of the translations
+pub fn catalog(language: Language) -> &'static [(&'static str, &'static str)] {
+    return match language {
+        Language::En => &[],
+        Language::Ja => JA,
+        Language::ZhHans => ZH_HANS,
+    };
+}
+
+/// Returns the translation of `message` in `language` (`message` itself if not translated)
+///
+/// # Arguments
+///
+/// * `language` - language of the translation
+/// * `message` - message in English
+pub fn translate(language: Language, message: &str) -> &str {
+    return catalog(language)
+        .iter()
+        .find(|(key, _)| *key == message)
+        .map_or(message, |(_, translation)| translation);
+}
+
+/// Returns the translation of `message` in the language set by `set_language` (English if not set)
+///
+/// # Arguments
+///
+/// * `message` - message in English
+pub fn tr(message: &str) -> &str {
+    return translate(language(), message);
+}
+
+/// Returns the translation of `message` in `language` with its `{}`s replaced by `args` in order
+///
+/// The arguments can't be reordered, so translations have to keep the placeholders in the English order
+/// (e.g. `{} of {} entries would be renamed.` → `{}個のエントリーの名前が変更されます（全{}個中）。`).
+/// Only the template is translated, so translate it before formatting and not the formatted message.
+///
+/// # Arguments
+///
+/// * `language` - language of the translation
+/// * `message` - message in English with `{}` placeholders
+/// * `args` - values of the placeholders
+pub fn translatef(language: Language, message: &str, args: &[&dyn std::fmt::Display]) -> String {
+    let mut pieces = translate(language, message).split("{}");
+    let mut result = pieces.next().unwrap_or_default().to_string();
+    for (piece, arg) in pieces.zip(args.iter().map(Some).chain(std::iter::repeat(None))) {
+        if let Some(arg) = arg {
+            result.push_str(&arg.to_string());
+        }
+        result.push_str(piece);
+    }
+    return result;
+}
+
+/// Returns the translation of `message` in the language set by `set_language` with its `{}`s replaced by `args`
+/// (see `translatef`)
+///
+/// # Arguments
+///
+/// * `message` - message in English with `{}` placeholders
+/// * `args` - values of the placeholders
+pub fn trf(message: &str, args: &[&dyn std::fmt::Display]) -> String {
+    return translatef(language(), message, args);
+}
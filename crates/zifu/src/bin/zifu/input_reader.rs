@@ -0,0 +1,63 @@
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+#[cfg(feature = "mmap")]
+use zifu_core::mmap_input::{open_mmap, MmapReader};
+
+use crate::is_stdio;
+use crate::long_path::extended_length_path;
+
+/// Reader for the input archive; stdin is spooled into memory because archives must be seekable
+pub enum InputReader {
+    File(BufReader<File>),
+    Memory(Cursor<Vec<u8>>),
+    #[cfg(feature = "mmap")]
+    Mapped(MmapReader),
+}
+
+impl InputReader {
+    /// Opens the input archive (reads all of stdin if `path` is `-`)
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the input archive or `-`
+    /// * `mmap` - maps the file into memory instead of reading it through `BufReader` (ignored for stdin)
+    pub fn open(path: &Path, mmap: bool) -> std::io::Result<Self> {
+        if is_stdio(path) {
+            let mut buffer = Vec::new();
+            std::io::stdin().lock().read_to_end(&mut buffer)?;
+            return Ok(InputReader::Memory(Cursor::new(buffer)));
+        }
+        let path = extended_length_path(path);
+        #[cfg(feature = "mmap")]
+        if mmap {
+            return Ok(InputReader::Mapped(open_mmap(&path)?));
+        }
+        #[cfg(not(feature = "mmap"))]
+        let _ = mmap;
+        return Ok(InputReader::File(BufReader::new(File::open(path)?)));
+    }
+}
+
+impl Read for InputReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        return match self {
+            InputReader::File(file) => file.read(buf),
+            InputReader::Memory(memory) => memory.read(buf),
+            #[cfg(feature = "mmap")]
+            InputReader::Mapped(mapped) => mapped.read(buf),
+        };
+    }
+}
+
+impl Seek for InputReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        return match self {
+            InputReader::File(file) => file.seek(pos),
+            InputReader::Memory(memory) => memory.seek(pos),
+            #[cfg(feature = "mmap")]
+            InputReader::Mapped(mapped) => mapped.seek(pos),
+        };
+    }
+}
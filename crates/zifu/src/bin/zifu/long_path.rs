@@ -0,0 +1,57 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+/// Prefix of extended-length paths in Windows, which are not limited by `MAX_PATH` (260 characters)
+#[cfg(any(windows, test))]
+const VERBATIM_PREFIX: &str = r"\\?\";
+
+/// Returns the absolute Windows path in the extended-length form (e.g. `C:\a` → `\\?\C:\a` and `\\server\share\a` →
+/// `\\?\UNC\server\share\a`)
+///
+/// Paths already in the form and device paths (`\\.\`) are returned as they are.
+///
+/// # Arguments
+///
+/// * `absolute` - absolute path with backslashes (`std::path::absolute` normalizes `/` & `..`, which the form doesn't)
+#[cfg(any(windows, test))]
+pub fn to_extended_length(absolute: &str) -> Cow<'_, str> {
+    if absolute.starts_with(VERBATIM_PREFIX) || absolute.starts_with(r"\\.\") {
+        return Cow::Borrowed(absolute);
+    }
+    if let Some(unc) = absolute.strip_prefix(r"\\") {
+        return Cow::Owned(format!(r"{}UNC\{}", VERBATIM_PREFIX, unc));
+    }
+    return Cow::Owned(format!("{}{}", VERBATIM_PREFIX, absolute));
+}
+
+/// Returns the path that can be opened, created, and renamed even if it is longer than `MAX_PATH` in Windows
+///
+/// Relative paths are resolved against the current directory first.  The path is returned as it is if it can't be
+/// (e.g. the current directory was removed) and in the other OSes, which have no such limit.
+/// Use this only for the file operations; messages and logs should show the paths given by the user.
+///
+/// # Arguments
+///
+/// * `path` - path given by the user
+#[cfg(windows)]
+pub fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    let absolute = match std::path::absolute(path) {
+        Ok(absolute) => absolute,
+        Err(_) => return Cow::Borrowed(path),
+    };
+    return match absolute.to_str() {
+        Some(absolute) => Cow::Owned(to_extended_length(absolute).into_owned().into()),
+        // Windows paths are UTF-16, so unpaired surrogates are the only possibility
+        None => Cow::Owned(absolute),
+    };
+}
+
+/// Returns the path as it is (only Windows limits the lengths of paths to `MAX_PATH`)
+///
+/// # Arguments
+///
+/// * `path` - path given by the user
+#[cfg(not(windows))]
+pub fn extended_length_path(path: &Path) -> Cow<'_, Path> {
+    return Cow::Borrowed(path);
+}
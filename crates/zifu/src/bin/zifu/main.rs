@@ -0,0 +1,5219 @@
+#![allow(clippy::needless_return)]
+
+use bytesize::ByteSize;
+use clap::builder::TypedValueParser;
+use clap::{CommandFactory, Parser};
+use color::{stderr_style, stdout_style, ANSIGenericString, ColorChoice};
+use exit_status::ExitStatus;
+use filename_decoder::IDecoder;
+use filename_encoder::IEncoder;
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use i18n::{tr, translatef, trf, Language};
+use indicatif::{ProgressBar, ProgressFinish, ProgressStyle};
+use input_reader::InputReader;
+use long_path::extended_length_path;
+use name_editor::{edit_names, Answer};
+use once_cell::sync::Lazy;
+use output_writer::{OutputWriter, PostCompression, SpannedVolumes};
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use replace::replace_file;
+use run_summary::{run_summary_to_json, ArchiveSummary};
+use session_log::SessionLog;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use std::vec;
+use zifu_core::attributes::{normalize_trailing_slash, PermissionNormalization};
+use zifu_core::cancellation::CancellationToken;
+use zifu_core::collisions::{find_collisions, resolve_collisions_by_suffix, NameCollisionError};
+use zifu_core::create::{create_zip_file, CreateOptions};
+use zifu_core::deadline::DeadlineExceeded;
+use zifu_core::duplicates::{deduplicate, find_duplicates, DuplicateKind};
+use zifu_core::encoding_detection::{
+    is_ambiguous, EncodingCandidate, NamePreview, COMPARED_ENCODINGS,
+};
+use zifu_core::encodings::search_encodings;
+use zifu_core::encryption::{EntryEncryption, UnsupportedEncryptionError};
+use zifu_core::extract::{extract_zip_file, ExtractOptions};
+use zifu_core::feature_gate::Operation;
+use zifu_core::metadata::{compression_method_name, EntryMetadata};
+use zifu_core::name_mismatches::NameMismatchPolicy;
+use zifu_core::name_pipeline::{NameTrace, NormalizationForm};
+use zifu_core::name_scoring::DecoderScore;
+use zifu_core::origin_hints::{
+    format_unix_time_utc, format_version_made_by, CreatingTool, CreatorSystem, HostSystem,
+    OriginHints,
+};
+use zifu_core::path_length::find_long_paths;
+use zifu_core::path_safety::find_unsafe_path_reasons;
+use zifu_core::rename_rules::RenameRule;
+use zifu_core::repair::EntryRepair;
+use zifu_core::reserved_names::ReservedNameReason;
+use zifu_core::spanning::SpannedWriter;
+use zifu_core::target_os::{find_illegal_names, TargetOS};
+use zifu_core::timestamps::{format_local_minutes, parse_utc_offset, TimestampCorrection};
+use zifu_core::top_directory::common_top_directory;
+use zifu_core::verification::verify_written_archive_with_deadline;
+use zifu_core::{
+    filename_decoder, filename_encoder, FileNameEncodingType, FileNameEntry, FileNamesDiagnosis,
+};
+use zifu_core::{
+    ConversionOptions, EntryDecision, InputZIPArchive, OutputOptions, OutputReport, RenamePlanEntry,
+};
+
+mod color;
+mod exit_status;
+mod i18n;
+mod input_reader;
+mod long_path;
+mod name_editor;
+mod output_writer;
+mod replace;
+mod run_summary;
+mod session_log;
+#[cfg(feature = "tui")]
+mod tui;
+
+/// Path that stands for stdin (input) or stdout (output)
+const STDIO_PATH: &str = "-";
+
+/// Returns `true` if `path` stands for stdin or stdout
+fn is_stdio(path: &Path) -> bool {
+    return path == Path::new(STDIO_PATH);
+}
+
+/// Returns `path` followed by `suffix` (e.g. a backup path) without decoding the path
+///
+/// # Arguments
+///
+/// * `path` - original path
+/// * `suffix` - string appended to the file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    return PathBuf::from(path);
+}
+
+/// Errors in the arguments (the messages are translated when shown)
+#[derive(thiserror::Error, Debug)]
+enum InvalidArgument {
+    #[error("{}", trf("no argument <{}> was passed", &[.arg_name]))]
+    NoArgument { arg_name: String },
+    #[error("{}", trf("unknown encoding name: {}", &[.encoding_name]))]
+    InvalidEncodingName { encoding_name: String },
+    #[error(
+        "{}",
+        tr("you cannot specify the same file for input and output files.")
+    )]
+    SameInputOutput,
+    #[error("{}", trf("no such entry in the archive: {}", &[.entry]))]
+    NoSuchEntry { entry: String },
+    #[error("{}", trf("invalid glob pattern: {}", &[.pattern]))]
+    InvalidGlobPattern { pattern: String },
+    #[error("{}", tr("--post-check cannot be used for compressed output."))]
+    PostCheckWithCompression,
+    #[error("{}", tr("specify -i or --output-dir to convert multiple archives."))]
+    NoOutputForBatch,
+    #[error("{}", tr("stdin and stdout (-) cannot be used in batch mode."))]
+    StdioInBatch,
+    #[error("{}", tr("--post-check cannot be used for the output to stdout."))]
+    PostCheckWithStdout,
+    #[error("{}", trf("--span-size cannot be used with {}.", &[.option]))]
+    SpanSizeConflict { option: String },
+    #[error("{}", trf("the span size must be at least {}.", &[.minimum]))]
+    SpanSizeTooSmall { minimum: ByteSize },
+    #[error("{}", trf("{} cannot be used with --to-encoding.", &[.option]))]
+    PlanWithReverseConversion { option: String },
+    #[error("{}", tr("--summary cannot be used with the output to stdout."))]
+    SummaryWithStdout,
+    #[error("{}", trf("{} is not a directory.", &[.path]))]
+    NotADirectory { path: String },
+    #[cfg(feature = "tui")]
+    #[error("{}", tr("--tui cannot be used for the output to stdout."))]
+    TuiWithStdout,
+}
+
+/// Errors with their own exit statuses (see `ExitStatus::from_error`)
+#[derive(thiserror::Error, Debug)]
+enum ArchiveError {
+    #[error(transparent)]
+    Parse(anyhow::Error),
+    #[error(
+        "{}",
+        tr("failed to detect the encoding of file names.  Try with -e <encoding> option.")
+    )]
+    NoEncodingCandidate,
+    #[error("{}", trf("file names & comments are not encoded in UTF-8 or {}.  Try with -e <another encoding> option.", &[.encoding]))]
+    UndecodableNames { encoding: String },
+}
+
+/// Archive to be processed and where to write it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    /// Path to the input archive (may not be in UTF-8)
+    pub input: PathBuf,
+    /// Path to the output archive (`None` for in-place conversion or modes that write nothing)
+    pub output: Option<PathBuf>,
+}
+
+impl Job {
+    /// Returns the path to the input archive for messages and logs (invalid characters are replaced with U+FFFD)
+    pub fn input_name(&self) -> Cow<'_, str> {
+        return self.input.to_string_lossy();
+    }
+}
+
+/// Global behavior options for this program
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BehaviorFlags {
+    pub verbose: bool,
+    pub ask_user: bool,
+}
+
+/// Prints messages (`.get_status_primary_message()` & `.get_statius_note()`)
+pub fn print_status_message(diagnosis: &FileNamesDiagnosis) {
+    use color::Colour::*;
+    println!(
+        "{}  {}",
+        stdout_style(
+            (if diagnosis.is_universal_archive() {
+                Green
+            } else {
+                Red
+            })
+            .bold()
+        )
+        .paint(tr(diagnosis.get_status_primary_message())),
+        stdout_style(
+            (if diagnosis.is_universal_archive() {
+                Green
+            } else {
+                Yellow
+            })
+            .bold()
+        )
+        .paint(tr(diagnosis.get_status_note()))
+    );
+}
+
+fn print_you_do_not_have_to_apply_this_tool(diagnosis: &FileNamesDiagnosis) {
+    use color::Colour::*;
+    eprintln!(
+        "{}  {}\n{}  {}",
+        stderr_style(Green.bold()).paint(tr(diagnosis.get_status_primary_message())),
+        stderr_style(Green.bold()).paint(tr(diagnosis.get_status_note())),
+        stderr_style(Green.bold()).paint(tr("You do not have to apply this tool.")),
+        stderr_style(Yellow.bold()).paint(tr("Existing."))
+    );
+}
+
+/// Decodes and prints file names in central directories to stdout
+///
+/// # Arguments
+///
+/// * `cd_entries` - Central directories (contains file names)
+/// * `utf8_decoder` - UTF-8 decoder (used when explicitly encoded in UTF-8)
+/// * `legacy_decoder` - Legacy charset decoder (used otherwise)
+/// * `metadata` - metadata of the entries in the same order, shown before the names in the long format (`None` for the short one)
+fn list_names_in_archive(
+    fie_name_entries: &[FileNameEntry],
+    legacy_decoder: &dyn IDecoder,
+    metadata: Option<&[EntryMetadata]>,
+) {
+    use color::Colour::*;
+    use FileNameEncodingType::*;
+    static REGULAR_UTF8: Lazy<ANSIGenericString<'static, str>> =
+        Lazy::new(|| stdout_style(Green.bold()).paint("REGULAR UTF-8"));
+    static IRREGULAR_UTF8: Lazy<ANSIGenericString<'static, str>> =
+        Lazy::new(|| stdout_style(Red.bold()).paint("IRREGULAR UTF-8"));
+    static ASCII_GREEN: Lazy<ANSIGenericString<'static, str>> =
+        Lazy::new(|| stdout_style(Green.bold()).paint("ASCII"));
+    static GUESSED: Lazy<ANSIGenericString<'static, str>> =
+        Lazy::new(|| stdout_style(Red.bold()).paint("GUESSED"));
+    static UNDECODABLE: Lazy<ANSIGenericString<'static, str>> =
+        Lazy::new(|| stdout_style(Red.bold()).paint("UNDECODABLE"));
+    if metadata.is_some() {
+        println!(
+            "{:>12} {:>12} {:<16} {:<9} {:<8} Name",
+            "Size", "Compressed", "Modified", "Method", "CRC-32"
+        );
+    }
+    for (index, entry) in fie_name_entries.iter().enumerate() {
+        // Columns similar to `unzip -v`, which garbles the names that zifu can decode
+        if let Some(metadata) = metadata.and_then(|metadata| metadata.get(index)) {
+            print!(
+                "{:>12} {:>12} {:<16} {:<9} {:08x} ",
+                metadata.uncompressed_size,
+                metadata.compressed_size,
+                metadata
+                    .modified_local
+                    .map_or("-".to_string(), format_local_minutes),
+                compression_method_name(metadata.compression_method),
+                metadata.crc32
+            );
+        }
+        // Undecodable names are shown as the raw bytes, which can be searched for or passed to `--explain`
+        let name = if entry.lossless {
+            entry.name.clone()
+        } else {
+            escape_bytes(&entry.file_name_raw)
+        };
+        // Directories are shown in blue with a trailing `/` even if their names lack it, and symbolic links in cyan
+        let name = if entry.is_directory {
+            let name = normalize_trailing_slash(&name, true);
+            stdout_style(Blue.bold()).paint(name).to_string()
+        } else if entry.is_symlink {
+            stdout_style(Cyan.bold()).paint(name).to_string()
+        } else {
+            name
+        };
+        // Encrypted entries are copied as they are, but users may want to know which scheme they need to extract them
+        let name = match entry.encryption.as_ref() {
+            Some(encryption) => format!(
+                "{} {}",
+                name,
+                stdout_style(Yellow.normal()).paint(format!("({})", encryption.description()))
+            ),
+            None => name,
+        };
+        match entry.encoding_type {
+            ExplicitRegularUTF8 | ExplicitIrregularUTF8 if !entry.lossless => println!(
+                "{} {}:{}",
+                stdout_style(Red.bold()).paint("UTF-8"),
+                &*UNDECODABLE,
+                name
+            ),
+            ImplicitNonASCII if !entry.lossless => println!(
+                "{} {}:{}",
+                stdout_style(Red.bold()).paint(legacy_decoder.encoding_name()),
+                &*UNDECODABLE,
+                name
+            ),
+            ExplicitRegularUTF8 => println!("{}:{}", &*REGULAR_UTF8, name),
+            ExplicitIrregularUTF8 => println!("{}:{}", &*IRREGULAR_UTF8, name),
+            ImplicitASCII => println!("{}:{}", &*ASCII_GREEN, name),
+            ImplicitNonASCII => println!(
+                "{} {}:{}",
+                stdout_style(Red.bold()).paint(legacy_decoder.encoding_name()),
+                &*GUESSED,
+                name
+            ),
+        }
+    }
+}
+
+/// Returns the translated summary of the archiver & OS that likely created the archive
+/// (see `OriginHints::creation_summary`; `None` if the archive has no entries)
+///
+/// # Arguments
+///
+/// * `hints` - hints on the origin of the archive
+fn origin_summary(hints: &OriginHints) -> Option<String> {
+    let version_made_by = format_version_made_by(hints.version_made_by?);
+    let tool = hints.likely_tool();
+    let os = match (tool, hints.likely_creator()) {
+        (Some(CreatingTool::MacArchiveUtility), _) => Some("macOS"),
+        (_, CreatorSystem::Windows) => Some("Windows"),
+        (_, CreatorSystem::Unix) => Some(tr("a UNIX-like OS")),
+        (_, CreatorSystem::Unknown) => None,
+    };
+    let origin = match (tool, os) {
+        (Some(tool), Some(os)) => trf("created by ~{} on {}", &[&tool.name(), &os]),
+        (Some(tool), None) => trf("created by ~{}", &[&tool.name()]),
+        (None, Some(os)) => trf("created on ~{}", &[&os]),
+        (None, None) => tr("created by an unknown archiver").to_string(),
+    };
+    return Some(trf(
+        "{} (version made by: {})",
+        &[&origin, &version_made_by],
+    ));
+}
+
+/// Returns the translated description of the hints on the origin (see `OriginHints::description`)
+///
+/// # Arguments
+///
+/// * `hints` - hints on the origin of the archive
+fn origin_description(hints: &OriginHints) -> Option<String> {
+    return match hints.likely_creator() {
+        CreatorSystem::Windows if hints.n_ntfs_extra_fields > 0 => {
+            Some(match hints.latest_ntfs_modification {
+                Some(modified) => trf(
+                    "likely created on Windows NTFS at {}",
+                    &[&format_unix_time_utc(modified)],
+                ),
+                None => tr("likely created on Windows NTFS").to_string(),
+            })
+        }
+        CreatorSystem::Windows => Some(tr("likely created on Windows (MS-DOS host)").to_string()),
+        CreatorSystem::Unix => Some(tr("likely created on a UNIX-like OS").to_string()),
+        CreatorSystem::Unknown => None,
+    };
+}
+
+/// Prints the origin of the archive to stderr and the list of its entries to stdout (`-l`)
+///
+/// # Arguments
+///
+/// * `input_zip_file` - archive to be listed
+/// * `legacy_decoder` - decoder for the implicitly-encoded file names
+/// * `cli_options` - `-ll` shows the sizes, times, etc. too, and `--format tsv`, `csv`, & `ndjson` print them in the format
+fn print_list(
+    input_zip_file: &InputZIPArchive<InputReader>,
+    legacy_decoder: &dyn IDecoder,
+    cli_options: &CLIOptions,
+) -> std::io::Result<()> {
+    // stdout is kept for the names so that scripts can parse them
+    if let Some(summary) = origin_summary(&input_zip_file.origin_hints()) {
+        eprintln!("{}", trf("Origin: {}.", &[&summary]));
+    }
+    // Neither the names nor the metadata are collected so that huge archives don't double the memory usage
+    if cli_options.format == OutputFormat::Ndjson {
+        let stdout = std::io::stdout();
+        let mut writer = BufWriter::new(stdout.lock());
+        write_entries_ndjson(
+            &mut writer,
+            input_zip_file
+                .entries(legacy_decoder)
+                .zip(input_zip_file.entries_metadata()),
+            legacy_decoder,
+        )?;
+        return writer.flush();
+    }
+    let metadata = input_zip_file.entries_metadata().collect::<Vec<_>>();
+    let entries = input_zip_file.get_file_names_list(legacy_decoder);
+    if cli_options.format.lists_entries() {
+        print!(
+            "{}",
+            entries_to_table(&entries, &metadata, legacy_decoder, cli_options.format)
+        );
+        return Ok(());
+    }
+    list_names_in_archive(
+        &entries,
+        legacy_decoder,
+        if cli_options.lists_long() {
+            Some(&metadata)
+        } else {
+            None
+        },
+    );
+    return Ok(());
+}
+
+/// Prints the version (and what this build supports if `verbose`) to stdout
+///
+/// # Arguments
+///
+/// * `verbose` - also prints the capabilities of `zifu_core`
+fn print_version(verbose: bool) {
+    print!("{}", CLIOptions::command().render_version());
+    if !verbose {
+        return;
+    }
+    let capabilities = zifu_core::capabilities();
+    let yes_no = |supported: bool| tr(if supported { "yes" } else { "no" });
+    println!("zifu_core {}", capabilities.version);
+    println!(
+        "{}",
+        trf(
+            "ZIP64: input {}, output {}",
+            &[
+                &yes_no(capabilities.zip64_read),
+                &yes_no(capabilities.zip64_write)
+            ]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Encrypted entries (ZipCrypto / AES) pass-through: {}",
+            &[&yes_no(capabilities.encryption_pass_through)]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Strong / central directory encryption: {}",
+            &[&yes_no(capabilities.strong_encryption)]
+        )
+    );
+    println!(
+        "{}",
+        trf(
+            "Spanned archives: input {}, output {}",
+            &[
+                &yes_no(capabilities.spanned_input),
+                &yes_no(capabilities.spanned_output)
+            ]
+        )
+    );
+    println!("WebAssembly: {}", yes_no(capabilities.wasm));
+    println!(
+        "{}",
+        trf("Async I/O: {}", &[&yes_no(capabilities.async_io)])
+    );
+    println!(
+        "{}",
+        trf("Parallel processing: {}", &[&yes_no(capabilities.parallel)])
+    );
+    println!(
+        "{}",
+        trf(
+            "Features: {}",
+            &[&if capabilities.features.is_empty() {
+                tr("(none)").to_string()
+            } else {
+                capabilities.features.join(", ")
+            }]
+        )
+    );
+    println!(
+        "{}",
+        trf("Encodings: {}", &[&capabilities.encodings.join(", ")])
+    );
+}
+
+/// Prints the encodings that can be passed to `-e` grouped by language to stdout
+///
+/// # Arguments
+///
+/// * `query` - prints only the encodings containing it (all if empty)
+fn print_encodings(query: &str) {
+    let encodings = search_encodings(query);
+    if encodings.is_empty() {
+        eprintln!("{}", trf("No encodings match `{}`.", &[&query]));
+        return;
+    }
+    let mut group = None;
+    for encoding in encodings.iter() {
+        if group != Some(encoding.group) {
+            group = Some(encoding.group);
+            println!("{}:", encoding.group.description());
+        }
+        println!("  {} ({})", encoding.name, encoding.labels.join(", "));
+    }
+}
+
+/// Returns a progress bar (on stderr) of writing the output archive with throughput and ETA
+///
+/// It is cleared when dropped.
+fn create_progress_bar() -> ProgressBar {
+    return ProgressBar::new(0)
+        .with_style(
+            ProgressStyle::with_template(
+                "[{bar:40}] {bytes}/{total_bytes} ({binary_bytes_per_sec}, ETA {eta})",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        )
+        .with_finish(ProgressFinish::AndClear);
+}
+
+/// Prints the statistically detected encoding (and the runner-ups if the detection is ambiguous) to stderr
+///
+/// # Arguments
+///
+/// * `candidates` - candidates sorted by confidence (must not be empty)
+fn print_detected_encoding_candidates(candidates: &[EncodingCandidate]) {
+    use color::Colour::*;
+    let winner = &candidates[0];
+    eprintln!(
+        "{}",
+        trf(
+            "Detected encoding: {} (confidence: {}%)",
+            &[
+                &stderr_style(Green.bold()).paint(winner.decoder.encoding_name()),
+                &format!("{:.0}", winner.confidence * 100.0)
+            ]
+        )
+    );
+    if is_ambiguous(candidates) {
+        eprintln!(
+            "{}",
+            stderr_style(Yellow.bold())
+                .paint(tr("The detection is ambiguous.  Other candidates (use -e to choose one; --compare-encodings shows how names look in each):"))
+        );
+        for candidate in candidates.iter().skip(1) {
+            eprintln!(
+                "  {}",
+                trf(
+                    "{} (confidence: {}%)",
+                    &[
+                        &candidate.decoder.encoding_name(),
+                        &format!("{:.0}", candidate.confidence * 100.0)
+                    ]
+                )
+            );
+        }
+    }
+}
+
+/// Prints the decoders ranked by `--scoring`
+///
+/// # Arguments
+///
+/// * `ranking` - decoders sorted by score (descending)
+fn print_decoder_ranking(ranking: &[DecoderScore]) {
+    eprintln!("{}", tr("Decoder scores:"));
+    for score in ranking.iter() {
+        if score.lossless {
+            eprintln!("  {} ({:.2})", score.encoding_name, score.score);
+        } else {
+            eprintln!(
+                "  {}",
+                trf(
+                    "{} ({}; lossy)",
+                    &[&score.encoding_name, &format!("{:.2}", score.score)]
+                )
+            );
+        }
+    }
+}
+
+/// Prints how the file names are decoded in each candidate encoding (`--compare-encodings`)
+///
+/// Names that cannot be decoded without errors are dimmed.
+///
+/// # Arguments
+///
+/// * `previews` - names decoded in each of `decoders`
+/// * `decoders` - candidate decoders
+fn print_name_previews(previews: &[NamePreview], decoders: &[&dyn filename_decoder::IDecoder]) {
+    if previews.is_empty() {
+        println!(
+            "{}",
+            tr("All the file names are in ASCII or explicitly in UTF-8.")
+        );
+        return;
+    }
+    let width = decoders
+        .iter()
+        .map(|decoder| decoder.encoding_name().len())
+        .max()
+        .unwrap_or(0);
+    for preview in previews.iter() {
+        println!("#{} ({})", preview.index, escape_bytes(&preview.raw));
+        for (decoder, decoded) in decoders.iter().zip(preview.decoded.iter()) {
+            let style = if decoded.lossless {
+                color::Style::new()
+            } else {
+                color::Style::new().dimmed()
+            };
+            println!(
+                "  {:width$}  {}",
+                decoder.encoding_name(),
+                stdout_style(style).paint(&decoded.name),
+                width = width
+            );
+        }
+    }
+}
+
+/// Returns the descriptions of the encryption schemes in the archive with the numbers of the entries using them
+///
+/// # Arguments
+///
+/// * `encryptions` - encryption scheme of each entry (`None` if not encrypted)
+fn summarize_encryptions<I: Iterator<Item = Option<EntryEncryption>>>(
+    encryptions: I,
+) -> Vec<(String, usize)> {
+    let mut summary: Vec<(String, usize)> = vec![];
+    for encryption in encryptions.flatten() {
+        let description = encryption.description();
+        match summary.iter_mut().find(|(known, _)| *known == description) {
+            Some((_, count)) => *count += 1,
+            None => summary.push((description, 1)),
+        }
+    }
+    return summary;
+}
+
+/// Returns the warning with its `{}`s replaced by `args` in `language` (e.g. `Warning: 4 bytes of junk follow ...`)
+///
+/// # Arguments
+///
+/// * `language` - language of the warning
+/// * `message` - warning in English with `{}` placeholders
+/// * `args` - values of the placeholders
+fn format_warning(language: Language, message: &str, args: &[&dyn std::fmt::Display]) -> String {
+    return translatef(
+        language,
+        "Warning: {}",
+        &[&translatef(language, message, args)],
+    );
+}
+
+/// Prints the warning unless `silent` (`-s` or `-q`) and writes it to the session log (always in English)
+///
+/// # Arguments
+///
+/// * `silent` - doesn't print the warning
+/// * `job` - archive the warning is about
+/// * `log` - session log
+/// * `message` - warning in English with `{}` placeholders (translated before they are replaced)
+/// * `args` - values of the placeholders
+fn warn(
+    silent: bool,
+    job: &Job,
+    log: &mut SessionLog,
+    message: &str,
+    args: &[&dyn std::fmt::Display],
+) -> anyhow::Result<()> {
+    if !silent {
+        eprintln!("{}", format_warning(i18n::language(), message, args));
+    }
+    log.write(&format!(
+        "[{}] {}",
+        job.input.display(),
+        format_warning(Language::En, message, args)
+    ))?;
+    return Ok(());
+}
+
+/// Warns about an entry name that cannot be extracted safely (see `warn`)
+///
+/// # Arguments
+///
+/// * `silent` - doesn't print the warning
+/// * `job` - archive the warning is about
+/// * `log` - session log
+/// * `index` - 0-based index of the entry in the central directory
+/// * `name` - decoded name of the entry
+/// * `reasons` - descriptions of the problems
+/// * `option` - option that fixes the problems
+fn warn_name<'a>(
+    silent: bool,
+    job: &Job,
+    log: &mut SessionLog,
+    index: usize,
+    name: &str,
+    reasons: impl Iterator<Item = &'a str>,
+    option: &str,
+) -> anyhow::Result<()> {
+    return warn(
+        silent,
+        job,
+        log,
+        "#{} {}: {} (use {} to rewrite it)",
+        &[
+            &index,
+            &name,
+            &reasons.collect::<Vec<&str>>().join("; "),
+            &option,
+        ],
+    );
+}
+
+/// Returns the description of the changes to an entry made by `--repair-metadata`
+/// (e.g. `#3: CRC-32 00000000 -> 3610A686, data descriptor dropped`)
+fn describe_repair(repair: &EntryRepair) -> String {
+    let mut changes = vec![];
+    if let Some((old, new)) = repair.crc32 {
+        changes.push(format!("CRC-32 {:08X} -> {:08X}", old, new));
+    }
+    if let Some((old, new)) = repair.uncompressed_size {
+        changes.push(format!("uncompressed size {} -> {}", old, new));
+    }
+    if repair.data_descriptor_dropped {
+        changes.push("data descriptor dropped".to_string());
+    }
+    return format!("#{}: {}", repair.index, changes.join(", "));
+}
+
+/// Returns the byte sequence with non-printable-ASCII bytes escaped as `\xHH`
+fn escape_bytes(bytes: &[u8]) -> String {
+    return bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                (b as char).to_string()
+            } else {
+                format!("\\x{:02X}", b)
+            }
+        })
+        .collect();
+}
+
+/// Returns the rename plan as a colorless unified-diff-like text (`--format diff`)
+///
+/// Only the entries to be renamed are included.  Keep the format stable; scripts and reviews depend on it.
+///
+/// # Arguments
+///
+/// * `input` - path to the archive
+/// * `plan` - rename plan of the archive
+/// * `entry_selector` - entries to be converted (`--include` & `--exclude`)
+fn rename_plan_to_diff(
+    input: &str,
+    plan: &[RenamePlanEntry],
+    entry_selector: &EntrySelector,
+) -> String {
+    let mut diff = format!("--- {}\n+++ {}\n", input, input);
+    for entry in plan
+        .iter()
+        .filter(|entry| entry.changes && entry_selector.is_selected(&entry.current_name))
+    {
+        diff.push_str(&format!(
+            "@@ #{} @@\n- {}\n+ {}\n",
+            entry.index,
+            old_name_in_plan(entry),
+            entry.proposed_name
+        ));
+    }
+    return diff;
+}
+
+/// Returns the field escaped for `--format tsv` or `--format csv`
+///
+/// TSV fields escape tabs, line breaks, and backslashes as `\t`, `\n`, `\r`, and `\\`, so that every line is a row for awk.
+/// CSV fields are quoted as in RFC 4180 if they contain commas, double quotes, or line breaks.
+///
+/// # Arguments
+///
+/// * `field` - field to be escaped
+/// * `format` - `OutputFormat::Tsv` or `OutputFormat::Csv`
+fn escape_table_field(field: &str, format: OutputFormat) -> Cow<'_, str> {
+    if format == OutputFormat::Csv {
+        if !field.contains(&[',', '"', '\r', '\n'][..]) {
+            return Cow::Borrowed(field);
+        }
+        return Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")));
+    }
+    if !field.contains(&['\t', '\r', '\n', '\\'][..]) {
+        return Cow::Borrowed(field);
+    }
+    return Cow::Owned(
+        field
+            .replace('\\', "\\\\")
+            .replace('\t', "\\t")
+            .replace('\r', "\\r")
+            .replace('\n', "\\n"),
+    );
+}
+
+/// Returns the bytes in lowercase hex without separators (e.g. `83652e747874`)
+fn hex_string(bytes: &[u8]) -> String {
+    return bytes.iter().map(|b| format!("{:02x}", b)).collect();
+}
+
+/// Returns the encoding type (e.g. `implicit_non_ascii`) and the encoding (e.g. `Shift_JIS`) of the entry for the machine-readable
+/// formats
+///
+/// # Arguments
+///
+/// * `entry` - file name of the entry
+/// * `legacy_decoder` - decoder used for the implicitly-encoded file names
+fn encoding_columns<'a>(
+    entry: &FileNameEntry,
+    legacy_decoder: &'a dyn IDecoder,
+) -> (&'static str, &'a str) {
+    use FileNameEncodingType::*;
+    return match entry.encoding_type {
+        ExplicitRegularUTF8 => ("explicit_utf8", "UTF-8"),
+        ExplicitIrregularUTF8 => ("explicit_irregular_utf8", "UTF-8"),
+        ImplicitASCII => ("implicit_ascii", "ASCII"),
+        ImplicitNonASCII => ("implicit_non_ascii", legacy_decoder.encoding_name()),
+    };
+}
+
+/// Writes one JSON object per entry and line (`--format ndjson`), as soon as each entry is decoded
+///
+/// The keys are those of the columns of `--format tsv` plus some of the metadata.  Keep them stable; scripts depend on them.
+///
+/// # Arguments
+///
+/// * `writer` - destination of the lines (usually stdout)
+/// * `entries` - file names and metadata of the entries (e.g. `InputZIPArchive::entries` zipped with `entries_metadata`)
+/// * `legacy_decoder` - decoder used for the implicitly-encoded file names
+fn write_entries_ndjson<W: Write, I: Iterator<Item = (FileNameEntry, EntryMetadata)>>(
+    writer: &mut W,
+    entries: I,
+    legacy_decoder: &dyn IDecoder,
+) -> std::io::Result<()> {
+    for (entry, metadata) in entries {
+        let (encoding_type, encoding) = encoding_columns(&entry, legacy_decoder);
+        let object = serde_json::json!({
+            "index": metadata.index,
+            "raw_name_hex": hex_string(&entry.file_name_raw),
+            "name": entry.name,
+            "encoding_type": encoding_type,
+            "encoding": encoding,
+            "lossless": entry.lossless,
+            "is_directory": entry.is_directory,
+            "is_symlink": entry.is_symlink,
+            "encrypted": entry.encryption.is_some(),
+            "size": metadata.uncompressed_size,
+            "compressed_size": metadata.compressed_size,
+            "method": compression_method_name(metadata.compression_method),
+            "crc32": format!("{:08x}", metadata.crc32),
+        });
+        writeln!(writer, "{}", object)?;
+    }
+    return Ok(());
+}
+
+/// Returns the entries as a table with a header row (`--format tsv` or `--format csv`)
+///
+/// The raw names are in lowercase hex so that undecodable ones can be told apart.  Keep the columns stable; scripts
+/// and spreadsheets depend on them.
+///
+/// # Arguments
+///
+/// * `entries` - file names of the entries
+/// * `metadata` - metadata of the entries in the same order
+/// * `legacy_decoder` - decoder used for the implicitly-encoded file names
+/// * `format` - `OutputFormat::Tsv` or `OutputFormat::Csv`
+fn entries_to_table(
+    entries: &[FileNameEntry],
+    metadata: &[EntryMetadata],
+    legacy_decoder: &dyn IDecoder,
+    format: OutputFormat,
+) -> String {
+    let separator = if format == OutputFormat::Csv {
+        ","
+    } else {
+        "\t"
+    };
+    let mut table = [
+        "index",
+        "raw_name_hex",
+        "name",
+        "encoding_type",
+        "encoding",
+        "size",
+        "method",
+    ]
+    .join(separator);
+    table.push('\n');
+    for (index, entry) in entries.iter().enumerate() {
+        let (encoding_type, encoding) = encoding_columns(entry, legacy_decoder);
+        let metadata = metadata.get(index);
+        let fields = [
+            index.to_string(),
+            hex_string(&entry.file_name_raw),
+            entry.name.clone(),
+            encoding_type.to_string(),
+            encoding.to_string(),
+            metadata.map_or(String::new(), |metadata| {
+                metadata.uncompressed_size.to_string()
+            }),
+            metadata.map_or(String::new(), |metadata| {
+                compression_method_name(metadata.compression_method)
+            }),
+        ];
+        let fields: Vec<Cow<str>> = fields
+            .iter()
+            .map(|field| escape_table_field(field, format))
+            .collect();
+        table.push_str(&fields.join(separator));
+        table.push('\n');
+    }
+    return table;
+}
+
+/// Returns the rename plan where the entries excluded by `--include` & `--exclude` keep their names
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive
+/// * `entry_selector` - entries to be converted (`--include` & `--exclude`)
+fn selected_plan(
+    mut plan: Vec<RenamePlanEntry>,
+    entry_selector: &EntrySelector,
+) -> Vec<RenamePlanEntry> {
+    for entry in plan.iter_mut() {
+        if !entry_selector.is_selected(&entry.current_name) {
+            entry.proposed_name = entry.current_name.clone();
+            entry.changes = false;
+        }
+    }
+    return plan;
+}
+
+/// Makes the archive write the given names instead of the converted ones
+///
+/// # Arguments
+///
+/// * `archive` - archive to be converted
+/// * `name_overrides` - new names keyed by the byte sequences of the original names
+fn set_name_overrides<F: std::io::Read + std::io::Seek>(
+    archive: &mut InputZIPArchive<F>,
+    name_overrides: &HashMap<Vec<u8>, String>,
+) {
+    let name_overrides = name_overrides.clone();
+    archive.set_rename_hook(move |raw, _| name_overrides.get(raw).cloned());
+}
+
+/// Returns the number of the entries to be renamed
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive
+/// * `entry_selector` - entries to be converted (`--include` & `--exclude`)
+fn count_renamed_entries(plan: &[RenamePlanEntry], entry_selector: &EntrySelector) -> usize {
+    return plan
+        .iter()
+        .filter(|entry| entry.changes && entry_selector.is_selected(&entry.current_name))
+        .count();
+}
+
+/// Returns the current name of the entry for the rename plan
+///
+/// Implicitly-encoded names look different depending on the receiver, so their bytes are shown instead.
+fn old_name_in_plan(entry: &RenamePlanEntry) -> String {
+    if entry.explicit_utf8 {
+        return entry.current_name.clone();
+    }
+    return escape_bytes(&entry.raw);
+}
+
+/// Prints the rename plan of all the entries and the number of the renamed ones to stdout (`--dry-run`)
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive
+/// * `entry_selector` - entries to be converted (`--include` & `--exclude`)
+fn print_rename_plan(plan: &[RenamePlanEntry], entry_selector: &EntrySelector) {
+    for entry in plan.iter() {
+        if entry.changes && entry_selector.is_selected(&entry.current_name) {
+            if entry.proposed_name.is_empty() {
+                // The top-level directory removed by `--strip-top-dir`
+                println!("* {} {}", old_name_in_plan(entry), tr("(left out)"));
+            } else {
+                println!("* {} -> {}", old_name_in_plan(entry), entry.proposed_name);
+            }
+        } else {
+            println!("  {}", old_name_in_plan(entry));
+        }
+    }
+    let n_renamed = count_renamed_entries(plan, entry_selector);
+    if n_renamed == 0 {
+        println!("{}", tr("The archive would not change."));
+    } else {
+        println!(
+            "{}",
+            trf(
+                "{} of {} entries would be renamed.",
+                &[&n_renamed, &plan.len()]
+            )
+        );
+    }
+}
+
+/// Prints each stage of the conversion of a file name to stdout
+///
+/// # Arguments
+///
+/// * `index` - index of the entry
+/// * `trace` - record of the stages
+/// * `legacy_decoder` - decoder used for the implicitly-encoded name
+fn print_name_trace(index: usize, trace: &NameTrace, legacy_decoder: &dyn IDecoder) {
+    use color::Colour::*;
+    println!("{}", trf("Entry #{}", &[&index]));
+    println!(
+        "  {:<16}{} ({})",
+        tr("raw:"),
+        escape_bytes(&trace.raw),
+        tr(if trace.explicit_utf8 {
+            "explicit UTF-8"
+        } else {
+            "implicit"
+        })
+    );
+    for (i, (stage, name)) in trace.stages.iter().enumerate() {
+        let note = if i == 0 {
+            if trace.explicit_utf8 {
+                "UTF-8".to_string()
+            } else {
+                legacy_decoder.encoding_name().to_string()
+            }
+        } else if trace.is_changed_at(i) {
+            tr("changed").to_string()
+        } else {
+            tr("unchanged").to_string()
+        };
+        println!("  {:<16}{} ({})", format!("{}:", stage.name()), name, note);
+    }
+    println!(
+        "  {:<16}{}",
+        tr("final:"),
+        stdout_style(Green.bold()).paint(trace.final_name())
+    );
+    for loss in trace.losses.iter() {
+        println!(
+            "  {:<16}{}",
+            tr("loss:"),
+            stdout_style(Red.bold()).paint(loss.description())
+        );
+    }
+}
+
+/// Returns the JSON representation of the report on the written archive
+///
+/// # Arguments
+///
+/// * `input` - path to the input archive
+/// * `output` - path to the written archive
+/// * `report` - report returned by the core
+fn report_to_json(input: &Path, output: &Path, report: &OutputReport) -> serde_json::Value {
+    return serde_json::json!({
+        "input": input.to_string_lossy(),
+        "output": output.to_string_lossy(),
+        "entries": report
+            .entries
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "index": entry.index,
+                    "name": entry.file_name(),
+                    "explicit_utf8": entry.explicit_utf8,
+                    "compressed_size": entry.compressed_size,
+                    "sha256": entry.payload_sha256_hex(),
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+}
+
+fn process_answer_default_yes(ans: &str) -> bool {
+    return match ans.chars().next() {
+        Some('n') | Some('N') => false,
+        None | Some(_) => true,
+    };
+}
+
+/// Returns `Ok(false)` if a line starting with `'n'` (or `'N'`) is input from stdin, otherwise `Ok(true)`.
+///
+/// Returns `Err(std::io::Error)` if I/O fails.
+fn ask_default_yes() -> Result<bool, std::io::Error> {
+    let ask_result = (|| {
+        let mut ret = String::new();
+        match std::io::stdin().read_line(&mut ret) {
+            Ok(_) => return Ok(ret),
+            Err(e) => return Err(e),
+        }
+    })()?;
+    return Ok(process_answer_default_yes(&ask_result));
+}
+
+/// Choices of `--normalization`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum NormalizationArg {
+    /// NFC (regular in Windows and Linux)
+    Nfc,
+    /// NFD (HFS+ variant; for macOS)
+    Nfd,
+    /// NFKC (also unifies compatibility characters, e.g. full-width alphabets & half-width katakana)
+    Nfkc,
+    /// Keeps the normalization of decoded names
+    Preserve,
+}
+
+impl From<NormalizationArg> for NormalizationForm {
+    fn from(arg: NormalizationArg) -> Self {
+        return match arg {
+            NormalizationArg::Nfc => NormalizationForm::NFC,
+            NormalizationArg::Nfd => NormalizationForm::NFD,
+            NormalizationArg::Nfkc => NormalizationForm::NFKC,
+            NormalizationArg::Preserve => NormalizationForm::Preserve,
+        };
+    }
+}
+
+/// Format of the output for `--format`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// Human-readable messages (converts the archive)
+    Text,
+    /// Colorless unified-diff-like rename plan (`- old` / `+ new`; writes nothing)
+    Diff,
+    /// Tab-separated list of the entries (writes nothing)
+    Tsv,
+    /// Comma-separated list of the entries (writes nothing)
+    Csv,
+    /// One JSON object per entry and line, written as the entries are decoded (writes nothing)
+    Ndjson,
+}
+
+impl OutputFormat {
+    /// Returns `true` if the format lists the entries (`tsv`, `csv`, or `ndjson`)
+    fn lists_entries(&self) -> bool {
+        return matches!(
+            self,
+            OutputFormat::Tsv | OutputFormat::Csv | OutputFormat::Ndjson
+        );
+    }
+}
+
+/// What to do when the conversion would give multiple entries the same name (`--on-collision`)
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum CollisionStrategy {
+    /// Stops without writing anything
+    Abort,
+    /// Appends ` (2)`, ` (3)`, ... to the names of the 2nd and later entries
+    Suffix,
+}
+
+/// Which name to use when the local file header and the central directory disagree (`--name-mismatch`)
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum NameMismatchArg {
+    /// Uses the name in the central directory
+    Cd,
+    /// Uses the name in the local file header
+    Local,
+    /// Stops without writing anything
+    Error,
+}
+
+impl From<NameMismatchArg> for NameMismatchPolicy {
+    fn from(arg: NameMismatchArg) -> Self {
+        return match arg {
+            NameMismatchArg::Cd => NameMismatchPolicy::PreferCentralDirectory,
+            NameMismatchArg::Local => NameMismatchPolicy::PreferLocalHeader,
+            NameMismatchArg::Error => NameMismatchPolicy::Error,
+        };
+    }
+}
+
+/// Choices of `--target-os`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum TargetOSArg {
+    /// Forbids <>:"\|?* and control characters
+    Windows,
+    /// Forbids : and NUL
+    Macos,
+    /// Forbids NUL
+    Linux,
+}
+
+impl From<TargetOSArg> for TargetOS {
+    fn from(arg: TargetOSArg) -> Self {
+        return match arg {
+            TargetOSArg::Windows => TargetOS::Windows,
+            TargetOSArg::Macos => TargetOS::MacOS,
+            TargetOSArg::Linux => TargetOS::Linux,
+        };
+    }
+}
+
+/// Choices of `--made-by`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum MadeByArg {
+    /// MS-DOS (0); external attributes are read as FAT attributes
+    Dos,
+    /// Unix (3); external attributes have the permissions in the upper 16 bits
+    Unix,
+    /// Windows NTFS (10)
+    Ntfs,
+    /// macOS (19)
+    Macos,
+}
+
+impl From<MadeByArg> for HostSystem {
+    fn from(arg: MadeByArg) -> Self {
+        return match arg {
+            MadeByArg::Dos => HostSystem::MSDOS,
+            MadeByArg::Unix => HostSystem::Unix,
+            MadeByArg::Ntfs => HostSystem::NTFS,
+            MadeByArg::Macos => HostSystem::MacOS,
+        };
+    }
+}
+
+/// Format of the summary of the run for `--summary`
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum SummaryFormat {
+    /// A line of JSON with the status and what was detected & done for each archive
+    Json,
+}
+
+/// Selects the entries to be converted by `--include` & `--exclude`
+#[derive(Debug, Clone)]
+pub struct EntrySelector {
+    /// `None` if `--include` is not specified (all the entries are included)
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl EntrySelector {
+    /// Returns `true` if the entry named `name` should be converted
+    pub fn is_selected(&self, name: &str) -> bool {
+        return self
+            .include
+            .as_ref()
+            .is_none_or(|include| include.is_match(name))
+            && !self.exclude.is_match(name);
+    }
+}
+
+/// Returns the name of the value of an option in the command line (e.g. `nfd`)
+fn value_name<T: clap::ValueEnum>(value: T) -> Option<String> {
+    return value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string());
+}
+
+/// Parses the value of `--source-utc-offset` & `--target-utc-offset` into seconds
+fn parse_utc_offset_arg(offset: &str) -> Result<i32, String> {
+    return parse_utc_offset(offset)
+        .ok_or_else(|| format!("`{}` is not a UTC offset like +09:00 or -05:00", offset));
+}
+
+/// Validates the value of `--rename` (substitution like `s/old/new/`)
+fn parse_rename_arg(expression: &str) -> Result<String, String> {
+    return RenameRule::parse(expression)
+        .map(|_| expression.to_string())
+        .map_err(|e| e.to_string());
+}
+
+/// Validates the value of `--add-top-dir` (non-empty safe relative path)
+fn parse_top_dir_arg(directory: &str) -> Result<String, String> {
+    let directory = directory.trim_end_matches('/');
+    if directory.is_empty() || !find_unsafe_path_reasons(directory).is_empty() {
+        return Err(format!("`{}` is not a safe relative path", directory));
+    }
+    return Ok(directory.to_string());
+}
+
+/// Parses the value of `--file-mode` & `--dir-mode` (octal permission bits like 644)
+fn parse_permissions_arg(mode: &str) -> Result<u32, String> {
+    return u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|mode| *mode <= 0o777)
+        .ok_or_else(|| format!("`{}` is not octal permission bits like 644", mode));
+}
+
+/// Formats a UTC offset in seconds as `+hh:mm`
+fn format_utc_offset(offset: i32) -> String {
+    return format!(
+        "{}{:02}:{:02}",
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 3600,
+        offset.abs() % 3600 / 60
+    );
+}
+
+/// Compiles glob patterns into a set
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet, InvalidArgument> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(
+            GlobBuilder::new(pattern)
+                .literal_separator(true)
+                .build()
+                .map_err(|_| InvalidArgument::InvalidGlobPattern {
+                    pattern: pattern.to_string(),
+                })?,
+        );
+    }
+    return builder
+        .build()
+        .map_err(|_| InvalidArgument::InvalidGlobPattern {
+            pattern: patterns.join(" "),
+        });
+}
+
+/// Subcommands other than the conversion (`zifu <COMMAND> ...`)
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Extracts the entries of ARCHIVE into DEST under the corrected file names
+    Extract(ExtractArgs),
+    /// Creates OUTPUT from the contents of SOURCE with universal (UTF-8 & NFC) file names
+    Create(CreateArgs),
+}
+
+/// Arguments of `zifu extract`
+#[derive(clap::Args, Debug)]
+struct ExtractArgs {
+    #[clap(help = "Path to the ZIP file to extract")]
+    archive: String,
+    #[clap(help = "Directory to extract the entries into (created if it doesn't exist)")]
+    dest: String,
+    #[clap(
+        short,
+        long,
+        value_name = "ENCODING",
+        help = "Encoding of the file names not explicitly encoded in UTF-8 (detected if omitted)"
+    )]
+    encoding: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORM",
+        default_value = "nfc",
+        help = "Unicode normalization form of the extracted file names."
+    )]
+    normalization: NormalizationArg,
+    #[clap(
+        long,
+        help = "Overwrites existing files instead of skipping the entries."
+    )]
+    overwrite: bool,
+}
+
+/// Arguments of `zifu create`
+#[derive(clap::Args, Debug)]
+struct CreateArgs {
+    #[clap(help = "Path to the new ZIP file")]
+    output: String,
+    #[clap(help = "Directory whose contents are archived")]
+    source: String,
+    #[clap(
+        long,
+        help = "Adds the Unicode Path extra fields (0x7075) for extractors that ignore the UTF-8 flag."
+    )]
+    unicode_path_fields: bool,
+}
+
+#[derive(Parser, Debug)]
+#[clap(
+    name = "ZIP File Names to UTF-8 (ZIFU)",
+    version,
+    author,
+    about,
+    disable_version_flag = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true,
+    // override_help = crate_description!()
+)]
+struct CLIOptions {
+    #[clap(
+        required_unless_present_any = ["show_version", "list_encodings"],
+        default_value = "",
+        hide_default_value = true,
+        // `PathBuf`'s own parser rejects the empty default
+        value_parser = clap::builder::OsStringValueParser::new().map(PathBuf::from),
+        help = "Path to the ZIP file where you want to change the encoding of the file name to UTF-8 (- for stdin)"
+    )]
+    input: PathBuf,
+    #[clap(help = "Path to output (- for stdout; or the 2nd input in batch mode)")]
+    output: Option<PathBuf>,
+    #[clap(help = "More inputs (batch mode only)")]
+    more_inputs: Vec<PathBuf>,
+    #[clap(
+        short,
+        long,
+        help = "Processes all the paths as inputs with the same options. (implied by -i, --output-dir, or 3 or more paths)"
+    )]
+    batch: bool,
+    #[clap(
+        long,
+        value_name = "DIR",
+        conflicts_with = "in_place",
+        help = "Writes the converted archives into DIR with the same file names (batch mode)."
+    )]
+    output_dir: Option<PathBuf>,
+    #[clap(short = 'V', long = "version", help = "Print version")]
+    show_version: bool,
+    #[clap(
+        short = 'v',
+        long,
+        action = clap::ArgAction::Count,
+        help = "Logs parsing, detection, conversion, and output to stderr (-v: steps, -vv: decoders & headers, -vvv: every entry).  With --version, also shows what this build supports (e.g. ZIP64 and encodings)."
+    )]
+    verbose: u8,
+    #[clap(
+        long,
+        value_name = "QUERY",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "",
+        help = "Lists the encodings that can be passed to -e (only those containing QUERY like jis or cyrillic if given) and exits."
+    )]
+    list_encodings: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "LANG",
+        help = "Language of the messages (detected from the locale by default; also set by ZIFU_LANG).  The help, the file names in the messages, and the details from zifu_core (e.g. why an entry is skipped) are not translated."
+    )]
+    lang: Option<Language>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        help = "When to color the messages (default: auto, i.e. only stdout and stderr connected to terminals; also set by ZIFU_COLOR, NO_COLOR, CLICOLOR_FORCE, and CLICOLOR)."
+    )]
+    color: Option<ColorChoice>,
+    #[clap(
+        short,
+        long,
+        help = "Finds out if its file names are encoded in UTF-8."
+    )]
+    check: bool,
+    #[clap(
+        short,
+        long,
+        action = clap::ArgAction::Count,
+        help = "Displays the list of file names in the ZIP archive.  Repeat it (-ll) for the long format."
+    )]
+    list: u8,
+    #[clap(
+        long,
+        requires = "list",
+        help = "Shows the sizes, modification times, compression methods, and CRC-32 of the entries in the list."
+    )]
+    long: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "10",
+        help = "Shows how the first N (Default: 10) non-ASCII file names are decoded in each candidate encoding side by side."
+    )]
+    compare_encodings: Option<usize>,
+    #[clap(
+        long,
+        value_name = "ENTRY",
+        help = "Shows how the name of ENTRY (index from 0 or file name) is converted stage by stage."
+    )]
+    explain: Option<String>,
+    #[clap(short, long, help = "Don't show any messages. (implies -y)")]
+    silent: bool,
+    #[clap(short, long, help = "Don't show any messages. (implies -y)")]
+    quiet: bool,
+    #[clap(
+        short,
+        long,
+        value_name = "ENCODING",
+        help = "Specifies the encoding of file names in the ZIP archive. (Default: $ZIFU_ENCODING if set)"
+    )]
+    encoding: Option<String>,
+    #[clap(
+        short,
+        long,
+        conflicts_with = "encoding",
+        help = "Detects the encoding of file names statistically instead of using that of the current locale."
+    )]
+    auto_detect: bool,
+    #[clap(
+        long,
+        help = "Picks the decoder whose results look the most plausible (e.g. no box drawing or halfwidth katakana garbage) instead of the first one that decodes all the names."
+    )]
+    scoring: bool,
+    #[clap(
+        short,
+        long,
+        help = "Treats the encoding of the ZIP archive as UTF-8 first. (Default: try legacy encoding first unless $ZIFU_UTF8_FIRST is 1)"
+    )]
+    utf8: bool,
+    #[clap(short, long, help = "Don't confirm")]
+    yes: bool,
+    #[clap(
+        short,
+        long,
+        help = "Try to convert even if we don't have to apply this tool."
+    )]
+    force: bool,
+    #[clap(short, long, help = "Replace the archive")]
+    in_place: bool,
+    #[clap(
+        long,
+        value_name = "SUFFIX",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = ".bak",
+        value_parser = clap::builder::NonEmptyStringValueParser::new(),
+        requires = "in_place",
+        help = "Keeps the original archive as <ARCHIVE><SUFFIX> (Default: .bak) instead of deleting it in -i."
+    )]
+    backup: Option<String>,
+    #[clap(
+        long,
+        help = "Transliterates file names to pure ASCII (e.g. テスト.txt -> tesuto.txt) for receivers that cannot handle UTF-8."
+    )]
+    transliterate: bool,
+    #[clap(
+        long,
+        value_name = "EXPR",
+        value_parser = parse_rename_arg,
+        conflicts_with = "to_encoding",
+        help = "Rewrites the decoded file names by a substitution like s/old/new/ (regex; flags: g, i; $1 for groups; can be specified multiple times and applied in order)."
+    )]
+    rename: Vec<String>,
+    #[clap(
+        long,
+        conflicts_with = "to_encoding",
+        help = "Removes the top-level directory that wraps all the entries (e.g. New Folder/a.txt -> a.txt)."
+    )]
+    strip_top_dir: bool,
+    #[clap(
+        long,
+        value_name = "DIR",
+        value_parser = parse_top_dir_arg,
+        conflicts_with = "to_encoding",
+        help = "Moves all the entries into DIR (e.g. a.txt -> DIR/a.txt).  With --strip-top-dir, renames the top-level directory to DIR."
+    )]
+    add_top_dir: Option<String>,
+    #[clap(
+        long,
+        conflicts_with = "to_encoding",
+        help = "Rewrites unsafe file names (e.g. ../a.txt, /a.txt, or C:\\a.txt) to safe relative paths (e.g. a.txt)."
+    )]
+    sanitize_paths: bool,
+    #[clap(
+        long,
+        conflicts_with = "to_encoding",
+        help = "Appends _ to file names that cannot be extracted in Windows (e.g. aux.txt -> aux_.txt, a. -> a._)."
+    )]
+    fix_reserved_names: bool,
+    #[clap(
+        long,
+        conflicts_with = "to_encoding",
+        help = "Trims dots and spaces at the end of file names, which Windows strips (e.g. a. -> a), instead of appending _ with --fix-reserved-names."
+    )]
+    trim_trailing_dots: bool,
+    #[clap(
+        long,
+        conflicts_with = "to_encoding",
+        help = "Truncates file names longer than 255 bytes per component or 260 characters in total, keeping the extensions."
+    )]
+    truncate_long_names: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "OS",
+        conflicts_with = "to_encoding",
+        help = "Warns about converted file names that have characters forbidden in OS (e.g. : in Windows)."
+    )]
+    target_os: Option<TargetOSArg>,
+    #[clap(
+        long,
+        requires = "target_os",
+        help = "Replaces the characters forbidden in the OS of --target-os with _."
+    )]
+    substitute_forbidden_chars: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "HOST",
+        conflicts_with = "to_encoding",
+        help = "Rewrites the host system in \"version made by\" of all the entries, which decides how extractors read the file attributes."
+    )]
+    made_by: Option<MadeByArg>,
+    #[clap(
+        long,
+        conflicts_with = "to_encoding",
+        help = "Leaves out duplicated entries but the last and appends (2), (3), ... to names that differ only in case."
+    )]
+    dedupe: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORM",
+        default_value = "nfc",
+        help = "Unicode normalization form of the converted file names."
+    )]
+    normalization: NormalizationArg,
+    #[clap(
+        long,
+        help = "Fails if the conversion would lose any information (e.g. lossy decoding or transliteration)."
+    )]
+    strict_lossless: bool,
+    #[clap(
+        long,
+        help = "Re-validates the structure of the output archive after writing it. (Always done in -i before replacing the original)"
+    )]
+    post_check: bool,
+    #[clap(
+        long,
+        help = "Also decompresses the entries in --post-check (or -i) to check their CRC-32."
+    )]
+    post_check_crc: bool,
+    #[clap(
+        long,
+        help = "Restores entries present in the archive but missing from its central directory (e.g. truncated archives)."
+    )]
+    recover_orphans: bool,
+    #[clap(
+        long,
+        help = "Searches for the local headers of the entries whose offsets in the central directory are wrong and fixes the offsets."
+    )]
+    repair_offsets: bool,
+    #[clap(
+        long,
+        help = "Decompresses the entries to fix wrong CRC-32 & uncompressed sizes and writes them without data descriptors."
+    )]
+    repair_metadata: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "COMPRESSOR",
+        conflicts_with = "in_place",
+        help = "Streams the output archive through COMPRESSOR. (Default: guessed from the extension of the output e.g. .zip.gz)"
+    )]
+    post_compress: Option<PostCompression>,
+    #[clap(
+        long,
+        value_name = "SIZE",
+        conflicts_with_all = ["in_place", "post_compress", "post_check", "post_check_crc", "preserve_unchanged"],
+        help = "Splits the output archive into disks of at most SIZE (e.g. 700M) named out.z01, out.z02, ..., out.zip."
+    )]
+    span_size: Option<ByteSize>,
+    #[clap(
+        long,
+        value_name = "SIZE",
+        help = "Copies the contents of entries in chunks of SIZE (e.g. 1M), which bounds the memory used while writing. (Default: 256KiB)"
+    )]
+    chunk_size: Option<ByteSize>,
+    #[clap(
+        long,
+        help = "Writes the input as it is (byte for byte) if nothing in it has to be changed, e.g. with -f."
+    )]
+    preserve_unchanged: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u16).range(1..),
+        help = "Pads the local headers of stored entries so that their data begin at multiples of N bytes like zipalign (e.g. 4 for APKs)."
+    )]
+    align: Option<u16>,
+    #[clap(
+        long,
+        value_name = "SECS",
+        help = "Gives up (and skips in batch mode) an archive if reading or writing it takes more than SECS seconds."
+    )]
+    timeout: Option<u64>,
+    #[clap(
+        long,
+        value_name = "ENCODING",
+        conflicts_with = "transliterate",
+        help = "Converts UTF-8 file names back to ENCODING (reverse conversion) for receivers that cannot handle UTF-8."
+    )]
+    to_encoding: Option<String>,
+    #[clap(
+        long,
+        value_name = "ENCODING",
+        conflicts_with = "to_encoding",
+        help = "Writes file names in ENCODING (e.g. cp437) without the UTF-8 flag for ancient extractors, keeping the UTF-8 names in Unicode Path extra fields (0x7075)."
+    )]
+    legacy_compatible: Option<String>,
+    #[clap(
+        long,
+        value_name = "OFFSET",
+        value_parser = parse_utc_offset_arg,
+        allow_hyphen_values = true,
+        help = "UTC offset (e.g. +09:00) of the machine that created the archive, whose local time the timestamps are in."
+    )]
+    source_utc_offset: Option<i32>,
+    #[clap(
+        long,
+        value_name = "OFFSET",
+        value_parser = parse_utc_offset_arg,
+        allow_hyphen_values = true,
+        requires = "source_utc_offset",
+        help = "Rewrites the timestamps into the local time of OFFSET (e.g. -05:00) for the receivers."
+    )]
+    target_utc_offset: Option<i32>,
+    #[clap(
+        long,
+        requires = "source_utc_offset",
+        help = "Adds the modification times in UTC (extended timestamp extra fields) to the entries without them."
+    )]
+    add_utc_times: bool,
+    #[clap(
+        long,
+        value_name = "MODE",
+        value_parser = parse_permissions_arg,
+        help = "Forces the Unix permissions of files created in Unix or macOS to MODE (e.g. 644)."
+    )]
+    file_mode: Option<u32>,
+    #[clap(
+        long,
+        value_name = "MODE",
+        value_parser = parse_permissions_arg,
+        help = "Forces the Unix permissions of directories created in Unix or macOS to MODE (e.g. 755)."
+    )]
+    dir_mode: Option<u32>,
+    #[clap(
+        long,
+        help = "Clears the setuid, setgid, and sticky bits of entries created in Unix or macOS."
+    )]
+    strip_special_bits: bool,
+    #[clap(
+        long,
+        help = "Removes the comments of the entries and the archive, which are often left as mojibake."
+    )]
+    strip_comments: bool,
+    #[clap(
+        long,
+        value_name = "TEXT",
+        conflicts_with = "comment_file",
+        help = "Sets the archive comment to TEXT in UTF-8 (e.g. a note that the archive was fixed by zifu)."
+    )]
+    set_comment: Option<String>,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Sets the archive comment to the contents of FILE, which must be in UTF-8."
+    )]
+    comment_file: Option<String>,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Writes a JSON report on the written entries to FILE."
+    )]
+    report: Option<String>,
+    #[clap(
+        long,
+        requires = "report",
+        help = "Includes SHA-256 of the compressed payload of each entry in the report."
+    )]
+    hash: bool,
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        help = "Converts only the entries whose decoded names match PATTERN (glob; can be specified multiple times)."
+    )]
+    include: Vec<String>,
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        help = "Copies the entries whose decoded names match PATTERN (glob; can be specified multiple times) untouched."
+    )]
+    exclude: Vec<String>,
+    #[clap(
+        long,
+        help = "Prints the rename plan without writing anything.  Exits with 2 if some entries would be renamed."
+    )]
+    dry_run: bool,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        default_value = "text",
+        help = "Output format.  diff prints the rename plan as `- old` / `+ new` lines (stable across versions) without writing anything.  tsv & csv list the entries one per row (raw name in hex, decoded name, encoding type, size, and compression method), and ndjson one JSON object per line as they are decoded, without writing anything."
+    )]
+    format: OutputFormat,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "FORMAT",
+        help = "Prints the summary of the run (exit status, detected encodings, renamed entries, etc.) to stdout at the end."
+    )]
+    summary: Option<SummaryFormat>,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "STRATEGY",
+        default_value = "abort",
+        help = "What to do if the conversion would give multiple entries the same name (e.g. NFD & NFC forms)."
+    )]
+    on_collision: CollisionStrategy,
+    #[clap(
+        long,
+        value_enum,
+        value_name = "POLICY",
+        default_value = "cd",
+        help = "Which name to use if the local file header and the central directory of an entry have different names."
+    )]
+    name_mismatch: NameMismatchArg,
+    #[clap(
+        long,
+        value_name = "FILE",
+        help = "Appends a timestamped log of the options, decisions, warnings, and outcome for each archive to FILE."
+    )]
+    log: Option<String>,
+    #[cfg(feature = "tui")]
+    #[clap(
+        long,
+        conflicts_with_all = ["check", "list", "dry_run", "explain", "yes"],
+        help = "Chooses the encoding in full screen while watching the file names before the conversion."
+    )]
+    tui: bool,
+    #[cfg(feature = "mmap")]
+    #[clap(
+        long,
+        help = "Reads the input archives through memory maps.  Faster for very large archives on local disks; don't modify them meanwhile."
+    )]
+    mmap: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+impl CLIOptions {
+    pub fn to_behavior_flags(&self) -> BehaviorFlags {
+        let verbose = !self.silent && !self.quiet;
+        return BehaviorFlags {
+            verbose,
+            // stdin is occupied by the archive
+            ask_user: verbose && !self.yes && !is_stdio(&self.input) && !self.uses_tui(),
+        };
+    }
+
+    /// Returns `true` if the encoding is chosen in the TUI (`--tui`)
+    pub fn uses_tui(&self) -> bool {
+        #[cfg(feature = "tui")]
+        return self.tui;
+        #[cfg(not(feature = "tui"))]
+        return false;
+    }
+
+    /// Returns `true` if the input archives are memory-mapped (`--mmap`)
+    pub fn uses_mmap(&self) -> bool {
+        #[cfg(feature = "mmap")]
+        return self.mmap;
+        #[cfg(not(feature = "mmap"))]
+        return false;
+    }
+
+    pub fn to_conversion_options(&self) -> ConversionOptions {
+        return ConversionOptions {
+            transliterate: self.transliterate,
+            rename_rules: self
+                .rename
+                .iter()
+                .map(|expression| RenameRule::parse(expression).expect("validated by clap"))
+                .collect(),
+            normalization: self.normalization.into(),
+            sanitize_paths: self.sanitize_paths,
+            fix_reserved_names: self.fix_reserved_names,
+            trim_trailing_dots: self.trim_trailing_dots,
+            substitute_forbidden_characters: match self.target_os {
+                Some(target_os) if self.substitute_forbidden_chars => Some(target_os.into()),
+                _ => None,
+            },
+            made_by: self.made_by.map(HostSystem::from),
+            // Determined for each archive (see `common_top_directory`)
+            strip_top_directory: None,
+            add_top_directory: self.add_top_dir.clone(),
+            truncate_long_names: self.truncate_long_names,
+        };
+    }
+
+    /// Fills in the options not given in the command line from the environment variables
+    ///
+    /// `ZIFU_ENCODING` is the default of `-e` (ignored with `-a`), `ZIFU_UTF8_FIRST=1` (or `true`) turns on `-u`,
+    /// `ZIFU_COLOR`, `NO_COLOR`, `CLICOLOR_FORCE`, & `CLICOLOR` decide `--color` (see `ColorChoice::from_env`),
+    /// and `ZIFU_LANG` (e.g. `ja`) is the default of `--lang` (ignored if invalid).
+    ///
+    /// # Arguments
+    ///
+    /// * `var` - returns the value of an environment variable (e.g. `std::env::var(name).ok()`)
+    pub fn apply_env_defaults<F: Fn(&str) -> Option<String>>(&mut self, var: F) {
+        if self.encoding.is_none() && !self.auto_detect && !self.uses_tui() {
+            self.encoding = var("ZIFU_ENCODING").filter(|encoding| !encoding.is_empty());
+        }
+        if !self.utf8 {
+            self.utf8 = var("ZIFU_UTF8_FIRST").is_some_and(|value| {
+                !matches!(
+                    value.to_ascii_lowercase().as_str(),
+                    "" | "0" | "false" | "no" | "off"
+                )
+            });
+        }
+        if self.color.is_none() {
+            self.color = ColorChoice::from_env(&var);
+        }
+        if self.lang.is_none() {
+            self.lang =
+                var("ZIFU_LANG").and_then(|lang| clap::ValueEnum::from_str(&lang, true).ok());
+        }
+    }
+
+    /// Returns the timestamp correction (`None` if no option on it is specified)
+    pub fn to_timestamp_correction(&self) -> Option<TimestampCorrection> {
+        if self.target_utc_offset.is_none() && !self.add_utc_times {
+            return None;
+        }
+        return Some(TimestampCorrection {
+            source_utc_offset: self.source_utc_offset?,
+            target_utc_offset: self.target_utc_offset,
+            add_extended_timestamps: self.add_utc_times,
+        });
+    }
+
+    /// Returns the normalization of Unix permissions (`None` if no option on it is specified)
+    pub fn to_permission_normalization(&self) -> Option<PermissionNormalization> {
+        if self.file_mode.is_none() && self.dir_mode.is_none() && !self.strip_special_bits {
+            return None;
+        }
+        return Some(PermissionNormalization {
+            file_mode: self.file_mode,
+            directory_mode: self.dir_mode,
+            strip_special_bits: self.strip_special_bits,
+        });
+    }
+
+    pub fn to_entry_selector(&self) -> Result<EntrySelector, InvalidArgument> {
+        return Ok(EntrySelector {
+            include: if self.include.is_empty() {
+                None
+            } else {
+                Some(build_glob_set(&self.include)?)
+            },
+            exclude: build_glob_set(&self.exclude)?,
+        });
+    }
+
+    pub fn to_post_compression(&self) -> PostCompression {
+        if let Some(compression) = self.post_compress {
+            return compression;
+        }
+        if self.in_place {
+            return PostCompression::None;
+        }
+        return self
+            .output
+            .as_deref()
+            .map_or(PostCompression::None, |output| {
+                PostCompression::from_path(output)
+            });
+    }
+
+    /// Returns `true` if the output archives are re-parsed and verified after being written
+    /// (`--post-check` or `--post-check-crc`, and always in `-i` so that a broken output never replaces the original)
+    pub fn verifies_output(&self) -> bool {
+        return self.post_check || self.post_check_crc || self.in_place;
+    }
+
+    /// Returns `true` if the list is shown in the long format (`-ll` or `--list --long`)
+    pub fn lists_long(&self) -> bool {
+        return self.list > 1 || self.long;
+    }
+
+    /// Returns `true` if the options make zifu list the entries (`-l`, `--format tsv`, `--format csv`, or `--format ndjson`)
+    pub fn lists_entries(&self) -> bool {
+        return self.list > 0 || self.format.lists_entries();
+    }
+
+    /// Returns `true` if the options make zifu write archives (i.e. not `-c`, `-l`, `--explain`, `--format` other than `text`,
+    /// or `--dry-run`)
+    pub fn writes_archives(&self) -> bool {
+        return !self.check
+            && !self.lists_entries()
+            && self.compare_encodings.is_none()
+            && self.explain.is_none()
+            && self.format != OutputFormat::Diff
+            && !self.dry_run;
+    }
+
+    /// Returns the archives to be processed
+    ///
+    /// All the paths are inputs in batch mode; otherwise the 2nd path is the output.
+    pub fn to_jobs(&self) -> Result<Vec<Job>, InvalidArgument> {
+        let paths: Vec<&PathBuf> = std::iter::once(&self.input)
+            .chain(self.output.iter())
+            .chain(self.more_inputs.iter())
+            .collect();
+        let batch = self.batch || self.in_place || self.output_dir.is_some() || paths.len() > 2;
+        if !batch {
+            return Ok(vec![Job {
+                input: self.input.clone(),
+                output: self.output.clone(),
+            }]);
+        }
+        if paths.iter().any(|path| is_stdio(path)) {
+            return Err(InvalidArgument::StdioInBatch);
+        }
+        if self.writes_archives() && !self.in_place && self.output_dir.is_none() {
+            return Err(InvalidArgument::NoOutputForBatch);
+        }
+        return Ok(paths
+            .into_iter()
+            .map(|input| Job {
+                input: input.clone(),
+                output: self
+                    .output_dir
+                    .as_ref()
+                    .map(|dir| dir.join(input.file_name().unwrap_or(input.as_os_str()))),
+            })
+            .collect());
+    }
+
+    /// Returns the deadline of each archive for `--timeout`
+    ///
+    /// # Arguments
+    ///
+    /// * `started` - time when processing of the archive started
+    pub fn to_deadline(&self, started: Instant) -> Option<Instant> {
+        return self
+            .timeout
+            .map(|seconds| started + Duration::from_secs(seconds));
+    }
+
+    /// Returns `Err` if `--span-size` is too small or used with outputs that cannot be spanned
+    pub fn check_span_size(&self) -> Result<(), InvalidArgument> {
+        let span_size = match self.span_size {
+            Some(span_size) => span_size,
+            None => return Ok(()),
+        };
+        // Every header must fit in a disk
+        if span_size < MIN_SPAN_SIZE {
+            return Err(InvalidArgument::SpanSizeTooSmall {
+                minimum: MIN_SPAN_SIZE,
+            });
+        }
+        if self.output.as_deref().is_some_and(is_stdio) {
+            return Err(InvalidArgument::SpanSizeConflict {
+                option: "the output to stdout".to_string(),
+            });
+        }
+        if self.to_post_compression() != PostCompression::None {
+            return Err(InvalidArgument::SpanSizeConflict {
+                option: "compressed output".to_string(),
+            });
+        }
+        return Ok(());
+    }
+
+    /// Returns the arguments that process `job` in the same way, pinning the decided encoding (for `--log`)
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - archive to be processed
+    /// * `encoding` - encoding of file names decided for the archive (replaces `-a` and the locale default)
+    pub fn to_command_line(&self, job: &Job, encoding: Option<&str>) -> Vec<String> {
+        let mut args = vec![env!("CARGO_BIN_NAME").to_string()];
+        let mut flag = |enabled: bool, name: &str| {
+            if enabled {
+                args.push(name.to_string());
+            }
+        };
+        flag(self.check, "--check");
+        flag(self.list > 0, "--list");
+        flag(self.lists_long(), "--long");
+        flag(self.silent, "--silent");
+        flag(self.quiet, "--quiet");
+        flag(self.auto_detect && encoding.is_none(), "--auto-detect");
+        flag(self.utf8, "--utf8");
+        flag(self.scoring, "--scoring");
+        flag(self.yes, "--yes");
+        flag(self.force, "--force");
+        flag(self.in_place, "--in-place");
+        flag(self.transliterate, "--transliterate");
+        flag(self.sanitize_paths, "--sanitize-paths");
+        flag(self.fix_reserved_names, "--fix-reserved-names");
+        flag(self.trim_trailing_dots, "--trim-trailing-dots");
+        flag(self.truncate_long_names, "--truncate-long-names");
+        flag(
+            self.substitute_forbidden_chars,
+            "--substitute-forbidden-chars",
+        );
+        flag(self.dedupe, "--dedupe");
+        flag(self.strip_top_dir, "--strip-top-dir");
+        flag(self.strict_lossless, "--strict-lossless");
+        flag(self.post_check, "--post-check");
+        flag(self.post_check_crc, "--post-check-crc");
+        flag(self.recover_orphans, "--recover-orphans");
+        flag(self.repair_offsets, "--repair-offsets");
+        flag(self.repair_metadata, "--repair-metadata");
+        flag(self.preserve_unchanged, "--preserve-unchanged");
+        flag(self.hash, "--hash");
+        flag(self.dry_run, "--dry-run");
+        flag(self.add_utc_times, "--add-utc-times");
+        flag(self.strip_special_bits, "--strip-special-bits");
+        flag(self.strip_comments, "--strip-comments");
+        let mut option = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                args.push(name.to_string());
+                args.push(value);
+            }
+        };
+        option("--explain", self.explain.clone());
+        option(
+            "--encoding",
+            encoding
+                .map(str::to_string)
+                .or_else(|| self.encoding.clone()),
+        );
+        if self.normalization != NormalizationArg::Nfc {
+            option("--normalization", value_name(self.normalization));
+        }
+        if self.format != OutputFormat::Text {
+            option("--format", value_name(self.format));
+        }
+        option("--summary", self.summary.and_then(value_name));
+        option("--target-os", self.target_os.and_then(value_name));
+        option("--made-by", self.made_by.and_then(value_name));
+        if self.on_collision != CollisionStrategy::Abort {
+            option("--on-collision", value_name(self.on_collision));
+        }
+        if self.name_mismatch != NameMismatchArg::Cd {
+            option("--name-mismatch", value_name(self.name_mismatch));
+        }
+        option("--post-compress", self.post_compress.and_then(value_name));
+        option(
+            "--span-size",
+            self.span_size.map(|size| size.as_u64().to_string()),
+        );
+        option(
+            "--chunk-size",
+            self.chunk_size.map(|size| size.as_u64().to_string()),
+        );
+        option("--align", self.align.map(|n| n.to_string()));
+        option("--timeout", self.timeout.map(|secs| secs.to_string()));
+        option("--to-encoding", self.to_encoding.clone());
+        option("--legacy-compatible", self.legacy_compatible.clone());
+        option(
+            "--source-utc-offset",
+            self.source_utc_offset.map(format_utc_offset),
+        );
+        option(
+            "--target-utc-offset",
+            self.target_utc_offset.map(format_utc_offset),
+        );
+        option(
+            "--file-mode",
+            self.file_mode.map(|mode| format!("{:o}", mode)),
+        );
+        option(
+            "--dir-mode",
+            self.dir_mode.map(|mode| format!("{:o}", mode)),
+        );
+        option("--report", self.report.clone());
+        option("--set-comment", self.set_comment.clone());
+        option("--comment-file", self.comment_file.clone());
+        option("--add-top-dir", self.add_top_dir.clone());
+        for expression in self.rename.iter() {
+            option("--rename", Some(expression.clone()));
+        }
+        for pattern in self.include.iter() {
+            option("--include", Some(pattern.clone()));
+        }
+        for pattern in self.exclude.iter() {
+            option("--exclude", Some(pattern.clone()));
+        }
+        if let Some(suffix) = self.backup.as_ref() {
+            args.push(format!("--backup={}", suffix));
+        }
+        // Paths can start with `-`
+        args.push("--".to_string());
+        // Rendered lossily only here; the command line is for the log
+        args.push(job.input_name().into_owned());
+        args.extend(
+            job.output
+                .iter()
+                .map(|output| output.to_string_lossy().into_owned()),
+        );
+        return args;
+    }
+
+    pub fn to_output_options(&self, deadline: Option<Instant>) -> OutputOptions {
+        return OutputOptions {
+            hash_payloads: self.hash,
+            deadline,
+            cancellation: Some(CANCELLATION.clone()),
+            chunk_size: self.chunk_size.map(|size| size.as_u64() as usize),
+            preserve_unchanged: self.preserve_unchanged,
+            alignment: self.align,
+        };
+    }
+}
+
+/// Cancelled by Ctrl-C while an archive is being written or verified
+static CANCELLATION: Lazy<CancellationToken> = Lazy::new(CancellationToken::new);
+/// `true` while an archive is being written (Ctrl-C cancels it instead of exiting immediately)
+static OUTPUT_IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+
+/// Marks that an archive is being written until dropped
+struct OutputInProgress;
+
+impl OutputInProgress {
+    fn new() -> Self {
+        OUTPUT_IN_PROGRESS.store(true, Ordering::SeqCst);
+        return OutputInProgress;
+    }
+}
+
+impl Drop for OutputInProgress {
+    fn drop(&mut self) {
+        OUTPUT_IN_PROGRESS.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Temporary output of `-i` that has not replaced the original yet (removed by the signal handler)
+static TEMPORARY_FILE: Lazy<Mutex<Option<PathBuf>>> = Lazy::new(|| Mutex::new(None));
+
+/// Removes the temporary output of `-i` if exists
+fn remove_temporary_file() {
+    let mut temporary_file = TEMPORARY_FILE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(path) = temporary_file.take() {
+        // It may have not been created or have already been removed
+        let _ = std::fs::remove_file(extended_length_path(&path));
+    }
+}
+
+/// Temporary output of `-i` removed when dropped (on errors, panics, etc.) unless `keep` is called
+struct TemporaryFile;
+
+impl TemporaryFile {
+    /// Registers the temporary file so that signals also remove it
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the temporary file
+    fn new(path: &Path) -> Self {
+        *TEMPORARY_FILE.lock().unwrap_or_else(|e| e.into_inner()) = Some(path.to_path_buf());
+        return TemporaryFile;
+    }
+
+    /// Stops removing the file (after it has replaced the original)
+    fn keep(self) {
+        TEMPORARY_FILE
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .take();
+    }
+}
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        remove_temporary_file();
+    }
+}
+
+/// Minimum of `--span-size` (large enough for the largest possible headers)
+const MIN_SPAN_SIZE: ByteSize = ByteSize::kib(256);
+
+fn main() {
+    let status = match run() {
+        Ok(status) => status,
+        Err(e) => {
+            // Same format as returning `Err` from `main`
+            eprintln!("{}", trf("Error: {}", &[&format!("{:?}", e)]));
+            ExitStatus::from_error(&e)
+        }
+    };
+    std::process::exit(status.code());
+}
+
+/// Writes the events of `zifu_core` & the CLI to stderr according to the number of `-v`s (nothing without them)
+///
+/// # Arguments
+///
+/// * `verbosity` - number of `-v`s
+fn init_tracing(verbosity: u8) {
+    let level = match verbosity {
+        0 => return,
+        1 => tracing::Level::INFO,
+        2 => tracing::Level::DEBUG,
+        _ => tracing::Level::TRACE,
+    };
+    // The messages of zifu itself are still written by `eprintln!` so that `-v` doesn't change them
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_ansi(color::uses_color(color::Stream::Stderr))
+        .without_time()
+        .init();
+}
+
+/// Extracts the archive under the corrected file names (`zifu extract`) and returns the exit status
+///
+/// # Arguments
+///
+/// * `args` - arguments of the subcommand
+/// * `encoding` - encoding of the implicitly-encoded file names (`None` to detect it)
+fn run_extract(args: &ExtractArgs, encoding: Option<String>) -> anyhow::Result<ExitStatus> {
+    let report = extract_zip_file(
+        &args.archive,
+        &args.dest,
+        &ExtractOptions {
+            encoding,
+            conversion: ConversionOptions {
+                normalization: args.normalization.into(),
+                ..Default::default()
+            },
+            overwrite: args.overwrite,
+        },
+    )?;
+    for skipped in report.skipped.iter() {
+        eprintln!(
+            "{}",
+            format_warning(
+                i18n::language(),
+                "#{} {} is not extracted: {}",
+                &[&skipped.index, &skipped.name, &skipped.reason]
+            )
+        );
+    }
+    eprintln!(
+        "{}",
+        trf(
+            "Extracted {} entries into {}.",
+            &[&report.extracted.len(), &args.dest]
+        )
+    );
+    return Ok(if report.skipped.is_empty() {
+        ExitStatus::Ok
+    } else {
+        ExitStatus::UnsupportedFeature
+    });
+}
+
+/// Creates a new archive (`zifu create`) and returns the exit status
+///
+/// # Arguments
+///
+/// * `args` - arguments of the subcommand
+fn run_create(args: &CreateArgs) -> anyhow::Result<ExitStatus> {
+    if !Path::new(&args.source).is_dir() {
+        return Err(InvalidArgument::NotADirectory {
+            path: args.source.clone(),
+        }
+        .into());
+    }
+    let report = create_zip_file(
+        &args.output,
+        &args.source,
+        &CreateOptions {
+            unicode_path_fields: args.unicode_path_fields,
+        },
+    )?;
+    for skipped in report.skipped.iter() {
+        eprintln!(
+            "{}",
+            format_warning(
+                i18n::language(),
+                "{} is not archived: {}",
+                &[&skipped.path, &skipped.reason]
+            )
+        );
+    }
+    eprintln!(
+        "{}",
+        trf(
+            "Created {} with {} entries.",
+            &[&args.output, &report.entries.len()]
+        )
+    );
+    return Ok(if report.skipped.is_empty() {
+        ExitStatus::Ok
+    } else {
+        ExitStatus::UnsupportedFeature
+    });
+}
+
+/// Runs zifu and returns the exit status of the process
+fn run() -> anyhow::Result<ExitStatus> {
+    // Expands wildcards on Windows (done by the shell on the other platforms)
+    // Paths not in UTF-8 are kept as they are
+    let args: Vec<OsString> = wild::args_os().collect();
+    let mut cli_options = match CLIOptions::try_parse_from(&args) {
+        Ok(cli_options) => cli_options,
+        Err(e) => {
+            // `--help` is also reported as `Err`
+            e.print()?;
+            return Ok(if e.use_stderr() {
+                ExitStatus::Usage
+            } else {
+                ExitStatus::Ok
+            });
+        }
+    };
+    cli_options.apply_env_defaults(|name| std::env::var(name).ok());
+    i18n::set_language(cli_options.lang.unwrap_or_else(Language::detect));
+    color::set_color_choice(cli_options.color.unwrap_or(ColorChoice::Auto));
+    init_tracing(cli_options.verbose);
+    match cli_options.command.as_ref() {
+        Some(Command::Extract(args)) => {
+            // `ZIFU_ENCODING` is applied to `cli_options.encoding`
+            return run_extract(args, args.encoding.clone().or(cli_options.encoding.clone()));
+        }
+        Some(Command::Create(args)) => return run_create(args),
+        None => {}
+    }
+    if cli_options.show_version {
+        print_version(cli_options.verbose > 0);
+        return Ok(ExitStatus::Ok);
+    }
+    if let Some(query) = cli_options.list_encodings.as_deref() {
+        print_encodings(query);
+        return Ok(ExitStatus::Ok);
+    }
+    let jobs = cli_options.to_jobs()?;
+    if cli_options.summary.is_some()
+        && jobs
+            .iter()
+            .any(|job| job.output.as_deref().is_some_and(is_stdio))
+    {
+        return Err(InvalidArgument::SummaryWithStdout.into());
+    }
+    let post_compression = cli_options.to_post_compression();
+    if cli_options.verifies_output() && post_compression != PostCompression::None {
+        return Err(InvalidArgument::PostCheckWithCompression.into());
+    }
+    if cli_options.verifies_output() && cli_options.output.as_deref().is_some_and(is_stdio) {
+        return Err(InvalidArgument::PostCheckWithStdout.into());
+    }
+    cli_options.check_span_size()?;
+    // Stop writing cleanly to remove the partially written output
+    ctrlc::set_handler(|| {
+        if !OUTPUT_IN_PROGRESS.load(Ordering::SeqCst) {
+            // `exit` does not run destructors
+            remove_temporary_file();
+            std::process::exit(130);
+        }
+        CANCELLATION.cancel();
+    })?;
+    let mut log = SessionLog::open(cli_options.log.as_deref())?;
+    log.write(&format!(
+        "zifu {} started: {}",
+        env!("CARGO_PKG_VERSION"),
+        session_log::command_line(
+            &args
+                .iter()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+        )
+    ))?;
+
+    if jobs.len() == 1 {
+        let mut archive_summary = ArchiveSummary::default();
+        let result = process_archive(&cli_options, &jobs[0], &mut log, &mut archive_summary);
+        log_outcome(&mut log, &cli_options, &jobs[0], &result)?;
+        let status = match &result {
+            Ok((status, _)) => *status,
+            Err(e) => ExitStatus::from_error(e),
+        };
+        print_run_summary(
+            &cli_options,
+            status,
+            vec![archive_summary.to_json(&jobs[0], status, result.as_ref().err())],
+        );
+        let (status, report) = result?;
+        if let Some(report) = report {
+            write_report(&cli_options, &report)?;
+        }
+        return Ok(status);
+    }
+
+    let mut statuses = Vec::with_capacity(jobs.len());
+    let mut reports = vec![];
+    let mut summary = Vec::with_capacity(jobs.len());
+    let mut archive_summaries = Vec::with_capacity(jobs.len());
+    for job in jobs.iter() {
+        if cli_options.to_behavior_flags().verbose {
+            eprintln!("==> {}", job.input.display());
+        }
+        let mut archive_summary = ArchiveSummary::default();
+        let result = process_archive(&cli_options, job, &mut log, &mut archive_summary);
+        log_outcome(&mut log, &cli_options, job, &result)?;
+        let (status, description) = match &result {
+            Ok((status, _)) => (*status, tr(status.describe(cli_options.check)).to_string()),
+            Err(e) => (ExitStatus::from_error(e), trf("failed: {}", &[e])),
+        };
+        archive_summaries.push(archive_summary.to_json(job, status, result.as_ref().err()));
+        if let Ok((_, report)) = result {
+            reports.extend(report);
+        }
+        statuses.push(status);
+        summary.push((job.input_name(), description));
+        if CANCELLATION.is_cancelled() {
+            break;
+        }
+    }
+    if cli_options.report.is_some() {
+        write_report(&cli_options, &serde_json::Value::Array(reports))?;
+    }
+    if cli_options.to_behavior_flags().verbose {
+        eprintln!("{}", tr("Summary:"));
+        for (input, description) in summary {
+            eprintln!("  {}: {}", input, description);
+        }
+    }
+    let status = ExitStatus::combine(&statuses);
+    print_run_summary(&cli_options, status, archive_summaries);
+    return Ok(status);
+}
+
+/// Prints the summary of the run to stdout as a line of JSON (`--summary json`)
+///
+/// # Arguments
+///
+/// * `cli_options` - options shared among all the archives
+/// * `status` - exit status of the process
+/// * `archives` - summaries of the archives
+fn print_run_summary(
+    cli_options: &CLIOptions,
+    status: ExitStatus,
+    archives: Vec<serde_json::Value>,
+) {
+    match cli_options.summary {
+        Some(SummaryFormat::Json) => println!("{}", run_summary_to_json(status, archives)),
+        None => {}
+    }
+}
+
+/// Writes the outcome of an archive to the session log
+///
+/// # Arguments
+///
+/// * `log` - session log
+/// * `cli_options` - options shared among all the archives
+/// * `job` - processed archive
+/// * `result` - result of `process_archive`
+fn log_outcome(
+    log: &mut SessionLog,
+    cli_options: &CLIOptions,
+    job: &Job,
+    result: &anyhow::Result<(ExitStatus, Option<serde_json::Value>)>,
+) -> std::io::Result<()> {
+    let outcome = match result {
+        Ok((status, _)) => status.describe(cli_options.check).to_string(),
+        // The log stays in English
+        Err(e) => i18n::untranslated(|| format!("failed: {}", e)),
+    };
+    return log.write(&format!("[{}] Outcome: {}", job.input.display(), outcome));
+}
+
+/// Writes the JSON report to the path given by `--report` (if any)
+fn write_report(cli_options: &CLIOptions, report: &serde_json::Value) -> anyhow::Result<()> {
+    if let Some(report_path) = cli_options.report.as_deref() {
+        let mut report_file = BufWriter::new(File::create(report_path)?);
+        serde_json::to_writer_pretty(&mut report_file, report)?;
+        report_file.write_all(b"\n")?;
+    }
+    return Ok(());
+}
+
+/// Processes an archive and returns the exit status and the report on the written archive (if written)
+///
+/// # Arguments
+///
+/// * `cli_options` - options shared among all the archives
+/// * `job` - archive to be processed
+/// * `log` - session log (`--log`)
+/// * `summary` - what was detected & done (`--summary`)
+fn process_archive(
+    cli_options: &CLIOptions,
+    job: &Job,
+    log: &mut SessionLog,
+    summary: &mut ArchiveSummary,
+) -> anyhow::Result<(ExitStatus, Option<serde_json::Value>)> {
+    let _span = tracing::info_span!("archive", input = %job.input.display()).entered();
+    let behavior_flags = cli_options.to_behavior_flags();
+    let mut conversion_options = cli_options.to_conversion_options();
+    let entry_selector = cli_options.to_entry_selector()?;
+    let post_compression = cli_options.to_post_compression();
+    let deadline = cli_options.to_deadline(Instant::now());
+    let to_stdout = job.output.as_deref().is_some_and(is_stdio);
+    let mut input_zip_file = InputZIPArchive::new_with_deadline(
+        InputReader::open(&job.input, cli_options.uses_mmap())?,
+        deadline,
+    )
+    .map_err(|e| {
+        // Timeouts are not the fault of the archive, and encrypted central directories are valid but unsupported
+        if e.is::<DeadlineExceeded>() || e.is::<UnsupportedEncryptionError>() {
+            return e;
+        }
+        return ArchiveError::Parse(e).into();
+    })?;
+
+    let operation = if cli_options.check {
+        Operation::Check
+    } else if !cli_options.writes_archives() {
+        Operation::List
+    } else {
+        Operation::Fix
+    };
+    for feature in input_zip_file.feature_gate().check(operation)? {
+        warn(
+            !behavior_flags.verbose,
+            job,
+            log,
+            "{} is not fully supported",
+            &[&feature.description()],
+        )?;
+    }
+    if input_zip_file.trailing_data_size() > 0 {
+        warn(
+            !behavior_flags.verbose,
+            job,
+            log,
+            "{} bytes of junk follow the end of central directory record (ignored and dropped from the output)",
+            &[&input_zip_file.trailing_data_size()],
+        )?;
+    }
+
+    // Before the recovery so that the entries with wrong offsets are not mistaken for orphans
+    if cli_options.repair_offsets {
+        for repair in input_zip_file.repair_local_header_offsets()?.iter() {
+            match repair.found_position {
+                Some(position) => {
+                    if behavior_flags.verbose {
+                        eprintln!(
+                            "{}",
+                            trf(
+                                "Fixed the offset of #{}: {} -> {}",
+                                &[&repair.index, &repair.recorded_position, &position]
+                            )
+                        );
+                    }
+                    log.write(&format!(
+                        "[{}] Fixed the offset of #{}: {} -> {}",
+                        job.input.display(),
+                        repair.index,
+                        repair.recorded_position,
+                        position
+                    ))?;
+                }
+                None => {
+                    warn(
+                        !behavior_flags.verbose,
+                        job,
+                        log,
+                        "the local header of #{} is not found (recorded at {})",
+                        &[&repair.index, &repair.recorded_position],
+                    )?;
+                }
+            }
+        }
+    }
+    if cli_options.recover_orphans {
+        let recovery = input_zip_file.recover_orphan_entries()?;
+        if behavior_flags.verbose {
+            eprintln!(
+                "{}",
+                trf(
+                    "Recovered {} entries missing from the central directory.",
+                    &[&recovery.recovered.len()]
+                )
+            );
+        }
+        log.write(&format!(
+            "[{}] Recovered {} entries missing from the central directory",
+            job.input.display(),
+            recovery.recovered.len()
+        ))?;
+        for header in recovery.skipped.iter() {
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "cannot recover the streamed entry at {} ({})",
+                &[&header.position, &escape_bytes(&header.file_name_raw)],
+            )?;
+        }
+    }
+    // After the recovery so that the recovered entries are repaired too
+    let mut has_repaired_metadata = false;
+    if cli_options.repair_metadata && operation == Operation::Fix {
+        let report = input_zip_file.repair_metadata_with_deadline(deadline, Some(&CANCELLATION))?;
+        for repair in report.repaired.iter() {
+            if behavior_flags.verbose {
+                eprintln!("{}", trf("Repaired {}", &[&describe_repair(repair)]));
+            }
+            log.write(&format!(
+                "[{}] Repaired {}",
+                job.input.display(),
+                describe_repair(repair)
+            ))?;
+        }
+        for skipped in report.skipped.iter() {
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "cannot repair #{} ({})",
+                &[&skipped.index, &skipped.reason],
+            )?;
+        }
+        has_repaired_metadata = !report.repaired.is_empty();
+    }
+    // Listing only needs the central directory, and checking only reports the mismatches
+    // (reading the local headers also honors the UTF-8 flags set only in them)
+    let mismatches = match operation {
+        Operation::Fix => {
+            input_zip_file.reconcile_name_mismatches(cli_options.name_mismatch.into())?
+        }
+        Operation::Check => input_zip_file.find_name_mismatches()?,
+        Operation::Extract => {
+            // The extraction reads every local header anyway
+            input_zip_file.read_local_header_flags();
+            vec![]
+        }
+        Operation::List => vec![],
+    };
+    for mismatch in mismatches.iter() {
+        warn(
+            !behavior_flags.verbose,
+            job,
+            log,
+            "#{} has different names in the central directory ({}) and the local file header ({}) (use --name-mismatch to choose one)",
+            &[
+                &mismatch.index,
+                &escape_bytes(&mismatch.central_directory_name_raw),
+                &escape_bytes(&mismatch.local_header_name_raw),
+            ],
+        )?;
+    }
+    // Stripped before the conversion so that the comments do not affect the lossless checks
+    let strips_comments =
+        cli_options.strip_comments && !cli_options.check && input_zip_file.has_comments();
+    if strips_comments {
+        input_zip_file.strip_comments();
+    }
+    // After the stripping so that `--strip-comments` removes only the old ones
+    let new_comment = match (&cli_options.set_comment, &cli_options.comment_file) {
+        (Some(comment), _) => Some(comment.clone()),
+        (None, Some(path)) => Some(std::fs::read_to_string(path)?),
+        (None, None) => None,
+    };
+    let sets_comment = match new_comment {
+        Some(comment) if !cli_options.check && input_zip_file.comment() != comment.as_bytes() => {
+            input_zip_file.set_comment(comment.as_bytes())?;
+            true
+        }
+        _ => false,
+    };
+    summary.universal = Some(
+        input_zip_file
+            .diagnose_file_name_encoding()
+            .is_universal_archive(),
+    );
+
+    if cli_options.check {
+        let archive_names_type = input_zip_file.diagnose_file_name_encoding();
+        log.write(&format!(
+            "[{}] Reproduce with: {}",
+            job.input.display(),
+            session_log::command_line(&cli_options.to_command_line(job, None))
+        ))?;
+        print_status_message(&archive_names_type);
+        if let Some(summary) = origin_summary(&input_zip_file.origin_hints()) {
+            println!("{}", trf("Origin: {}.", &[&summary]));
+        }
+        for (description, count) in summarize_encryptions(
+            input_zip_file
+                .entries_metadata()
+                .map(|metadata| metadata.encryption),
+        ) {
+            let message = if count == 1 {
+                "Encryption: {} entry is {} (passed through untouched when converted)."
+            } else {
+                "Encryption: {} entries are {} (passed through untouched when converted)."
+            };
+            println!("{}", trf(message, &[&count, &description]));
+        }
+        if archive_names_type.has_unsafe_paths {
+            println!(
+                "{}",
+                tr("Warning: some file names can escape the extraction directory (e.g. ../a.txt). Convert with --sanitize-paths to rewrite them.")
+            );
+        }
+        if archive_names_type.has_windows_reserved_names {
+            println!(
+                "{}",
+                tr("Warning: some file names cannot be extracted in Windows (e.g. aux.txt). Convert with --fix-reserved-names to rewrite them.")
+            );
+        }
+        if archive_names_type.has_trailing_dots_or_spaces {
+            println!(
+                "{}",
+                tr("Warning: some file names end with a dot or a space, which Windows strips (e.g. a.). Convert with --trim-trailing-dots to trim them.")
+            );
+        }
+        if archive_names_type.has_duplicate_names
+            || archive_names_type.has_case_insensitive_duplicates
+        {
+            println!(
+                "{}",
+                tr("Warning: some file names are duplicated (possibly except for case). Convert with --dedupe to remove the duplicates.")
+            );
+        }
+        if archive_names_type.has_utf8_flags_only_in_local_headers {
+            println!(
+                "{}",
+                tr("Warning: some UTF-8 flags are set only in the local file headers, so extractors that follow the central directory garble the names. Convert to set them in both.")
+            );
+        }
+        if behavior_flags.verbose {
+            if let Some(description) = origin_description(&input_zip_file.origin_hints()) {
+                println!("{}", trf("Hint: {}", &[&description]));
+            }
+        }
+        return Ok((
+            if archive_names_type.is_universal_archive() {
+                ExitStatus::Ok
+            } else {
+                ExitStatus::NeedsFix
+            },
+            None,
+        ));
+    }
+
+    if let Some(limit) = cli_options.compare_encodings {
+        let mut decoders = input_zip_file
+            .detect_filename_encoding_candidates()
+            .into_iter()
+            .map(|candidate| candidate.decoder)
+            .collect::<Vec<_>>();
+        let extra_encodings = cli_options.encoding.iter().map(String::as_str);
+        for encoding_name in extra_encodings.chain(COMPARED_ENCODINGS.iter().copied()) {
+            let decoder = <dyn filename_decoder::IDecoder>::from_encoding_name(encoding_name)
+                .ok_or(InvalidArgument::InvalidEncodingName {
+                    encoding_name: encoding_name.to_string(),
+                })?;
+            if decoders
+                .iter()
+                .all(|added| added.encoding_name() != decoder.encoding_name())
+            {
+                decoders.push(decoder);
+            }
+        }
+        let decoders = decoders
+            .iter()
+            .map(|decoder| &**decoder)
+            .collect::<Vec<_>>();
+        print_name_previews(&input_zip_file.preview_names(&decoders, limit), &decoders);
+        return Ok((ExitStatus::Ok, None));
+    }
+
+    // Decoders tried after the locale default (the ANSI code page and CP437)
+    let mut fallback_decoders = vec![];
+    // "version made by" is a prior only when the user doesn't choose the encoding
+    let host = input_zip_file.origin_hints().host_system();
+    let mut utf8_first = cli_options.utf8;
+    let (legacy_decoder, decided_by) = if cli_options.auto_detect {
+        let mut candidates = input_zip_file.detect_filename_encoding_candidates();
+        if candidates.is_empty() {
+            return Err(ArchiveError::NoEncodingCandidate.into());
+        }
+        if behavior_flags.verbose {
+            print_detected_encoding_candidates(&candidates);
+        }
+        let decided_by = format!(
+            "auto-detected; confidence: {:.0}%{}",
+            candidates[0].confidence * 100.0,
+            if is_ambiguous(&candidates) {
+                "; ambiguous"
+            } else {
+                ""
+            }
+        );
+        (candidates.swap_remove(0).decoder, decided_by)
+    } else if let Some(encoding_name) = cli_options.encoding.as_deref() {
+        (
+            <dyn filename_decoder::IDecoder>::from_encoding_name(encoding_name).ok_or(
+                InvalidArgument::InvalidEncodingName {
+                    encoding_name: encoding_name.to_string(),
+                },
+            )?,
+            "specified".to_string(),
+        )
+    } else {
+        let mut chain = <dyn filename_decoder::IDecoder>::native_fallback_chain_for_host(host);
+        let decoder = chain.remove(0);
+        fallback_decoders = chain;
+        match host {
+            Some(host) if host.prefers_utf8() && !cli_options.utf8 => {
+                utf8_first = true;
+                (
+                    decoder,
+                    format!("locale default; UTF-8 first for {} hosts", host.name()),
+                )
+            }
+            _ => (decoder, "locale default".to_string()),
+        }
+    };
+    #[cfg(feature = "tui")]
+    let (legacy_decoder, decided_by) = if cli_options.tui {
+        // The screen would be mixed into the output archive
+        if to_stdout {
+            return Err(InvalidArgument::TuiWithStdout.into());
+        }
+        fallback_decoders.clear();
+        let mut preferred = vec![legacy_decoder];
+        preferred.extend(
+            input_zip_file
+                .detect_filename_encoding_candidates()
+                .into_iter()
+                .map(|candidate| candidate.decoder),
+        );
+        match tui::choose_encoding(&input_zip_file, tui::candidate_encodings(preferred))? {
+            Some(decoder) => (decoder, "chosen in TUI".to_string()),
+            None => return Ok((ExitStatus::Rejected, None)),
+        }
+    } else {
+        (legacy_decoder, decided_by)
+    };
+    let utf8_decoder = <dyn filename_decoder::IDecoder>::utf8();
+    let ascii_decoder = <dyn filename_decoder::IDecoder>::ascii();
+    let mut legacy_decoders = vec![&*legacy_decoder];
+    legacy_decoders.extend(fallback_decoders.iter().map(|decoder| &**decoder));
+    let decoders_list = if utf8_first {
+        [
+            vec![&*ascii_decoder, &*utf8_decoder],
+            legacy_decoders.clone(),
+        ]
+        .concat()
+    } else {
+        [
+            vec![&*ascii_decoder],
+            legacy_decoders.clone(),
+            vec![&*utf8_decoder],
+        ]
+        .concat()
+    };
+    // Detect encoding by trying decoding all of file names and comments
+    let best_fit_decoder_index_ = if cli_options.scoring {
+        let ranking = input_zip_file.rank_filename_decoders(&decoders_list);
+        if behavior_flags.verbose {
+            print_decoder_ranking(&ranking);
+        }
+        ranking
+            .iter()
+            .find(|score| score.lossless)
+            .map(|score| score.index)
+    } else {
+        input_zip_file.get_filename_decoder_index(&decoders_list)
+    };
+    let best_fit_decoder_index = match best_fit_decoder_index_ {
+        Some(index) => index,
+        // The list is still useful to find the right encoding
+        None if cli_options.lists_entries() => {
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "some file names cannot be decoded in {} and are shown as raw bytes.  Try with -e <another encoding> option.",
+                &[&legacy_decoder.encoding_name()],
+            )?;
+            print_list(&input_zip_file, &*legacy_decoder, cli_options)?;
+            return Ok((ExitStatus::DetectionFailure, None));
+        }
+        None => {
+            return Err(ArchiveError::UndecodableNames {
+                encoding: legacy_decoder.encoding_name().to_string(),
+            }
+            .into())
+        }
+    };
+    let guessed_encoder = decoders_list[best_fit_decoder_index];
+    // One of the fallbacks if the locale default cannot decode the names
+    let (legacy_decoder, decided_by) = match legacy_decoders[1..]
+        .iter()
+        .find(|decoder| decoder.encoding_name() == guessed_encoder.encoding_name())
+    {
+        Some(fallback_decoder) => (*fallback_decoder, format!("{}; fallback", decided_by)),
+        None => (&*legacy_decoder, decided_by),
+    };
+    log.write(&format!(
+        "[{}] Legacy encoding: {} ({}); file names decoded as {}",
+        job.input.display(),
+        legacy_decoder.encoding_name(),
+        decided_by,
+        guessed_encoder.encoding_name()
+    ))?;
+    summary.legacy_encoding = Some(legacy_decoder.encoding_name().to_string());
+    summary.decided_by = Some(decided_by);
+    summary.decoded_as = Some(guessed_encoder.encoding_name().to_string());
+    log.write(&format!(
+        "[{}] Reproduce with: {}",
+        job.input.display(),
+        session_log::command_line(
+            &cli_options.to_command_line(job, Some(legacy_decoder.encoding_name()))
+        )
+    ))?;
+    let reverse_encoder = match cli_options.to_encoding.as_deref() {
+        Some(encoding_name) => Some(<dyn IEncoder>::from_encoding_name(encoding_name).ok_or(
+            InvalidArgument::InvalidEncodingName {
+                encoding_name: encoding_name.to_string(),
+            },
+        )?),
+        None => None,
+    };
+    let legacy_compatible_encoder = match cli_options.legacy_compatible.as_deref() {
+        Some(encoding_name) => Some(<dyn IEncoder>::from_encoding_name(encoding_name).ok_or(
+            InvalidArgument::InvalidEncodingName {
+                encoding_name: encoding_name.to_string(),
+            },
+        )?),
+        None => None,
+    };
+    if cli_options.strip_top_dir {
+        conversion_options.strip_top_directory = common_top_directory(
+            input_zip_file
+                .plan_conversion_with_options(
+                    guessed_encoder,
+                    &ConversionOptions {
+                        add_top_directory: None,
+                        ..conversion_options.clone()
+                    },
+                )
+                .iter()
+                .map(|entry| entry.proposed_name.as_str()),
+        );
+        if conversion_options.strip_top_directory.is_none() {
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "the entries are not in a common top-level directory (--strip-top-dir is ignored)",
+                &[],
+            )?;
+        }
+    }
+
+    if let Some(entry) = cli_options.explain.as_deref() {
+        let index = entry
+            .parse::<usize>()
+            .ok()
+            .or_else(|| {
+                input_zip_file.find_entry_index(entry, guessed_encoder, &conversion_options)
+            })
+            .ok_or(InvalidArgument::NoSuchEntry {
+                entry: entry.to_string(),
+            })?;
+        let trace = input_zip_file
+            .explain_file_name(index, guessed_encoder, &conversion_options)
+            .ok_or(InvalidArgument::NoSuchEntry {
+                entry: entry.to_string(),
+            })?;
+        print_name_trace(index, &trace, guessed_encoder);
+        return Ok((ExitStatus::Ok, None));
+    }
+
+    if !cli_options.sanitize_paths {
+        for unsafe_path in input_zip_file.find_unsafe_paths(guessed_encoder).iter() {
+            let reasons = unsafe_path
+                .reasons
+                .iter()
+                .map(|reason| reason.description());
+            warn_name(
+                !behavior_flags.verbose,
+                job,
+                log,
+                unsafe_path.index,
+                &unsafe_path.name,
+                reasons,
+                "--sanitize-paths",
+            )?;
+        }
+    }
+    if !cli_options.fix_reserved_names {
+        for reserved_name in input_zip_file.find_reserved_names(guessed_encoder).iter() {
+            let mut reasons = reserved_name
+                .reasons
+                .iter()
+                .filter(|reason| {
+                    !(cli_options.trim_trailing_dots
+                        && **reason == ReservedNameReason::TrailingDotOrSpace)
+                })
+                .map(|reason| reason.description())
+                .peekable();
+            if reasons.peek().is_none() {
+                continue;
+            }
+            warn_name(
+                !behavior_flags.verbose,
+                job,
+                log,
+                reserved_name.index,
+                &reserved_name.name,
+                reasons,
+                "--fix-reserved-names",
+            )?;
+        }
+    }
+    // Validated against the names converted without the substitution
+    let illegal_names = match cli_options.target_os {
+        Some(target_os) => find_illegal_names(
+            &input_zip_file.plan_conversion_with_options(
+                guessed_encoder,
+                &ConversionOptions {
+                    substitute_forbidden_characters: None,
+                    ..conversion_options.clone()
+                },
+            ),
+            target_os.into(),
+        ),
+        None => vec![],
+    };
+    // Validated against the names converted without the truncation
+    let long_paths = find_long_paths(&input_zip_file.plan_conversion_with_options(
+        guessed_encoder,
+        &ConversionOptions {
+            truncate_long_names: false,
+            ..conversion_options.clone()
+        },
+    ));
+    if !cli_options.truncate_long_names {
+        for long_path in long_paths.iter() {
+            warn_name(
+                !behavior_flags.verbose,
+                job,
+                log,
+                long_path.index,
+                &long_path.name,
+                long_path.reasons.iter().map(|reason| reason.description()),
+                "--truncate-long-names",
+            )?;
+        }
+    }
+    if !cli_options.dedupe {
+        let plan =
+            input_zip_file.plan_conversion_with_options(guessed_encoder, &conversion_options);
+        for duplicate in find_duplicates(&plan).iter() {
+            let indices: Vec<String> = duplicate
+                .indices
+                .iter()
+                .map(|index| format!("#{}", index))
+                .collect();
+            let message = match duplicate.kind {
+                DuplicateKind::Identical => {
+                    "{} {} are duplicated (use --dedupe to remove the duplicates)"
+                }
+                DuplicateKind::CaseInsensitive => {
+                    "{} {} are duplicated except for case (use --dedupe to remove the duplicates)"
+                }
+            };
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                message,
+                &[&indices.join(" "), &duplicate.name],
+            )?;
+        }
+    }
+    for symlink in input_zip_file
+        .find_renamed_symlinks(guessed_encoder, &conversion_options)
+        .iter()
+    {
+        warn(
+            !behavior_flags.verbose,
+            job,
+            log,
+            "#{} {} is a symbolic link renamed to {} (its target is kept as it is, and links pointing to it may break)",
+            &[&symlink.index, &symlink.current_name, &symlink.proposed_name],
+        )?;
+    }
+    if !cli_options.substitute_forbidden_chars {
+        for illegal_name in illegal_names.iter() {
+            let characters: Vec<String> = illegal_name
+                .characters
+                .iter()
+                .map(|c| format!("{:?}", c))
+                .collect();
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "#{} {}: forbidden characters {} in {} (use --substitute-forbidden-chars to rewrite it)",
+                &[
+                    &illegal_name.index,
+                    &illegal_name.name,
+                    &characters.join(" "),
+                    &value_name(cli_options.target_os.unwrap()).unwrap_or_default(),
+                ],
+            )?;
+        }
+    }
+
+    if cli_options.format == OutputFormat::Diff || cli_options.dry_run {
+        if reverse_encoder.is_some() {
+            return Err(InvalidArgument::PlanWithReverseConversion {
+                option: if cli_options.dry_run {
+                    "--dry-run"
+                } else {
+                    "--format diff"
+                }
+                .to_string(),
+            }
+            .into());
+        }
+        let plan =
+            input_zip_file.plan_conversion_with_options(guessed_encoder, &conversion_options);
+        match cli_options.format {
+            OutputFormat::Diff => print!(
+                "{}",
+                rename_plan_to_diff(&job.input_name(), &plan, &entry_selector)
+            ),
+            _ => print_rename_plan(&plan, &entry_selector),
+        }
+        let collisions = find_collisions(&selected_plan(plan.clone(), &entry_selector));
+        if !collisions.is_empty() && behavior_flags.verbose {
+            eprintln!(
+                "{}",
+                trf("Warning: {}", &[&NameCollisionError { collisions }])
+            );
+        }
+        let n_renamed = count_renamed_entries(&plan, &entry_selector);
+        summary.renamed = Some(n_renamed);
+        if cli_options.dry_run && n_renamed > 0 {
+            return Ok((ExitStatus::NeedsFix, None));
+        }
+        return Ok((ExitStatus::Ok, None));
+    }
+    if cli_options.lists_entries() {
+        print_list(&input_zip_file, guessed_encoder, cli_options)?;
+        return Ok((ExitStatus::Ok, None));
+    }
+    if cli_options.strict_lossless {
+        input_zip_file.check_lossless_conversion(guessed_encoder, &conversion_options)?;
+    }
+
+    // Names matched by `--rename`, moved by `--strip-top-dir` & `--add-top-dir`, or normalized into NFKC or NFD
+    // have to be rewritten even if they are universal
+    let rewrites_names = (!conversion_options.rename_rules.is_empty()
+        || !matches!(
+            conversion_options.normalization,
+            NormalizationForm::NFC | NormalizationForm::Preserve
+        )
+        || conversion_options.strip_top_directory.is_some()
+        || conversion_options.add_top_directory.is_some())
+        && count_renamed_entries(
+            &input_zip_file.plan_conversion_with_options(guessed_encoder, &conversion_options),
+            &entry_selector,
+        ) > 0;
+    // Unsafe paths, reserved names, forbidden characters, and duplicates have to be rewritten even if the names are universal
+    let has_nothing_to_convert = reverse_encoder.is_none()
+        && legacy_compatible_encoder.is_none()
+        && cli_options.to_timestamp_correction().is_none()
+        && cli_options.made_by.is_none()
+        && cli_options.to_permission_normalization().is_none()
+        && !strips_comments
+        && !sets_comment
+        && !has_repaired_metadata
+        && !rewrites_names
+        && {
+            let diagnosis = input_zip_file.diagnose_file_name_encoding();
+            diagnosis.is_universal_archive()
+                && !(cli_options.sanitize_paths && diagnosis.has_unsafe_paths)
+                && !(cli_options.fix_reserved_names && diagnosis.has_windows_reserved_names)
+                && !(cli_options.trim_trailing_dots && diagnosis.has_trailing_dots_or_spaces)
+                && (!cli_options.substitute_forbidden_chars || illegal_names.is_empty())
+                && (!cli_options.truncate_long_names || long_paths.is_empty())
+                && !(cli_options.dedupe
+                    && (diagnosis.has_duplicate_names || diagnosis.has_case_insensitive_duplicates))
+        };
+    // Names given by the user (`e` in the prompt) or `--on-collision suffix`, keyed by the byte sequences
+    let mut name_overrides: HashMap<Vec<u8>, String> = HashMap::new();
+    if behavior_flags.verbose || behavior_flags.ask_user {
+        if let Some(description) = origin_description(&input_zip_file.origin_hints()) {
+            eprintln!("{}", trf("Hint: {}", &[&description]));
+        }
+        // The list would be mixed into the output archive
+        if !to_stdout {
+            list_names_in_archive(
+                &input_zip_file.get_file_names_list(guessed_encoder),
+                guessed_encoder,
+                None,
+            );
+        }
+        if !cli_options.force && has_nothing_to_convert {
+            print_you_do_not_have_to_apply_this_tool(&input_zip_file.diagnose_file_name_encoding());
+            return Ok((ExitStatus::NotApplied, None));
+        }
+
+        if behavior_flags.ask_user && reverse_encoder.is_some() {
+            eprint!("{}", tr("Are these file names correct? [Y/n]: "));
+            if !(ask_default_yes()?) {
+                return Ok((ExitStatus::Rejected, None));
+            }
+        } else if behavior_flags.ask_user {
+            // Names converted back to the legacy encoding cannot be edited
+            eprint!("{}", tr("Are these file names correct? [Y/n/e(dit)]: "));
+            let mut answer = String::new();
+            std::io::stdin().read_line(&mut answer)?;
+            match name_editor::process_answer(&answer) {
+                Answer::Yes => {}
+                Answer::No => return Ok((ExitStatus::Rejected, None)),
+                Answer::Edit => {
+                    let plan = input_zip_file
+                        .plan_conversion_with_options(guessed_encoder, &conversion_options);
+                    let edits = match edit_names(&mut std::io::stdin().lock(), &plan)? {
+                        Some(edits) => edits,
+                        None => return Ok((ExitStatus::Rejected, None)),
+                    };
+                    for (raw, name) in edits.iter() {
+                        log.write(&format!(
+                            "[{}] Edited: {} -> {}",
+                            job.input.display(),
+                            escape_bytes(raw),
+                            name
+                        ))?;
+                    }
+                    name_overrides.extend(edits);
+                    set_name_overrides(&mut input_zip_file, &name_overrides);
+                }
+            }
+        }
+    } else if !cli_options.force && has_nothing_to_convert {
+        print_you_do_not_have_to_apply_this_tool(&input_zip_file.diagnose_file_name_encoding());
+        return Ok((ExitStatus::NotApplied, None));
+    }
+
+    if reverse_encoder.is_none() {
+        let plan = selected_plan(
+            input_zip_file.plan_conversion_with_options(guessed_encoder, &conversion_options),
+            &entry_selector,
+        );
+        let collisions = find_collisions(&plan);
+        if !collisions.is_empty() {
+            if cli_options.on_collision == CollisionStrategy::Abort {
+                return Err(NameCollisionError { collisions }.into());
+            }
+            for (raw, name) in resolve_collisions_by_suffix(&plan, &collisions) {
+                let old_name = match std::str::from_utf8(&raw) {
+                    Ok(old_name) => old_name.to_string(),
+                    Err(_) => escape_bytes(&raw),
+                };
+                warn(
+                    !behavior_flags.verbose,
+                    job,
+                    log,
+                    "renamed {} to {} to avoid a collision",
+                    &[&old_name, &name],
+                )?;
+                name_overrides.insert(raw, name);
+            }
+            set_name_overrides(&mut input_zip_file, &name_overrides);
+        }
+    }
+    if cli_options.dedupe {
+        let plan =
+            input_zip_file.plan_conversion_with_options(guessed_encoder, &conversion_options);
+        let deduplication = deduplicate(&plan, &find_duplicates(&plan));
+        for &index in deduplication.skipped.iter() {
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "left out #{} {} as a duplicate",
+                &[&index, &plan[index].proposed_name],
+            )?;
+        }
+        if !deduplication.renamed.is_empty() {
+            for (raw, name) in deduplication.renamed {
+                let old_name = match std::str::from_utf8(&raw) {
+                    Ok(old_name) => old_name.to_string(),
+                    Err(_) => escape_bytes(&raw),
+                };
+                warn(
+                    !behavior_flags.verbose,
+                    job,
+                    log,
+                    "renamed {} to {} to avoid a case-insensitive duplicate",
+                    &[&old_name, &name],
+                )?;
+                name_overrides.insert(raw, name);
+            }
+            set_name_overrides(&mut input_zip_file, &name_overrides);
+        }
+        let skipped = deduplication.skipped;
+        input_zip_file.set_entry_filter(move |entry| {
+            if skipped.binary_search(&entry.index).is_ok() {
+                return EntryDecision::Skip;
+            }
+            return EntryDecision::Keep;
+        });
+    }
+
+    let output_zip_file_path: Cow<Path> = if cli_options.in_place {
+        // Temporary file name in hte same directory (expecting that rename reuses file contents (& inodes))
+        let mut rng = StdRng::from_entropy();
+        // Ctrl-C while writing removes the temporary file (see `CANCELLATION` & `TemporaryFile`)
+        Cow::from(with_suffix(
+            &job.input,
+            &format!(".{:016x}.tmp", rng.next_u64()),
+        ))
+    } else {
+        let output_zip_file_path = job.output.as_ref().ok_or(InvalidArgument::NoArgument {
+            arg_name: "output".to_string(),
+        })?;
+        if &job.input == output_zip_file_path && !is_stdio(output_zip_file_path) {
+            return Err(InvalidArgument::SameInputOutput.into());
+        }
+        Cow::from(output_zip_file_path.as_path())
+    };
+    match reverse_encoder.as_deref() {
+        Some(encoder) => {
+            input_zip_file.convert_central_directory_file_names_to_encoding(encoder)?
+        }
+        None => {
+            summary.renamed = Some(count_renamed_entries(
+                &input_zip_file.plan_conversion_with_options(guessed_encoder, &conversion_options),
+                &entry_selector,
+            ));
+            input_zip_file.convert_central_directory_file_names_with_filter(
+                guessed_encoder,
+                &conversion_options,
+                |name| entry_selector.is_selected(name),
+            )
+        }
+    }
+    if let Some(correction) = cli_options.to_timestamp_correction() {
+        input_zip_file.correct_timestamps(&correction)?;
+    }
+    if let Some(normalization) = cli_options.to_permission_normalization() {
+        let count = input_zip_file.normalize_unix_permissions(&normalization);
+        if behavior_flags.verbose {
+            eprintln!(
+                "{}",
+                trf("Unix permissions of {} entries were normalized.", &[&count])
+            );
+        }
+    }
+    if let Some(encoder) = legacy_compatible_encoder.as_deref() {
+        for lossy_entry in input_zip_file
+            .convert_central_directory_file_names_to_legacy_with_unicode_path(encoder)?
+            .iter()
+        {
+            warn(
+                !behavior_flags.verbose,
+                job,
+                log,
+                "#{} {} cannot be fully represented in {} (the UTF-8 name is kept in the Unicode Path extra field)",
+                &[&lossy_entry.index, &lossy_entry.name, &encoder.encoding_name()],
+            )?;
+        }
+    }
+    if behavior_flags.verbose && std::io::stderr().is_terminal() {
+        let progress_bar = create_progress_bar();
+        input_zip_file.set_progress_callback(move |progress| {
+            progress_bar.set_length(progress.total_bytes);
+            progress_bar.set_position(progress.bytes_copied);
+        });
+    }
+    let temporary_file = if cli_options.in_place {
+        Some(TemporaryFile::new(output_zip_file_path.as_ref()))
+    } else {
+        None
+    };
+    let _output_in_progress = OutputInProgress::new();
+    if let Some(span_size) = cli_options.span_size {
+        let output_report = output_spanned_archive(
+            &mut input_zip_file,
+            output_zip_file_path.as_ref(),
+            span_size.as_u64(),
+            &cli_options.to_output_options(deadline),
+        )?;
+        log.write(&format!(
+            "[{}] Wrote {} entries to {}",
+            job.input.display(),
+            output_report.entries.len(),
+            output_zip_file_path.display()
+        ))?;
+        summary.written = Some(output_zip_file_path.to_string_lossy().into_owned());
+        let report = report_to_json(&job.input, output_zip_file_path.as_ref(), &output_report);
+        return Ok((ExitStatus::Ok, Some(report)));
+    }
+    let mut output_zip_file =
+        OutputWriter::create(output_zip_file_path.as_ref(), post_compression)?;
+    let output_result = input_zip_file
+        .output_archive_with_options(
+            &mut output_zip_file,
+            &cli_options.to_output_options(deadline),
+        )
+        .and_then(|report| {
+            // Make the output closed
+            output_zip_file.finish()?;
+            return Ok(report);
+        });
+    let output_report = match output_result {
+        Ok(report) => report,
+        Err(e) => {
+            // Never leave the partially written archive; failing to remove it must not hide why it failed
+            if !to_stdout {
+                let _ = std::fs::remove_file(extended_length_path(Path::new(
+                    output_zip_file_path.as_ref(),
+                )));
+            }
+            return Err(e);
+        }
+    };
+    if cli_options.verifies_output() {
+        let mut written = BufReader::new(File::open(extended_length_path(Path::new(
+            output_zip_file_path.as_ref(),
+        )))?);
+        let result = verify_written_archive_with_deadline(
+            &mut written,
+            &output_report,
+            cli_options.post_check_crc,
+            deadline,
+            Some(&CANCELLATION),
+        );
+        #[cfg(feature = "zip-crate-check")]
+        let result = result.and_then(|_| {
+            zifu_core::verification::verify_with_zip_crate(written)?;
+            return Ok(());
+        });
+        if let Err(e) = result {
+            // Never leave (or replace the original with) the broken archive; the verification error is what matters
+            let _ = std::fs::remove_file(extended_length_path(Path::new(
+                output_zip_file_path.as_ref(),
+            )));
+            return Err(e);
+        }
+    }
+    if cli_options.in_place {
+        // Make files closed
+        drop(input_zip_file);
+        let backup_path = cli_options
+            .backup
+            .as_ref()
+            .map(|suffix| with_suffix(&job.input, suffix));
+        // The original is intact & the temporary file is removed (by `temporary_file`) on failure
+        replace_file(
+            output_zip_file_path.as_ref(),
+            &job.input,
+            backup_path.as_deref(),
+        )?;
+        if let Some(temporary_file) = temporary_file {
+            temporary_file.keep();
+        }
+        if let Some(backup_path) = backup_path {
+            log.write(&format!(
+                "[{}] Kept the original as {}",
+                job.input.display(),
+                backup_path.display()
+            ))?;
+        }
+    }
+    let written_path = if cli_options.in_place {
+        job.input.as_path()
+    } else {
+        output_zip_file_path.as_ref()
+    };
+    log.write(&format!(
+        "[{}] Wrote {} entries to {}",
+        job.input.display(),
+        output_report.entries.len(),
+        written_path.display()
+    ))?;
+    summary.written = Some(written_path.to_string_lossy().into_owned());
+    let report = report_to_json(&job.input, written_path, &output_report);
+
+    return Ok((ExitStatus::Ok, Some(report)));
+}
+
+/// Writes the archive into disks of at most `span_size` bytes (`out.z01`, `out.z02`, ..., `out.zip`)
+///
+/// # Arguments
+///
+/// * `input_zip_file` - archive to be written
+/// * `path` - path to the output archive (the last disk)
+/// * `span_size` - maximum size of each disk
+/// * `options` - output options
+fn output_spanned_archive(
+    input_zip_file: &mut InputZIPArchive<InputReader>,
+    path: &Path,
+    span_size: u64,
+    options: &OutputOptions,
+) -> anyhow::Result<OutputReport> {
+    let volumes = SpannedVolumes::new(path);
+    let mut writer = SpannedWriter::new(span_size, |disk| {
+        File::create(volumes.volume_path(disk)).map(BufWriter::new)
+    })?;
+    let result = input_zip_file.output_archive_to_sink(&mut writer, options);
+    let n_disks = writer.n_disks();
+    let result = result.and_then(|report| {
+        let n_disks = writer.finish()?;
+        volumes.finish(n_disks)?;
+        return Ok(report);
+    });
+    if result.is_err() {
+        // Never leave the partially written disks
+        volumes.remove(n_disks);
+    }
+    return result;
+}
+
+#[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn basic_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        let global_flags = cli_options.to_behavior_flags();
+
+        assert_eq!(global_flags.ask_user, true);
+        assert_eq!(global_flags.verbose, true);
+
+        assert_eq!(cli_options.input, Path::new("before.zip"));
+        assert_eq!(cli_options.output.as_deref(), Some(Path::new("after.zip")));
+    }
+
+    #[test]
+    fn extended_args_parse_test1() {
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip", "-q", "-u", "-l"]);
+        let global_flags = cli_options.to_behavior_flags();
+
+        assert_eq!(global_flags.ask_user, false);
+        assert_eq!(global_flags.verbose, false);
+
+        assert_eq!(cli_options.input, Path::new("before.zip"));
+        assert_eq!(cli_options.output.as_deref(), Some(Path::new("after.zip")));
+        assert_eq!(cli_options.encoding.as_deref(), None);
+        assert_eq!(cli_options.utf8, true);
+        assert_eq!(cli_options.check, false);
+        assert_eq!(cli_options.list, 1);
+        assert_eq!(cli_options.force, false);
+        assert_eq!(cli_options.in_place, false);
+    }
+
+    #[test]
+    fn extended_args_parse_test2() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "-s",
+            "-e",
+            "sjis",
+            "-c",
+        ]);
+        let global_flags = cli_options.to_behavior_flags();
+
+        assert_eq!(global_flags.ask_user, false);
+        assert_eq!(global_flags.verbose, false);
+
+        assert_eq!(cli_options.input, Path::new("before.zip"));
+        assert_eq!(cli_options.output.as_deref(), Some(Path::new("after.zip")));
+        assert_eq!(cli_options.encoding.as_deref(), Some("sjis"));
+        assert_eq!(cli_options.utf8, false);
+        assert_eq!(cli_options.check, true);
+        assert_eq!(cli_options.list, 0);
+        assert_eq!(cli_options.force, false);
+        assert_eq!(cli_options.in_place, false);
+    }
+
+    #[test]
+    fn extended_args_parse_test3() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "-y",
+            "--encoding",
+            "cp437",
+        ]);
+        let global_flags = cli_options.to_behavior_flags();
+
+        assert_eq!(global_flags.ask_user, false);
+        assert_eq!(global_flags.verbose, true);
+
+        assert_eq!(cli_options.input, Path::new("before.zip"));
+        assert_eq!(cli_options.output.as_deref(), Some(Path::new("after.zip")));
+        assert_eq!(cli_options.encoding.as_deref(), Some("cp437"));
+        assert_eq!(cli_options.utf8, false);
+        assert_eq!(cli_options.check, false);
+        assert_eq!(cli_options.list, 0);
+        assert_eq!(cli_options.force, false);
+        assert_eq!(cli_options.in_place, false);
+    }
+
+    #[test]
+    fn extended_args_parse_test4() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--yes",
+            "-e",
+            "gbk",
+            "-f",
+        ]);
+        let global_flags = cli_options.to_behavior_flags();
+
+        assert_eq!(global_flags.ask_user, false);
+        assert_eq!(global_flags.verbose, true);
+
+        assert_eq!(cli_options.input, Path::new("before.zip"));
+        assert_eq!(cli_options.output.as_deref(), Some(Path::new("after.zip")));
+        assert_eq!(cli_options.encoding.as_deref(), Some("gbk"));
+        assert_eq!(cli_options.utf8, false);
+        assert_eq!(cli_options.check, false);
+        assert_eq!(cli_options.list, 0);
+        assert_eq!(cli_options.force, true);
+        assert_eq!(cli_options.in_place, false);
+    }
+
+    #[test]
+    fn extended_args_parse_test5() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "-i"]);
+        assert_eq!(cli_options.input, Path::new("before.zip"));
+        assert_eq!(cli_options.output.as_deref(), None);
+        assert_eq!(cli_options.encoding.as_deref(), None);
+        assert_eq!(cli_options.utf8, false);
+        assert_eq!(cli_options.check, false);
+        assert_eq!(cli_options.list, 0);
+        assert_eq!(cli_options.force, false);
+        assert_eq!(cli_options.in_place, true);
+    }
+
+    #[test]
+    fn transliterate_args_parse_test() {
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip", "--transliterate"]);
+        assert_eq!(cli_options.transliterate, true);
+        assert_eq!(cli_options.to_conversion_options().transliterate, true);
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.to_conversion_options().transliterate, false);
+    }
+
+    #[test]
+    fn normalization_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_conversion_options().normalization,
+            NormalizationForm::NFC
+        );
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--normalization",
+            "nfd",
+        ]);
+        assert_eq!(
+            cli_options.to_conversion_options().normalization,
+            NormalizationForm::NFD
+        );
+    }
+
+    #[test]
+    fn to_encoding_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "after.zip", "before.zip"]);
+        assert_eq!(cli_options.to_encoding.as_deref(), None);
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "after.zip",
+            "before.zip",
+            "--to-encoding",
+            "sjis",
+        ]);
+        assert_eq!(cli_options.to_encoding.as_deref(), Some("sjis"));
+
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "after.zip",
+            "before.zip",
+            "--to-encoding",
+            "sjis",
+            "--transliterate",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn utc_offset_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "after.zip", "before.zip"]);
+        assert_eq!(cli_options.to_timestamp_correction(), None);
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--source-utc-offset",
+            "+09:00",
+            "--target-utc-offset",
+            "-05:30",
+            "--add-utc-times",
+            "after.zip",
+            "before.zip",
+        ]);
+        assert_eq!(
+            cli_options.to_timestamp_correction(),
+            Some(TimestampCorrection {
+                source_utc_offset: 9 * 3600,
+                target_utc_offset: Some(-(5 * 3600 + 30 * 60)),
+                add_extended_timestamps: true,
+            })
+        );
+        assert_eq!(format_utc_offset(-(5 * 3600 + 30 * 60)), "-05:30");
+
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--add-utc-times",
+            "after.zip",
+            "before.zip",
+        ])
+        .is_err());
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--source-utc-offset",
+            "JST",
+            "after.zip",
+            "before.zip",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn legacy_compatible_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "after.zip", "before.zip"]);
+        assert_eq!(cli_options.legacy_compatible.as_deref(), None);
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--legacy-compatible",
+            "cp437",
+            "after.zip",
+            "before.zip",
+        ]);
+        assert_eq!(cli_options.legacy_compatible.as_deref(), Some("cp437"));
+
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--legacy-compatible",
+            "cp437",
+            "--to-encoding",
+            "sjis",
+            "after.zip",
+            "before.zip",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn report_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--report",
+            "report.json",
+            "--hash",
+        ]);
+        assert_eq!(cli_options.report.as_deref(), Some("report.json"));
+        assert_eq!(cli_options.to_output_options(None).hash_payloads, true);
+
+        assert!(
+            CLIOptions::try_parse_from(vec!["zifu", "before.zip", "after.zip", "--hash"]).is_err()
+        );
+    }
+
+    #[test]
+    fn entry_selector_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        let selector = cli_options.to_entry_selector().unwrap();
+        assert_eq!(selector.is_selected("テスト.txt"), true);
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--include",
+            "docs/**",
+            "--exclude",
+            "docs/*.bak",
+        ]);
+        let selector = cli_options.to_entry_selector().unwrap();
+        assert_eq!(selector.is_selected("docs/テスト.txt"), true);
+        assert_eq!(selector.is_selected("docs/sub/テスト.txt"), true);
+        assert_eq!(selector.is_selected("docs/テスト.bak"), false);
+        assert_eq!(selector.is_selected("src/テスト.txt"), false);
+
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip", "--include", "a[b"]);
+        assert!(cli_options.to_entry_selector().is_err());
+    }
+
+    #[test]
+    fn rename_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--rename",
+            r"s/^New Folder\///",
+            "--rename",
+            "s/TXT$/txt/i",
+        ]);
+        let conversion_options = cli_options.to_conversion_options();
+        assert_eq!(conversion_options.rename_rules.len(), 2);
+        assert_eq!(
+            zifu_core::rename_rules::apply_rename_rules(
+                &conversion_options.rename_rules,
+                "New Folder/テスト.Txt"
+            ),
+            "テスト.txt"
+        );
+        let job = Job {
+            input: PathBuf::from("before.zip"),
+            output: Some(PathBuf::from("after.zip")),
+        };
+        let reproduced = CLIOptions::parse_from(cli_options.to_command_line(&job, None));
+        assert_eq!(reproduced.rename, cli_options.rename);
+
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--rename",
+            "s/[/x/"
+        ])
+        .is_err());
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--rename",
+            "old/new"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn top_dir_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--strip-top-dir",
+            "--add-top-dir",
+            "docs/",
+        ]);
+        assert_eq!(cli_options.strip_top_dir, true);
+        let conversion_options = cli_options.to_conversion_options();
+        assert_eq!(conversion_options.strip_top_directory, None);
+        assert_eq!(
+            conversion_options.add_top_directory,
+            Some("docs".to_string())
+        );
+        let job = Job {
+            input: PathBuf::from("before.zip"),
+            output: Some(PathBuf::from("after.zip")),
+        };
+        let reproduced = CLIOptions::parse_from(cli_options.to_command_line(&job, None));
+        assert_eq!(reproduced.strip_top_dir, true);
+        assert_eq!(reproduced.add_top_dir, Some("docs".to_string()));
+
+        for directory in ["../docs", "/docs", "C:\\docs", "/"] {
+            assert!(CLIOptions::try_parse_from(vec![
+                "zifu",
+                "before.zip",
+                "after.zip",
+                "--add-top-dir",
+                directory
+            ])
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn long_list_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-l", "before.zip"]);
+        assert_eq!(cli_options.list, 1);
+        assert_eq!(cli_options.lists_long(), false);
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-ll", "before.zip"]);
+        assert_eq!(cli_options.list, 2);
+        assert_eq!(cli_options.lists_long(), true);
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--list", "--long", "before.zip"]);
+        assert_eq!(cli_options.lists_long(), true);
+        assert_eq!(cli_options.writes_archives(), false);
+        assert!(CLIOptions::try_parse_from(vec!["zifu", "--long", "before.zip"]).is_err());
+    }
+
+    #[test]
+    fn subcommand_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "extract",
+            "archive.zip",
+            "out",
+            "-e",
+            "sjis",
+            "--overwrite",
+        ]);
+        match cli_options.command {
+            Some(Command::Extract(args)) => {
+                assert_eq!(args.archive, "archive.zip");
+                assert_eq!(args.dest, "out");
+                assert_eq!(args.encoding, Some("sjis".to_string()));
+                assert_eq!(args.normalization, NormalizationArg::Nfc);
+                assert_eq!(args.overwrite, true);
+            }
+            _ => panic!("extract is not parsed as a subcommand"),
+        }
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "create",
+            "out.zip",
+            "dir",
+            "--unicode-path-fields",
+        ]);
+        match cli_options.command {
+            Some(Command::Create(args)) => {
+                assert_eq!(args.output, "out.zip");
+                assert_eq!(args.source, "dir");
+                assert_eq!(args.unicode_path_fields, true);
+            }
+            _ => panic!("create is not parsed as a subcommand"),
+        }
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert!(cli_options.command.is_none());
+        assert!(CLIOptions::try_parse_from(vec!["zifu", "extract", "archive.zip"]).is_err());
+    }
+
+    #[test]
+    fn post_compress_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.to_post_compression(), PostCompression::None);
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip.gz"]);
+        assert_eq!(cli_options.to_post_compression(), PostCompression::Gzip);
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip.gz",
+            "--post-compress",
+            "zstd",
+        ]);
+        assert_eq!(cli_options.to_post_compression(), PostCompression::Zstd);
+
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "before.zip",
+            "-i",
+            "--post-compress",
+            "gzip",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn timeout_args_parse_test() {
+        let started = Instant::now();
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.to_deadline(started), None);
+
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip", "--timeout", "30"]);
+        assert_eq!(
+            cli_options.to_deadline(started),
+            Some(started + Duration::from_secs(30))
+        );
+        assert_eq!(
+            cli_options
+                .to_output_options(cli_options.to_deadline(started))
+                .deadline,
+            Some(started + Duration::from_secs(30))
+        );
+    }
+
+    #[test]
+    fn batch_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_jobs().unwrap(),
+            vec![Job {
+                input: PathBuf::from("before.zip"),
+                output: Some(PathBuf::from("after.zip")),
+            }]
+        );
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "a.zip", "b.zip", "c.zip", "-i"]);
+        let jobs = cli_options.to_jobs().unwrap();
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[2].input, Path::new("c.zip"));
+        assert_eq!(jobs[2].output, None);
+
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "dir/a.zip", "b.zip", "--output-dir", "fixed"]);
+        let jobs = cli_options.to_jobs().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].output, Some(Path::new("fixed").join("a.zip")));
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-c", "-b", "a.zip", "b.zip"]);
+        let jobs = cli_options.to_jobs().unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[1].output, None);
+
+        // Non-UTF-8 paths reach the jobs as they are (not replaced with U+FFFD)
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStringExt;
+            let input = OsString::from_vec(b"dir/\x82\xa0.zip".to_vec());
+            let cli_options = CLIOptions::parse_from(vec![
+                OsString::from("zifu"),
+                input.clone(),
+                OsString::from("--output-dir"),
+                OsString::from("fixed"),
+            ]);
+            let jobs = cli_options.to_jobs().unwrap();
+            assert_eq!(jobs[0].input, PathBuf::from(&input));
+            assert_eq!(
+                jobs[0].output,
+                Some(Path::new("fixed").join(OsString::from_vec(b"\x82\xa0.zip".to_vec())))
+            );
+        }
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "a.zip", "b.zip", "c.zip"]);
+        assert!(cli_options.to_jobs().is_err());
+    }
+
+    #[test]
+    fn combine_exit_codes_test() {
+        use ExitStatus::*;
+        assert_eq!(ExitStatus::combine(&[Ok, Ok]), Ok);
+        assert_eq!(ExitStatus::combine(&[Ok, NotApplied]), NotApplied);
+        assert_eq!(ExitStatus::combine(&[NotApplied, Failure, Ok]), Failure);
+        assert_eq!(ExitStatus::combine(&[Rejected, NeedsFix]), Rejected);
+        assert_eq!(
+            ExitStatus::combine(&[ParseError, Ok, ParseError]),
+            ParseError
+        );
+        assert_eq!(
+            ExitStatus::combine(&[ParseError, Rejected, DetectionFailure]),
+            Failure
+        );
+        assert_eq!(ExitStatus::combine(&[]), Ok);
+    }
+
+    #[test]
+    fn exit_status_from_error_test() {
+        use zifu_core::encryption::UnsupportedEncryptionScheme;
+
+        assert_eq!(
+            ExitStatus::from_error(&ArchiveError::Parse(anyhow::anyhow!("broken")).into()),
+            ExitStatus::ParseError
+        );
+        assert_eq!(
+            ExitStatus::from_error(&ArchiveError::NoEncodingCandidate.into()),
+            ExitStatus::DetectionFailure
+        );
+        assert_eq!(
+            ExitStatus::from_error(&InvalidArgument::SameInputOutput.into()),
+            ExitStatus::Usage
+        );
+        assert_eq!(
+            ExitStatus::from_error(
+                &UnsupportedEncryptionError {
+                    scheme: UnsupportedEncryptionScheme::CentralDirectory(None),
+                    entries: vec![0],
+                }
+                .into()
+            ),
+            ExitStatus::UnsupportedFeature
+        );
+        assert_eq!(
+            ExitStatus::from_error(&std::io::Error::from(std::io::ErrorKind::NotFound).into()),
+            ExitStatus::Failure
+        );
+        assert_eq!(ExitStatus::NeedsFix.code(), 2);
+        assert_eq!(ExitStatus::DetectionFailure.code(), 5);
+    }
+
+    #[test]
+    fn stdio_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-", "-"]);
+        assert_eq!(
+            cli_options.to_jobs().unwrap(),
+            vec![Job {
+                input: PathBuf::from("-"),
+                output: Some(PathBuf::from("-")),
+            }]
+        );
+        assert_eq!(cli_options.to_behavior_flags().ask_user, false);
+        assert_eq!(cli_options.to_post_compression(), PostCompression::None);
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-i", "a.zip", "-"]);
+        assert!(matches!(
+            cli_options.to_jobs(),
+            Err(InvalidArgument::StdioInBatch)
+        ));
+    }
+
+    #[test]
+    fn recover_orphans_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.recover_orphans, false);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--recover-orphans", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.recover_orphans, true);
+    }
+
+    #[test]
+    fn repair_offsets_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.repair_offsets, false);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--repair-offsets", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.repair_offsets, true);
+        assert!(cli_options
+            .to_command_line(
+                &Job {
+                    input: PathBuf::from("before.zip"),
+                    output: Some(PathBuf::from("after.zip")),
+                },
+                None
+            )
+            .contains(&"--repair-offsets".to_string()));
+    }
+
+    #[test]
+    fn verifies_output_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.verifies_output(), false);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--post-check-crc", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.verifies_output(), true);
+        // The original is never replaced with an unverified archive
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-i", "before.zip"]);
+        assert_eq!(cli_options.verifies_output(), true);
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--post-check-crc",
+            "--span-size=1M",
+            "before.zip",
+            "after.zip"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn repair_metadata_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.repair_metadata, false);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--repair-metadata", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.repair_metadata, true);
+        assert_eq!(
+            describe_repair(&EntryRepair {
+                index: 3,
+                crc32: Some((0, 0x3610A686)),
+                uncompressed_size: Some((0, 5)),
+                data_descriptor_dropped: true,
+            }),
+            "#3: CRC-32 00000000 -> 3610A686, uncompressed size 0 -> 5, data descriptor dropped"
+        );
+    }
+
+    #[test]
+    fn name_mismatch_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.name_mismatch, NameMismatchArg::Cd);
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--name-mismatch",
+            "local",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(
+            NameMismatchPolicy::from(cli_options.name_mismatch),
+            NameMismatchPolicy::PreferLocalHeader
+        );
+    }
+
+    #[test]
+    fn made_by_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.to_conversion_options().made_by, None);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--made-by", "unix", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_conversion_options().made_by,
+            Some(HostSystem::Unix)
+        );
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--made-by",
+            "amiga",
+            "before.zip",
+            "after.zip",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn permission_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.to_permission_normalization(), None);
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--file-mode",
+            "644",
+            "--strip-special-bits",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(
+            cli_options.to_permission_normalization(),
+            Some(PermissionNormalization {
+                file_mode: Some(0o644),
+                directory_mode: None,
+                strip_special_bits: true,
+            })
+        );
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--dir-mode",
+            "789",
+            "before.zip",
+            "after.zip",
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn strip_comments_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.strip_comments, false);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--strip-comments", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.strip_comments, true);
+    }
+
+    #[test]
+    fn set_comment_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--set-comment",
+            "Fixed by zifu",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(cli_options.set_comment.as_deref(), Some("Fixed by zifu"));
+        assert!(cli_options
+            .to_command_line(
+                &Job {
+                    input: PathBuf::from("before.zip"),
+                    output: Some(PathBuf::from("after.zip")),
+                },
+                None
+            )
+            .windows(2)
+            .any(|args| args == ["--set-comment", "Fixed by zifu"]));
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--comment-file",
+            "comment.txt",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(cli_options.comment_file.as_deref(), Some("comment.txt"));
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--set-comment",
+            "Fixed by zifu",
+            "--comment-file",
+            "comment.txt",
+            "before.zip",
+            "after.zip"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn list_encodings_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--list-encodings"]);
+        assert_eq!(cli_options.list_encodings.as_deref(), Some(""));
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--list-encodings=jis"]);
+        assert_eq!(cli_options.list_encodings.as_deref(), Some("jis"));
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip"]);
+        assert_eq!(cli_options.list_encodings, None);
+    }
+
+    #[test]
+    fn env_defaults_test() {
+        let env = |name: &str| match name {
+            "ZIFU_ENCODING" => Some("cp437".to_string()),
+            "ZIFU_UTF8_FIRST" => Some("1".to_string()),
+            _ => None,
+        };
+        let mut cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip"]);
+        cli_options.apply_env_defaults(env);
+        assert_eq!(cli_options.encoding.as_deref(), Some("cp437"));
+        assert_eq!(cli_options.utf8, true);
+
+        let mut cli_options = CLIOptions::parse_from(vec!["zifu", "-e", "sjis", "before.zip"]);
+        cli_options.apply_env_defaults(env);
+        assert_eq!(cli_options.encoding.as_deref(), Some("sjis"), "flags win");
+        let mut cli_options = CLIOptions::parse_from(vec!["zifu", "-a", "before.zip"]);
+        cli_options.apply_env_defaults(env);
+        assert_eq!(cli_options.encoding, None, "-a ignores ZIFU_ENCODING");
+
+        let mut cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip"]);
+        cli_options.apply_env_defaults(|name| (name == "ZIFU_UTF8_FIRST").then(|| "0".to_string()));
+        assert_eq!(cli_options.utf8, false);
+    }
+
+    #[test]
+    fn lang_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--lang", "zh-hans", "before.zip"]);
+        assert_eq!(cli_options.lang, Some(Language::ZhHans));
+        let mut cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip"]);
+        cli_options.apply_env_defaults(|name| (name == "ZIFU_LANG").then(|| "JA".to_string()));
+        assert_eq!(cli_options.lang, Some(Language::Ja));
+        let mut cli_options = CLIOptions::parse_from(vec!["zifu", "--lang", "en", "before.zip"]);
+        cli_options.apply_env_defaults(|name| (name == "ZIFU_LANG").then(|| "ja".to_string()));
+        assert_eq!(cli_options.lang, Some(Language::En), "flags win");
+
+        assert_eq!(Language::from_locale_name("ja_JP.UTF-8"), Language::Ja);
+        assert_eq!(Language::from_locale_name("zh-CN"), Language::ZhHans);
+        assert_eq!(Language::from_locale_name("zh_TW.Big5"), Language::En);
+        assert_eq!(Language::from_locale_name("C"), Language::En);
+        assert_eq!(
+            i18n::translate(Language::Ja, "You do not have to apply this tool."),
+            "このツールを適用する必要はありません。"
+        );
+        assert_eq!(
+            i18n::translate(Language::ZhHans, "untranslated message"),
+            "untranslated message"
+        );
+        assert_eq!(
+            trf("{} of {} entries would be renamed.", &[&1, &3]),
+            "1 of 3 entries would be renamed."
+        );
+    }
+
+    #[test]
+    fn translation_catalogs_test() {
+        let catalogs = [Language::Ja, Language::ZhHans].map(i18n::catalog);
+        for catalog in catalogs.iter() {
+            for (key, translation) in catalog.iter() {
+                for other in catalogs.iter() {
+                    assert!(
+                        other.iter().any(|(other_key, _)| other_key == key),
+                        "{:?} is translated in every language",
+                        key
+                    );
+                }
+                assert_eq!(
+                    key.matches("{}").count(),
+                    translation.matches("{}").count(),
+                    "{:?} keeps the placeholders",
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn warning_translation_test() {
+        let message = "{} bytes of junk follow the end of central directory record (ignored and dropped from the output)";
+        assert_eq!(
+            format_warning(Language::En, message, &[&4]),
+            "Warning: 4 bytes of junk follow the end of central directory record (ignored and dropped from the output)"
+        );
+        // The template is translated before the placeholders are replaced
+        assert_eq!(
+            format_warning(Language::Ja, message, &[&4]),
+            "警告: セントラルディレクトリー終端レコードの後に4バイトの不要なデータがあります（無視して出力から取り除きます）"
+        );
+        assert_eq!(
+            format_warning(Language::ZhHans, "cannot repair #{} ({})", &[&3, &"broken"]),
+            "警告：无法修复 #3（broken）"
+        );
+    }
+
+    #[test]
+    fn origin_hints_translation_test() {
+        // Same as those of zifu_core in English
+        for name in [
+            "before.zip",
+            "after.zip",
+            "mac_finder_emulate.zip",
+            "utf8_ascii_mix.zip",
+        ] {
+            let hints = InputZIPArchive::from_path(
+                Path::new(env!("CARGO_MANIFEST_DIR"))
+                    .join("tests/assets")
+                    .join(name),
+            )
+            .unwrap()
+            .origin_hints();
+            assert_eq!(origin_summary(&hints), hints.creation_summary(), "{}", name);
+            assert_eq!(origin_description(&hints), hints.description(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn color_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--color", "never", "before.zip"]);
+        assert_eq!(cli_options.color, Some(ColorChoice::Never));
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            return move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            };
+        };
+        let mut cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--color", "always", "before.zip"]);
+        cli_options.apply_env_defaults(env(&[("NO_COLOR", "1")]));
+        assert_eq!(cli_options.color, Some(ColorChoice::Always), "flags win");
+        assert_eq!(ColorChoice::from_env(env(&[])), None);
+        assert_eq!(
+            ColorChoice::from_env(env(&[("ZIFU_COLOR", "always"), ("NO_COLOR", "1")])),
+            Some(ColorChoice::Always)
+        );
+        assert_eq!(
+            ColorChoice::from_env(env(&[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")])),
+            Some(ColorChoice::Never)
+        );
+        assert_eq!(ColorChoice::from_env(env(&[("NO_COLOR", "")])), None);
+        assert_eq!(
+            ColorChoice::from_env(env(&[("CLICOLOR_FORCE", "1")])),
+            Some(ColorChoice::Always)
+        );
+        assert_eq!(ColorChoice::from_env(env(&[("CLICOLOR_FORCE", "0")])), None);
+        assert_eq!(
+            ColorChoice::from_env(env(&[("CLICOLOR", "0")])),
+            Some(ColorChoice::Never)
+        );
+    }
+
+    #[test]
+    fn scoring_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--scoring", "a.zip", "b.zip"]);
+        assert!(cli_options.scoring);
+        let jobs = cli_options.to_jobs().unwrap();
+        assert!(cli_options
+            .to_command_line(&jobs[0], Some("Shift_JIS"))
+            .contains(&"--scoring".to_string()));
+    }
+
+    #[test]
+    fn compare_encodings_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--compare-encodings", "a.zip"]);
+        assert_eq!(cli_options.compare_encodings, Some(10));
+        assert_eq!(cli_options.writes_archives(), false);
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--compare-encodings=3", "a.zip"]);
+        assert_eq!(cli_options.compare_encodings, Some(3));
+    }
+
+    #[test]
+    fn span_size_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--span-size",
+            "700M",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(cli_options.span_size, Some(ByteSize::mb(700)));
+        assert!(cli_options.check_span_size().is_ok());
+
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--span-size", "1k", "before.zip", "after.zip"]);
+        assert!(matches!(
+            cli_options.check_span_size(),
+            Err(InvalidArgument::SpanSizeTooSmall { .. })
+        ));
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--span-size",
+            "1MiB",
+            "before.zip",
+            "after.zip.gz",
+        ]);
+        assert!(matches!(
+            cli_options.check_span_size(),
+            Err(InvalidArgument::SpanSizeConflict { .. })
+        ));
+    }
+
+    #[test]
+    fn summarize_encryptions_test() {
+        use zifu_core::encryption::{AesInfo, AesStrength};
+
+        let aes = EntryEncryption::Aes(Some(AesInfo {
+            vendor_version: 2,
+            strength: AesStrength::Aes256,
+            compression_method: 8,
+        }));
+        let summary = summarize_encryptions(
+            vec![
+                Some(aes.clone()),
+                None,
+                Some(EntryEncryption::ZipCrypto),
+                Some(aes),
+            ]
+            .into_iter(),
+        );
+        assert_eq!(
+            summary,
+            vec![
+                ("AES-256 encrypted, method deflate".to_string(), 2),
+                ("ZipCrypto encrypted".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_size_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--chunk-size",
+            "1MiB",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(cli_options.chunk_size, Some(ByteSize::mib(1)));
+        assert_eq!(
+            cli_options.to_output_options(None).chunk_size(),
+            1024 * 1024
+        );
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_output_options(None).chunk_size(),
+            zifu_core::output::DEFAULT_CHUNK_SIZE
+        );
+    }
+
+    #[test]
+    fn align_args_parse_test() {
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--align", "4", "app.apk", "fixed.apk"]);
+        assert_eq!(cli_options.to_output_options(None).alignment, Some(4));
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "app.apk", "fixed.apk"]);
+        assert_eq!(cli_options.to_output_options(None).alignment, None);
+        assert!(
+            CLIOptions::try_parse_from(vec!["zifu", "--align", "0", "app.apk", "fixed.apk"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn preserve_unchanged_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_output_options(None).preserve_unchanged,
+            false
+        );
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "-f",
+            "--preserve-unchanged",
+            "before.zip",
+            "after.zip",
+        ]);
+        assert_eq!(cli_options.to_output_options(None).preserve_unchanged, true);
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "--preserve-unchanged",
+            "--span-size=1M",
+            "before.zip",
+            "after.zip"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn version_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--version", "--verbose"]);
+        assert_eq!(cli_options.show_version, true);
+        assert_eq!(cli_options.verbose, 1);
+        assert!(CLIOptions::try_parse_from(vec!["zifu"]).is_err());
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-vv", "before.zip"]);
+        assert_eq!(cli_options.verbose, 2);
+        assert_eq!(cli_options.show_version, false);
+    }
+
+    #[test]
+    fn log_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "-a",
+            "--normalization",
+            "nfd",
+            "--include",
+            "*.txt",
+            "--span-size",
+            "1M",
+            "--log",
+            "zifu.log",
+            "-b",
+            "--output-dir",
+            "out",
+            "--",
+            "a.zip",
+            "-b.zip",
+        ]);
+        assert_eq!(cli_options.log.as_deref(), Some("zifu.log"));
+        let jobs = cli_options.to_jobs().unwrap();
+        let args = cli_options.to_command_line(&jobs[1], Some("Shift_JIS"));
+        let reproduced = CLIOptions::parse_from(&args);
+        assert_eq!(reproduced.encoding.as_deref(), Some("Shift_JIS"));
+        assert_eq!(reproduced.auto_detect, false);
+        assert_eq!(reproduced.normalization, NormalizationArg::Nfd);
+        assert_eq!(reproduced.include, vec!["*.txt"]);
+        assert_eq!(reproduced.span_size, cli_options.span_size);
+        assert_eq!(reproduced.log, None);
+        assert_eq!(
+            reproduced.to_jobs().unwrap(),
+            vec![Job {
+                input: PathBuf::from("-b.zip"),
+                output: jobs[1].output.clone(),
+            }]
+        );
+        assert_eq!(
+            session_log::command_line(&["zifu", "--include", "*.txt", "it's.zip"]),
+            r"zifu --include '*.txt' 'it'\''s.zip'"
+        );
+    }
+
+    #[test]
+    fn diff_format_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "--format",
+            "diff",
+            "--exclude",
+            "b/*",
+            "a.zip",
+        ]);
+        assert_eq!(cli_options.format, OutputFormat::Diff);
+        assert_eq!(cli_options.writes_archives(), false);
+        assert_eq!(cli_options.to_jobs().unwrap().len(), 1);
+        let entry = |index: usize, raw: &[u8], explicit_utf8: bool, name: &str, changes: bool| {
+            return RenamePlanEntry {
+                index,
+                raw: raw.to_vec(),
+                explicit_utf8,
+                current_name: name.to_string(),
+                proposed_name: name.to_string(),
+                changes,
+            };
+        };
+        let plan = vec![
+            entry(0, b"\x83e\x83X\x83g.txt", false, "テスト.txt", true),
+            entry(1, b"ascii.txt", false, "ascii.txt", false),
+            entry(2, b"b/\x83e.txt", false, "b/テ.txt", true),
+        ];
+        assert_eq!(
+            rename_plan_to_diff("a.zip", &plan, &cli_options.to_entry_selector().unwrap()),
+            "--- a.zip\n+++ a.zip\n@@ #0 @@\n- \\x83e\\x83X\\x83g.txt\n+ テスト.txt\n"
+        );
+    }
+
+    #[test]
+    fn table_format_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--format", "tsv", "a.zip"]);
+        assert_eq!(cli_options.format, OutputFormat::Tsv);
+        assert_eq!(cli_options.lists_entries(), true);
+        assert_eq!(cli_options.writes_archives(), false);
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-l", "--format", "csv", "a.zip"]);
+        assert_eq!(cli_options.format, OutputFormat::Csv);
+        assert_eq!(cli_options.lists_entries(), true);
+        let entry = |name: &str, raw: &[u8], encoding_type: FileNameEncodingType| {
+            return FileNameEntry {
+                name: name.to_string(),
+                encoding_type,
+                is_directory: false,
+                is_symlink: false,
+                encryption: None,
+                file_name_raw: raw.to_vec(),
+                lossless: true,
+            };
+        };
+        let entries = vec![
+            entry(
+                "テ.txt",
+                b"\x83e.txt",
+                FileNameEncodingType::ImplicitNonASCII,
+            ),
+            entry(
+                "a,\"b\".txt",
+                b"a,\"b\".txt",
+                FileNameEncodingType::ImplicitASCII,
+            ),
+        ];
+        let decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+        assert_eq!(
+            entries_to_table(&entries, &[], &*decoder, OutputFormat::Csv),
+            "index,raw_name_hex,name,encoding_type,encoding,size,method\n\
+             0,83652e747874,テ.txt,implicit_non_ascii,Shift_JIS,,\n\
+             1,612c2262222e747874,\"a,\"\"b\"\".txt\",implicit_ascii,ASCII,,\n"
+        );
+        assert_eq!(
+            escape_table_field("a\tb\\c\nd", OutputFormat::Tsv),
+            "a\\tb\\\\c\\nd"
+        );
+        assert_eq!(escape_table_field("a,b", OutputFormat::Tsv), "a,b");
+    }
+
+    #[test]
+    fn ndjson_format_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--format", "ndjson", "a.zip"]);
+        assert_eq!(cli_options.format, OutputFormat::Ndjson);
+        assert_eq!(cli_options.lists_entries(), true);
+        assert_eq!(cli_options.writes_archives(), false);
+        let zip_file = InputZIPArchive::from_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/assets/before.zip"
+        ))
+        .unwrap();
+        let decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+        let mut output = vec![];
+        write_entries_ndjson(
+            &mut output,
+            zip_file.entries(&*decoder).zip(zip_file.entries_metadata()),
+            &*decoder,
+        )
+        .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.lines().count(), 1);
+        let object: serde_json::Value = serde_json::from_str(output.trim_end()).unwrap();
+        assert_eq!(object["index"], 0);
+        assert_eq!(object["raw_name_hex"], "8365835883672e747874");
+        assert_eq!(object["name"], "テスト.txt");
+        assert_eq!(object["encoding_type"], "implicit_non_ascii");
+        assert_eq!(object["encoding"], "Shift_JIS");
+        assert_eq!(object["lossless"], true);
+        assert_eq!(object["size"], 9);
+        assert_eq!(object["method"], "store");
+    }
+
+    #[test]
+    fn dry_run_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--dry-run", "a.zip"]);
+        assert!(cli_options.dry_run);
+        assert_eq!(cli_options.writes_archives(), false);
+        assert!(cli_options
+            .to_command_line(&cli_options.to_jobs().unwrap()[0], None)
+            .contains(&"--dry-run".to_string()));
+        assert_eq!(
+            ExitStatus::combine(&[ExitStatus::Ok, ExitStatus::NeedsFix, ExitStatus::NotApplied]),
+            ExitStatus::NeedsFix
+        );
+    }
+
+    #[test]
+    fn backup_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-i", "--backup", "a.zip"]);
+        assert_eq!(cli_options.backup.as_deref(), Some(".bak"));
+        assert_eq!(cli_options.input, Path::new("a.zip"));
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-i", "--backup=.orig", "a.zip"]);
+        assert_eq!(cli_options.backup.as_deref(), Some(".orig"));
+        assert!(cli_options
+            .to_command_line(&cli_options.to_jobs().unwrap()[0], None)
+            .contains(&"--backup=.orig".to_string()));
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-i", "a.zip"]);
+        assert_eq!(cli_options.backup, None);
+        assert!(CLIOptions::try_parse_from(vec!["zifu", "--backup", "a.zip", "b.zip"]).is_err());
+        assert!(CLIOptions::try_parse_from(vec!["zifu", "-i", "--backup=", "a.zip"]).is_err());
+    }
+
+    #[test]
+    fn temporary_file_test() {
+        let path = std::env::temp_dir().join(format!("zifu-test-{}.tmp", std::process::id()));
+
+        std::fs::write(&path, b"").unwrap();
+        drop(TemporaryFile::new(&path));
+        assert!(!path.exists());
+
+        std::fs::write(&path, b"").unwrap();
+        TemporaryFile::new(&path).keep();
+        assert!(path.exists());
+        remove_temporary_file();
+        assert!(path.exists());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn long_path_test() {
+        use long_path::to_extended_length;
+        assert_eq!(to_extended_length(r"C:\a\b.zip"), r"\\?\C:\a\b.zip");
+        assert_eq!(
+            to_extended_length(r"\\server\share\b.zip"),
+            r"\\?\UNC\server\share\b.zip"
+        );
+        assert_eq!(to_extended_length(r"\\?\C:\b.zip"), r"\\?\C:\b.zip");
+        assert_eq!(to_extended_length(r"\\.\pipe\zifu"), r"\\.\pipe\zifu");
+        #[cfg(not(windows))]
+        assert_eq!(
+            extended_length_path(Path::new("a/b.zip")),
+            Path::new("a/b.zip")
+        );
+    }
+
+    #[test]
+    fn replace_file_test() {
+        let dir = std::env::temp_dir().join(format!("zifu-replace-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let temporary = dir.join("a.zip.tmp");
+        let dest = dir.join("a.zip");
+        let backup = dir.join("a.zip.bak");
+
+        std::fs::write(&dest, b"original").unwrap();
+        std::fs::write(&temporary, b"converted").unwrap();
+        replace_file(&temporary, &dest, Some(&backup)).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"converted");
+        assert_eq!(std::fs::read(&backup).unwrap(), b"original");
+        assert!(!temporary.exists());
+
+        std::fs::write(&temporary, b"converted again").unwrap();
+        replace_file(&temporary, &dest, None).unwrap();
+        assert_eq!(std::fs::read(&dest).unwrap(), b"converted again");
+        assert_eq!(std::fs::read(&backup).unwrap(), b"original");
+
+        // The original must survive a failure
+        assert!(replace_file(&temporary, &dest, None).is_err());
+        assert_eq!(std::fs::read(&dest).unwrap(), b"converted again");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn summary_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "-c", "--summary", "json", "a.zip"]);
+        assert_eq!(cli_options.summary, Some(SummaryFormat::Json));
+        let job = &cli_options.to_jobs().unwrap()[0];
+        assert!(
+            session_log::command_line(&cli_options.to_command_line(job, None))
+                .contains("--summary json")
+        );
+        let summary = ArchiveSummary {
+            universal: Some(false),
+            ..Default::default()
+        };
+        let json = run_summary_to_json(
+            ExitStatus::NeedsFix,
+            vec![summary.to_json(job, ExitStatus::NeedsFix, None)],
+        );
+        assert_eq!(json["exit_code"], 2);
+        assert_eq!(json["archives"][0]["status"], "needs_fix");
+        assert_eq!(json["archives"][0]["universal"], false);
+        assert_eq!(
+            json["archives"][0]["legacy_encoding"],
+            serde_json::Value::Null
+        );
+    }
+
+    #[test]
+    fn name_editor_test() {
+        use name_editor::{parse_edit_command, process_answer, EditCommand};
+        assert_eq!(process_answer("\n"), Answer::Yes);
+        assert_eq!(process_answer("y\n"), Answer::Yes);
+        assert_eq!(process_answer("N\n"), Answer::No);
+        assert_eq!(process_answer("edit\n"), Answer::Edit);
+
+        assert_eq!(parse_edit_command("\n"), EditCommand::Done);
+        assert_eq!(parse_edit_command("n\r\n"), EditCommand::Abort);
+        assert_eq!(
+            parse_edit_command("#2 a b.txt\n"),
+            EditCommand::Rename {
+                index: 2,
+                name: "a b.txt".to_string()
+            }
+        );
+        assert_eq!(parse_edit_command("x y\n"), EditCommand::Invalid);
+        assert_eq!(parse_edit_command("2\n"), EditCommand::Invalid);
+
+        let plan = vec![RenamePlanEntry {
+            index: 0,
+            raw: b"\x83e.txt".to_vec(),
+            explicit_utf8: false,
+            current_name: "テ.txt".to_string(),
+            proposed_name: "テ.txt".to_string(),
+            changes: true,
+        }];
+        let edits = edit_names(&mut std::io::Cursor::new("9 x.txt\n0 て.txt\n\n"), &plan)
+            .unwrap()
+            .unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[&b"\x83e.txt".to_vec()], "て.txt");
+        assert_eq!(
+            edit_names(&mut std::io::Cursor::new("0 て.txt\nn\n"), &plan).unwrap(),
+            None
+        );
+    }
+
+    #[cfg(feature = "tui")]
+    #[test]
+    fn tui_candidates_test() {
+        let preferred = vec![
+            <dyn IDecoder>::from_encoding_name("cp437").unwrap(),
+            <dyn IDecoder>::from_encoding_name("sjis").unwrap(),
+        ];
+        let candidates = tui::candidate_encodings(preferred);
+        assert_eq!(candidates[0].encoding_name(), "CP437");
+        assert_eq!(candidates[1].encoding_name(), "Shift_JIS");
+        let names: Vec<&str> = candidates.iter().map(|c| c.encoding_name()).collect();
+        assert_eq!(names.iter().filter(|&&name| name == "Shift_JIS").count(), 1);
+        assert!(names.contains(&"EUC-KR"));
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "--tui", "a.zip", "b.zip"]);
+        assert!(cli_options.uses_tui());
+        assert_eq!(cli_options.to_behavior_flags().ask_user, false);
+        assert!(CLIOptions::try_parse_from(vec!["zifu", "--tui", "-c", "a.zip"]).is_err());
+    }
+
+    #[test]
+    fn on_collision_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "a.zip", "b.zip"]);
+        assert_eq!(cli_options.on_collision, CollisionStrategy::Abort);
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--on-collision", "suffix", "a.zip", "b.zip"]);
+        assert_eq!(cli_options.on_collision, CollisionStrategy::Suffix);
+
+        let entry = |index: usize, name: &str, proposed_name: &str| {
+            return RenamePlanEntry {
+                index,
+                raw: name.as_bytes().to_vec(),
+                explicit_utf8: false,
+                current_name: name.to_string(),
+                proposed_name: proposed_name.to_string(),
+                changes: true,
+            };
+        };
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "--exclude", "b/*", "a.zip", "b.zip"]);
+        let plan = selected_plan(
+            vec![entry(0, "a/x", "a/y"), entry(1, "b/x", "b/y")],
+            &cli_options.to_entry_selector().unwrap(),
+        );
+        assert_eq!(plan[0].proposed_name, "a/y");
+        assert_eq!(plan[1].proposed_name, "b/x");
+        assert!(!plan[1].changes);
+    }
+
+    #[test]
+    fn sanitize_paths_args_parse_test() {
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip", "--sanitize-paths"]);
+        assert_eq!(cli_options.to_conversion_options().sanitize_paths, true);
+        assert!(cli_options
+            .to_command_line(&cli_options.to_jobs().unwrap()[0], None)
+            .contains(&"--sanitize-paths".to_string()));
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(cli_options.to_conversion_options().sanitize_paths, false);
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--sanitize-paths",
+            "--to-encoding",
+            "sjis"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn fix_reserved_names_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--fix-reserved-names",
+        ]);
+        assert_eq!(cli_options.to_conversion_options().fix_reserved_names, true);
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_conversion_options().fix_reserved_names,
+            false
+        );
+    }
+
+    #[test]
+    fn trim_trailing_dots_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--trim-trailing-dots",
+        ]);
+        let conversion_options = cli_options.to_conversion_options();
+        assert_eq!(conversion_options.trim_trailing_dots, true);
+        assert_eq!(conversion_options.fix_reserved_names, false);
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_conversion_options().trim_trailing_dots,
+            false
+        );
+    }
+
+    #[test]
+    fn truncate_long_names_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--truncate-long-names",
+        ]);
+        assert_eq!(
+            cli_options.to_conversion_options().truncate_long_names,
+            true
+        );
+        let job = Job {
+            input: PathBuf::from("before.zip"),
+            output: Some(PathBuf::from("after.zip")),
+        };
+        let reproduced = CLIOptions::parse_from(cli_options.to_command_line(&job, None));
+        assert_eq!(reproduced.truncate_long_names, true);
+
+        let cli_options = CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip"]);
+        assert_eq!(
+            cli_options.to_conversion_options().truncate_long_names,
+            false
+        );
+    }
+
+    #[test]
+    fn target_os_args_parse_test() {
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--target-os",
+            "macos",
+        ]);
+        assert_eq!(cli_options.target_os, Some(TargetOSArg::Macos));
+        assert_eq!(
+            cli_options
+                .to_conversion_options()
+                .substitute_forbidden_characters,
+            None
+        );
+
+        let cli_options = CLIOptions::parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--target-os",
+            "windows",
+            "--substitute-forbidden-chars",
+        ]);
+        assert_eq!(
+            cli_options
+                .to_conversion_options()
+                .substitute_forbidden_characters,
+            Some(TargetOS::Windows)
+        );
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--substitute-forbidden-chars"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn dedupe_args_parse_test() {
+        let cli_options =
+            CLIOptions::parse_from(vec!["zifu", "before.zip", "after.zip", "--dedupe"]);
+        assert_eq!(cli_options.dedupe, true);
+        assert!(cli_options
+            .to_command_line(&cli_options.to_jobs().unwrap()[0], None)
+            .contains(&"--dedupe".to_string()));
+        assert!(CLIOptions::try_parse_from(vec![
+            "zifu",
+            "before.zip",
+            "after.zip",
+            "--dedupe",
+            "--to-encoding",
+            "sjis"
+        ])
+        .is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::HashMap;
+use std::io::BufRead;
+
+use zifu_core::RenamePlanEntry;
+
+use crate::i18n::{tr, trf};
+
+/// Answer to "Are these file names correct? [Y/n/e]"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Answer {
+    /// Converts the archive as listed (default)
+    Yes,
+    /// Cancels the conversion
+    No,
+    /// Edits some names before the conversion
+    Edit,
+}
+
+/// Interprets a line input for the confirmation prompt
+///
+/// # Arguments
+///
+/// * `ans` - line input by the user
+pub fn process_answer(ans: &str) -> Answer {
+    return match ans.trim_start().chars().next() {
+        Some('n') | Some('N') => Answer::No,
+        Some('e') | Some('E') => Answer::Edit,
+        None | Some(_) => Answer::Yes,
+    };
+}
+
+/// Command input in the editing mode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditCommand {
+    /// Finishes editing and converts the archive
+    Done,
+    /// Cancels the conversion
+    Abort,
+    /// Renames the entry of `index` (in the central directory) to `name`
+    Rename { index: usize, name: String },
+    /// Unrecognized input
+    Invalid,
+}
+
+/// Interprets a line input in the editing mode (`<index> <new name>`, empty, or `n`)
+///
+/// # Arguments
+///
+/// * `line` - line input by the user
+pub fn parse_edit_command(line: &str) -> EditCommand {
+    let line = line.trim_end_matches(['\r', '\n']);
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() {
+        return EditCommand::Done;
+    }
+    if trimmed == "n" || trimmed == "N" {
+        return EditCommand::Abort;
+    }
+    let (index, name) = match trimmed.split_once(' ') {
+        Some(pair) => pair,
+        None => return EditCommand::Invalid,
+    };
+    // Spaces after the separator are part of the name
+    return match index.trim_start_matches('#').parse::<usize>() {
+        Ok(index) if !name.is_empty() => EditCommand::Rename {
+            index,
+            name: name.to_string(),
+        },
+        _ => EditCommand::Invalid,
+    };
+}
+
+/// Lets the user rename entries by index and returns the new names keyed by the byte sequences of the original names
+///
+/// Returns `Ok(None)` if the user cancels the conversion.
+/// The result is meant to be fed to `InputZIPArchive::set_rename_hook`.
+///
+/// # Arguments
+///
+/// * `input` - where the commands are read from (stdin)
+/// * `plan` - rename plan of the archive
+pub fn edit_names<R: BufRead>(
+    input: &mut R,
+    plan: &[RenamePlanEntry],
+) -> std::io::Result<Option<HashMap<Vec<u8>, String>>> {
+    for entry in plan.iter() {
+        eprintln!("#{} {}", entry.index, entry.proposed_name);
+    }
+    let mut edits = HashMap::new();
+    loop {
+        eprint!(
+            "{}",
+            tr("Rename an entry by \"<index> <new name>\" (empty: done, n: cancel): ")
+        );
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            // EOF
+            return Ok(Some(edits));
+        }
+        match parse_edit_command(&line) {
+            EditCommand::Done => return Ok(Some(edits)),
+            EditCommand::Abort => return Ok(None),
+            EditCommand::Rename { index, name } => {
+                match plan.iter().find(|entry| entry.index == index) {
+                    Some(entry) => {
+                        eprintln!("#{} {} -> {}", index, entry.proposed_name, name);
+                        edits.insert(entry.raw.clone(), name);
+                    }
+                    None => eprintln!("{}", trf("No entry #{}", &[&index])),
+                }
+            }
+            EditCommand::Invalid => {
+                eprintln!("{}", trf("Invalid input: {}", &[&line.trim_end()]))
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::is_stdio;
+use crate::long_path::extended_length_path;
+
+/// Compressor that the output archive is streamed through (e.g. for zip-in-gzip delivery pipelines)
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostCompression {
+    /// Writes the archive as it is
+    None,
+    /// gzip (`.gz`)
+    Gzip,
+    /// Zstandard (`.zst`)
+    Zstd,
+}
+
+impl PostCompression {
+    /// Guesses the compressor from the extension of the output path (e.g. `out.zip.gz` -> gzip)
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the output file
+    pub fn from_path(path: &Path) -> Self {
+        let lower = path.to_string_lossy().to_ascii_lowercase();
+        if lower.ends_with(".gz") {
+            return PostCompression::Gzip;
+        }
+        if lower.ends_with(".zst") {
+            return PostCompression::Zstd;
+        }
+        return PostCompression::None;
+    }
+}
+
+/// Destination of the output archive
+pub enum OutputSink {
+    File(File),
+    Stdout(std::io::Stdout),
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return match self {
+            OutputSink::File(file) => file.write(buf),
+            OutputSink::Stdout(stdout) => stdout.write(buf),
+        };
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return match self {
+            OutputSink::File(file) => file.flush(),
+            OutputSink::Stdout(stdout) => stdout.flush(),
+        };
+    }
+}
+
+/// Writer for the output file that compresses the archive on the fly if requested
+pub enum OutputWriter {
+    Plain(BufWriter<OutputSink>),
+    Gzip(GzEncoder<BufWriter<OutputSink>>),
+    Zstd(zstd::Encoder<'static, BufWriter<OutputSink>>),
+}
+
+impl OutputWriter {
+    /// Creates the output file (or uses stdout if `path` is `-`) and wraps it with the compressor
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the output file or `-`
+    /// * `compression` - compressor that the archive is streamed through
+    pub fn create(path: &Path, compression: PostCompression) -> std::io::Result<Self> {
+        let file = BufWriter::new(if is_stdio(path) {
+            OutputSink::Stdout(std::io::stdout())
+        } else {
+            OutputSink::File(File::create(extended_length_path(path))?)
+        });
+        return Ok(match compression {
+            PostCompression::None => OutputWriter::Plain(file),
+            PostCompression::Gzip => {
+                OutputWriter::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            PostCompression::Zstd => OutputWriter::Zstd(zstd::Encoder::new(file, 0)?),
+        });
+    }
+
+    /// Writes the trailer of the compressed stream (if any) and flushes the file
+    ///
+    /// Errors are lost if the writer is just dropped, so call this before closing the file.
+    pub fn finish(self) -> std::io::Result<()> {
+        let mut file = match self {
+            OutputWriter::Plain(file) => file,
+            OutputWriter::Gzip(encoder) => encoder.finish()?,
+            OutputWriter::Zstd(encoder) => encoder.finish()?,
+        };
+        return file.flush();
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        return match self {
+            OutputWriter::Plain(file) => file.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+            OutputWriter::Zstd(encoder) => encoder.write(buf),
+        };
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return match self {
+            OutputWriter::Plain(file) => file.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+            OutputWriter::Zstd(encoder) => encoder.flush(),
+        };
+    }
+}
+
+/// Paths of the disks of a spanned archive (`out.z01`, `out.z02`, ..., and `out.zip` for the last one)
+pub struct SpannedVolumes {
+    path: PathBuf,
+}
+
+impl SpannedVolumes {
+    /// # Arguments
+    ///
+    /// * `path` - path to the output archive (the last disk)
+    pub fn new(path: &Path) -> Self {
+        return Self {
+            path: extended_length_path(path).into_owned(),
+        };
+    }
+
+    /// Returns the path to the disk of the given 0-based number (before the last one is renamed)
+    pub fn volume_path(&self, disk: u16) -> PathBuf {
+        return self.path.with_extension(format!("z{:02}", disk as u32 + 1));
+    }
+
+    /// Renames the last disk to the path to the output archive
+    ///
+    /// # Arguments
+    ///
+    /// * `n_disks` - number of the written disks
+    pub fn finish(&self, n_disks: u16) -> std::io::Result<()> {
+        return std::fs::rename(self.volume_path(n_disks - 1), &self.path);
+    }
+
+    /// Removes the written disks (errors are ignored because this is a cleanup after a failure)
+    ///
+    /// # Arguments
+    ///
+    /// * `n_disks` - number of the written disks
+    pub fn remove(&self, n_disks: u16) {
+        for disk in 0..n_disks {
+            let _ = std::fs::remove_file(self.volume_path(disk));
+        }
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::long_path::extended_length_path;
+
+/// Replaces `dest` with `temporary` without a moment when neither of them exists (`-i`)
+///
+/// `dest` stays untouched if this fails.
+/// Falls back to copying if `temporary` cannot be renamed to `dest` because they are on different file systems.
+///
+/// # Arguments
+///
+/// * `temporary` - path to the converted archive
+/// * `dest` - path to the original archive to be replaced
+/// * `backup` - path to keep the original archive as (`--backup`)
+pub fn replace_file(temporary: &Path, dest: &Path, backup: Option<&Path>) -> std::io::Result<()> {
+    // Deeply nested archives in batch mode can exceed `MAX_PATH` in Windows
+    let temporary = extended_length_path(temporary);
+    let dest = extended_length_path(dest);
+    let backup = backup.map(extended_length_path);
+    let (temporary, dest, backup) = (temporary.as_ref(), dest.as_ref(), backup.as_deref());
+    // Make the content reach the disk before it becomes visible as `dest`
+    File::open(temporary)?.sync_all()?;
+    if let Some(backup) = backup {
+        keep_backup(dest, backup)?;
+    }
+    return match std::fs::rename(temporary, dest) {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_and_replace(temporary, dest)?;
+            std::fs::remove_file(temporary)
+        }
+        result => result,
+    };
+}
+
+/// Makes `backup` a copy of `dest` leaving `dest` as is (hard link if possible)
+fn keep_backup(dest: &Path, backup: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(backup) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    if std::fs::hard_link(dest, backup).is_err() {
+        std::fs::copy(dest, backup)?;
+    }
+    return Ok(());
+}
+
+/// Copies `source` to a temporary file next to `dest` and renames it to `dest`
+///
+/// The temporary file is removed if the copy fails.
+fn copy_and_replace(source: &Path, dest: &Path) -> std::io::Result<()> {
+    let directory = match dest.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut staging = tempfile::Builder::new()
+        .suffix(".tmp")
+        .tempfile_in(directory)?;
+    std::io::copy(&mut File::open(source)?, staging.as_file_mut())?;
+    staging.as_file().sync_all()?;
+    staging.persist(dest).map_err(|e| e.error)?;
+    return Ok(());
+}
@@ -0,0 +1,68 @@
+use crate::exit_status::ExitStatus;
+use crate::Job;
+
+/// What was detected in and done to an archive (`--summary json`)
+///
+/// Filled while the archive is processed; fields stay `None` if the processing stopped before them.
+#[derive(Debug, Default)]
+pub struct ArchiveSummary {
+    /// `true` if the file names can be decompressed without garbling everywhere (before the conversion)
+    pub universal: Option<bool>,
+    /// Legacy encoding assumed for implicitly-encoded file names
+    pub legacy_encoding: Option<String>,
+    /// How the legacy encoding was decided (auto-detected, specified, or locale default)
+    pub decided_by: Option<String>,
+    /// Encoding the file names were actually decoded with
+    pub decoded_as: Option<String>,
+    /// Number of the entries renamed (or to be renamed)
+    pub renamed: Option<usize>,
+    /// Path the archive was written to
+    pub written: Option<String>,
+}
+
+impl ArchiveSummary {
+    /// Returns the summary as JSON
+    ///
+    /// # Arguments
+    ///
+    /// * `job` - processed archive
+    /// * `status` - status of the archive
+    /// * `error` - error that stopped the processing (if any)
+    pub fn to_json(
+        &self,
+        job: &Job,
+        status: ExitStatus,
+        error: Option<&anyhow::Error>,
+    ) -> serde_json::Value {
+        return serde_json::json!({
+            "input": job.input_name(),
+            "status": status.name(),
+            "exit_code": status.code(),
+            "error": error.map(|e| e.to_string()),
+            "universal": self.universal,
+            "legacy_encoding": self.legacy_encoding,
+            "decided_by": self.decided_by,
+            "decoded_as": self.decoded_as,
+            "renamed": self.renamed,
+            "written": self.written,
+        });
+    }
+}
+
+/// Returns the summary of the whole run as JSON
+///
+/// # Arguments
+///
+/// * `status` - combined status (the exit code of the process)
+/// * `archives` - summaries of the archives (`ArchiveSummary::to_json`)
+pub fn run_summary_to_json(
+    status: ExitStatus,
+    archives: Vec<serde_json::Value>,
+) -> serde_json::Value {
+    return serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "status": status.name(),
+        "exit_code": status.code(),
+        "archives": archives,
+    });
+}
@@ -0,0 +1,68 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::time::SystemTime;
+
+/// Timestamped record of what was done to each archive (`--log`)
+///
+/// Does nothing if `--log` is not specified.
+/// Each line is written immediately so that the record survives `std::process::exit` and crashes.
+pub struct SessionLog {
+    file: Option<File>,
+}
+
+impl SessionLog {
+    /// Opens the log file in append mode to keep the records of the previous sessions
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the log file (`None` disables logging)
+    pub fn open(path: Option<&str>) -> std::io::Result<Self> {
+        let file = match path {
+            Some(path) => Some(OpenOptions::new().create(true).append(true).open(path)?),
+            None => None,
+        };
+        return Ok(Self { file });
+    }
+
+    /// Writes a line prefixed with the current time (UTC)
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - content of the line
+    pub fn write(&mut self, message: &str) -> std::io::Result<()> {
+        if let Some(file) = self.file.as_mut() {
+            writeln!(
+                file,
+                "{} {}",
+                humantime::format_rfc3339_seconds(SystemTime::now()),
+                message
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+/// Quotes an argument for POSIX shells if needed
+fn quote_arg(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c))
+    {
+        return arg.to_string();
+    }
+    return format!("'{}'", arg.replace('\'', r"'\''"));
+}
+
+/// Joins arguments into a command line that can be pasted into a shell
+///
+/// # Arguments
+///
+/// * `args` - arguments including the program name
+pub fn command_line<S: AsRef<str>>(args: &[S]) -> String {
+    return args
+        .iter()
+        .map(|arg| quote_arg(arg.as_ref()))
+        .collect::<Vec<_>>()
+        .join(" ");
+}
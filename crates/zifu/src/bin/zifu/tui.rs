@@ -0,0 +1,201 @@
+use std::io::{Read, Seek};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use zifu_core::filename_decoder::IDecoder;
+use zifu_core::{FileNameEncodingType, FileNameEntry, InputZIPArchive};
+
+/// Encodings that can always be chosen in the TUI (the primary ones of the supported languages)
+const COMMON_ENCODINGS: &[&str] = &[
+    "Shift_JIS",
+    "EUC-JP",
+    "GBK",
+    "Big5",
+    "EUC-KR",
+    "windows-1258",
+    "windows-874",
+    "cp437",
+    "cp850",
+    "cp737",
+    "cp852",
+    "cp855",
+    "cp857",
+    "cp862",
+    "cp866",
+    "cp720",
+];
+
+/// Returns the encodings that can be chosen in the TUI without duplicates
+///
+/// # Arguments
+///
+/// * `preferred` - encodings listed first in this order (e.g. the decided one & the auto-detected ones)
+pub fn candidate_encodings(preferred: Vec<Box<dyn IDecoder>>) -> Vec<Box<dyn IDecoder>> {
+    let mut candidates: Vec<Box<dyn IDecoder>> = vec![];
+    let common = COMMON_ENCODINGS
+        .iter()
+        .filter_map(|name| <dyn IDecoder>::from_encoding_name(name));
+    for decoder in preferred.into_iter().chain(common) {
+        if candidates
+            .iter()
+            .all(|candidate| candidate.encoding_name() != decoder.encoding_name())
+        {
+            candidates.push(decoder);
+        }
+    }
+    return candidates;
+}
+
+/// File names of the archive decoded with a candidate encoding
+struct Rendering {
+    names: Vec<FileNameEntry>,
+    /// `true` if all the implicitly-encoded names can be decoded without errors
+    decodable: bool,
+}
+
+/// State of the TUI
+struct EncodingChooser<'a, F: Read + Seek> {
+    archive: &'a InputZIPArchive<F>,
+    candidates: Vec<Box<dyn IDecoder>>,
+    /// Names rendered with each candidate (computed when the candidate is shown first)
+    renderings: Vec<Option<Rendering>>,
+    current: usize,
+    list_state: ListState,
+}
+
+impl<'a, F: Read + Seek> EncodingChooser<'a, F> {
+    fn new(archive: &'a InputZIPArchive<F>, candidates: Vec<Box<dyn IDecoder>>) -> Self {
+        let renderings = candidates.iter().map(|_| None).collect();
+        return Self {
+            archive,
+            candidates,
+            renderings,
+            current: 0,
+            list_state: ListState::default().with_selected(Some(0)),
+        };
+    }
+
+    fn rendering(&mut self) -> &Rendering {
+        let archive = self.archive;
+        let decoder = &*self.candidates[self.current];
+        return self.renderings[self.current].get_or_insert_with(|| Rendering {
+            names: archive.get_file_names_list(decoder),
+            decodable: archive.get_filename_decoder_index(&[decoder]).is_some(),
+        });
+    }
+
+    fn switch(&mut self, forward: bool) {
+        let n = self.candidates.len();
+        self.current = if forward {
+            (self.current + 1) % n
+        } else {
+            (self.current + n - 1) % n
+        };
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header_area, list_area, footer_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        let encoding_name = self.candidates[self.current].encoding_name().to_string();
+        let position = format!(" ({}/{})", self.current + 1, self.candidates.len());
+        let rendering = self.rendering();
+        let status = if rendering.decodable {
+            Span::styled("all names can be decoded", Style::new().fg(Color::Green))
+        } else {
+            Span::styled("some names cannot be decoded", Style::new().fg(Color::Red))
+        };
+        let header = Paragraph::new(Line::from(vec![
+            Span::raw("Encoding: "),
+            Span::raw(encoding_name.clone()).bold(),
+            Span::raw(position),
+            Span::raw("  "),
+            status,
+        ]))
+        .block(Block::default().borders(Borders::ALL).title(" zifu "));
+        let items: Vec<ListItem> = rendering
+            .names
+            .iter()
+            .map(|entry| {
+                ListItem::new(Line::from(vec![
+                    badge(entry, &encoding_name),
+                    Span::raw(entry.name.clone()),
+                ]))
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::LEFT | Borders::RIGHT))
+            .highlight_style(Style::new().reversed());
+        let footer =
+            Paragraph::new("↑/↓: scroll  ←/→: switch encoding  Enter: convert  q: cancel").dim();
+        frame.render_widget(header, header_area);
+        frame.render_stateful_widget(list, list_area, &mut self.list_state);
+        frame.render_widget(footer, footer_area);
+    }
+
+    /// Handles key inputs and returns the index of the chosen candidate (`None` if cancelled)
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> std::io::Result<Option<usize>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let key = match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => key,
+                _ => continue,
+            };
+            match key.code {
+                KeyCode::Enter => return Ok(Some(self.current)),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                KeyCode::Left | KeyCode::Char('h') | KeyCode::BackTab => self.switch(false),
+                KeyCode::Right | KeyCode::Char('l') | KeyCode::Tab => self.switch(true),
+                KeyCode::Up | KeyCode::Char('k') => self.list_state.select_previous(),
+                KeyCode::Down | KeyCode::Char('j') => self.list_state.select_next(),
+                KeyCode::PageUp => self.list_state.scroll_up_by(10),
+                KeyCode::PageDown => self.list_state.scroll_down_by(10),
+                KeyCode::Home => self.list_state.select_first(),
+                KeyCode::End => self.list_state.select_last(),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Returns the badge of the entry (same as `-l`)
+fn badge(entry: &FileNameEntry, encoding_name: &str) -> Span<'static> {
+    use FileNameEncodingType::*;
+    return match entry.encoding_type {
+        ExplicitRegularUTF8 => Span::styled("REGULAR UTF-8 ", Style::new().fg(Color::Green).bold()),
+        ExplicitIrregularUTF8 => {
+            Span::styled("IRREGULAR UTF-8 ", Style::new().fg(Color::Red).bold())
+        }
+        ImplicitASCII => Span::styled("ASCII ", Style::new().fg(Color::Green).bold()),
+        ImplicitNonASCII => Span::styled(
+            format!("{} GUESSED ", encoding_name),
+            Style::new().fg(Color::Red).bold(),
+        ),
+    };
+}
+
+/// Shows the file names in full screen, letting the user switch the encoding, and returns the chosen one
+///
+/// Returns `Ok(None)` if the user cancels the conversion.
+///
+/// # Arguments
+///
+/// * `archive` - archive to be converted
+/// * `candidates` - encodings that can be chosen (`candidate_encodings`); the first one is shown first
+pub fn choose_encoding<F: Read + Seek>(
+    archive: &InputZIPArchive<F>,
+    candidates: Vec<Box<dyn IDecoder>>,
+) -> std::io::Result<Option<Box<dyn IDecoder>>> {
+    let mut chooser = EncodingChooser::new(archive, candidates);
+    let mut terminal = ratatui::init();
+    let result = chooser.run(&mut terminal);
+    ratatui::restore();
+    return Ok(result?.map(|index| chooser.candidates.swap_remove(index)));
+}
@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zifu_core::verification::read_central_directory;
+use zip_structs::zip_eocd::ZipEOCD;
+
+// `ZipCDEntry::all_from_eocd` overflows if the entries run past the EOCD, so its replacement is fed instead
+fuzz_target!(|data: &[u8]| {
+    let mut reader = std::io::Cursor::new(data);
+    if let Ok(eocd) = ZipEOCD::from_reader(&mut reader) {
+        let _ = read_central_directory(&mut reader, &eocd);
+    }
+});
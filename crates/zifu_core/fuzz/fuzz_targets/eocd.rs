@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zip_structs::zip_eocd::ZipEOCD;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ZipEOCD::from_reader(&mut std::io::Cursor::new(data));
+});
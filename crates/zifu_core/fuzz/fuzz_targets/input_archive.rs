@@ -0,0 +1,13 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zifu_core::{fix_zip_bytes, InputZIPArchive};
+
+// zifu's own parsers: the EOCD search over trailing data, ZIP64, the local headers, and the whole conversion
+fuzz_target!(|data: &[u8]| {
+    if let Ok(mut archive) = InputZIPArchive::new(std::io::Cursor::new(data)) {
+        let _ = archive.find_name_mismatches();
+        let _ = archive.find_orphan_local_headers();
+    }
+    let _ = fix_zip_bytes(data, &Default::default());
+});
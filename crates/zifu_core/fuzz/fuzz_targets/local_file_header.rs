@@ -0,0 +1,20 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use zifu_core::verification::read_central_directory;
+use zip_structs::{zip_eocd::ZipEOCD, zip_local_file_header::ZipLocalFileHeader};
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = std::io::Cursor::new(data);
+    let eocd = match ZipEOCD::from_reader(&mut reader) {
+        Ok(eocd) => eocd,
+        Err(_) => return,
+    };
+    let cd_entries = match read_central_directory(&mut reader, &eocd) {
+        Ok(cd_entries) => cd_entries,
+        Err(_) => return,
+    };
+    for cd in cd_entries.iter() {
+        let _ = ZipLocalFileHeader::from_central_directory(&mut reader, cd);
+    }
+});
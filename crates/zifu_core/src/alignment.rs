@@ -0,0 +1,94 @@
+//! Alignment of the data of stored entries (`zipalign`)
+//!
+//! Android maps the stored entries of APKs (e.g. `resources.arsc` and native libraries) into memory, so their data
+//! have to begin at multiples of 4 bytes (or the page size).  Renaming entries changes the sizes of the local headers
+//! and shifts the data after them, so the extra fields of the local headers are re-padded like `zipalign` does.
+
+use crate::extra_fields;
+use crate::local_headers::{LocalFileHeader, LOCAL_HEADER_FIXED_SIZE};
+use crate::origin_hints::iter_extra_fields;
+
+/// Header ID of the extra field record that pads the local header (Android's "alignment" record)
+///
+/// Its data are the alignment (2 bytes) followed by the zero padding.
+pub const ALIGNMENT_EXTRA_FIELD_ID: u16 = 0xd935;
+/// Size of the smallest alignment record (header ID, size, and alignment)
+const MIN_ALIGNMENT_RECORD_SIZE: u64 = 6;
+
+/// Returns the extra field without the padding of `zipalign` (alignment records, empty records with header ID 0,
+/// and a tail of zeros)
+///
+/// # Arguments
+///
+/// * `extra_field` - extra field of a local header
+fn strip_padding(extra_field: &[u8]) -> Vec<u8> {
+    let mut stripped = Vec::with_capacity(extra_field.len());
+    let mut consumed = 0;
+    for (record_id, data) in iter_extra_fields(extra_field) {
+        let is_padding = record_id == ALIGNMENT_EXTRA_FIELD_ID
+            || (record_id == 0 && data.iter().all(|&b| b == 0));
+        if !is_padding {
+            stripped.extend_from_slice(&extra_field[consumed..consumed + 4 + data.len()]);
+        }
+        consumed += 4 + data.len();
+    }
+    let tail = &extra_field[consumed..];
+    if tail.iter().any(|&b| b != 0) {
+        stripped.extend_from_slice(tail);
+    }
+    return stripped;
+}
+
+/// Pads the extra field of the local header of a stored entry so that its data begin at a multiple of `alignment`
+///
+/// Compressed entries and alignments of 0 or 1 are left as they are, and so is the header if the padding doesn't fit
+/// in the extra field (65535 bytes).
+///
+/// # Arguments
+///
+/// * `local_header` - local header to be written
+/// * `position` - position of the local header in the output (in its disk)
+/// * `alignment` - alignment of the data in bytes (e.g. 4 for APKs)
+pub(crate) fn align_local_header(
+    local_header: &mut LocalFileHeader,
+    position: u64,
+    alignment: u16,
+) {
+    if local_header.compression_method != 0 || alignment <= 1 {
+        return;
+    }
+    let alignment_u64 = alignment as u64;
+    let extra_field = strip_padding(&local_header.extra_field);
+    let data_position = position
+        + LOCAL_HEADER_FIXED_SIZE
+        + local_header.file_name_raw.len() as u64
+        + extra_field.len() as u64;
+    let mut padding = (alignment_u64 - data_position % alignment_u64) % alignment_u64;
+    while padding != 0 && padding < MIN_ALIGNMENT_RECORD_SIZE {
+        padding += alignment_u64;
+    }
+    if extra_field.len() as u64 + padding > u16::MAX as u64 {
+        return;
+    }
+    local_header.extra_field = if padding == 0 {
+        extra_field
+    } else {
+        let mut data = alignment.to_le_bytes().to_vec();
+        data.resize((padding - 4) as usize, 0);
+        // Fits in a record because of the check above
+        let record = extra_fields::build_record(ALIGNMENT_EXTRA_FIELD_ID, &data).unwrap();
+        [extra_field, record].concat()
+    };
+}
+
+/// Returns `true` if the data of the stored entry begin at a multiple of `alignment` (always for compressed ones)
+///
+/// # Arguments
+///
+/// * `local_header` - local header read from the input
+/// * `alignment` - alignment of the data in bytes
+pub(crate) fn is_aligned(local_header: &LocalFileHeader, alignment: u16) -> bool {
+    return local_header.compression_method != 0
+        || alignment <= 1
+        || local_header.data_position.is_multiple_of(alignment as u64);
+}
@@ -0,0 +1,334 @@
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWrite, AsyncWriteExt};
+
+use crate::error::EntryReadError;
+use crate::local_headers::{LocalFileHeader, LOCAL_HEADER_FIXED_SIZE, MAX_DATA_DESCRIPTOR_SIZE};
+use crate::output::{self, OutputProgress, PayloadHasher};
+use crate::spanning::ArchiveSink;
+use crate::trailing_data::{self, TRAILING_DATA_SEARCH_LIMIT};
+use crate::{write_local_header, zip64, InputZIPArchive, OutputOptions, OutputReport};
+
+/// Size of the tail read first to find the EOCD
+/// (the longest comment & trailing data + the EOCD & the ZIP64 locator & record with margin)
+const TAIL_SIZE: u64 = u16::MAX as u64 + TRAILING_DATA_SEARCH_LIMIT + 4096;
+
+/// Part of the input archive read in advance, addressed by the positions in the whole archive
+///
+/// The synchronous parser runs on this, so it never blocks on I/O.
+/// Reading outside of the region results in `Err`.
+pub struct BufferedRegion {
+    /// Position of `data` in the archive
+    start: u64,
+    data: Vec<u8>,
+    /// Size of the whole archive (for `SeekFrom::End`)
+    archive_len: u64,
+    /// Current position in the archive
+    position: u64,
+}
+
+impl BufferedRegion {
+    fn new(start: u64, data: Vec<u8>, archive_len: u64) -> Self {
+        return Self {
+            start,
+            data,
+            archive_len,
+            position: start,
+        };
+    }
+}
+
+impl Read for BufferedRegion {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.position < self.start {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!(
+                    "position {} is outside of the buffered region (from {})",
+                    self.position, self.start
+                ),
+            ));
+        }
+        let offset = (self.position - self.start).min(self.data.len() as u64) as usize;
+        let n_read = Read::read(&mut &self.data[offset..], buf)?;
+        self.position += n_read as u64;
+        return Ok(n_read);
+    }
+}
+
+impl Seek for BufferedRegion {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(position) => Some(position),
+            SeekFrom::End(delta) => self.archive_len.checked_add_signed(delta),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+        }
+        .ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        self.position = position;
+        return Ok(position);
+    }
+}
+
+/// In-memory sink whose content is flushed to the asynchronous destination after each entry
+struct BufferSink {
+    buffer: Vec<u8>,
+    position: u64,
+}
+
+impl Write for BufferSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        self.position += buf.len() as u64;
+        return Ok(buf.len());
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl ArchiveSink for BufferSink {
+    fn position(&self) -> (u16, u64) {
+        return (0, self.position);
+    }
+    fn keep_together(&mut self, _len: u64) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl BufferSink {
+    async fn drain_to<W: AsyncWrite + Unpin>(&mut self, dest: &mut W) -> std::io::Result<()> {
+        dest.write_all(&self.buffer).await?;
+        self.buffer.clear();
+        return Ok(());
+    }
+}
+
+/// Asynchronous counterpart of `InputZIPArchive` for `tokio` (e.g. web services fixing uploaded archives)
+///
+/// Only the I/O is asynchronous: the central directory and each local header are read into memory with `await`,
+/// and the synchronous logic runs on them.  Payloads are copied chunk by chunk like the synchronous output.
+/// Conversions and diagnoses are available via `archive()` and `archive_mut()`.
+/// The local headers are read only by the output, so UTF-8 flags set only in them (see `InputZIPArchive::read_local_header_flags`)
+/// are honored there for the names left unconverted.
+pub struct AsyncInputZIPArchive<R: AsyncRead + AsyncSeek + Unpin> {
+    reader: R,
+    archive_len: u64,
+    /// Archive parsed from the central directory & the EOCD
+    archive: InputZIPArchive<BufferedRegion>,
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncInputZIPArchive<R> {
+    /// Returns an initialized instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - reader representing the input ZIP file (`tokio::io::BufReader<tokio::fs::File>` recommended)
+    pub async fn new(mut reader: R) -> anyhow::Result<Self> {
+        let archive_len = reader.seek(SeekFrom::End(0)).await?;
+        let tail_start = archive_len.saturating_sub(TAIL_SIZE);
+        let tail = read_region(&mut reader, tail_start, archive_len - tail_start).await?;
+        let mut region = BufferedRegion::new(tail_start, tail, archive_len);
+        let (eocd, _) = trailing_data::read_eocd(&mut region)?;
+        let cd_starting_position = match zip64::read_zip64_eocd(&mut region, &eocd)? {
+            Some(zip64_eocd) => zip64_eocd.cd_starting_position,
+            None => eocd.cd_starting_position as u64,
+        };
+        if cd_starting_position < tail_start {
+            let cd_start = cd_starting_position.min(archive_len);
+            let cd = read_region(&mut reader, cd_start, archive_len - cd_start).await?;
+            region = BufferedRegion::new(cd_start, cd, archive_len);
+        }
+        return Ok(Self {
+            reader,
+            archive_len,
+            archive: InputZIPArchive::new(region)?,
+        });
+    }
+
+    /// Returns the parsed archive (for diagnoses, listing, etc.)
+    pub fn archive(&self) -> &InputZIPArchive<BufferedRegion> {
+        return &self.archive;
+    }
+
+    /// Returns the parsed archive (for conversions of file names, hooks, etc.)
+    pub fn archive_mut(&mut self) -> &mut InputZIPArchive<BufferedRegion> {
+        return &mut self.archive;
+    }
+
+    /// Outputs the ZIP archive to the given writer with options and returns the report on the written entries.
+    ///
+    /// Same as `InputZIPArchive::output_archive_with_options` except that the I/O is asynchronous.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - writer representing the output file
+    /// * `options` - output options (e.g. hashing of payloads)
+    pub async fn output_archive_with_options<W: AsyncWrite + Unpin>(
+        &mut self,
+        dest: &mut W,
+        options: &OutputOptions,
+    ) -> anyhow::Result<OutputReport> {
+        let mut sink = BufferSink {
+            buffer: vec![],
+            position: 0,
+        };
+        let mut state = self.archive.begin_output()?;
+        for index in 0..self.archive.cd_entries.len() {
+            if !self.archive.filter_entry(index, &mut state, options)? {
+                continue;
+            }
+            let position = zip64::local_header_position(&self.archive.cd_entries[index]);
+            let mut region = self.read_local_header(position).await?;
+            let mut local_header =
+                LocalFileHeader::read_at(&mut region, position).map_err(|error| {
+                    EntryReadError::local_file_header(index, &self.archive.cd_entries[index], error)
+                })?;
+            self.archive.honor_local_utf8_flag(
+                index,
+                local_header.general_purpose_flags,
+                &local_header.file_name_raw,
+            );
+            if local_header.has_data_descriptor() {
+                let cd = &self.archive.cd_entries[index];
+                let descriptor_position = local_header.data_position + zip64::compressed_size(cd);
+                let available = self.archive_len.saturating_sub(descriptor_position);
+                let head = read_region(
+                    &mut self.reader,
+                    descriptor_position,
+                    MAX_DATA_DESCRIPTOR_SIZE.min(available),
+                )
+                .await?;
+                local_header.resolve_data_descriptor(cd, &head);
+            }
+            if self.archive.repaired_entries.binary_search(&index).is_ok() {
+                local_header.fill_from_central_directory(&self.archive.cd_entries[index])?;
+            }
+            let header_position = state.pos;
+            state.pos += write_local_header(
+                &mut self.archive.cd_entries[index],
+                &mut local_header,
+                &mut sink,
+                options.alignment,
+            )?;
+            sink.drain_to(dest).await?;
+            let mut hasher = PayloadHasher::new(options.hash_payloads);
+            let payload_size = local_header.payload_size;
+            self.copy_payload(
+                local_header.data_position,
+                payload_size,
+                &mut sink,
+                dest,
+                options,
+                &mut hasher,
+                Some(&mut state.progress),
+            )
+            .await?;
+            // The data descriptor is not a part of the payload
+            let trailer_size = local_header.data_descriptor_size;
+            self.copy_payload(
+                local_header.data_position + payload_size,
+                trailer_size,
+                &mut sink,
+                dest,
+                options,
+                &mut PayloadHasher::new(false),
+                None,
+            )
+            .await?;
+            state.pos += payload_size + trailer_size;
+            self.archive.finish_local_entry(
+                index,
+                header_position,
+                &local_header,
+                hasher,
+                &mut state,
+            );
+        }
+        let report = self.archive.finish_output(&mut sink, state)?;
+        sink.drain_to(dest).await?;
+        dest.flush().await?;
+        return Ok(report);
+    }
+
+    /// Reads the local header (without the payload) starting at `position` into memory
+    async fn read_local_header(&mut self, position: u64) -> anyhow::Result<BufferedRegion> {
+        let available = self.archive_len.saturating_sub(position);
+        let fixed = read_region(
+            &mut self.reader,
+            position,
+            LOCAL_HEADER_FIXED_SIZE.min(available),
+        )
+        .await?;
+        if fixed.len() < LOCAL_HEADER_FIXED_SIZE as usize {
+            // Let the parser report the truncated header
+            return Ok(BufferedRegion::new(position, fixed, self.archive_len));
+        }
+        let file_name_length = u16::from_le_bytes([fixed[26], fixed[27]]);
+        let extra_field_length = u16::from_le_bytes([fixed[28], fixed[29]]);
+        let len = LOCAL_HEADER_FIXED_SIZE + file_name_length as u64 + extra_field_length as u64;
+        let data = read_region(&mut self.reader, position, len.min(available)).await?;
+        return Ok(BufferedRegion::new(position, data, self.archive_len));
+    }
+
+    /// Copies `size` bytes from `start` to `dest` through `sink` in chunks of `options.chunk_size()` bytes
+    ///
+    /// The deadline & cancellation of `options` are checked before each chunk.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - position of the payload in the input
+    /// * `size` - number of bytes to copy
+    /// * `sink` - in-memory sink drained after each chunk
+    /// * `dest` - writer representing the output file
+    /// * `options` - output options (chunk size, deadline, and cancellation)
+    /// * `hasher` - hasher of the payload
+    /// * `progress` - progress of the output advanced after each chunk (`None` for the data that are not payloads)
+    #[allow(clippy::too_many_arguments)]
+    async fn copy_payload<W: AsyncWrite + Unpin>(
+        &mut self,
+        start: u64,
+        size: u64,
+        sink: &mut BufferSink,
+        dest: &mut W,
+        options: &OutputOptions,
+        hasher: &mut PayloadHasher,
+        mut progress: Option<&mut OutputProgress>,
+    ) -> anyhow::Result<()> {
+        let mut copied = 0;
+        while copied < size {
+            options.interruption().check("writing the output archive")?;
+            let len = (options.chunk_size() as u64).min(size - copied);
+            let chunk = read_region(&mut self.reader, start + copied, len).await?;
+            if chunk.is_empty() {
+                return Err(output::truncated_payload_error(size, copied).into());
+            }
+            hasher.update(&chunk);
+            sink.write_all(&chunk)?;
+            sink.drain_to(dest).await?;
+            // Not through `PayloadProgress`, whose callback (not `Sync`) can't be held across the awaits
+            if let Some(progress) = progress.as_deref_mut() {
+                progress.bytes_copied += chunk.len() as u64;
+                self.archive.report_progress(progress);
+            }
+            copied += chunk.len() as u64;
+        }
+        return Ok(());
+    }
+}
+
+/// Reads `len` bytes from `start` (or less at the end of the input)
+async fn read_region<R: AsyncRead + AsyncSeek + Unpin>(
+    reader: &mut R,
+    start: u64,
+    len: u64,
+) -> std::io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(start)).await?;
+    let mut data = Vec::with_capacity(len as usize);
+    reader.take(len).read_to_end(&mut data).await?;
+    return Ok(data);
+}
@@ -0,0 +1,178 @@
+//! Interpretation of the external file attributes of entries
+
+use zip_structs::zip_central_directory::ZipCDEntry;
+
+use crate::origin_hints::HostSystem;
+use crate::zip64;
+
+/// MS-DOS directory attribute in the lowest byte of the external file attributes
+const MSDOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+/// Mask of the file type in Unix modes (`S_IFMT`)
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+/// File type of directories in Unix modes (`S_IFDIR`)
+const UNIX_DIRECTORY: u32 = 0o040000;
+/// File type of symbolic links in Unix modes (`S_IFLNK`)
+const UNIX_SYMLINK: u32 = 0o120000;
+/// File type of regular files in Unix modes (`S_IFREG`)
+const UNIX_REGULAR_FILE: u32 = 0o100000;
+/// Mask of the permission bits in Unix modes
+const UNIX_PERMISSION_MASK: u32 = 0o777;
+/// setuid, setgid, and sticky bits in Unix modes
+const UNIX_SPECIAL_BITS: u32 = 0o7000;
+
+/// How to rewrite the Unix modes in the external file attributes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PermissionNormalization {
+    /// Permission bits of files (e.g. `0o644`)
+    pub file_mode: Option<u32>,
+    /// Permission bits of directories (e.g. `0o755`)
+    pub directory_mode: Option<u32>,
+    /// Clears the setuid, setgid, and sticky bits
+    pub strip_special_bits: bool,
+}
+
+/// Returns the Unix mode in the upper 16 bits of the external file attributes (`None` if the host system does not use them)
+///
+/// # Arguments
+///
+/// * `version_made_by` - "version made by" of the central directory
+/// * `external_file_attributes` - external file attributes of the central directory
+pub fn unix_mode(version_made_by: u16, external_file_attributes: u32) -> Option<u32> {
+    return match HostSystem::from_version_made_by(version_made_by) {
+        HostSystem::Unix | HostSystem::MacOS => Some(external_file_attributes >> 16),
+        _ => None,
+    };
+}
+
+/// Returns `true` if the entry represents a directory
+///
+/// A trailing `/` is enough.  Empty entries without it are also directories
+/// if they have the MS-DOS directory attribute or a Unix directory mode (e.g. written by old archivers).
+///
+/// # Arguments
+///
+/// * `file_name_raw` - byte sequence of the file name
+/// * `uncompressed_size` - size of the original content
+/// * `version_made_by` - "version made by" of the central directory
+/// * `external_file_attributes` - external file attributes of the central directory
+pub fn is_directory(
+    file_name_raw: &[u8],
+    uncompressed_size: u64,
+    version_made_by: u16,
+    external_file_attributes: u32,
+) -> bool {
+    if file_name_raw.ends_with(b"/") {
+        return true;
+    }
+    if uncompressed_size != 0 {
+        return false;
+    }
+    return external_file_attributes & MSDOS_DIRECTORY_ATTRIBUTE != 0
+        || unix_mode(version_made_by, external_file_attributes)
+            .is_some_and(|mode| mode & UNIX_FILE_TYPE_MASK == UNIX_DIRECTORY);
+}
+
+/// Returns `true` if the central directory represents a directory (see `is_directory`)
+pub(crate) fn is_directory_entry(cd: &ZipCDEntry) -> bool {
+    return is_directory(
+        &cd.file_name_raw,
+        zip64::uncompressed_size(cd),
+        cd.version_made_by,
+        cd.external_file_attributes,
+    );
+}
+
+/// Returns `true` if the Unix mode marks the entry as a symbolic link
+///
+/// The content of such entries is the byte sequence of the link target, which is never rewritten.
+///
+/// # Arguments
+///
+/// * `version_made_by` - "version made by" of the central directory
+/// * `external_file_attributes` - external file attributes of the central directory
+pub fn is_symlink(version_made_by: u16, external_file_attributes: u32) -> bool {
+    return unix_mode(version_made_by, external_file_attributes)
+        .is_some_and(|mode| mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK);
+}
+
+/// Returns `true` if the central directory represents a symbolic link (see `is_symlink`)
+pub(crate) fn is_symlink_entry(cd: &ZipCDEntry) -> bool {
+    return is_symlink(cd.version_made_by, cd.external_file_attributes);
+}
+
+/// Returns the Unix mode rewritten according to `normalization`
+///
+/// The file type is filled in if it is missing (e.g. garbage written by Windows tools) and the permission bits are forced.
+/// Symbolic links are left as they are.
+///
+/// # Arguments
+///
+/// * `mode` - original Unix mode
+/// * `is_directory` - `true` if the entry represents a directory
+/// * `normalization` - how to rewrite the mode
+pub fn normalize_unix_mode(
+    mode: u32,
+    is_directory: bool,
+    normalization: &PermissionNormalization,
+) -> u32 {
+    if mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK {
+        return mode;
+    }
+    let forced_permissions = if is_directory {
+        normalization.directory_mode
+    } else {
+        normalization.file_mode
+    };
+    let mut mode = mode;
+    if let Some(permissions) = forced_permissions {
+        if mode & UNIX_FILE_TYPE_MASK == 0 {
+            mode |= if is_directory {
+                UNIX_DIRECTORY
+            } else {
+                UNIX_REGULAR_FILE
+            };
+        }
+        mode = (mode & !UNIX_PERMISSION_MASK) | (permissions & UNIX_PERMISSION_MASK);
+    }
+    if normalization.strip_special_bits {
+        mode &= !UNIX_SPECIAL_BITS;
+    }
+    return mode;
+}
+
+/// Rewrites the Unix mode of the central directory (see `normalize_unix_mode`)
+///
+/// Returns `true` if the external file attributes are changed.
+/// Entries whose host system does not use Unix modes are left as they are.
+pub(crate) fn normalize_unix_mode_of_entry(
+    cd: &mut ZipCDEntry,
+    normalization: &PermissionNormalization,
+) -> bool {
+    let mode = match unix_mode(cd.version_made_by, cd.external_file_attributes) {
+        Some(mode) => mode,
+        None => return false,
+    };
+    let normalized = normalize_unix_mode(mode, is_directory_entry(cd), normalization);
+    if normalized == mode {
+        return false;
+    }
+    cd.external_file_attributes = (cd.external_file_attributes & 0xffff) | (normalized << 16);
+    return true;
+}
+
+/// Makes the trailing `/` of the converted name consistent with whether the entry is a directory
+///
+/// Directories get exactly one trailing `/` (a trailing `\` is replaced), and files get none.
+///
+/// # Arguments
+///
+/// * `name` - converted name of the entry
+/// * `is_directory` - `true` if the entry represents a directory
+pub fn normalize_trailing_slash(name: &str, is_directory: bool) -> String {
+    let body = name.trim_end_matches('/');
+    if !is_directory {
+        return body.to_string();
+    }
+    return format!("{}/", body.trim_end_matches('\\'));
+}
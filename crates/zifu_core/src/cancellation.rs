@@ -0,0 +1,58 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Token shared with another thread (or a signal handler) to abort a long operation
+///
+/// Checked cooperatively between entries and between the chunks of their data;
+/// the caller should remove the partially written output.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    /// Requests the operations observing this token to stop
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns `true` if `cancel` has been called
+    pub fn is_cancelled(&self) -> bool {
+        return self.cancelled.load(Ordering::SeqCst);
+    }
+}
+
+/// Error returned when an operation is aborted through a `CancellationToken`
+#[derive(Clone, Debug)]
+pub struct Cancelled {
+    /// Operation that was interrupted (e.g. `"writing the output archive"`)
+    pub during: &'static str,
+}
+
+impl std::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "cancelled while {}", self.during);
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// Returns `Err(Cancelled)` if `token` has been cancelled
+///
+/// # Arguments
+///
+/// * `token` - token of the operation (`None` means it can't be cancelled)
+/// * `during` - operation in progress
+pub(crate) fn check_cancelled(
+    token: Option<&CancellationToken>,
+    during: &'static str,
+) -> Result<(), Cancelled> {
+    if token.is_some_and(CancellationToken::is_cancelled) {
+        return Err(Cancelled { during });
+    }
+    return Ok(());
+}
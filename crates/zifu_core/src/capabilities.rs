@@ -0,0 +1,84 @@
+use crate::encodings::LEGACY_ENCODINGS;
+use crate::feature_gate::{Operation, Support, UnsupportedFeature};
+use crate::filename_decoder::oem_codepages;
+
+/// What this build of `zifu_core` supports (for front-ends and `zifu --version --verbose`)
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// Version of `zifu_core`
+    pub version: &'static str,
+    /// Reading (checking & listing) ZIP64 archives (> 4 GiB or > 65535 entries)
+    pub zip64_read: bool,
+    /// Writing ZIP64 archives (fixed archives must be smaller than 4 GiB and have at most 65535 entries)
+    pub zip64_write: bool,
+    /// Entries encrypted with ZipCrypto or WinZip AES are copied as they are (no password needed)
+    pub encryption_pass_through: bool,
+    /// PKWARE strong encryption & central directory encryption
+    pub strong_encryption: bool,
+    /// Reading spanned (multi-disk) archives
+    pub spanned_input: bool,
+    /// Writing spanned (multi-disk) archives (see `spanning::SpannedWriter`)
+    pub spanned_output: bool,
+    /// Built for WebAssembly
+    pub wasm: bool,
+    /// Async I/O API (see `async_io::AsyncInputZIPArchive`)
+    pub async_io: bool,
+    /// Decoding & diagnosing names of entries in parallel (see `parallel`)
+    pub parallel: bool,
+    /// Optional Cargo features enabled in this build
+    pub features: Vec<&'static str>,
+    /// Names of the legacy encodings accepted by `IDecoder::from_encoding_name` (IBM OEM code pages as `CPxxx`)
+    pub encodings: Vec<String>,
+}
+
+/// Returns what this build supports
+pub fn capabilities() -> Capabilities {
+    let mut features = vec![];
+    if cfg!(feature = "zip-crate-check") {
+        features.push("zip-crate-check");
+    }
+    if cfg!(feature = "tokio") {
+        features.push("tokio");
+    }
+    if cfg!(feature = "serde") {
+        features.push("serde");
+    }
+    if cfg!(feature = "rayon") {
+        features.push("rayon");
+    }
+    if cfg!(feature = "mmap") {
+        features.push("mmap");
+    }
+    if cfg!(feature = "oem-cp") {
+        features.push("oem-cp");
+    }
+    if cfg!(feature = "locale") {
+        features.push("locale");
+    }
+    let encodings = LEGACY_ENCODINGS
+        .iter()
+        .map(|encoding| encoding.name().to_string())
+        .chain(
+            oem_codepages()
+                .into_iter()
+                .map(|codepage| format!("CP{}", codepage)),
+        )
+        .collect();
+    // Taken from the support matrix of `FeatureGate`
+    let supports_zip64 =
+        |operation: Operation| UnsupportedFeature::Zip64.support(operation) != Support::Blocker;
+    return Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        zip64_read: supports_zip64(Operation::Check) && supports_zip64(Operation::List),
+        zip64_write: supports_zip64(Operation::Fix),
+        encryption_pass_through: true,
+        strong_encryption: false,
+        spanned_input: false,
+        spanned_output: true,
+        wasm: cfg!(target_arch = "wasm32"),
+        async_io: cfg!(feature = "tokio"),
+        parallel: crate::parallel::is_enabled(),
+        features,
+        encodings,
+    };
+}
@@ -0,0 +1,134 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::RenamePlanEntry;
+
+/// Entries that the conversion would give the same name
+///
+/// Two different legacy byte sequences can be decoded to the same name, and so can NFD & NFC forms after the normalization.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NameCollision {
+    /// Name shared by the entries after the conversion
+    pub name: String,
+    /// 0-based indices of the entries in the central directory
+    pub indices: Vec<usize>,
+}
+
+/// Error returned when the conversion would give multiple entries the same name
+#[derive(Clone, Debug)]
+pub struct NameCollisionError {
+    pub collisions: Vec<NameCollision>,
+}
+
+impl std::fmt::Display for NameCollisionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the conversion would give {} names to multiple entries",
+            self.collisions.len()
+        )?;
+        for collision in self.collisions.iter() {
+            write!(f, "\n  {}:", collision.name)?;
+            for index in collision.indices.iter() {
+                write!(f, " #{}", index)?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl std::error::Error for NameCollisionError {}
+
+/// Returns the names that multiple entries would share after the conversion in the order of the first entries
+///
+/// Entries that already share the same byte sequence in the input archive are not reported
+/// because the conversion did not cause their collision.
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive (`InputZIPArchive::plan_conversion`)
+pub fn find_collisions(plan: &[RenamePlanEntry]) -> Vec<NameCollision> {
+    let mut groups: HashMap<&str, Vec<&RenamePlanEntry>> = HashMap::new();
+    let mut order = vec![];
+    for entry in plan.iter() {
+        let group = groups.entry(entry.proposed_name.as_str()).or_default();
+        if group.is_empty() {
+            order.push(entry.proposed_name.as_str());
+        }
+        group.push(entry);
+    }
+    return order
+        .into_iter()
+        .filter_map(|name| {
+            let group = &groups[name];
+            let distinct_raws: HashSet<&[u8]> =
+                group.iter().map(|entry| entry.raw.as_slice()).collect();
+            if distinct_raws.len() < 2 {
+                return None;
+            }
+            return Some(NameCollision {
+                name: name.to_string(),
+                indices: group.iter().map(|entry| entry.index).collect(),
+            });
+        })
+        .collect();
+}
+
+/// Returns new names that resolve the collisions by suffixes (e.g. `a.txt` -> `a (2).txt`) keyed by the byte sequences of the entries
+///
+/// The first entry of each collision keeps the name.
+/// The result is meant to be fed to `InputZIPArchive::set_rename_hook`.
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of all the entries of the archive
+/// * `collisions` - collisions found by `find_collisions`
+pub fn resolve_collisions_by_suffix(
+    plan: &[RenamePlanEntry],
+    collisions: &[NameCollision],
+) -> HashMap<Vec<u8>, String> {
+    let mut taken: HashSet<String> = plan
+        .iter()
+        .map(|entry| entry.proposed_name.clone())
+        .collect();
+    let mut renamed = HashMap::new();
+    for collision in collisions.iter() {
+        let mut seen: HashSet<&[u8]> = HashSet::new();
+        for &index in collision.indices.iter() {
+            let raw = plan[index].raw.as_slice();
+            let is_first = seen.is_empty();
+            // The first entry keeps the name; entries with the same bytes share the name anyway
+            if !seen.insert(raw) || is_first {
+                continue;
+            }
+            let new_name = (2..)
+                .map(|n| with_suffix(&collision.name, n))
+                .find(|candidate| !taken.contains(candidate))
+                .unwrap();
+            taken.insert(new_name.clone());
+            renamed.insert(raw.to_vec(), new_name);
+        }
+    }
+    return renamed;
+}
+
+/// Returns the name with ` (n)` before the extension (e.g. `dir/a.txt` -> `dir/a (2).txt`, `dir/` -> `dir (2)/`)
+pub(crate) fn with_suffix(name: &str, n: usize) -> String {
+    let (body, slash) = match name.strip_suffix('/') {
+        Some(body) => (body, "/"),
+        None => (name, ""),
+    };
+    let file_name_start = body.rfind('/').map_or(0, |i| i + 1);
+    // Dot files (e.g. `.gitignore`) have no extension
+    let extension_start = match body[file_name_start..].rfind('.') {
+        Some(i) if i > 0 && slash.is_empty() => file_name_start + i,
+        _ => body.len(),
+    };
+    return format!(
+        "{} ({}){}{}",
+        &body[..extension_start],
+        n,
+        &body[extension_start..],
+        slash
+    );
+}
@@ -0,0 +1,358 @@
+//! Creation of new archives whose names are readable everywhere
+//!
+//! Every name is NFC-normalized (names from macOS file systems are decomposed) and explicitly encoded in UTF-8
+//! (general purpose flag #11), so the archives need no fixing by zifu or anyone else.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use byteorder::{WriteBytesExt, LE};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use zip_structs::zip_central_directory::{ZipCDEntry, UTF8_FLAG_BIT};
+use zip_structs::zip_eocd::ZipEOCD;
+
+use crate::local_headers::LocalFileHeader;
+use crate::name_pipeline::NormalizationForm;
+use crate::origin_hints::HostSystem;
+use crate::zip64::narrow;
+use crate::{timestamps, unicode_path};
+
+/// "version needed to extract" of the entries (2.0: deflate & directories)
+const VERSION_NEEDED: u16 = 20;
+/// Lower byte of "version made by" (6.3 of the APPNOTE)
+const SPECIFICATION_VERSION: u16 = 63;
+/// MS-DOS directory attribute in the lowest byte of the external file attributes
+const MSDOS_DIRECTORY_ATTRIBUTE: u32 = 0x10;
+/// Offset of CRC-32 in local file headers
+const LOCAL_HEADER_CRC32_OFFSET: u64 = 14;
+
+/// Options of `create_zip_file`
+#[derive(Clone, Debug, Default)]
+pub struct CreateOptions {
+    /// Adds the Info-ZIP Unicode Path extra field (0x7075) for extractors that read it but not the UTF-8 flag
+    pub unicode_path_fields: bool,
+}
+
+/// Entry written to the new archive
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CreatedEntry {
+    /// Name of the entry (directories end with `/`)
+    pub name: String,
+    /// Size of the original content (0 for directories)
+    pub size: u64,
+    /// Size of the compressed payload
+    pub compressed_size: u64,
+}
+
+/// File or directory left out of the new archive
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedSource {
+    /// Path of the file (lossily converted for display)
+    pub path: String,
+    /// Why the file was not archived (e.g. symbolic links)
+    pub reason: String,
+}
+
+/// Result of the creation
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CreateReport {
+    /// Entries written in the order of the central directory
+    pub entries: Vec<CreatedEntry>,
+    /// Files and directories that were not archived
+    pub skipped: Vec<SkippedSource>,
+}
+
+/// File or directory to be archived
+struct Source {
+    path: PathBuf,
+    /// Name of the entry (NFC, `/`-separated, directories end with `/`)
+    name: String,
+    is_directory: bool,
+}
+
+/// Writer that counts the bytes written through it
+struct CountingWriter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n_written = self.inner.write(buf)?;
+        self.count += n_written as u64;
+        return Ok(n_written);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+/// Collects the files and directories under `dir` recursively in the order of their names
+///
+/// # Arguments
+///
+/// * `dir` - directory to walk
+/// * `prefix` - name of `dir` in the archive (empty or ending with `/`)
+/// * `exclude` - file not to be archived (the output archive itself)
+/// * `sources` - destination of the found files & directories
+/// * `skipped` - destination of the files that cannot be archived
+fn collect_sources(
+    dir: &Path,
+    prefix: &str,
+    exclude: Option<&Path>,
+    sources: &mut Vec<Source>,
+    skipped: &mut Vec<SkippedSource>,
+) -> std::io::Result<()> {
+    let mut children = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    children.sort_by_key(|child| child.file_name());
+    for child in children {
+        let path = child.path();
+        let skip = |reason: &str| SkippedSource {
+            path: path.to_string_lossy().into_owned(),
+            reason: reason.to_string(),
+        };
+        let file_name = match child.file_name().to_str() {
+            Some(file_name) => NormalizationForm::NFC.normalize(file_name),
+            None => {
+                skipped.push(skip("the name is not valid Unicode"));
+                continue;
+            }
+        };
+        let file_type = child.file_type()?;
+        if file_type.is_symlink() {
+            skipped.push(skip("symbolic links are not archived"));
+        } else if file_type.is_dir() {
+            let name = format!("{}{}/", prefix, file_name);
+            sources.push(Source {
+                path: path.clone(),
+                name: name.clone(),
+                is_directory: true,
+            });
+            collect_sources(&path, &name, exclude, sources, skipped)?;
+        } else if file_type.is_file() {
+            if exclude.is_some_and(|exclude| path.canonicalize().ok().as_deref() == Some(exclude)) {
+                continue;
+            }
+            sources.push(Source {
+                path,
+                name: format!("{}{}", prefix, file_name),
+                is_directory: false,
+            });
+        } else {
+            skipped.push(skip("special files are not archived"));
+        }
+    }
+    return Ok(());
+}
+
+/// Returns the external file attributes of the source (Unix mode in the upper 16 bits)
+fn external_file_attributes(metadata: &std::fs::Metadata, is_directory: bool) -> u32 {
+    #[cfg(unix)]
+    let permissions = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o7777
+    };
+    #[cfg(not(unix))]
+    let permissions = if metadata.permissions().readonly() {
+        0o444
+    } else {
+        0o644
+    } | if is_directory { 0o111 } else { 0 };
+    return if is_directory {
+        ((0o040000 | permissions) << 16) | MSDOS_DIRECTORY_ATTRIBUTE
+    } else {
+        (0o100000 | permissions) << 16
+    };
+}
+
+/// Writes a new archive of the files and directories under `source` to `writer` and returns the report on it.
+///
+/// The entries are named relative to `source` and sorted by name.  Files are deflated (empty ones are stored), and the
+/// sizes & CRC-32 are filled in the local file headers afterwards, so the archive has no data descriptors.
+/// The MS-DOS times are in UTC, and the extended timestamp extra field carries the exact modification times.
+/// Symbolic links and files whose names are not valid Unicode are skipped.
+///
+/// # Arguments
+///
+/// * `writer` - destination of the archive
+/// * `source` - directory whose contents are archived
+/// * `options` - creation options
+pub fn create_archive<W: Write + Seek, P: AsRef<Path>>(
+    writer: &mut W,
+    source: P,
+    options: &CreateOptions,
+) -> anyhow::Result<CreateReport> {
+    return write_archive(writer, source.as_ref(), None, options);
+}
+
+/// Same as `create_archive` except that `exclude` (canonicalized) is left out
+fn write_archive<W: Write + Seek>(
+    writer: &mut W,
+    source: &Path,
+    exclude: Option<&Path>,
+    options: &CreateOptions,
+) -> anyhow::Result<CreateReport> {
+    let mut report = CreateReport::default();
+    let mut sources = vec![];
+    collect_sources(source, "", exclude, &mut sources, &mut report.skipped)?;
+    let start = writer.stream_position()?;
+    let mut cd_entries = vec![];
+    for source in sources {
+        let metadata = std::fs::metadata(&source.path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64);
+        let (last_mod_date, last_mod_time) = timestamps::seconds_to_dos_time(modified);
+        let name_raw = source.name.as_bytes().to_vec();
+        let mut extra_field = timestamps::extended_timestamp_extra_field(modified);
+        if options.unicode_path_fields {
+            extra_field.extend(unicode_path::unicode_path_extra_field(
+                &name_raw,
+                &source.name,
+            )?);
+        }
+        let compression_method = if source.is_directory || metadata.len() == 0 {
+            0
+        } else {
+            8
+        };
+        let local_header_position = writer.stream_position()? - start;
+        let mut local_header = LocalFileHeader {
+            version_required_to_extract: VERSION_NEEDED,
+            general_purpose_flags: UTF8_FLAG_BIT,
+            compression_method,
+            last_mod_time,
+            last_mod_date,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_name_raw: name_raw.clone(),
+            extra_field: extra_field.clone(),
+            data_position: 0,
+            payload_size: 0,
+            data_descriptor_size: 0,
+        };
+        local_header.write(writer)?;
+        let mut size = 0;
+        let mut compressed_size = 0;
+        if compression_method != 0 {
+            let mut hasher = crc32fast::Hasher::new();
+            let mut counter = CountingWriter {
+                inner: &mut *writer,
+                count: 0,
+            };
+            let mut encoder = DeflateEncoder::new(&mut counter, Compression::default());
+            let mut file = File::open(&source.path)?;
+            let mut buffer = vec![0; 64 * 1024];
+            loop {
+                let n_read = file.read(&mut buffer)?;
+                if n_read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..n_read]);
+                encoder.write_all(&buffer[..n_read])?;
+                size += n_read as u64;
+            }
+            encoder.finish()?;
+            compressed_size = counter.count;
+            local_header.crc32 = hasher.finalize();
+            local_header.compressed_size = narrow(compressed_size, "compressed size")?;
+            local_header.uncompressed_size = narrow(size, "uncompressed size")?;
+            let end = writer.stream_position()?;
+            writer.seek(SeekFrom::Start(
+                start + local_header_position + LOCAL_HEADER_CRC32_OFFSET,
+            ))?;
+            writer.write_u32::<LE>(local_header.crc32)?;
+            writer.write_u32::<LE>(local_header.compressed_size)?;
+            writer.write_u32::<LE>(local_header.uncompressed_size)?;
+            writer.seek(SeekFrom::Start(end))?;
+        }
+        cd_entries.push(ZipCDEntry {
+            version_made_by: ((HostSystem::Unix.code() as u16) << 8) | SPECIFICATION_VERSION,
+            version_required_to_extract: VERSION_NEEDED,
+            general_purpose_flags: UTF8_FLAG_BIT,
+            compression_method,
+            last_mod_time,
+            last_mod_date,
+            crc32: local_header.crc32,
+            compressed_size: local_header.compressed_size,
+            uncompressed_size: local_header.uncompressed_size,
+            file_name_length: name_raw.len() as u16,
+            extra_field_length: extra_field.len() as u16,
+            file_comment_length: 0,
+            disk_number_start: 0,
+            internal_file_attributes: 0,
+            external_file_attributes: external_file_attributes(&metadata, source.is_directory),
+            local_header_position: narrow(local_header_position, "offset of the local header")?,
+            file_name_raw: name_raw,
+            extra_field,
+            file_comment: vec![],
+            starting_position_with_signature: 0,
+            starting_position_without_signature: 0,
+        });
+        report.entries.push(CreatedEntry {
+            name: source.name,
+            size,
+            compressed_size,
+        });
+    }
+    let cd_start = writer.stream_position()? - start;
+    let mut cd_size = 0;
+    for cd in cd_entries.iter() {
+        cd_size += cd.write(writer)?;
+    }
+    let n_entries = narrow(cd_entries.len() as u64, "number of entries")?;
+    ZipEOCD {
+        eocd_disk_index: 0,
+        cd_start_disk_index: 0,
+        n_cd_entries_in_disk: n_entries,
+        n_cd_entries: n_entries,
+        cd_size: narrow(cd_size, "size of the central directory")?,
+        cd_starting_position: narrow(cd_start, "offset of the central directory")?,
+        comment_length: 0,
+        comment: vec![],
+        starting_position_with_signature: 0,
+        starting_position_without_signature: 0,
+    }
+    .write(writer)?;
+    return Ok(report);
+}
+
+/// Creates a new archive at `path` from the files and directories under `source` and returns the report on it.
+///
+/// Same as `create_archive` except that the archive itself is not archived even if it is under `source`.
+/// On failure, the partially written file is removed.
+///
+/// # Arguments
+///
+/// * `path` - path to the new ZIP file
+/// * `source` - directory whose contents are archived
+/// * `options` - creation options
+pub fn create_zip_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    source: Q,
+    options: &CreateOptions,
+) -> anyhow::Result<CreateReport> {
+    let path = path.as_ref();
+    let mut writer = BufWriter::new(File::create(path)?);
+    let exclude = path.canonicalize()?;
+    let result =
+        write_archive(&mut writer, source.as_ref(), Some(&exclude), options).and_then(|report| {
+            writer.flush()?;
+            writer.get_ref().sync_all()?;
+            return Ok(report);
+        });
+    if result.is_err() {
+        drop(writer);
+        let _ = std::fs::remove_file(path);
+    }
+    return result;
+}
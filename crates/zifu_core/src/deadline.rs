@@ -0,0 +1,57 @@
+use std::time::Instant;
+
+use crate::cancellation::{check_cancelled, CancellationToken};
+
+/// Error returned when an operation does not finish by its deadline
+///
+/// The deadline is checked cooperatively between entries and between the chunks of their data,
+/// so a single slow read may exceed it.
+#[derive(Clone, Debug)]
+pub struct DeadlineExceeded {
+    /// Operation that was interrupted (e.g. `"reading the central directory"`)
+    pub during: &'static str,
+}
+
+impl std::fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(f, "timed out while {}", self.during);
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Returns `Err(DeadlineExceeded)` if `deadline` has passed
+///
+/// # Arguments
+///
+/// * `deadline` - deadline of the operation (`None` means no deadline)
+/// * `during` - operation in progress
+pub(crate) fn check_deadline(
+    deadline: Option<Instant>,
+    during: &'static str,
+) -> Result<(), DeadlineExceeded> {
+    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+        return Err(DeadlineExceeded { during });
+    }
+    return Ok(());
+}
+
+/// Deadline & cancellation token checked together in the loops over entries and chunks
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Interruption<'a> {
+    pub deadline: Option<Instant>,
+    pub cancellation: Option<&'a CancellationToken>,
+}
+
+impl Interruption<'_> {
+    /// Returns `Err` containing `DeadlineExceeded` or `Cancelled` if the operation must stop
+    ///
+    /// # Arguments
+    ///
+    /// * `during` - operation in progress
+    pub fn check(&self, during: &'static str) -> anyhow::Result<()> {
+        check_deadline(self.deadline, during)?;
+        check_cancelled(self.cancellation, during)?;
+        return Ok(());
+    }
+}
@@ -0,0 +1,133 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::collisions::with_suffix;
+use crate::RenamePlanEntry;
+
+/// How the names of the entries are duplicated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DuplicateKind {
+    /// The entries have the same byte sequence; only one of them survives the extraction
+    Identical,
+    /// The names differ only in case (e.g. `a.txt` & `A.txt`); they overwrite each other in Windows & macOS
+    CaseInsensitive,
+}
+
+/// Entries whose names are duplicated
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateNames {
+    /// Name of the first entry
+    pub name: String,
+    /// 0-based indices of the entries in the central directory
+    pub indices: Vec<usize>,
+    pub kind: DuplicateKind,
+}
+
+/// Returns the duplicated names in the order of the first entries
+///
+/// Entries that share the name only after the conversion are reported by `collisions::find_collisions` instead.
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive (`InputZIPArchive::plan_conversion`)
+pub fn find_duplicates(plan: &[RenamePlanEntry]) -> Vec<DuplicateNames> {
+    let mut duplicates = vec![];
+    let mut raw_groups: HashMap<&[u8], Vec<&RenamePlanEntry>> = HashMap::new();
+    let mut raw_order = vec![];
+    for entry in plan.iter() {
+        let group = raw_groups.entry(entry.raw.as_slice()).or_default();
+        if group.is_empty() {
+            raw_order.push(entry.raw.as_slice());
+        }
+        group.push(entry);
+    }
+    for raw in raw_order.into_iter() {
+        let group = &raw_groups[raw];
+        if group.len() > 1 {
+            duplicates.push(DuplicateNames {
+                name: group[0].proposed_name.clone(),
+                indices: group.iter().map(|entry| entry.index).collect(),
+                kind: DuplicateKind::Identical,
+            });
+        }
+    }
+    let mut case_groups: HashMap<String, Vec<&RenamePlanEntry>> = HashMap::new();
+    let mut case_order = vec![];
+    for entry in plan.iter() {
+        let key = entry.proposed_name.to_lowercase();
+        let group = case_groups.entry(key.clone()).or_default();
+        if group.is_empty() {
+            case_order.push(key);
+        }
+        group.push(entry);
+    }
+    for key in case_order.into_iter() {
+        let group = &case_groups[&key];
+        let distinct_names: HashSet<&str> = group
+            .iter()
+            .map(|entry| entry.proposed_name.as_str())
+            .collect();
+        if distinct_names.len() > 1 {
+            duplicates.push(DuplicateNames {
+                name: group[0].proposed_name.clone(),
+                indices: group.iter().map(|entry| entry.index).collect(),
+                kind: DuplicateKind::CaseInsensitive,
+            });
+        }
+    }
+    return duplicates;
+}
+
+/// How to write the archive without duplicated names
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Deduplication {
+    /// Indices of the entries to be left out (all but the last of each `Identical` group, as extractors overwrite earlier ones)
+    pub skipped: Vec<usize>,
+    /// New names keyed by the byte sequences of the entries (e.g. `A.txt` -> `A (2).txt` if `a.txt` comes first)
+    ///
+    /// Meant to be fed to `InputZIPArchive::set_rename_hook`.
+    pub renamed: HashMap<Vec<u8>, String>,
+}
+
+/// Returns how to remove the duplicates found by `find_duplicates`
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of all the entries of the archive
+/// * `duplicates` - duplicates found by `find_duplicates`
+pub fn deduplicate(plan: &[RenamePlanEntry], duplicates: &[DuplicateNames]) -> Deduplication {
+    let mut deduplication = Deduplication::default();
+    let mut taken: HashSet<String> = plan
+        .iter()
+        .map(|entry| entry.proposed_name.to_lowercase())
+        .collect();
+    for duplicate in duplicates.iter() {
+        match duplicate.kind {
+            DuplicateKind::Identical => {
+                let (_, skipped) = duplicate.indices.split_last().unwrap();
+                deduplication.skipped.extend_from_slice(skipped);
+            }
+            DuplicateKind::CaseInsensitive => {
+                // The first spelling keeps the name
+                let mut seen: HashSet<&str> = HashSet::new();
+                for &index in duplicate.indices.iter() {
+                    let entry = &plan[index];
+                    let is_first = seen.is_empty();
+                    if !seen.insert(entry.proposed_name.as_str()) || is_first {
+                        continue;
+                    }
+                    let new_name = (2..)
+                        .map(|n| with_suffix(&entry.proposed_name, n))
+                        .find(|candidate| !taken.contains(&candidate.to_lowercase()))
+                        .unwrap();
+                    taken.insert(new_name.to_lowercase());
+                    deduplication.renamed.insert(entry.raw.clone(), new_name);
+                }
+            }
+        }
+    }
+    deduplication.skipped.sort_unstable();
+    deduplication.skipped.dedup();
+    return deduplication;
+}
@@ -0,0 +1,230 @@
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+
+use crate::filename_decoder::{ASCIIDecoder, IDecoder, LegacyEncodingDecoder, UTF8NFCDecoder};
+use crate::name_scoring::score_name;
+use crate::origin_hints::CreatorSystem;
+
+/// Top-level domains passed to `chardetng` as hints
+///
+/// Each of them makes the detector prefer the encodings native to the region, so the number of hints
+/// that agree on an encoding tells how robust the guess is.
+static TLD_HINTS: &[Option<&[u8]>] = &[
+    None,
+    Some(b"jp"),
+    Some(b"cn"),
+    Some(b"tw"),
+    Some(b"kr"),
+    Some(b"th"),
+    Some(b"vn"),
+    Some(b"ru"),
+    Some(b"gr"),
+    Some(b"tr"),
+    Some(b"il"),
+    Some(b"pl"),
+    Some(b"de"),
+];
+
+/// Encodings that are always examined even if no hint votes for them
+static BASELINE_ENCODINGS: &[&Encoding] = &[
+    encoding_rs::UTF_8,
+    encoding_rs::SHIFT_JIS,
+    encoding_rs::GBK,
+    encoding_rs::BIG5,
+    encoding_rs::EUC_KR,
+    encoding_rs::WINDOWS_1252,
+];
+
+/// Base of the factor applied to the score of an encoding by the plausibility of the decoded names
+/// (see `name_scoring::score_name`) when several encodings decode all of them
+///
+/// A difference of 0.75 in plausibility (e.g. between kana and uncommon ideographs) makes about 5x difference in score.
+const PLAUSIBILITY_BASE: f64 = 8.0;
+
+/// Encoding candidate guessed by `detect_encoding_candidates`
+pub struct EncodingCandidate {
+    /// Decoder for the candidate encoding
+    pub decoder: Box<dyn IDecoder>,
+    /// Relative confidence between 0 and 1; the sum over all the returned candidates is 1
+    pub confidence: f64,
+}
+
+impl std::fmt::Debug for EncodingCandidate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncodingCandidate")
+            .field("encoding", &self.decoder.encoding_name())
+            .field("confidence", &self.confidence)
+            .finish()
+    }
+}
+
+/// Guesses encoding statistically from an array of sequences and returns candidates sorted by confidence (descending).
+///
+/// The statistics come from `chardetng`, queried with several regional hints.  The confidence of each candidate is
+/// derived from the number of hints that voted for it and from the ratio of the sequences it can decode without error.
+///
+/// Short names are often decoded without error in several encodings (typically Shift_JIS, GBK, and Big5),
+/// and the statistics alone can't tell them apart.  In that case, the scores of those encodings are also weighted by how
+/// plausible the decoded names look (common kanji/hanzi, runs of katakana, intact extensions, etc.).
+///
+/// If all the sequences consist of ASCII characters, returns only the ASCII decoder.
+/// Candidates that can decode none of the non-ASCII sequences are not returned.
+///
+/// # Arguments
+///
+/// * `strings` - sequences that may represent strings (e.g. file names)
+pub fn detect_encoding_candidates<T>(strings: &[T]) -> Vec<EncodingCandidate>
+where
+    T: AsRef<[u8]>,
+{
+    return detect_encoding_candidates_with_creator(strings, CreatorSystem::Unknown);
+}
+
+/// Same as `detect_encoding_candidates`, but also takes the OS that likely created the archive into account.
+///
+/// UTF-8 is preferred for archives from UNIX-like OSes and the others for those from Windows.
+///
+/// # Arguments
+///
+/// * `strings` - sequences that may represent strings (e.g. file names)
+/// * `creator` - OS that likely created the archive (see `origin_hints::OriginHints::likely_creator`)
+pub fn detect_encoding_candidates_with_creator<T>(
+    strings: &[T],
+    creator: CreatorSystem,
+) -> Vec<EncodingCandidate>
+where
+    T: AsRef<[u8]>,
+{
+    let non_ascii_strings: Vec<&[u8]> = strings
+        .iter()
+        .map(|s| s.as_ref())
+        .filter(|s| !s.is_ascii())
+        .collect();
+    if non_ascii_strings.is_empty() {
+        return vec![EncodingCandidate {
+            decoder: Box::new(ASCIIDecoder {}),
+            confidence: 1.0,
+        }];
+    }
+
+    let mut detector = EncodingDetector::new();
+    for s in non_ascii_strings.iter() {
+        detector.feed(s, false);
+        // Separate names so that a multibyte character is never considered to straddle two of them
+        detector.feed(b"\n", false);
+    }
+    detector.feed(b"", true);
+
+    let mut votes: Vec<(&'static Encoding, usize)> =
+        BASELINE_ENCODINGS.iter().map(|e| (*e, 0)).collect();
+    for tld in TLD_HINTS {
+        let (guessed, plausible) = detector.guess_assess(*tld, true);
+        // Implausible guesses are just the defaults for the regions
+        if !plausible {
+            continue;
+        }
+        match votes.iter_mut().find(|(e, _)| *e == guessed) {
+            Some((_, n)) => *n += 1,
+            None => votes.push((guessed, 1)),
+        }
+    }
+
+    let decoded: Vec<Vec<Option<String>>> = votes
+        .iter()
+        .map(|(encoding, _)| {
+            non_ascii_strings
+                .iter()
+                .map(|s| {
+                    encoding
+                        .decode_without_bom_handling_and_without_replacement(s)
+                        .map(|decoded| decoded.into_owned())
+                })
+                .collect()
+        })
+        .collect();
+    let n_fully_decodable = decoded
+        .iter()
+        .filter(|names| names.iter().all(Option::is_some))
+        .count();
+
+    let mut scored: Vec<(&'static Encoding, f64)> = votes
+        .into_iter()
+        .zip(decoded)
+        .map(|((encoding, n_votes), names)| {
+            let n_decodable = names.iter().filter(|name| name.is_some()).count();
+            let validity = n_decodable as f64 / non_ascii_strings.len() as f64;
+            let plausibility_factor = if n_fully_decodable > 1 && n_decodable == names.len() {
+                let plausibility = names
+                    .iter()
+                    .flatten()
+                    .map(|name| score_name(name))
+                    .sum::<f64>()
+                    / names.len() as f64;
+                PLAUSIBILITY_BASE.powf(plausibility)
+            } else {
+                1.0
+            };
+            let creator_factor = match (creator, encoding == encoding_rs::UTF_8) {
+                (CreatorSystem::Windows, true) => 0.5,
+                (CreatorSystem::Unix, true) => 2.0,
+                _ => 1.0,
+            };
+            (
+                encoding,
+                validity * validity * (1 + n_votes) as f64 * creator_factor * plausibility_factor,
+            )
+        })
+        .filter(|(_, score)| *score > 0.0)
+        .collect();
+    let total: f64 = scored.iter().map(|(_, score)| score).sum();
+    // Stable sort keeps the order of `BASELINE_ENCODINGS` for ties
+    scored.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    return scored
+        .into_iter()
+        .map(|(encoding, score)| EncodingCandidate {
+            decoder: if encoding == encoding_rs::UTF_8 {
+                Box::new(UTF8NFCDecoder {})
+            } else {
+                Box::new(LegacyEncodingDecoder { decoder: encoding })
+            },
+            confidence: score / total,
+        })
+        .collect();
+}
+
+/// Encodings compared in `InputZIPArchive::preview_names` in addition to the detected candidates
+pub static COMPARED_ENCODINGS: &[&str] = &["UTF-8", "Shift_JIS", "GBK", "Big5", "EUC-KR", "CP437"];
+
+/// File name decoded in a candidate encoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecodedName {
+    /// Decoded name (with replacement characters if not `lossless`)
+    pub name: String,
+    /// `true` if the name is decoded without errors
+    pub lossless: bool,
+}
+
+/// How an implicitly-encoded file name is decoded in each candidate encoding
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NamePreview {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the file name
+    pub raw: Vec<u8>,
+    /// Decoded names in the same order as the decoders passed
+    pub decoded: Vec<DecodedName>,
+}
+
+/// Returns `true` if the winner of `candidates` is not decisive (the runner-up has more than half the confidence of the winner)
+///
+/// # Arguments
+///
+/// * `candidates` - candidates returned by `detect_encoding_candidates` (sorted by confidence)
+pub fn is_ambiguous(candidates: &[EncodingCandidate]) -> bool {
+    return match candidates {
+        [winner, runner_up, ..] => runner_up.confidence * 2.0 > winner.confidence,
+        _ => false,
+    };
+}
@@ -0,0 +1,233 @@
+//! Encodings that `IDecoder::from_encoding_name` accepts, for users who look for the name to pass (`zifu --list-encodings`)
+
+use crate::filename_decoder::oem_codepages;
+use crate::shift_jis::STRICT_SHIFT_JIS_NAME;
+
+/// Legacy encodings supported via `encoding_rs` (UTF-16 is excluded because file names can't be encoded in it)
+pub(crate) static LEGACY_ENCODINGS: &[&encoding_rs::Encoding] = &[
+    encoding_rs::BIG5,
+    encoding_rs::EUC_JP,
+    encoding_rs::EUC_KR,
+    encoding_rs::GB18030,
+    encoding_rs::GBK,
+    encoding_rs::IBM866,
+    encoding_rs::ISO_2022_JP,
+    encoding_rs::ISO_8859_2,
+    encoding_rs::ISO_8859_3,
+    encoding_rs::ISO_8859_4,
+    encoding_rs::ISO_8859_5,
+    encoding_rs::ISO_8859_6,
+    encoding_rs::ISO_8859_7,
+    encoding_rs::ISO_8859_8,
+    encoding_rs::ISO_8859_8_I,
+    encoding_rs::ISO_8859_10,
+    encoding_rs::ISO_8859_13,
+    encoding_rs::ISO_8859_14,
+    encoding_rs::ISO_8859_15,
+    encoding_rs::ISO_8859_16,
+    encoding_rs::KOI8_R,
+    encoding_rs::KOI8_U,
+    encoding_rs::MACINTOSH,
+    encoding_rs::SHIFT_JIS,
+    encoding_rs::WINDOWS_874,
+    encoding_rs::WINDOWS_1250,
+    encoding_rs::WINDOWS_1251,
+    encoding_rs::WINDOWS_1252,
+    encoding_rs::WINDOWS_1253,
+    encoding_rs::WINDOWS_1254,
+    encoding_rs::WINDOWS_1255,
+    encoding_rs::WINDOWS_1256,
+    encoding_rs::WINDOWS_1257,
+    encoding_rs::WINDOWS_1258,
+    encoding_rs::X_MAC_CYRILLIC,
+];
+
+/// Language or script group of encodings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EncodingGroup {
+    Japanese,
+    SimplifiedChinese,
+    TraditionalChinese,
+    Korean,
+    Western,
+    CentralEuropean,
+    Cyrillic,
+    Greek,
+    Turkish,
+    Hebrew,
+    Arabic,
+    Baltic,
+    Thai,
+    Vietnamese,
+    /// e.g. South European (ISO-8859-3)
+    Other,
+}
+
+impl EncodingGroup {
+    /// Returns the name of the group for display (e.g. `Central European`)
+    pub fn description(&self) -> &'static str {
+        return match self {
+            EncodingGroup::Japanese => "Japanese",
+            EncodingGroup::SimplifiedChinese => "Simplified Chinese",
+            EncodingGroup::TraditionalChinese => "Traditional Chinese",
+            EncodingGroup::Korean => "Korean",
+            EncodingGroup::Western => "Western European",
+            EncodingGroup::CentralEuropean => "Central European",
+            EncodingGroup::Cyrillic => "Cyrillic",
+            EncodingGroup::Greek => "Greek",
+            EncodingGroup::Turkish => "Turkish",
+            EncodingGroup::Hebrew => "Hebrew",
+            EncodingGroup::Arabic => "Arabic",
+            EncodingGroup::Baltic => "Baltic",
+            EncodingGroup::Thai => "Thai",
+            EncodingGroup::Vietnamese => "Vietnamese",
+            EncodingGroup::Other => "Other",
+        };
+    }
+}
+
+/// Encoding that `IDecoder::from_encoding_name` accepts
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EncodingInfo {
+    /// Canonical name (e.g. `Shift_JIS` or `CP437`)
+    pub name: String,
+    /// Common labels that can be passed instead of the name (e.g. `sjis`)
+    pub labels: Vec<String>,
+    /// Language or script group
+    pub group: EncodingGroup,
+}
+
+impl EncodingInfo {
+    /// Returns `true` if the name, a label, or the group contains `query` (case-insensitive)
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - part of a name, a label, or a group (e.g. `jis` or `cyrillic`)
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        return self.name.to_lowercase().contains(&query)
+            || self.labels.iter().any(|label| label.contains(&query))
+            || self.group.description().to_lowercase().contains(&query);
+    }
+}
+
+/// Returns the group and common labels of an encoding of `encoding_rs`
+fn describe_legacy_encoding(
+    encoding: &'static encoding_rs::Encoding,
+) -> (EncodingGroup, &'static [&'static str]) {
+    use EncodingGroup::*;
+    return match encoding.name() {
+        "Big5" => (TraditionalChinese, &["big5", "big5-hkscs", "cn-big5"]),
+        "EUC-JP" => (Japanese, &["euc-jp", "x-euc-jp"]),
+        "EUC-KR" => (Korean, &["euc-kr", "windows-949", "ks_c_5601-1987"]),
+        "gb18030" => (SimplifiedChinese, &["gb18030"]),
+        "GBK" => (SimplifiedChinese, &["gbk", "gb2312", "x-gbk"]),
+        "IBM866" => (Cyrillic, &["ibm866", "cp866", "866"]),
+        "ISO-2022-JP" => (Japanese, &["iso-2022-jp", "csiso2022jp"]),
+        "ISO-8859-2" => (CentralEuropean, &["iso-8859-2", "latin2"]),
+        "ISO-8859-3" => (Other, &["iso-8859-3", "latin3"]),
+        "ISO-8859-4" => (Baltic, &["iso-8859-4", "latin4"]),
+        "ISO-8859-5" => (Cyrillic, &["iso-8859-5", "cyrillic"]),
+        "ISO-8859-6" => (Arabic, &["iso-8859-6", "arabic"]),
+        "ISO-8859-7" => (Greek, &["iso-8859-7", "greek"]),
+        "ISO-8859-8" => (Hebrew, &["iso-8859-8", "hebrew", "visual"]),
+        "ISO-8859-8-I" => (Hebrew, &["iso-8859-8-i", "logical"]),
+        "ISO-8859-10" => (Western, &["iso-8859-10", "latin6"]),
+        "ISO-8859-13" => (Baltic, &["iso-8859-13"]),
+        "ISO-8859-14" => (Western, &["iso-8859-14"]),
+        "ISO-8859-15" => (Western, &["iso-8859-15", "l9"]),
+        "ISO-8859-16" => (CentralEuropean, &["iso-8859-16"]),
+        "KOI8-R" => (Cyrillic, &["koi8-r", "koi8"]),
+        "KOI8-U" => (Cyrillic, &["koi8-u", "koi8-ru"]),
+        "macintosh" => (Western, &["macintosh", "mac", "x-mac-roman"]),
+        "Shift_JIS" => (
+            Japanese,
+            &["shift_jis", "sjis", "cp932", "ms932", "windows-31j"],
+        ),
+        "windows-874" => (Thai, &["windows-874", "tis-620", "iso-8859-11"]),
+        "windows-1250" => (CentralEuropean, &["windows-1250", "cp1250"]),
+        "windows-1251" => (Cyrillic, &["windows-1251", "cp1251"]),
+        "windows-1252" => (
+            Western,
+            &["windows-1252", "cp1252", "latin1", "iso-8859-1", "ascii"],
+        ),
+        "windows-1253" => (Greek, &["windows-1253", "cp1253"]),
+        "windows-1254" => (Turkish, &["windows-1254", "cp1254", "iso-8859-9", "latin5"]),
+        "windows-1255" => (Hebrew, &["windows-1255", "cp1255"]),
+        "windows-1256" => (Arabic, &["windows-1256", "cp1256"]),
+        "windows-1257" => (Baltic, &["windows-1257", "cp1257"]),
+        "windows-1258" => (Vietnamese, &["windows-1258", "cp1258"]),
+        "x-mac-cyrillic" => (Cyrillic, &["x-mac-cyrillic", "x-mac-ukrainian"]),
+        _ => (Other, &[]),
+    };
+}
+
+/// Returns the group of an IBM OEM code page
+fn oem_codepage_group(codepage: u16) -> EncodingGroup {
+    use EncodingGroup::*;
+    return match codepage {
+        437 | 850 | 858 | 860 | 861 | 863 | 865 => Western,
+        852 => CentralEuropean,
+        855 | 866 => Cyrillic,
+        737 | 869 => Greek,
+        857 => Turkish,
+        862 => Hebrew,
+        720 | 864 => Arabic,
+        775 => Baltic,
+        874 => Thai,
+        _ => Other,
+    };
+}
+
+/// Returns all the legacy encodings accepted by `IDecoder::from_encoding_name` sorted by group and name
+///
+/// IBM OEM code pages are named `CPxxx` unless `encoding_rs` already handles them (e.g. CP866).
+pub fn list_encodings() -> Vec<EncodingInfo> {
+    let mut encodings = LEGACY_ENCODINGS
+        .iter()
+        .map(|encoding| {
+            let (group, labels) = describe_legacy_encoding(encoding);
+            return EncodingInfo {
+                name: encoding.name().to_string(),
+                labels: labels.iter().map(|label| label.to_string()).collect(),
+                group,
+            };
+        })
+        .collect::<Vec<_>>();
+    for codepage in oem_codepages() {
+        let label = format!("cp{}", codepage);
+        if encoding_rs::Encoding::for_label(label.as_bytes()).is_some() {
+            continue;
+        }
+        let mut labels = vec![label, format!("ibm{}", codepage)];
+        if codepage == 437 {
+            labels.push("oem-us".to_string());
+        }
+        encodings.push(EncodingInfo {
+            name: format!("CP{}", codepage),
+            labels,
+            group: oem_codepage_group(codepage),
+        });
+    }
+    encodings.push(EncodingInfo {
+        name: STRICT_SHIFT_JIS_NAME.to_string(),
+        labels: vec!["shift_jis-strict".to_string(), "sjis-strict".to_string()],
+        group: EncodingGroup::Japanese,
+    });
+    encodings.sort_by(|a, b| a.group.cmp(&b.group).then_with(|| a.name.cmp(&b.name)));
+    return encodings;
+}
+
+/// Returns the encodings whose names, labels, or groups contain `query` (case-insensitive)
+///
+/// # Arguments
+///
+/// * `query` - part of a name, a label, or a group (e.g. `jis` or `cyrillic`)
+pub fn search_encodings(query: &str) -> Vec<EncodingInfo> {
+    return list_encodings()
+        .into_iter()
+        .filter(|encoding| encoding.matches(query))
+        .collect();
+}
@@ -0,0 +1,398 @@
+//! Encryption schemes of entries (traditional ZipCrypto, WinZip AES, and PKWARE strong encryption)
+//!
+//! zifu never decrypts entries.  Encrypted payloads and the extra fields describing them are copied untouched;
+//! the schemes are only reported (e.g. "AES-256 encrypted, method deflate").
+//!
+//! PKWARE strong encryption of entries and of the central directory can't be handled by operations that need
+//! the plain data; `UnsupportedEncryptionError` tells which scheme was found and which entries use it.
+
+use std::io::{Read, Seek, SeekFrom};
+
+use zip_structs::{zip_central_directory::ZipCDEntry, zip_error::ZipReadError};
+
+use crate::local_headers::iter_local_headers;
+use crate::metadata::compression_method_name;
+use crate::origin_hints::iter_extra_fields;
+
+/// Header ID of the WinZip AES extra field
+pub const AES_EXTRA_FIELD_ID: u16 = 0x9901;
+/// Compression method of AES-encrypted entries (the actual one is in the AES extra field)
+pub const AES_COMPRESSION_METHOD: u16 = 99;
+/// Header ID of the strong encryption header (PKWARE)
+pub const STRONG_ENCRYPTION_EXTRA_FIELD_ID: u16 = 0x0017;
+/// Signature of the archive extra data record, which follows the archive decryption header
+/// just before an encrypted central directory
+pub const ARCHIVE_EXTRA_DATA_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x08];
+
+/// General purpose flag #6 (strong encryption)
+const STRONG_ENCRYPTION_FLAG_BIT: u16 = 1 << 6;
+/// General purpose flag #13 (the central directory is encrypted and the local headers are masked)
+const CENTRAL_DIRECTORY_ENCRYPTION_FLAG_BIT: u16 = 1 << 13;
+
+/// Key length of WinZip AES
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AesStrength {
+    Aes128,
+    Aes192,
+    Aes256,
+}
+
+impl AesStrength {
+    /// Returns the key length in bits (e.g. 256)
+    pub fn key_bits(&self) -> u16 {
+        return match self {
+            AesStrength::Aes128 => 128,
+            AesStrength::Aes192 => 192,
+            AesStrength::Aes256 => 256,
+        };
+    }
+}
+
+/// Contents of the WinZip AES extra field (0x9901)
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AesInfo {
+    /// 1 for AE-1 (with CRC-32) or 2 for AE-2 (CRC-32 is 0)
+    pub vendor_version: u16,
+    /// Key length
+    pub strength: AesStrength,
+    /// Compression method applied before the encryption (e.g. 8 = deflated)
+    pub compression_method: u16,
+}
+
+/// Algorithm of PKWARE strong encryption
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrongAlgorithm {
+    Des,
+    Rc2,
+    TripleDes168,
+    TripleDes112,
+    Aes128,
+    Aes192,
+    Aes256,
+    Blowfish,
+    Twofish,
+    Rc4,
+    /// Algorithm ID not defined in APPNOTE.TXT
+    Unknown(u16),
+}
+
+impl StrongAlgorithm {
+    /// Returns the algorithm of the ID in the strong encryption header or the ZIP64 end of central directory
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - algorithm ID (e.g. 0x6610 for AES-256)
+    pub fn from_id(id: u16) -> Self {
+        return match id {
+            0x6601 => StrongAlgorithm::Des,
+            // 0x6602 is RC2 of PKZIP 5.0 - 5.1, which has a bug fixed in 0x6702
+            0x6602 | 0x6702 => StrongAlgorithm::Rc2,
+            0x6603 => StrongAlgorithm::TripleDes168,
+            0x6609 => StrongAlgorithm::TripleDes112,
+            0x660E => StrongAlgorithm::Aes128,
+            0x660F => StrongAlgorithm::Aes192,
+            0x6610 => StrongAlgorithm::Aes256,
+            0x6720 => StrongAlgorithm::Blowfish,
+            0x6721 => StrongAlgorithm::Twofish,
+            0x6801 => StrongAlgorithm::Rc4,
+            _ => StrongAlgorithm::Unknown(id),
+        };
+    }
+
+    /// Returns the name of the algorithm (e.g. `AES-256` or `algorithm 0x1234`)
+    pub fn name(&self) -> String {
+        return match self {
+            StrongAlgorithm::Des => "DES".to_string(),
+            StrongAlgorithm::Rc2 => "RC2".to_string(),
+            StrongAlgorithm::TripleDes168 => "3DES-168".to_string(),
+            StrongAlgorithm::TripleDes112 => "3DES-112".to_string(),
+            StrongAlgorithm::Aes128 => "AES-128".to_string(),
+            StrongAlgorithm::Aes192 => "AES-192".to_string(),
+            StrongAlgorithm::Aes256 => "AES-256".to_string(),
+            StrongAlgorithm::Blowfish => "Blowfish".to_string(),
+            StrongAlgorithm::Twofish => "Twofish".to_string(),
+            StrongAlgorithm::Rc4 => "RC4".to_string(),
+            StrongAlgorithm::Unknown(id) => format!("algorithm {:#06x}", id),
+        };
+    }
+}
+
+/// Parameters of PKWARE strong encryption (from the strong encryption header or the ZIP64 end of central directory)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StrongEncryptionInfo {
+    pub algorithm: StrongAlgorithm,
+    /// Key length in bits
+    pub bit_length: u16,
+    /// Processing flags (1: password, 2: certificates, 3: both)
+    pub flags: u16,
+}
+
+/// Encryption scheme of an entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EntryEncryption {
+    /// Traditional PKWARE encryption
+    ZipCrypto,
+    /// WinZip AES (`None` if the AES extra field is missing or broken)
+    Aes(Option<AesInfo>),
+    /// PKWARE strong encryption (general purpose flag #6; `None` if the strong encryption header is missing or broken)
+    Strong(Option<StrongEncryptionInfo>),
+}
+
+impl EntryEncryption {
+    /// Returns the scheme for display (e.g. `AES-256 encrypted, method deflate`)
+    pub fn description(&self) -> String {
+        return match self {
+            EntryEncryption::ZipCrypto => "ZipCrypto encrypted".to_string(),
+            EntryEncryption::Aes(Some(info)) => format!(
+                "AES-{} encrypted, method {}",
+                info.strength.key_bits(),
+                compression_method_name(info.compression_method)
+            ),
+            EntryEncryption::Aes(None) => {
+                "AES encrypted (the AES extra field is missing or broken)".to_string()
+            }
+            EntryEncryption::Strong(Some(info)) => {
+                format!("strongly encrypted (PKWARE, {})", info.algorithm.name())
+            }
+            EntryEncryption::Strong(None) => "strongly encrypted (PKWARE)".to_string(),
+        };
+    }
+}
+
+/// Reads the WinZip AES extra field if any
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+pub fn read_aes_extra_field(extra_field: &[u8]) -> Option<AesInfo> {
+    let (_, data) = iter_extra_fields(extra_field).find(|(id, _)| *id == AES_EXTRA_FIELD_ID)?;
+    if data.len() < 7 || &data[2..4] != b"AE" {
+        return None;
+    }
+    let strength = match data[4] {
+        1 => AesStrength::Aes128,
+        2 => AesStrength::Aes192,
+        3 => AesStrength::Aes256,
+        _ => return None,
+    };
+    return Some(AesInfo {
+        vendor_version: u16::from_le_bytes([data[0], data[1]]),
+        strength,
+        compression_method: u16::from_le_bytes([data[5], data[6]]),
+    });
+}
+
+/// Reads the strong encryption header (0x0017) if any
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+pub fn read_strong_encryption_extra_field(extra_field: &[u8]) -> Option<StrongEncryptionInfo> {
+    let (_, data) =
+        iter_extra_fields(extra_field).find(|(id, _)| *id == STRONG_ENCRYPTION_EXTRA_FIELD_ID)?;
+    if data.len() < 8 {
+        return None;
+    }
+    // The first 2 bytes are the format (always 2)
+    return Some(StrongEncryptionInfo {
+        algorithm: StrongAlgorithm::from_id(u16::from_le_bytes([data[2], data[3]])),
+        bit_length: u16::from_le_bytes([data[4], data[5]]),
+        flags: u16::from_le_bytes([data[6], data[7]]),
+    });
+}
+
+/// Returns the encryption scheme of the entry (`None` if not encrypted)
+///
+/// # Arguments
+///
+/// * `cd` - central directory of the entry
+pub fn entry_encryption(cd: &ZipCDEntry) -> Option<EntryEncryption> {
+    if !cd.is_encrypted_data() {
+        return None;
+    }
+    if cd.is_strongly_encrypted_data() {
+        return Some(EntryEncryption::Strong(read_strong_encryption_extra_field(
+            &cd.extra_field,
+        )));
+    }
+    if cd.compression_method == AES_COMPRESSION_METHOD {
+        return Some(EntryEncryption::Aes(read_aes_extra_field(&cd.extra_field)));
+    }
+    return Some(EntryEncryption::ZipCrypto);
+}
+
+/// Encryption scheme that zifu can't process
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnsupportedEncryptionScheme {
+    /// The central directory (including the file names) is encrypted by PKWARE strong encryption
+    CentralDirectory(Option<StrongEncryptionInfo>),
+    /// Entries are encrypted by PKWARE strong encryption
+    StrongEncryption(Option<StrongEncryptionInfo>),
+}
+
+impl UnsupportedEncryptionScheme {
+    /// Returns the scheme for display (e.g. `central directory encryption (PKWARE, AES-256)`)
+    pub fn description(&self) -> String {
+        let (name, info) = match self {
+            UnsupportedEncryptionScheme::CentralDirectory(info) => {
+                ("central directory encryption", info)
+            }
+            UnsupportedEncryptionScheme::StrongEncryption(info) => ("strong encryption", info),
+        };
+        return match info {
+            Some(info) => format!(
+                "{} (PKWARE, {}, {}-bit key)",
+                name,
+                info.algorithm.name(),
+                info.bit_length
+            ),
+            None => format!("{} (PKWARE)", name),
+        };
+    }
+}
+
+/// Error telling which unsupported encryption scheme was found and which entries use it
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnsupportedEncryptionError {
+    pub scheme: UnsupportedEncryptionScheme,
+    /// Indices of the entries using the scheme (in the order of the local headers if the central directory is encrypted)
+    pub entries: Vec<usize>,
+}
+
+impl std::fmt::Display for UnsupportedEncryptionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let entries = self
+            .entries
+            .iter()
+            .map(|index| format!("#{}", index))
+            .collect::<Vec<_>>()
+            .join(", ");
+        return match self.scheme {
+            UnsupportedEncryptionScheme::CentralDirectory(_) => {
+                write!(
+                f,
+                "{} is not supported; the file names can't be read without the key (entries: {})",
+                self.scheme.description(),
+                if entries.is_empty() { "unknown" } else { &entries }
+            )
+            }
+            UnsupportedEncryptionScheme::StrongEncryption(_) => write!(
+                f,
+                "{} is not supported; used by {} {} ({})",
+                self.scheme.description(),
+                self.entries.len(),
+                if self.entries.len() == 1 {
+                    "entry"
+                } else {
+                    "entries"
+                },
+                entries
+            ),
+        };
+    }
+}
+
+impl std::error::Error for UnsupportedEncryptionError {}
+
+impl From<UnsupportedEncryptionError> for ZipReadError {
+    fn from(error: UnsupportedEncryptionError) -> Self {
+        return ZipReadError::UnsupportedZipArchive {
+            reason: error.to_string(),
+        };
+    }
+}
+
+/// Returns the entries encrypted by PKWARE strong encryption, grouped by the algorithm
+///
+/// # Arguments
+///
+/// * `cd_entries` - central directories
+pub(crate) fn strongly_encrypted_entries(
+    cd_entries: &[ZipCDEntry],
+) -> Vec<UnsupportedEncryptionError> {
+    let mut errors: Vec<UnsupportedEncryptionError> = vec![];
+    for (index, cd) in cd_entries.iter().enumerate() {
+        if cd.is_encrypted_central_directory() {
+            let info = read_strong_encryption_extra_field(&cd.extra_field);
+            let scheme = UnsupportedEncryptionScheme::CentralDirectory(info);
+            push_entry(&mut errors, scheme, index);
+        }
+        if let Some(EntryEncryption::Strong(info)) = entry_encryption(cd) {
+            push_entry(
+                &mut errors,
+                UnsupportedEncryptionScheme::StrongEncryption(info),
+                index,
+            );
+        }
+    }
+    return errors;
+}
+
+/// Adds `index` to the error of `scheme`, creating it if needed
+fn push_entry(
+    errors: &mut Vec<UnsupportedEncryptionError>,
+    scheme: UnsupportedEncryptionScheme,
+    index: usize,
+) {
+    match errors.iter_mut().find(|error| error.scheme == scheme) {
+        Some(error) => error.entries.push(index),
+        None => errors.push(UnsupportedEncryptionError {
+            scheme,
+            entries: vec![index],
+        }),
+    }
+}
+
+/// Detects an encrypted central directory before it is parsed (it can't be)
+///
+/// The central directory is regarded as encrypted if the ZIP64 end of central directory has the encryption parameters,
+/// the archive extra data record is at the start of the central directory, or the first local header has flag #13.
+/// The entries using it are found by walking the local headers.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+/// * `cd_starting_position` - offset of the central directory
+/// * `zip64_info` - encryption parameters in the ZIP64 end of central directory (version 2) if any
+pub(crate) fn detect_encrypted_central_directory<R: Read + Seek>(
+    reader: &mut R,
+    cd_starting_position: u64,
+    zip64_info: Option<StrongEncryptionInfo>,
+) -> Result<Option<UnsupportedEncryptionError>, ZipReadError> {
+    reader.seek(SeekFrom::Start(cd_starting_position))?;
+    let mut signature = [0u8; 4];
+    let has_archive_extra_data = match reader.read_exact(&mut signature) {
+        Ok(()) => signature == ARCHIVE_EXTRA_DATA_SIGNATURE,
+        Err(_) => false,
+    };
+    let mut headers = iter_local_headers(reader).map_while(Result::ok).peekable();
+    let first_header_masked = headers.peek().is_some_and(|header| {
+        header.general_purpose_flags & CENTRAL_DIRECTORY_ENCRYPTION_FLAG_BIT != 0
+    });
+    // Walks the local headers only if the central directory is encrypted
+    if zip64_info.is_none() && !has_archive_extra_data && !first_header_masked {
+        return Ok(None);
+    }
+    let mut info = zip64_info;
+    let mut entries = vec![];
+    for (index, header) in headers.enumerate() {
+        if header.general_purpose_flags & CENTRAL_DIRECTORY_ENCRYPTION_FLAG_BIT == 0 {
+            continue;
+        }
+        if info.is_none() && header.general_purpose_flags & STRONG_ENCRYPTION_FLAG_BIT != 0 {
+            info = read_strong_encryption_extra_field(&header.extra_field);
+        }
+        entries.push(index);
+    }
+    return Ok(Some(UnsupportedEncryptionError {
+        scheme: UnsupportedEncryptionScheme::CentralDirectory(info),
+        entries,
+    }));
+}
@@ -0,0 +1,208 @@
+//! Typed error for callers who need to match on what went wrong
+//!
+//! The functions of this crate return `anyhow::Result` or their own error types.  `ZifuError` gathers them into one
+//! enum: convert with `ZifuError::from` (or `?` in a function returning `ZifuResult`) and match on the variants
+//! instead of downcasting `anyhow::Error` by hand.
+//!
+//! ```no_run
+//! use zifu_core::{InputZIPArchive, ZifuError, ZifuResult};
+//!
+//! fn open(path: &str) -> ZifuResult<()> {
+//!     let file = std::io::BufReader::new(std::fs::File::open(path)?);
+//!     match InputZIPArchive::new(file).map_err(ZifuError::from) {
+//!         Ok(_) => println!("parsed"),
+//!         Err(ZifuError::Parse(e)) => println!("broken archive: {}", e),
+//!         Err(e) => return Err(e),
+//!     }
+//!     return Ok(());
+//! }
+//! ```
+
+use zip_structs::zip_central_directory::ZipCDEntry;
+use zip_structs::zip_error::ZipReadError;
+
+use crate::cancellation::Cancelled;
+use crate::deadline::DeadlineExceeded;
+use crate::encryption::UnsupportedEncryptionError;
+use crate::feature_gate::UnsupportedFeatureError;
+use crate::fix::FixError;
+use crate::zip64;
+
+/// Error of the public API of this crate by kind
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum ZifuError {
+    /// The archive is broken or in a structure `zip_structs` can't read (never `ZipReadError::IOError`)
+    #[error(transparent)]
+    Parse(ZipReadError),
+    /// The central directory or the local file header of an entry is broken or truncated
+    #[error(transparent)]
+    Entry(#[from] EntryReadError),
+    /// The archive uses a construct that blocks the operation (e.g. split archives or encrypted central directories)
+    #[error(transparent)]
+    UnsupportedFeature(#[from] UnsupportedFeatureError),
+    /// Some entries are encrypted in a scheme that hides or can't be read without the key
+    #[error(transparent)]
+    UnsupportedEncryption(#[from] UnsupportedEncryptionError),
+    /// The encoding name is unknown or no encoding decodes all the names (detection failure)
+    #[error(transparent)]
+    Encoding(#[from] FixError),
+    /// The deadline passed before the operation finished
+    #[error(transparent)]
+    DeadlineExceeded(#[from] DeadlineExceeded),
+    /// The operation was cancelled through its token
+    #[error(transparent)]
+    Cancelled(#[from] Cancelled),
+    /// Reading or writing the files failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Any other error (e.g. `LossyConversionError` or `NameCollisionError`)
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+/// `Result` with `ZifuError`
+pub type ZifuResult<T> = Result<T, ZifuError>;
+
+impl From<ZipReadError> for ZifuError {
+    fn from(error: ZipReadError) -> Self {
+        return match error {
+            ZipReadError::IOError(e) => ZifuError::Io(e),
+            e => ZifuError::Parse(e),
+        };
+    }
+}
+
+impl From<anyhow::Error> for ZifuError {
+    /// Picks the variant by the type of the error (`Other` if it is not of this crate or `std::io`)
+    ///
+    /// The contexts attached to the error are dropped unless it falls back to `Other`.
+    fn from(error: anyhow::Error) -> Self {
+        let error = match error.downcast::<ZipReadError>() {
+            Ok(e) => return e.into(),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<EntryReadError>() {
+            Ok(e) => return ZifuError::Entry(e),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<std::io::Error>() {
+            Ok(e) => return ZifuError::Io(e),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<UnsupportedFeatureError>() {
+            Ok(e) => return ZifuError::UnsupportedFeature(e),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<UnsupportedEncryptionError>() {
+            Ok(e) => return ZifuError::UnsupportedEncryption(e),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<FixError>() {
+            Ok(e) => return ZifuError::Encoding(e),
+            Err(error) => error,
+        };
+        let error = match error.downcast::<DeadlineExceeded>() {
+            Ok(e) => return ZifuError::DeadlineExceeded(e),
+            Err(error) => error,
+        };
+        return match error.downcast::<Cancelled>() {
+            Ok(e) => ZifuError::Cancelled(e),
+            Err(error) => ZifuError::Other(error),
+        };
+    }
+}
+
+/// Structure of an entry that failed to be read
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EntryStructure {
+    /// Entry of the central directory
+    CentralDirectory,
+    /// Local file header before the data
+    LocalFileHeader,
+}
+
+impl EntryStructure {
+    /// Returns a human-readable description (e.g. `local file header`)
+    pub fn description(&self) -> &'static str {
+        return match self {
+            EntryStructure::CentralDirectory => "central directory",
+            EntryStructure::LocalFileHeader => "local file header",
+        };
+    }
+}
+
+/// Error returned when the central directory or the local file header of an entry can't be read
+///
+/// Tells which entry is broken and where, which `ZipReadError` alone doesn't.
+#[derive(Debug)]
+pub struct EntryReadError {
+    /// Structure that failed to be read
+    pub structure: EntryStructure,
+    /// Index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the file name in the central directory, converted if the error occurred while writing
+    /// (`None` if the central directory itself is broken)
+    pub name_raw: Option<Vec<u8>>,
+    /// Position of the structure in the input (where its signature should be)
+    pub position: u64,
+    /// Error while reading the structure
+    pub error: ZipReadError,
+}
+
+impl EntryReadError {
+    /// Returns the error of reading the central directory of entry `index`
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - index of the entry
+    /// * `position` - position of the central directory
+    /// * `error` - error while reading it
+    pub(crate) fn central_directory(index: usize, position: u64, error: ZipReadError) -> Self {
+        return Self {
+            structure: EntryStructure::CentralDirectory,
+            index,
+            name_raw: None,
+            position,
+            error,
+        };
+    }
+
+    /// Returns the error of reading the local file header of entry `index`
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - index of the entry
+    /// * `cd` - central directory of the entry
+    /// * `error` - error while reading the local file header
+    pub(crate) fn local_file_header(index: usize, cd: &ZipCDEntry, error: ZipReadError) -> Self {
+        return Self {
+            structure: EntryStructure::LocalFileHeader,
+            index,
+            name_raw: Some(cd.file_name_raw.clone()),
+            position: zip64::local_header_position(cd),
+            error,
+        };
+    }
+}
+
+impl std::fmt::Display for EntryReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "failed to read the {} of entry #{}",
+            self.structure.description(),
+            self.index
+        )?;
+        // Names not in UTF-8 (not converted yet) are shown byte by byte
+        if let Some(name_raw) = &self.name_raw {
+            match std::str::from_utf8(name_raw) {
+                Ok(name) => write!(f, " ({:?})", name)?,
+                Err(_) => write!(f, " (\"{}\")", name_raw.escape_ascii())?,
+            }
+        }
+        return write!(f, " at position {}: {}", self.position, self.error);
+    }
+}
+
+impl std::error::Error for EntryReadError {}
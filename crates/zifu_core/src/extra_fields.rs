@@ -0,0 +1,74 @@
+//! Editing of extra field records (header ID + size + data)
+
+use crate::origin_hints::iter_extra_fields;
+use crate::{field_length, FieldTooLongError};
+
+/// Returns the first record (including the header ID & size) with the header ID in the extra field if any
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+/// * `id` - header ID of the record
+pub(crate) fn find_record(extra_field: &[u8], id: u16) -> Option<Vec<u8>> {
+    return iter_extra_fields(extra_field)
+        .find(|(record_id, _)| *record_id == id)
+        // Never fails because the data are read from a record
+        .and_then(|(_, data)| build_record(id, data).ok());
+}
+
+/// Returns a record from the header ID & data, or `Err(FieldTooLongError)` if the data exceed 65535 bytes
+///
+/// # Arguments
+///
+/// * `id` - header ID of the record
+/// * `data` - data of the record (without the header ID & size)
+pub(crate) fn build_record(id: u16, data: &[u8]) -> Result<Vec<u8>, FieldTooLongError> {
+    let size = field_length(data.len(), "extra field record")?;
+    let mut record = Vec::with_capacity(4 + data.len());
+    record.extend_from_slice(&id.to_le_bytes());
+    record.extend_from_slice(&size.to_le_bytes());
+    record.extend_from_slice(data);
+    return Ok(record);
+}
+
+/// Returns the extra field whose records with the header ID are replaced with `record` (or removed if `None`)
+///
+/// The other records and a truncated tail (if any) are kept as they are.
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+/// * `id` - header ID of the records to be replaced
+/// * `record` - new record (including the header ID & size)
+pub(crate) fn replace_record(extra_field: &[u8], id: u16, record: Option<&[u8]>) -> Vec<u8> {
+    let mut replaced = Vec::with_capacity(extra_field.len() + record.map_or(0, <[u8]>::len));
+    let mut consumed = 0;
+    for (record_id, data) in iter_extra_fields(extra_field) {
+        if record_id != id {
+            replaced.extend_from_slice(&extra_field[consumed..consumed + 4 + data.len()]);
+        }
+        consumed += 4 + data.len();
+    }
+    if let Some(record) = record {
+        replaced.extend_from_slice(record);
+    }
+    replaced.extend_from_slice(&extra_field[consumed..]);
+    return replaced;
+}
+
+/// Returns the range of the data (without the header ID & size) of the first record with the header ID in the extra field
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+/// * `id` - header ID of the record
+pub(crate) fn find_data_range(extra_field: &[u8], id: u16) -> Option<std::ops::Range<usize>> {
+    let mut consumed = 0;
+    for (record_id, data) in iter_extra_fields(extra_field) {
+        if record_id == id {
+            return Some(consumed + 4..consumed + 4 + data.len());
+        }
+        consumed += 4 + data.len();
+    }
+    return None;
+}
@@ -0,0 +1,275 @@
+//! Extraction of the entries under the corrected names
+//!
+//! Many users only want the files out with readable names and don't need a fixed archive.
+//! The names go through the same pipeline as the conversion, and unsafe paths (e.g. `../a.txt`) are always sanitized
+//! so that nothing is written outside the destination.
+
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use byteorder::ReadBytesExt;
+
+use crate::filename_decoder::IDecoder;
+use crate::fix::choose_decoder;
+use crate::local_headers::LocalFileHeader;
+use crate::path_safety::sanitize_path;
+use crate::{attributes, metadata, output, repair, zip64};
+use crate::{ConversionOptions, InputZIPArchive};
+
+/// Options of `extract_zip_file`
+#[derive(Clone, Debug, Default)]
+pub struct ExtractOptions {
+    /// Encoding of the implicitly-encoded file names (e.g. `"sjis"`; `None` detects it, falling back to the locale default)
+    pub encoding: Option<String>,
+    /// Conversion of the decoded file names (e.g. normalization)
+    pub conversion: ConversionOptions,
+    /// Overwrites existing files instead of skipping the entries
+    pub overwrite: bool,
+}
+
+/// Entry written by the extraction
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractedEntry {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Corrected name of the entry
+    pub name: String,
+    /// Size of the decompressed data (0 for directories)
+    pub size: u64,
+}
+
+/// Entry left unextracted
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedEntry {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Corrected name of the entry
+    pub name: String,
+    /// Why the entry was not extracted (e.g. `encrypted`)
+    pub reason: String,
+}
+
+/// Result of the extraction
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExtractReport {
+    /// Entries written to the destination (including directories)
+    pub extracted: Vec<ExtractedEntry>,
+    /// Entries that could not be extracted (encrypted, unsupported compression methods, broken data, etc.)
+    pub skipped: Vec<SkippedEntry>,
+}
+
+/// Decompresses the payload into `dest` and returns the size of the decompressed data
+///
+/// Returns the reason as `Ok(Err(reason))` if the entry can't be extracted.
+///
+/// # Arguments
+///
+/// * `payload` - compressed payload (limited to its size)
+/// * `compression_method` - compression method of the entry
+/// * `expected_crc32` - CRC-32 of the decompressed data recorded in the archive
+/// * `dest` - file to write the decompressed data to
+fn decompress_to<R: Read, W: Write>(
+    payload: R,
+    compression_method: u16,
+    expected_crc32: u32,
+    dest: &mut W,
+) -> std::io::Result<Result<u64, String>> {
+    let reader = match repair::decompressing_reader(payload, compression_method) {
+        Some(reader) => reader,
+        None => {
+            return Ok(Err(format!(
+                "compression method {} is not supported",
+                metadata::compression_method_name(compression_method)
+            )))
+        }
+    };
+    let mut hasher = crc32fast::Hasher::new();
+    let mut reader = HashingReader {
+        inner: reader,
+        hasher: &mut hasher,
+    };
+    let size = match std::io::copy(&mut reader, dest) {
+        Ok(size) => size,
+        // Errors of the decompression; those of the writer are returned as they are
+        Err(e)
+            if e.kind() == std::io::ErrorKind::InvalidInput
+                || e.kind() == std::io::ErrorKind::InvalidData =>
+        {
+            return Ok(Err(format!("failed to decompress: {}", e)))
+        }
+        Err(e) => return Err(e),
+    };
+    let crc32 = hasher.finalize();
+    if crc32 != expected_crc32 {
+        return Ok(Err(format!(
+            "CRC-32 mismatch ({:08X} recorded, {:08X} actual)",
+            expected_crc32, crc32
+        )));
+    }
+    return Ok(Ok(size));
+}
+
+/// Reader that feeds the data read through it to CRC-32
+struct HashingReader<'a, R: Read> {
+    inner: R,
+    hasher: &'a mut crc32fast::Hasher,
+}
+
+impl<R: Read> Read for HashingReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n_read = self.inner.read(buf)?;
+        self.hasher.update(&buf[..n_read]);
+        return Ok(n_read);
+    }
+}
+
+impl<F> InputZIPArchive<F>
+where
+    F: ReadBytesExt + std::io::Seek,
+{
+    /// Extracts the entries to `dest` under the converted names and returns the report on them.
+    ///
+    /// Only stored and deflated entries are decompressed; encrypted entries, symbolic links, and entries with other
+    /// compression methods are skipped.  Unsafe names are sanitized even if `options.sanitize_paths` is not set.
+    /// Entries whose files already exist are skipped unless `overwrite` is set.
+    /// Entries with unreadable local headers or data descriptors are skipped as well, and no partially-written file is
+    /// left even if writing one fails.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest` - directory to extract the entries into (created if it doesn't exist)
+    /// * `legacy_decoder` - decoder for file names with implicit encoding
+    /// * `options` - conversion options (e.g. normalization)
+    /// * `overwrite` - overwrites existing files
+    pub fn extract_to<P: AsRef<Path>>(
+        &mut self,
+        dest: P,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+        overwrite: bool,
+    ) -> anyhow::Result<ExtractReport> {
+        let dest = dest.as_ref();
+        std::fs::create_dir_all(dest)?;
+        let plan = self.plan_conversion_with_options(legacy_decoder, options);
+        let mut report = ExtractReport::default();
+        for entry in plan.into_iter() {
+            let index = entry.index;
+            // Empty names are left out of the converted archives too (see `ConversionOptions::strip_top_directory`)
+            if entry.proposed_name.is_empty() {
+                continue;
+            }
+            let name = sanitize_path(&entry.proposed_name);
+            let skipped = |reason: &str| SkippedEntry {
+                index,
+                name: name.clone(),
+                reason: reason.to_string(),
+            };
+            let cd = &self.cd_entries[index];
+            let path: PathBuf = dest.join(&name);
+            if attributes::is_directory_entry(cd) {
+                std::fs::create_dir_all(&path)?;
+                report.extracted.push(ExtractedEntry {
+                    index,
+                    name,
+                    size: 0,
+                });
+                continue;
+            }
+            if cd.is_encrypted_data() {
+                report.skipped.push(skipped("encrypted"));
+                continue;
+            }
+            if attributes::is_symlink_entry(cd) {
+                report
+                    .skipped
+                    .push(skipped("symbolic links are not extracted"));
+                continue;
+            }
+            if !overwrite && path.exists() {
+                report.skipped.push(skipped("the file already exists"));
+                continue;
+            }
+            // Broken entries are skipped like broken data
+            let mut local_header = match LocalFileHeader::read_at(
+                &mut self.file_handler,
+                zip64::local_header_position(cd),
+            ) {
+                Ok(local_header) => local_header,
+                Err(e) => {
+                    report
+                        .skipped
+                        .push(skipped(&format!("local file header can't be read: {}", e)));
+                    continue;
+                }
+            };
+            if let Err(e) = local_header.read_data_descriptor(&mut self.file_handler, cd) {
+                report
+                    .skipped
+                    .push(skipped(&format!("data descriptor can't be read: {}", e)));
+                continue;
+            }
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut writer = BufWriter::new(File::create(&path)?);
+            let mut payload =
+                std::io::Read::take(&mut self.file_handler, local_header.payload_size);
+            let result = decompress_to(&mut payload, cd.compression_method, cd.crc32, &mut writer)
+                .map(|result| match result {
+                    Ok(_) if payload.limit() > 0 => Err(output::truncated_payload_error(
+                        local_header.payload_size,
+                        local_header.payload_size - payload.limit(),
+                    )
+                    .to_string()),
+                    result => result,
+                })
+                .and_then(|result| {
+                    if result.is_ok() {
+                        writer.flush()?;
+                    }
+                    return Ok(result);
+                });
+            match result {
+                Ok(Ok(size)) => {
+                    report.extracted.push(ExtractedEntry { index, name, size });
+                }
+                Ok(Err(reason)) => {
+                    // Broken files are worse than missing ones
+                    drop(writer);
+                    std::fs::remove_file(&path)?;
+                    report.skipped.push(skipped(&reason));
+                }
+                Err(e) => {
+                    // Never leave the half-written file; failing to remove it must not hide why it failed
+                    drop(writer);
+                    let _ = std::fs::remove_file(&path);
+                    return Err(e.into());
+                }
+            }
+        }
+        return Ok(report);
+    }
+}
+
+/// Extracts the archive at `path` to `dest` under the corrected names (UTF-8 with the default normalization).
+///
+/// The encoding of the implicitly-encoded names is detected as in `fix_zip_file` unless `options.encoding` is given.
+///
+/// # Arguments
+///
+/// * `path` - path to the archive
+/// * `dest` - directory to extract the entries into (created if it doesn't exist)
+/// * `options` - encoding, conversion, and overwriting options
+pub fn extract_zip_file<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    dest: Q,
+    options: &ExtractOptions,
+) -> anyhow::Result<ExtractReport> {
+    let mut zip = InputZIPArchive::from_path(path)?;
+    let decoder = choose_decoder(&zip, options.encoding.as_deref())?;
+    return zip.extract_to(dest, &*decoder, &options.conversion, options.overwrite);
+}
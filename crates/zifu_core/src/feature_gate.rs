@@ -0,0 +1,223 @@
+use zip_structs::{zip_central_directory::ZipCDEntry, zip_eocd::ZipEOCD};
+
+use crate::encryption::{self, UnsupportedEncryptionError, UnsupportedEncryptionScheme};
+
+/// Operation on an archive whose support is decided by `FeatureGate`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Operation {
+    /// Diagnosis of the file name encodings (`zifu -c`)
+    Check,
+    /// Listing or explaining file names (`zifu -l` / `--explain`)
+    List,
+    /// Writing the archive with fixed file names
+    Fix,
+    /// Extracting the contents (not done by zifu itself; for front-ends)
+    Extract,
+}
+
+impl Operation {
+    fn description(&self) -> &'static str {
+        return match self {
+            Operation::Check => "checking",
+            Operation::List => "listing",
+            Operation::Fix => "fixing",
+            Operation::Extract => "extracting",
+        };
+    }
+}
+
+/// How an unsupported construct affects an operation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Support {
+    /// The operation can't be done
+    Blocker,
+    /// The construct is carried over untouched (e.g. encrypted payloads copied as they are)
+    PassThrough,
+    /// The operation can be done, but the result may be incomplete
+    Warning,
+}
+
+/// Construct in an archive that zifu does not fully support
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnsupportedFeature {
+    /// ZIP64 (> 4 GiB or > 65535 entries)
+    Zip64,
+    /// One of split (multi-disk) archives
+    SplitArchive,
+    /// Central directory encrypted by PKWARE strong encryption
+    EncryptedCentralDirectory,
+    /// Entries encrypted with ZipCrypto or WinZip AES
+    EncryptedEntries,
+    /// Entries encrypted by PKWARE strong encryption
+    StronglyEncryptedEntries,
+}
+
+impl UnsupportedFeature {
+    /// Returns a human-readable description (e.g. `ZIP64 format`)
+    pub fn description(&self) -> &'static str {
+        return match self {
+            UnsupportedFeature::Zip64 => "ZIP64 format",
+            UnsupportedFeature::SplitArchive => "split (multi-disk) archive",
+            UnsupportedFeature::EncryptedCentralDirectory => "encrypted central directory",
+            UnsupportedFeature::EncryptedEntries => "encrypted entries",
+            UnsupportedFeature::StronglyEncryptedEntries => "strongly encrypted entries",
+        };
+    }
+
+    /// Returns how the construct affects `operation` (the support matrix)
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - operation on the archive
+    pub fn support(&self, operation: Operation) -> Support {
+        use Operation::*;
+        use Support::*;
+        return match (self, operation) {
+            // 64-bit offsets are read, but not written yet
+            (UnsupportedFeature::Zip64, Check | List) => Warning,
+            (UnsupportedFeature::Zip64, Fix | Extract) => Blocker,
+            (UnsupportedFeature::SplitArchive, _) => Blocker,
+            // File names themselves are encrypted
+            (UnsupportedFeature::EncryptedCentralDirectory, _) => Blocker,
+            (
+                UnsupportedFeature::EncryptedEntries | UnsupportedFeature::StronglyEncryptedEntries,
+                Extract,
+            ) => Blocker,
+            (
+                UnsupportedFeature::EncryptedEntries | UnsupportedFeature::StronglyEncryptedEntries,
+                _,
+            ) => PassThrough,
+        };
+    }
+}
+
+/// Error returned when an operation is blocked by an unsupported construct
+#[derive(Clone, Debug)]
+pub struct UnsupportedFeatureError {
+    pub feature: UnsupportedFeature,
+    pub operation: Operation,
+    /// Encryption schemes and the entries using them (only for the encryption features)
+    pub encryptions: Vec<UnsupportedEncryptionError>,
+}
+
+impl std::fmt::Display for UnsupportedFeatureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is not supported for this archive: {}",
+            self.operation.description(),
+            self.feature.description()
+        )?;
+        for encryption in self.encryptions.iter() {
+            write!(f, "; {}", encryption)?;
+        }
+        return Ok(());
+    }
+}
+
+impl std::error::Error for UnsupportedFeatureError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return self
+            .encryptions
+            .first()
+            .map(|error| error as &(dyn std::error::Error + 'static));
+    }
+}
+
+/// Central registry of the unsupported constructs found in an archive
+///
+/// Decides per operation whether they block it, pass through, or only warrant a warning.
+#[derive(Clone, Debug, Default)]
+pub struct FeatureGate {
+    features: Vec<UnsupportedFeature>,
+    encryptions: Vec<UnsupportedEncryptionError>,
+}
+
+impl FeatureGate {
+    /// Collects the unsupported constructs from the EOCD & central directories
+    ///
+    /// # Arguments
+    ///
+    /// * `eocd` - (32-bit) end of central directory
+    /// * `cd_entries` - central directories
+    pub(crate) fn from_structures(eocd: &ZipEOCD, cd_entries: &[ZipCDEntry]) -> Self {
+        let mut features = vec![];
+        if !eocd.is_single_archive() || cd_entries.iter().any(|cd| cd.disk_number_start != 0) {
+            features.push(UnsupportedFeature::SplitArchive);
+        }
+        if eocd.is_zip64() {
+            features.push(UnsupportedFeature::Zip64);
+        }
+        if cd_entries
+            .iter()
+            .any(ZipCDEntry::is_encrypted_central_directory)
+        {
+            features.push(UnsupportedFeature::EncryptedCentralDirectory);
+        }
+        if cd_entries
+            .iter()
+            .any(ZipCDEntry::is_strongly_encrypted_data)
+        {
+            features.push(UnsupportedFeature::StronglyEncryptedEntries);
+        } else if cd_entries.iter().any(ZipCDEntry::is_encrypted_data) {
+            features.push(UnsupportedFeature::EncryptedEntries);
+        }
+        return Self {
+            features,
+            encryptions: encryption::strongly_encrypted_entries(cd_entries),
+        };
+    }
+
+    /// Returns the unsupported constructs found in the archive
+    pub fn features(&self) -> &[UnsupportedFeature] {
+        return &self.features;
+    }
+
+    /// Returns the strong encryption schemes found in the archive and the entries using them
+    pub fn encryptions(&self) -> &[UnsupportedEncryptionError] {
+        return &self.encryptions;
+    }
+
+    /// Returns the constructs that warrant a warning for `operation`, or `Err` for the first one that blocks it
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - operation on the archive
+    pub fn check(
+        &self,
+        operation: Operation,
+    ) -> Result<Vec<UnsupportedFeature>, UnsupportedFeatureError> {
+        if let Some(feature) = self
+            .features
+            .iter()
+            .find(|feature| feature.support(operation) == Support::Blocker)
+        {
+            let encryptions = self
+                .encryptions
+                .iter()
+                .filter(|error| match error.scheme {
+                    UnsupportedEncryptionScheme::CentralDirectory(_) => {
+                        *feature == UnsupportedFeature::EncryptedCentralDirectory
+                    }
+                    UnsupportedEncryptionScheme::StrongEncryption(_) => {
+                        *feature == UnsupportedFeature::StronglyEncryptedEntries
+                    }
+                })
+                .cloned()
+                .collect();
+            return Err(UnsupportedFeatureError {
+                feature: *feature,
+                operation,
+                encryptions,
+            });
+        }
+        return Ok(self
+            .features
+            .iter()
+            .filter(|feature| feature.support(operation) == Support::Warning)
+            .copied()
+            .collect());
+    }
+}
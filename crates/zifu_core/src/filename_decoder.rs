@@ -1,15 +1,23 @@
+#[cfg(feature = "locale")]
 use codepage::to_encoding;
+#[cfg(feature = "oem-cp")]
 use lazy_static::lazy_static;
-use locale_name_code_page::get_codepage;
+#[cfg(feature = "oem-cp")]
 use oem_cp::code_table::DECODING_TABLE_CP_MAP;
+#[cfg(feature = "oem-cp")]
 use oem_cp::code_table_type::TableType;
+#[cfg(feature = "oem-cp")]
 use regex::Regex;
 
 use hfs_nfd::compose_from_hfs_nfd;
-use locale_config::Locale;
+
+use crate::origin_hints::HostSystem;
+use crate::shift_jis::{is_strict_shift_jis_name, ShiftJISMapping};
 
 /// Trait (interface) of decoder
-pub trait IDecoder {
+///
+/// Decoders are shared between threads when the names are decoded in parallel (see `parallel`).
+pub trait IDecoder: Send + Sync {
     /// Converts to UTF-8 `String` only if possible completely
     ///
     /// # Arguments
@@ -46,6 +54,7 @@ pub struct ASCIIDecoder {}
 /// OEM code page decoder (other than Asian languages)
 ///
 /// Single byte & use `oem_cp` to implement
+#[cfg(feature = "oem-cp")]
 struct OEMCPDecoder {
     decoder: &'static TableType,
     encoding_str: String,
@@ -54,9 +63,9 @@ struct OEMCPDecoder {
 /// Asian ANSI+OEM codepages decoder
 ///
 /// Use encoding_rs (CJKV + Thai)
-struct LegacyEncodingDecoder {
+pub(crate) struct LegacyEncodingDecoder {
     /// `Encoding` object (e.g. `encoding_rs::SHIFT_JIS` for Shift-JIS)
-    decoder: &'static encoding_rs::Encoding,
+    pub(crate) decoder: &'static encoding_rs::Encoding,
 }
 
 impl IDecoder for UTF8NFCDecoder {
@@ -98,6 +107,7 @@ impl IDecoder for ASCIIDecoder {
     }
 }
 
+#[cfg(feature = "oem-cp")]
 impl OEMCPDecoder {
     fn from_codepage(codepage: u16) -> Option<Self> {
         return Some(Self {
@@ -105,11 +115,9 @@ impl OEMCPDecoder {
             encoding_str: format!("CP{}", codepage),
         });
     }
-    fn fallback() -> Self {
-        return Self::from_codepage(437).unwrap();
-    }
 }
 
+#[cfg(feature = "oem-cp")]
 impl IDecoder for OEMCPDecoder {
     fn to_string_lossless(&self, input: &[u8]) -> Option<String> {
         return self.decoder.decode_string_checked(input);
@@ -124,14 +132,14 @@ impl IDecoder for OEMCPDecoder {
 
 impl IDecoder for LegacyEncodingDecoder {
     fn to_string_lossless(&self, input: &[u8]) -> Option<String> {
-        let (result, _, met_invalid_char) = self.decoder.decode(&input);
+        let (result, _, met_invalid_char) = self.decoder.decode(input);
         if met_invalid_char {
             return None;
         }
         return Some(result.into_owned());
     }
     fn to_string_lossy(&self, input: &[u8]) -> String {
-        return self.decoder.decode(&input).0.into_owned();
+        return self.decoder.decode(input).0.into_owned();
     }
     fn encoding_name(&self) -> &str {
         return self.decoder.name();
@@ -154,17 +162,47 @@ impl dyn IDecoder {
     /// Returns native OEM code pages for the current locale
     ///
     /// Supported: CJKV / Thai / IBM OEM
+    /// CP437 is returned if the locale is unknown (ASCII without the `oem-cp` feature).
     pub fn native_oem_encoding() -> Box<dyn IDecoder> {
-        let current_locale_name_full = Locale::user_default().to_string();
-        if let Some(codepage) = get_codepage(current_locale_name_full) {
-            if let Some(encoding) = to_encoding(codepage.oem) {
-                return Box::new(LegacyEncodingDecoder { decoder: encoding });
-            }
-            if let Some(decoder) = OEMCPDecoder::from_codepage(codepage.oem) {
-                return Box::new(decoder);
+        return user_codepages()
+            .and_then(|(oem, _)| decoder_from_codepage(oem))
+            .or_else(|| decoder_from_codepage(437))
+            .unwrap_or_else(<dyn IDecoder>::ascii);
+    }
+
+    /// Returns decoders for the current locale in the order to try: OEM code page -> ANSI code page -> CP437
+    ///
+    /// Many tools write file names in the ANSI code page (e.g. CP1251 for Russian) instead of the OEM one.
+    /// Code pages that appear twice (e.g. CP932 for Japanese) are tried only once.
+    /// Pass them to `decide_decoder` after the ASCII decoder.
+    pub fn native_fallback_chain() -> Vec<Box<dyn IDecoder>> {
+        return Self::native_fallback_chain_for_host(None);
+    }
+
+    /// Same as `native_fallback_chain`, but ordered by the host system that created the archive ("version made by")
+    ///
+    /// NTFS hosts (Windows tools that write names in the ANSI code page) get the ANSI code page first;
+    /// the others (e.g. MS-DOS / FAT hosts such as Windows Explorer) get the OEM code page first.
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - host system of the archive (see `origin_hints::OriginHints::host_system`)
+    pub fn native_fallback_chain_for_host(host: Option<HostSystem>) -> Vec<Box<dyn IDecoder>> {
+        let codepages = match (user_codepages(), host) {
+            (Some((oem, ansi)), Some(HostSystem::NTFS)) => vec![ansi, oem, 437],
+            (Some((oem, ansi)), _) => vec![oem, ansi, 437],
+            (None, _) => vec![437],
+        };
+        let mut chain: Vec<Box<dyn IDecoder>> = vec![];
+        for decoder in codepages.into_iter().filter_map(decoder_from_codepage) {
+            if chain
+                .iter()
+                .all(|added| added.encoding_name() != decoder.encoding_name())
+            {
+                chain.push(decoder);
             }
         }
-        return Box::new(OEMCPDecoder::fallback());
+        return chain;
     }
 
     /// Generates an instance of a decoder from encoding name (e.g. `sjis` -> Shift-JIS)
@@ -173,29 +211,117 @@ impl dyn IDecoder {
     ///
     /// * `name` - encoding name
     pub fn from_encoding_name(name: &str) -> Option<Box<dyn IDecoder>> {
-        lazy_static! {
-            static ref OEM_CP_REGEX: Regex = Regex::new(r"(?i)(?:CP|OEM ?|IBM)(\d+)").unwrap();
-            static ref CP437_REGEX: Regex =
-                Regex::new("(?i)(OEM[-_]US|PC-8|DOS[-_ ]?Latin[-_ ]?US)").unwrap();
-        }
-        if let Some(decoder) = encoding_rs::Encoding::for_label(name.as_bytes()) {
-            return Some(Box::new(LegacyEncodingDecoder { decoder: decoder }));
+        if is_strict_shift_jis_name(name) {
+            return Some(<dyn IDecoder>::shift_jis(ShiftJISMapping::JIS));
         }
-        if let Some(decoder) = OEM_CP_REGEX
-            .captures(name)
-            .and_then(|captures| captures.get(1))
-            .and_then(|match_| -> Option<u16> { match_.as_str().parse().ok() })
-            .and_then(|codepage| OEMCPDecoder::from_codepage(codepage))
-        {
-            return Some(Box::new(decoder));
+        if name.eq_ignore_ascii_case("cp932") {
+            return Some(<dyn IDecoder>::shift_jis(ShiftJISMapping::Windows31J));
         }
-        if CP437_REGEX.is_match(name) {
-            return Some(Box::new(OEMCPDecoder::fallback()));
+        if let Some(decoder) = encoding_rs::Encoding::for_label(name.as_bytes()) {
+            return Some(Box::new(LegacyEncodingDecoder { decoder }));
         }
-        return None;
+        return oem_codepage_from_name(name).and_then(oem_decoder_from_codepage);
     }
 }
 
+/// Returns the (OEM, ANSI) code pages of the user's locale (`None` if unknown or without the `locale` feature)
+#[cfg(feature = "locale")]
+fn user_codepages() -> Option<(u16, u16)> {
+    let current_locale_name_full = locale_config::Locale::user_default().to_string();
+    return locale_name_code_page::get_codepage(current_locale_name_full)
+        .map(|codepage| (codepage.oem, codepage.ansi));
+}
+
+/// Returns the (OEM, ANSI) code pages of the user's locale (`None` if unknown or without the `locale` feature)
+#[cfg(not(feature = "locale"))]
+fn user_codepages() -> Option<(u16, u16)> {
+    return None;
+}
+
+/// Returns the decoder for a Windows code page number (e.g. 932 -> Shift_JIS, 437 -> CP437)
+///
+/// Without the `locale` feature, only CP437 is looked up, which `encoding_rs` doesn't have.
+///
+/// # Arguments
+///
+/// * `codepage` - code page number
+fn decoder_from_codepage(codepage: u16) -> Option<Box<dyn IDecoder>> {
+    #[cfg(feature = "locale")]
+    if let Some(encoding) = to_encoding(codepage) {
+        return Some(Box::new(LegacyEncodingDecoder { decoder: encoding }));
+    }
+    return oem_decoder_from_codepage(codepage);
+}
+
+/// Returns the decoder for an IBM OEM code page number that `encoding_rs` doesn't have (e.g. 437 -> CP437)
+///
+/// # Arguments
+///
+/// * `codepage` - code page number
+#[cfg(feature = "oem-cp")]
+fn oem_decoder_from_codepage(codepage: u16) -> Option<Box<dyn IDecoder>> {
+    return OEMCPDecoder::from_codepage(codepage)
+        .map(|decoder| -> Box<dyn IDecoder> { Box::new(decoder) });
+}
+
+/// Returns `None` (the IBM OEM code pages need the `oem-cp` feature)
+///
+/// # Arguments
+///
+/// * `codepage` - code page number
+#[cfg(not(feature = "oem-cp"))]
+fn oem_decoder_from_codepage(_codepage: u16) -> Option<Box<dyn IDecoder>> {
+    return None;
+}
+
+/// Returns the IBM OEM code pages supported by `oem_cp` in ascending order (none without the `oem-cp` feature)
+pub(crate) fn oem_codepages() -> Vec<u16> {
+    #[cfg(feature = "oem-cp")]
+    let mut codepages = DECODING_TABLE_CP_MAP.keys().copied().collect::<Vec<u16>>();
+    #[cfg(not(feature = "oem-cp"))]
+    let mut codepages: Vec<u16> = vec![];
+    codepages.sort_unstable();
+    return codepages;
+}
+
+/// Returns the IBM OEM code page number that `name` represents (e.g. `cp437` -> 437)
+///
+/// Returns `None` if `name` is not an OEM code page supported by `oem_cp`.
+///
+/// # Arguments
+///
+/// * `name` - encoding name
+#[cfg(feature = "oem-cp")]
+pub(crate) fn oem_codepage_from_name(name: &str) -> Option<u16> {
+    lazy_static! {
+        static ref OEM_CP_REGEX: Regex = Regex::new(r"(?i)(?:CP|OEM ?|IBM)(\d+)").unwrap();
+        static ref CP437_REGEX: Regex =
+            Regex::new("(?i)(OEM[-_]US|PC-8|DOS[-_ ]?Latin[-_ ]?US)").unwrap();
+    }
+    if let Some(codepage) = OEM_CP_REGEX
+        .captures(name)
+        .and_then(|captures| captures.get(1))
+        .and_then(|match_| -> Option<u16> { match_.as_str().parse().ok() })
+        .filter(|codepage| DECODING_TABLE_CP_MAP.contains_key(codepage))
+    {
+        return Some(codepage);
+    }
+    if CP437_REGEX.is_match(name) {
+        return Some(437);
+    }
+    return None;
+}
+
+/// Returns `None` (the IBM OEM code pages need the `oem-cp` feature)
+///
+/// # Arguments
+///
+/// * `name` - encoding name
+#[cfg(not(feature = "oem-cp"))]
+pub(crate) fn oem_codepage_from_name(_name: &str) -> Option<u16> {
+    return None;
+}
+
 /// Guesses encoding from an array of sequences.
 /// Returns an index of the array `decoders` corresponding to the encoding that was able to decode all the `strings` without error.
 /// If no `decoders` can decode all of `strings` without error, returns `None`.
@@ -208,10 +334,9 @@ pub fn decide_decoder<T>(decoders: &[&dyn IDecoder], strings: &[T]) -> Option<us
 where
     T: AsRef<[u8]>,
 {
-    for i in 0..decoders.len() {
-        let decoder = decoders[i];
+    for (i, decoder) in decoders.iter().enumerate() {
         if strings
-            .into_iter()
+            .iter()
             .all(|subject| decoder.can_decode(subject.as_ref()))
         {
             return Some(i);
@@ -0,0 +1,148 @@
+#[cfg(feature = "oem-cp")]
+use oem_cp::code_table::ENCODING_TABLE_CP_MAP;
+#[cfg(feature = "oem-cp")]
+use oem_cp::encode_string_checked;
+#[cfg(feature = "oem-cp")]
+use oem_cp::OEMCPHashMap;
+
+#[cfg(feature = "oem-cp")]
+use crate::filename_decoder::oem_codepage_from_name;
+use crate::shift_jis::{is_strict_shift_jis_name, ShiftJISMapping};
+
+/// Trait (interface) of encoder (used for the reverse conversion to legacy encodings)
+pub trait IEncoder {
+    /// Converts a string to the byte sequence only if all the characters can be represented in the encoding
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - string to be encoded
+    fn to_bytes_lossless(&self, input: &str) -> Option<Vec<u8>>;
+    /// Returns `true` if all the characters in `input` can be represented in the encoding
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - string to be encoded
+    fn can_encode(&self, input: &str) -> bool {
+        self.to_bytes_lossless(input).is_some()
+    }
+    /// Converts a string to the byte sequence replacing the characters that cannot be represented with `_`
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - string to be encoded
+    fn to_bytes_lossy(&self, input: &str) -> Vec<u8> {
+        if let Some(bytes) = self.to_bytes_lossless(input) {
+            return bytes;
+        }
+        let mut buffer = [0u8; 4];
+        return input
+            .chars()
+            .map(|c| {
+                self.to_bytes_lossless(c.encode_utf8(&mut buffer))
+                    .unwrap_or_else(|| vec![b'_'])
+            })
+            .collect::<Vec<Vec<u8>>>()
+            .concat();
+    }
+    /// Returns the name of the encoding that the encoder uses
+    fn encoding_name(&self) -> &str;
+}
+
+/// OEM code page encoder (other than Asian languages)
+///
+/// Single byte & use `oem_cp` to implement
+#[cfg(feature = "oem-cp")]
+struct OEMCPEncoder {
+    encoder: &'static OEMCPHashMap<char, u8>,
+    encoding_str: String,
+}
+
+/// Asian ANSI+OEM codepages encoder
+///
+/// Use encoding_rs (CJKV + Thai)
+struct LegacyEncodingEncoder {
+    /// `Encoding` object (e.g. `encoding_rs::SHIFT_JIS` for Shift-JIS)
+    encoder: &'static encoding_rs::Encoding,
+}
+
+#[cfg(feature = "oem-cp")]
+impl OEMCPEncoder {
+    fn from_codepage(codepage: u16) -> Option<Self> {
+        return Some(Self {
+            encoder: ENCODING_TABLE_CP_MAP.get(&codepage)?,
+            encoding_str: format!("CP{}", codepage),
+        });
+    }
+}
+
+#[cfg(feature = "oem-cp")]
+impl IEncoder for OEMCPEncoder {
+    fn to_bytes_lossless(&self, input: &str) -> Option<Vec<u8>> {
+        return encode_string_checked(input, self.encoder);
+    }
+    fn encoding_name(&self) -> &str {
+        return &self.encoding_str;
+    }
+}
+
+impl IEncoder for LegacyEncodingEncoder {
+    fn to_bytes_lossless(&self, input: &str) -> Option<Vec<u8>> {
+        // Unmappable characters are replaced with numeric character references (e.g. `&#12354;`)
+        let (result, _, met_unmappable_char) = self.encoder.encode(input);
+        if met_unmappable_char {
+            return None;
+        }
+        return Some(result.into_owned());
+    }
+    fn encoding_name(&self) -> &str {
+        return self.encoder.name();
+    }
+}
+
+impl dyn IEncoder {
+    /// Generates an instance of an encoder from encoding name (e.g. `sjis` -> Shift-JIS)
+    ///
+    /// UTF-16 is not supported because it cannot be used for file names in ZIP archives.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - encoding name
+    pub fn from_encoding_name(name: &str) -> Option<Box<dyn IEncoder>> {
+        if is_strict_shift_jis_name(name) {
+            return Some(<dyn IEncoder>::shift_jis(ShiftJISMapping::JIS));
+        }
+        if name.eq_ignore_ascii_case("cp932") {
+            return Some(<dyn IEncoder>::shift_jis(ShiftJISMapping::Windows31J));
+        }
+        if let Some(encoder) = encoding_rs::Encoding::for_label(name.as_bytes()) {
+            // encoding_rs silently encodes in UTF-8 instead of UTF-16
+            if encoder.output_encoding() != encoder {
+                return None;
+            }
+            return Some(Box::new(LegacyEncodingEncoder { encoder }));
+        }
+        return oem_encoder_from_name(name);
+    }
+}
+
+/// Returns the encoder for an IBM OEM code page that `encoding_rs` doesn't have (e.g. `cp437`)
+///
+/// # Arguments
+///
+/// * `name` - encoding name
+#[cfg(feature = "oem-cp")]
+fn oem_encoder_from_name(name: &str) -> Option<Box<dyn IEncoder>> {
+    return oem_codepage_from_name(name)
+        .and_then(OEMCPEncoder::from_codepage)
+        .map(|encoder| -> Box<dyn IEncoder> { Box::new(encoder) });
+}
+
+/// Returns `None` (the IBM OEM code pages need the `oem-cp` feature)
+///
+/// # Arguments
+///
+/// * `name` - encoding name
+#[cfg(not(feature = "oem-cp"))]
+fn oem_encoder_from_name(_name: &str) -> Option<Box<dyn IEncoder>> {
+    return None;
+}
@@ -0,0 +1,167 @@
+//! One-call conversions for callers who don't need fine control
+//!
+//! `fix_zip_bytes` & `fix_zip_file` run the same pipeline as the `zifu` CLI with its defaults:
+//! parse the archive, diagnose the names, detect the encoding of the implicitly-encoded ones, convert them to UTF-8,
+//! and write the archive.  Use `InputZIPArchive` directly for hooks, filters, reports, and so on.
+
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+use std::path::Path;
+
+use byteorder::ReadBytesExt;
+
+use crate::filename_decoder::IDecoder;
+use crate::verification::verify_written_archive;
+use crate::{ConversionOptions, InputZIPArchive, OutputOptions};
+
+/// Options of `fix_zip_bytes` & `fix_zip_file`
+#[derive(Clone, Debug, Default)]
+pub struct FixOptions {
+    /// Encoding of the implicitly-encoded file names (e.g. `"sjis"`; `None` detects it, falling back to the locale default)
+    pub encoding: Option<String>,
+    /// Conversion of the decoded file names (e.g. transliteration)
+    pub conversion: ConversionOptions,
+    /// Output options (e.g. chunk size)
+    pub output: OutputOptions,
+}
+
+/// Error returned by `fix_zip_bytes` & `fix_zip_file` when the names can't be converted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FixError {
+    /// `FixOptions::encoding` is not a known encoding name
+    InvalidEncodingName { encoding_name: String },
+    /// No encoding decodes all the file names & comments
+    UndecodableNames,
+}
+
+impl std::fmt::Display for FixError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            FixError::InvalidEncodingName { encoding_name } => {
+                write!(f, "{} is not a valid encoding name", encoding_name)
+            }
+            FixError::UndecodableNames => write!(
+                f,
+                "no encoding can decode all the file names & comments in the archive"
+            ),
+        };
+    }
+}
+
+impl std::error::Error for FixError {}
+
+/// Returns the decoder for the implicitly-encoded file names, detecting it unless `encoding` is given
+///
+/// The locale default code pages after the detected candidates are ordered by the host system of the archive.
+/// The explicitly UTF-8 encoded names are decoded as UTF-8 whatever decoder is returned.
+///
+/// # Arguments
+///
+/// * `zip` - parsed archive
+/// * `encoding` - name of the encoding (e.g. `"sjis"`; `None` detects it, falling back to the locale default)
+pub(crate) fn choose_decoder<F: ReadBytesExt + std::io::Seek>(
+    zip: &InputZIPArchive<F>,
+    encoding: Option<&str>,
+) -> Result<Box<dyn IDecoder>, FixError> {
+    let legacy_decoders: Vec<Box<dyn IDecoder>> = match encoding {
+        Some(encoding_name) => vec![<dyn IDecoder>::from_encoding_name(encoding_name).ok_or(
+            FixError::InvalidEncodingName {
+                encoding_name: encoding_name.to_string(),
+            },
+        )?],
+        None => zip
+            .detect_filename_encoding_candidates()
+            .into_iter()
+            .map(|candidate| candidate.decoder)
+            .chain(<dyn IDecoder>::native_fallback_chain_for_host(
+                zip.origin_hints().host_system(),
+            ))
+            .collect(),
+    };
+    let mut decoders = std::iter::once(<dyn IDecoder>::ascii())
+        .chain(legacy_decoders)
+        .chain(std::iter::once(<dyn IDecoder>::utf8()))
+        .collect::<Vec<_>>();
+    let index = zip
+        .get_filename_decoder_index(
+            &decoders
+                .iter()
+                .map(|decoder| &**decoder)
+                .collect::<Vec<_>>(),
+        )
+        .ok_or(FixError::UndecodableNames)?;
+    return Ok(decoders.swap_remove(index));
+}
+
+/// Converts the implicitly-encoded file names in the central directory to UTF-8
+///
+/// Returns `false` if the archive doesn't need the conversion (left as it is).
+///
+/// # Arguments
+///
+/// * `zip` - parsed archive
+/// * `options` - options of the conversion
+fn convert_names<F: ReadBytesExt + std::io::Seek>(
+    zip: &mut InputZIPArchive<F>,
+    options: &FixOptions,
+) -> anyhow::Result<bool> {
+    zip.read_local_header_flags();
+    if zip.diagnose_file_name_encoding().is_universal_archive() {
+        return Ok(false);
+    }
+    let decoder = choose_decoder(zip, options.encoding.as_deref())?;
+    zip.convert_central_directory_file_names_with_options(&*decoder, &options.conversion);
+    return Ok(true);
+}
+
+/// Converts the file names of the archive in memory to UTF-8 and returns the converted archive.
+///
+/// Returns a copy of `input` if the archive doesn't need the conversion.
+///
+/// # Arguments
+///
+/// * `input` - whole archive
+/// * `options` - encoding, conversion, and output options
+pub fn fix_zip_bytes(input: &[u8], options: &FixOptions) -> anyhow::Result<Vec<u8>> {
+    let mut zip = InputZIPArchive::new(Cursor::new(input))?;
+    if !convert_names(&mut zip, options)? {
+        return Ok(input.to_vec());
+    }
+    let mut output = Cursor::new(Vec::with_capacity(input.len()));
+    zip.output_archive_with_options(&mut output, &options.output)?;
+    return Ok(output.into_inner());
+}
+
+/// Converts the file names of the archive at `path` to UTF-8 in place.
+///
+/// The converted archive is written to a temporary file next to it, verified (see `verify_written_archive`),
+/// and then replaces the original, so a failure never destroys the original.
+/// Returns `false` if the archive doesn't need the conversion (the file is not touched).
+///
+/// # Arguments
+///
+/// * `path` - path to the archive
+/// * `options` - encoding, conversion, and output options
+pub fn fix_zip_file<P: AsRef<Path>>(path: P, options: &FixOptions) -> anyhow::Result<bool> {
+    let path = path.as_ref();
+    let mut zip = InputZIPArchive::from_path(path)?;
+    if !convert_names(&mut zip, options)? {
+        return Ok(false);
+    }
+    let mut temporary_path = path.as_os_str().to_owned();
+    temporary_path.push(format!(".{}.tmp", std::process::id()));
+    let result = zip
+        .write_to_path(&temporary_path, &options.output)
+        .and_then(|report| {
+            let mut written = BufReader::new(File::open(&temporary_path)?);
+            verify_written_archive(&mut written, &report, false)?;
+            return Ok(());
+        });
+    // The input must be closed before it is replaced (on Windows)
+    drop(zip);
+    let result = result.and_then(|_| Ok(std::fs::rename(&temporary_path, path)?));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary_path);
+    }
+    return result.map(|_| true);
+}
@@ -1,19 +1,98 @@
+#![allow(clippy::needless_return)]
+
+#[cfg(feature = "tokio")]
+pub use async_io::AsyncInputZIPArchive;
+use attributes::PermissionNormalization;
 use byteorder::{ReadBytesExt, WriteBytesExt};
+use bytesize::ByteSize;
+use cancellation::CancellationToken;
+pub use capabilities::{capabilities, Capabilities};
+use deadline::{check_deadline, Interruption};
+use encoding_detection::{DecodedName, EncodingCandidate, NamePreview};
+use encryption::EntryEncryption;
+use error::EntryReadError;
+pub use error::{ZifuError, ZifuResult};
+use feature_gate::{FeatureGate, Operation};
 use filename_decoder::{ASCIIDecoder, IDecoder};
+use filename_encoder::IEncoder;
+pub use fix::{fix_zip_bytes, fix_zip_file, FixOptions};
 use hfs_nfd::compose_from_hfs_nfd;
+use local_headers::{
+    LocalFileHeader, LocalHeaderInfo, LocalHeaderIter, OffsetRepair, OrphanRecovery,
+    DATA_DESCRIPTOR_FLAG_BIT, OFFSET_SEARCH_RADIUS,
+};
+use metadata::EntryMetadata;
+use name_mismatches::{NameMismatch, NameMismatchError, NameMismatchPolicy};
+pub use name_pipeline::ConversionOptions;
+use name_pipeline::{LossReason, NameTrace, RenameHook};
+use name_scoring::DecoderScore;
+use origin_hints::OriginHints;
+pub use output::{EntryDecision, EntryInfo, OutputOptions, OutputProgress, OutputReport};
+use output::{EntryFilter, OutputState, ProgressCallback, WrittenEntry};
+use repair::{EntryRepair, RepairReport, SkippedRepair};
+use spanning::{ArchiveSink, CountingSink};
+use std::convert::TryFrom;
+use std::time::Instant;
+use timestamps::{TimestampCorrection, EXTENDED_TIMESTAMP_EXTRA_FIELD_ID};
+use unicode_path::UNICODE_PATH_EXTRA_FIELD_ID;
+use verification::{CD_FIXED_SIZE, EOCD_FIXED_SIZE};
 use zip_structs::{
-    zip_central_directory::ZipCDEntry, zip_eocd::ZipEOCD, zip_error::ZipReadError,
-    zip_local_file_header,
+    zip_central_directory::{ZipCDEntry, UTF8_FLAG_BIT},
+    zip_eocd::ZipEOCD,
+    zip_error::ZipReadError,
 };
 
+pub mod alignment;
+#[cfg(feature = "tokio")]
+pub mod async_io;
+pub mod attributes;
+pub mod cancellation;
+pub mod capabilities;
+pub mod collisions;
+pub mod create;
+pub mod deadline;
+pub mod duplicates;
+pub mod encoding_detection;
+pub mod encodings;
+pub mod encryption;
+pub mod error;
+mod extra_fields;
+pub mod extract;
+pub mod feature_gate;
 pub mod filename_decoder;
+pub mod filename_encoder;
+pub mod fix;
+pub mod local_headers;
+pub mod metadata;
+#[cfg(feature = "mmap")]
+pub mod mmap_input;
+pub mod name_mismatches;
+pub mod name_pipeline;
+pub mod name_scoring;
+pub mod origin_hints;
+pub mod output;
+pub mod parallel;
+pub mod path_io;
+pub mod path_length;
+pub mod path_safety;
+pub mod rename_rules;
+pub mod repair;
+pub mod reserved_names;
+pub mod shift_jis;
+pub mod spanning;
+pub mod target_os;
+pub mod timestamps;
+pub mod top_directory;
+pub mod trailing_data;
+pub mod unicode_path;
+pub mod verification;
+pub mod zip64;
 
 static ASCII_DECODER: ASCIIDecoder = ASCIIDecoder {};
 
-/// This is for listing file names
-
 /// This is for listing file names
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileNameEntry {
     /// File name (or path)
     pub name: String,
@@ -21,12 +100,43 @@ pub struct FileNameEntry {
     ///
     /// If `false`, print the encoding name whose decoder you used
     pub encoding_type: FileNameEncodingType,
+    /// `true` if the entry represents a directory (see `attributes::is_directory`)
+    pub is_directory: bool,
+    /// `true` if the entry represents a symbolic link (see `attributes::is_symlink`)
+    pub is_symlink: bool,
+    /// Encryption scheme (`None` if not encrypted)
+    pub encryption: Option<EntryEncryption>,
+    /// Byte sequence of the file name
+    pub file_name_raw: Vec<u8>,
+    /// `false` if `name` has replacement characters for the bytes the decoder could not decode
+    pub lossless: bool,
+}
+
+/// Entry of the rename plan returned by `InputZIPArchive::plan_conversion`
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenamePlanEntry {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the file name in the input archive
+    pub raw: Vec<u8>,
+    /// `true` if the file name is explicitly encoded in UTF-8 in the input archive
+    pub explicit_utf8: bool,
+    /// File name decoded as it is (UTF-8 or the legacy encoding)
+    pub current_name: String,
+    /// File name that the conversion would write
+    pub proposed_name: String,
+    /// `true` if the conversion rewrites the file name
+    ///
+    /// ASCII names that would only get the UTF-8 flag are not counted.
+    pub changes: bool,
 }
 
 /// Enum that represents statistics of file name encoding
 ///
 /// UTF-8 (Regular normalization (NFC) / Irregular (HFS+ NFD-like)) / ASCII / Implicit multibyte
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FileNameEncodingType {
     /// genral bit #11 + NFC normalization (universal)
     ExplicitRegularUTF8,
@@ -44,27 +154,47 @@ impl FileNameEncodingType {
     /// Regturns `true` if the file name is correctly decoded in almost all devices
     pub fn is_universal(&self) -> bool {
         use FileNameEncodingType::*;
-        match self {
-            ExplicitRegularUTF8 | ImplicitASCII => true,
-            _ => false,
-        }
+        matches!(self, ExplicitRegularUTF8 | ImplicitASCII)
     }
 }
 
 /// Represents diagnostic result of the file names
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FileNamesDiagnosis {
     /// `true` if contains implicit (general purpose bit #11 not set) non-ASCII
     /// (e.g. UTF-8, CP437, or Shift-JIS) file names
     pub has_implicit_non_ascii_names: bool,
     /// contains explicit (general purpose bit #11) irregular (e.g. HFS+ NFD) file names
     pub has_non_nfc_explicit_utf8_names: bool,
+    /// contains names that can escape the extraction directory (e.g. `../a.txt`, `/a.txt`, or `C:\a.txt`)
+    ///
+    /// Not taken into account by `is_universal_archive` because it is not an encoding problem.
+    pub has_unsafe_paths: bool,
+    /// contains names that cannot be extracted as they are in Windows (e.g. `CON`, `aux.txt`, or `a.`)
+    ///
+    /// Not taken into account by `is_universal_archive` either.
+    pub has_windows_reserved_names: bool,
+    /// contains names whose components end with a dot or a space (e.g. `a.` or `b /c.txt`), which Windows strips
+    ///
+    /// Subset of `has_windows_reserved_names`.
+    pub has_trailing_dots_or_spaces: bool,
+    /// contains entries with the same byte sequence as names (only one of them survives the extraction)
+    pub has_duplicate_names: bool,
+    /// contains names that differ only in case (e.g. `a.txt` & `A.txt`), which overwrite each other in Windows & macOS
+    ///
+    /// Only explicitly UTF-8 and ASCII names are compared because the others cannot be decoded without guessing the encoding.
+    pub has_case_insensitive_duplicates: bool,
+    /// contains non-ASCII names whose UTF-8 flag (general purpose bit #11) is set only in the local file headers
+    ///
+    /// They are read as explicitly UTF-8 encoded, but extractors that follow the central directory garble them.
+    pub has_utf8_flags_only_in_local_headers: bool,
 }
 
 impl FileNamesDiagnosis {
     /// Getprimary message to explain name encoding status
     pub fn get_status_primary_message(&self) -> &'static str {
-        match (self.has_implicit_non_ascii_names, self.has_non_nfc_explicit_utf8_names) {
+        match (self.has_implicit_names_in_central_directory(), self.has_non_nfc_explicit_utf8_names) {
             (false, false) => "All file names are encoded in ASCII or explicitly in UTF-8.",
             (true, false) => "Some files are encoded implicitly in a multibyte encoding.",
             (false, true) => "Some file names use irregular unicode normalization.",
@@ -75,7 +205,7 @@ impl FileNamesDiagnosis {
     ///
     /// Use with `.get_status_primary_message()`
     pub fn get_status_note(&self) -> &'static str {
-        match (self.has_implicit_non_ascii_names, self.has_non_nfc_explicit_utf8_names) {
+        match (self.has_implicit_names_in_central_directory(), self.has_non_nfc_explicit_utf8_names) {
             (false, false) => "Almost all devices can decode its file names correctly.",
             (true, _) => "Apply this tool, or the receiver may not be able to see the correct file names.",
             (false, true) => "Apply this tool, or the receiver may not deal with the pericular file name normalization.",
@@ -84,10 +214,106 @@ impl FileNamesDiagnosis {
 
     /// Returns `true` if the ZIP archive is universal (do not have to apply this tool)
     pub fn is_universal_archive(&self) -> bool {
-        return !self.has_implicit_non_ascii_names && !self.has_non_nfc_explicit_utf8_names;
+        return !self.has_implicit_names_in_central_directory()
+            && !self.has_non_nfc_explicit_utf8_names;
+    }
+
+    /// Returns `true` if the central directory has implicitly-encoded non-ASCII names (regardless of the local file headers)
+    fn has_implicit_names_in_central_directory(&self) -> bool {
+        return self.has_implicit_non_ascii_names || self.has_utf8_flags_only_in_local_headers;
+    }
+}
+
+/// Entry whose conversion loses information
+#[derive(Clone, Debug)]
+pub struct LossyEntry {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// File name after the conversion
+    pub name: String,
+    /// Reasons why the conversion loses information
+    pub reasons: Vec<LossReason>,
+}
+
+/// Error returned when the conversion is required to be lossless but is not
+#[derive(Clone, Debug)]
+pub struct LossyConversionError {
+    /// Entries whose conversion loses information
+    pub entries: Vec<LossyEntry>,
+}
+
+impl std::fmt::Display for LossyConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the conversion of {} entries would lose information",
+            self.entries.len()
+        )?;
+        for entry in self.entries.iter() {
+            write!(f, "\n  #{} {}:", entry.index, entry.name)?;
+            for reason in entry.reasons.iter() {
+                write!(f, "\n    - {}", reason.description())?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl std::error::Error for LossyConversionError {}
+
+/// Error returned when an archive comment doesn't fit in the EOCD
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommentTooLongError {
+    /// Length of the comment in bytes
+    pub length: usize,
+}
+
+impl std::fmt::Display for CommentTooLongError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "the archive comment is too long ({} bytes; {} at most)",
+            self.length,
+            u16::MAX
+        );
+    }
+}
+
+impl std::error::Error for CommentTooLongError {}
+
+/// Error returned when a variable-length field (e.g. an extra field) doesn't fit in its 16-bit length
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldTooLongError {
+    /// Name of the field
+    pub field: &'static str,
+    /// Length of the field in bytes
+    pub length: usize,
+}
+
+impl std::fmt::Display for FieldTooLongError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "the {} is too long ({} bytes; {} at most)",
+            self.field,
+            self.length,
+            u16::MAX
+        );
     }
 }
 
+impl std::error::Error for FieldTooLongError {}
+
+/// Narrows the length of a variable-length field to its 16-bit field, or returns `Err(FieldTooLongError)` if it doesn't fit
+///
+/// # Arguments
+///
+/// * `length` - length of the field in bytes
+/// * `field` - name of the field for the error message
+pub(crate) fn field_length(length: usize, field: &'static str) -> Result<u16, FieldTooLongError> {
+    return u16::try_from(length).map_err(|_| FieldTooLongError { field, length });
+}
+
 /// This struct is for providing the internal processing API used in the `zifu` CLI.
 ///
 /// This helps you to create e.g. GUI version
@@ -100,6 +326,197 @@ pub struct InputZIPArchive<F: ReadBytesExt + std::io::Seek> {
     eocd: ZipEOCD,
     /// Central directories for the ZIP file represented by `file_handler`
     cd_entries: Vec<ZipCDEntry>,
+    /// Callback that overrides converted file names (set by `set_rename_hook`)
+    rename_hook: Option<Box<RenameHook>>,
+    /// Callback that decides whether each entry is written (set by `set_entry_filter`)
+    entry_filter: Option<Box<EntryFilter>>,
+    /// Callback that observes the progress of writing (set by `set_progress_callback`)
+    progress_callback: Option<Box<ProgressCallback>>,
+    /// 0-based indices (ascending) of the entries whose UTF-8 flags are set only in the local file headers
+    /// (found when the local headers are read; see `read_local_header_flags`)
+    utf8_flags_only_in_local_headers: Vec<usize>,
+    /// 0-based indices (ascending) of the entries whose local headers take the CRC-32 & sizes from the central directory
+    /// (set by `repair_metadata`)
+    repaired_entries: Vec<usize>,
+    /// 0-based indices (ascending) of the entries whose names became empty by stripping the top directory
+    /// (the directory entries of the top directory itself; left out of the output)
+    emptied_entries: Vec<usize>,
+    /// Size of the junk after the EOCD & its comment (not written to the output)
+    trailing_data_size: u64,
+}
+
+/// Builds the entry of the file name list from the central directory
+fn file_name_entry(cd: &ZipCDEntry, legacy_decoder: &dyn IDecoder) -> FileNameEntry {
+    use FileNameEncodingType::*;
+    let is_directory = attributes::is_directory_entry(cd);
+    let is_symlink = attributes::is_symlink_entry(cd);
+    let encryption = encryption::entry_encryption(cd);
+    if cd.is_encoded_in_utf8() {
+        let original_file_name = String::from_utf8_lossy(&cd.file_name_raw);
+        let nfc_file_name = compose_from_hfs_nfd(&original_file_name);
+        return FileNameEntry {
+            encoding_type: if original_file_name == nfc_file_name {
+                ExplicitRegularUTF8
+            } else {
+                ExplicitIrregularUTF8
+            },
+            name: nfc_file_name,
+            is_directory,
+            is_symlink,
+            encryption,
+            file_name_raw: cd.file_name_raw.clone(),
+            lossless: std::str::from_utf8(&cd.file_name_raw).is_ok(),
+        };
+    }
+    if let Some(ascii_file_name) = ASCII_DECODER.to_string_lossless(&cd.file_name_raw) {
+        return FileNameEntry {
+            encoding_type: ImplicitASCII,
+            name: ascii_file_name,
+            is_directory,
+            is_symlink,
+            encryption,
+            file_name_raw: cd.file_name_raw.clone(),
+            lossless: true,
+        };
+    }
+    return FileNameEntry {
+        encoding_type: ImplicitNonASCII,
+        name: legacy_decoder.to_string_lossy(&cd.file_name_raw),
+        is_directory,
+        is_symlink,
+        encryption,
+        file_name_raw: cd.file_name_raw.clone(),
+        lossless: legacy_decoder.can_decode(&cd.file_name_raw),
+    };
+}
+
+/// Passes the file name of the entry through the pipeline (and the rename hook if any)
+fn trace_entry(
+    cd: &ZipCDEntry,
+    legacy_decoder: &dyn IDecoder,
+    options: &ConversionOptions,
+    rename_hook: Option<&RenameHook>,
+) -> NameTrace {
+    let trace = name_pipeline::trace_file_name(
+        &cd.file_name_raw,
+        cd.is_encoded_in_utf8(),
+        legacy_decoder,
+        options,
+    );
+    return finish_trace(cd, trace, rename_hook);
+}
+
+/// Passes the file names of all the entries through the pipeline (in parallel if enabled), and then the rename hook if any
+///
+/// The rename hook is called one by one in the order of the entries because it doesn't have to be `Sync`.
+fn trace_entries(
+    cd_entries: &[ZipCDEntry],
+    legacy_decoder: &dyn IDecoder,
+    options: &ConversionOptions,
+    rename_hook: Option<&RenameHook>,
+) -> Vec<NameTrace> {
+    return parallel::map(cd_entries, |cd| {
+        name_pipeline::trace_file_name(
+            &cd.file_name_raw,
+            cd.is_encoded_in_utf8(),
+            legacy_decoder,
+            options,
+        )
+    })
+    .into_iter()
+    .zip(cd_entries.iter())
+    .map(|(trace, cd)| finish_trace(cd, trace, rename_hook))
+    .collect();
+}
+
+/// Applies the rename hook (if any) and the normalization of the trailing slash to the traced name of the entry
+fn finish_trace(
+    cd: &ZipCDEntry,
+    mut trace: NameTrace,
+    rename_hook: Option<&RenameHook>,
+) -> NameTrace {
+    if let Some(hook) = rename_hook {
+        name_pipeline::apply_rename_hook(&mut trace, hook);
+    }
+    // The stripped top directory itself is left out of the output instead of becoming `/`
+    if trace.final_name().is_empty() {
+        return trace;
+    }
+    let normalized = attributes::normalize_trailing_slash(
+        trace.final_name(),
+        attributes::is_directory_entry(cd),
+    );
+    if normalized != trace.final_name() {
+        trace
+            .stages
+            .push((name_pipeline::NameStage::DirectoryNormalized, normalized));
+    }
+    return trace;
+}
+
+/// Makes the local header follow the central directory, writes it, and returns the number of bytes written
+///
+/// The position of the local header in `cd` is updated too.
+///
+/// # Arguments
+///
+/// * `cd` - central directory record of the entry
+/// * `local_header` - local header read from the input archive
+/// * `sink` - destination of the archive
+/// * `alignment` - alignment of the data of stored entries (see `OutputOptions::alignment`)
+pub(crate) fn write_local_header<S: ArchiveSink>(
+    cd: &mut ZipCDEntry,
+    local_header: &mut LocalFileHeader,
+    sink: &mut S,
+    alignment: Option<u16>,
+) -> anyhow::Result<u64> {
+    if local_header.file_name_raw != cd.file_name_raw {
+        local_header.file_name_raw.clone_from(&cd.file_name_raw);
+    }
+    // Follow the UTF-8 flag in the central directory (cleared by the reverse conversion)
+    local_header.general_purpose_flags = (local_header.general_purpose_flags & !UTF8_FLAG_BIT)
+        | (cd.general_purpose_flags & UTF8_FLAG_BIT);
+    // Follow the modification time (changed by `correct_timestamps`)
+    local_header.last_mod_date = cd.last_mod_date;
+    local_header.last_mod_time = cd.last_mod_time;
+    // Follow the Unicode Path extra field in the central directory too (added by the legacy-compatible conversion)
+    if let Some(record) = extra_fields::find_record(&cd.extra_field, UNICODE_PATH_EXTRA_FIELD_ID) {
+        if extra_fields::find_record(&local_header.extra_field, UNICODE_PATH_EXTRA_FIELD_ID)
+            != Some(record.clone())
+        {
+            local_header.extra_field = extra_fields::replace_record(
+                &local_header.extra_field,
+                UNICODE_PATH_EXTRA_FIELD_ID,
+                Some(&record),
+            );
+        }
+    }
+    // Local extended timestamps have more times than central ones, so they are added only if missing
+    if let Some(record) =
+        extra_fields::find_record(&cd.extra_field, EXTENDED_TIMESTAMP_EXTRA_FIELD_ID)
+    {
+        if extra_fields::find_record(&local_header.extra_field, EXTENDED_TIMESTAMP_EXTRA_FIELD_ID)
+            .is_none()
+        {
+            local_header.extra_field = extra_fields::replace_record(
+                &local_header.extra_field,
+                EXTENDED_TIMESTAMP_EXTRA_FIELD_ID,
+                Some(&record),
+            );
+        }
+    }
+    if let Some(alignment) = alignment {
+        alignment::align_local_header(local_header, sink.position().1, alignment);
+    }
+    sink.keep_together(local_header.size())?;
+    let (disk, offset) = sink.position();
+    // Padded again if moved on to the next disk
+    if let Some(alignment) = alignment {
+        alignment::align_local_header(local_header, offset, alignment);
+    }
+    cd.disk_number_start = disk;
+    zip64::set_local_header_position(cd, offset)?;
+    return Ok(local_header.write(sink)?);
 }
 
 impl<F> InputZIPArchive<F>
@@ -111,36 +528,281 @@ where
     /// # Arguments
     ///
     /// * `handler` - File handler representing the input ZIP file (`Bufreader<File>` recommended)
-    pub fn new(mut handler: F) -> anyhow::Result<Self> {
-        let eocd = ZipEOCD::from_reader(&mut handler)?;
-        let cd_entries = ZipCDEntry::all_from_eocd(&mut handler, &eocd)?;
+    pub fn new(handler: F) -> anyhow::Result<Self> {
+        return Self::new_with_deadline(handler, None);
+    }
+
+    /// Returns an initialized instance, giving up reading the central directory when `deadline` has passed.
+    ///
+    /// Returns `Err` containing `deadline::DeadlineExceeded` on timeout (e.g. a huge central directory on a slow network share),
+    /// or `encryption::UnsupportedEncryptionError` if the central directory is encrypted.
+    ///
+    /// # Arguments
+    ///
+    /// * `handler` - File handler representing the input ZIP file (`Bufreader<File>` recommended)
+    /// * `deadline` - deadline of reading (`None` means no deadline)
+    pub fn new_with_deadline(mut handler: F, deadline: Option<Instant>) -> anyhow::Result<Self> {
+        let _span = tracing::info_span!("parse").entered();
+        let (eocd, trailing_data_size) = trailing_data::read_eocd(&mut handler)?;
+        tracing::debug!(
+            position = eocd.starting_position_with_signature,
+            trailing_data_size,
+            "found the end of central directory"
+        );
+        // The 32-bit fields are saturated in ZIP64 archives
+        let (cd_starting_position, n_cd_entries, cd_end_position, cd_encryption) =
+            match zip64::read_zip64_eocd(&mut handler, &eocd)? {
+                Some(zip64_eocd) => {
+                    tracing::debug!(
+                        position = zip64_eocd.position,
+                        "found the ZIP64 end of central directory"
+                    );
+                    (
+                        zip64_eocd.cd_starting_position,
+                        zip64_eocd.n_cd_entries,
+                        zip64_eocd.position,
+                        zip64_eocd.cd_encryption,
+                    )
+                }
+                None => (
+                    eocd.cd_starting_position as u64,
+                    eocd.n_cd_entries as u64,
+                    eocd.starting_position_with_signature,
+                    None,
+                ),
+            };
+        // An encrypted central directory can't be parsed; tell the scheme instead of a broken signature
+        if let Some(error) = encryption::detect_encrypted_central_directory(
+            &mut handler,
+            cd_starting_position,
+            cd_encryption,
+        )? {
+            return Err(error.into());
+        }
+        // Same as `ZipCDEntry::all_from_eocd` except for the deadline checks
+        handler.seek(std::io::SeekFrom::Start(cd_starting_position))?;
+        let mut cd_entries = Vec::with_capacity(n_cd_entries.min(u16::MAX as u64) as usize);
+        for index in 0..n_cd_entries as usize {
+            check_deadline(deadline, "reading the central directory")?;
+            let position = handler.stream_position()?;
+            cd_entries.push(
+                ZipCDEntry::read_and_generate_from_signature(&mut handler)
+                    .map_err(|error| EntryReadError::central_directory(index, position, error))?,
+            );
+        }
+        let end_pos = handler.stream_position()?;
+        if end_pos != cd_end_position {
+            return Err(ZipReadError::UnsupportedZipArchive {
+                reason: format!(
+                    "there are extra data ({}) between central directory and end of central directory",
+                    ByteSize::b(cd_end_position.saturating_sub(end_pos))
+                ),
+            }
+            .into());
+        }
+        tracing::info!(
+            n_entries = cd_entries.len(),
+            cd_position = cd_starting_position,
+            "read the central directory"
+        );
 
         return Ok(Self {
             file_handler: handler,
             eocd,
             cd_entries,
+            rename_hook: None,
+            entry_filter: None,
+            progress_callback: None,
+            utf8_flags_only_in_local_headers: vec![],
+            repaired_entries: vec![],
+            emptied_entries: vec![],
+            trailing_data_size,
         });
     }
 
+    /// Sets a callback that overrides converted file names before they are written.
+    ///
+    /// The hook receives the byte sequence of the file name in the input archive and the converted name,
+    /// and returns the name to be written instead (or `None` to keep it).
+    /// This enables manual corrections of entries the decoder gets wrong.
+    ///
+    /// # Arguments
+    ///
+    /// * `hook` - callback that overrides file names
+    pub fn set_rename_hook<H: Fn(&[u8], &str) -> Option<String> + Send + 'static>(
+        &mut self,
+        hook: H,
+    ) {
+        self.rename_hook = Some(Box::new(hook));
+    }
+
+    /// Removes the callback set by `set_rename_hook`
+    pub fn clear_rename_hook(&mut self) {
+        self.rename_hook = None;
+    }
+
+    /// Sets a callback invoked for each entry while the archive is written.
+    ///
+    /// The callback can keep, skip, or rename the entry (see `EntryDecision`).
+    /// Renamed names are also applied to the central directories of this instance.
+    ///
+    /// # Arguments
+    ///
+    /// * `filter` - callback that decides whether (and under which name) each entry is written
+    pub fn set_entry_filter<H: Fn(&EntryInfo) -> EntryDecision + Send + 'static>(
+        &mut self,
+        filter: H,
+    ) {
+        self.entry_filter = Some(Box::new(filter));
+    }
+
+    /// Removes the callback set by `set_entry_filter`
+    pub fn clear_entry_filter(&mut self) {
+        self.entry_filter = None;
+    }
+
+    /// Sets a callback invoked before the first entry, after each chunk of the payloads, and after each entry while the
+    /// archive is written.
+    ///
+    /// Useful for progress bars in multi-GB copies, even of a single huge entry (see `OutputOptions::chunk_size`).
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - callback that receives the entries done and the bytes copied so far
+    pub fn set_progress_callback<H: Fn(&OutputProgress) + Send + 'static>(&mut self, callback: H) {
+        self.progress_callback = Some(Box::new(callback));
+    }
+
+    /// Removes the callback set by `set_progress_callback`
+    pub fn clear_progress_callback(&mut self) {
+        self.progress_callback = None;
+    }
+
     /// Returns the file name encoding diagnossis.
     ///
     /// For details, see the description for `FileNamesDiagnosis`.
     pub fn diagnose_file_name_encoding(&self) -> FileNamesDiagnosis {
         FileNamesDiagnosis {
-            has_implicit_non_ascii_names: self
-                .cd_entries
-                .iter()
-                .filter(|cd| !cd.is_encoded_in_utf8())
-                .any(|cd| !ASCII_DECODER.can_decode(&cd.file_name_raw)),
-            has_non_nfc_explicit_utf8_names: self
-                .cd_entries
+            has_implicit_non_ascii_names: parallel::any(&self.cd_entries, |cd| {
+                !cd.is_encoded_in_utf8() && !ASCII_DECODER.can_decode(&cd.file_name_raw)
+            }),
+            has_non_nfc_explicit_utf8_names: parallel::any(&self.cd_entries, |cd| {
+                if !cd.is_encoded_in_utf8() {
+                    return false;
+                }
+                let original_name = String::from_utf8_lossy(&cd.file_name_raw);
+                let nfc_name = compose_from_hfs_nfd(&original_name);
+                original_name != nfc_name
+            }),
+            has_unsafe_paths: parallel::any(&self.cd_entries, |cd| {
+                if cd.is_encoded_in_utf8() {
+                    let name = String::from_utf8_lossy(&cd.file_name_raw);
+                    !path_safety::find_unsafe_path_reasons(&name).is_empty()
+                } else {
+                    path_safety::is_unsafe_raw_path(&cd.file_name_raw)
+                }
+            }),
+            // Only ASCII characters matter, and bytes of multibyte characters in legacy encodings are never `.`, ` `, or `/`
+            has_windows_reserved_names: parallel::any(&self.cd_entries, |cd| {
+                let name = String::from_utf8_lossy(&cd.file_name_raw);
+                !reserved_names::find_reserved_name_reasons(&name).is_empty()
+            }),
+            has_trailing_dots_or_spaces: parallel::any(&self.cd_entries, |cd| {
+                let name = String::from_utf8_lossy(&cd.file_name_raw);
+                reserved_names::find_reserved_name_reasons(&name)
+                    .contains(&reserved_names::ReservedNameReason::TrailingDotOrSpace)
+            }),
+            has_duplicate_names: {
+                let mut names = std::collections::HashSet::new();
+                !self
+                    .cd_entries
+                    .iter()
+                    .all(|cd| names.insert(cd.file_name_raw.as_slice()))
+            },
+            has_case_insensitive_duplicates: {
+                let mut names: std::collections::HashMap<String, String> =
+                    std::collections::HashMap::new();
+                self.cd_entries
+                    .iter()
+                    .filter(|cd| cd.is_encoded_in_utf8() || cd.file_name_raw.is_ascii())
+                    .any(|cd| {
+                        let name =
+                            compose_from_hfs_nfd(&String::from_utf8_lossy(&cd.file_name_raw));
+                        let previous = names.entry(name.to_lowercase()).or_insert(name.clone());
+                        *previous != name
+                    })
+            },
+            has_utf8_flags_only_in_local_headers: self
+                .utf8_flags_only_in_local_headers
                 .iter()
-                .filter(|cd| cd.is_encoded_in_utf8())
-                .any(|cd| {
-                    let original_name = String::from_utf8_lossy(&cd.file_name_raw);
-                    let nfc_name = compose_from_hfs_nfd(&original_name);
-                    &original_name != &nfc_name
-                }),
+                .any(|&index| !self.cd_entries[index].file_name_raw.is_ascii()),
+        }
+    }
+
+    /// Returns the 0-based indices of the entries whose UTF-8 flags (general purpose bit #11) are set only in the local file headers.
+    ///
+    /// They are treated as explicitly UTF-8 encoded, and the flags are written to both headers on output.
+    /// Only the entries whose local headers have been read so far are included
+    /// (by `read_local_header_flags`, `find_name_mismatches`, or the output).
+    pub fn utf8_flags_only_in_local_headers(&self) -> &[usize] {
+        return &self.utf8_flags_only_in_local_headers;
+    }
+
+    /// Reads the local file headers of the implicitly-encoded entries and honors the UTF-8 flags set only in them.
+    ///
+    /// The constructor reads only the central directory, so call this before diagnosing or converting the names
+    /// if such archives matter (`find_name_mismatches` does the same while reading the headers).
+    /// The output honors the flags of the names left unconverted anyway.
+    /// Entries whose local headers can't be read are skipped (writing them fails later).
+    /// Returns the same as `utf8_flags_only_in_local_headers`.
+    pub fn read_local_header_flags(&mut self) -> &[usize] {
+        for index in 0..self.cd_entries.len() {
+            let cd = &self.cd_entries[index];
+            if cd.is_encoded_in_utf8() || cd.file_name_raw.is_ascii() {
+                continue;
+            }
+            match local_headers::read_local_header_name(
+                &mut self.file_handler,
+                zip64::local_header_position(cd),
+            ) {
+                Ok((flags, name_raw)) => self.honor_local_utf8_flag(index, flags, &name_raw),
+                Err(error) => {
+                    let error = EntryReadError::local_file_header(index, cd, error);
+                    tracing::warn!(%error, "skipped the UTF-8 flag check of an unreadable local header");
+                }
+            }
+        }
+        return &self.utf8_flags_only_in_local_headers;
+    }
+
+    /// Sets the UTF-8 flag in the central directory if it is set only in the local file header
+    ///
+    /// Only non-ASCII names that are the same in both headers (i.e. not converted or renamed yet) are affected.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `local_flags` - general purpose flags in the local file header
+    /// * `local_name_raw` - file name in the local file header
+    pub(crate) fn honor_local_utf8_flag(
+        &mut self,
+        index: usize,
+        local_flags: u16,
+        local_name_raw: &[u8],
+    ) {
+        let cd = &mut self.cd_entries[index];
+        if local_flags & UTF8_FLAG_BIT == 0
+            || cd.is_encoded_in_utf8()
+            || cd.file_name_raw.is_ascii()
+            || cd.file_name_raw != local_name_raw
+        {
+            return;
+        }
+        tracing::debug!(index, "UTF-8 flag found only in the local file header");
+        cd.set_utf8_encoded_flag();
+        if let Err(position) = self.utf8_flags_only_in_local_headers.binary_search(&index) {
+            self.utf8_flags_only_in_local_headers
+                .insert(position, index);
         }
     }
 
@@ -152,129 +814,1517 @@ where
     ///
     /// * `decoders_list` - list of decoders; the former the higher priority.
     pub fn get_filename_decoder_index(&self, decoders_list: &[&dyn IDecoder]) -> Option<usize> {
-        return filename_decoder::decide_decoder(
+        let _span = tracing::info_span!("detect").entered();
+        // Same as `filename_decoder::decide_decoder`, but tries the entries in parallel if enabled
+        let index = decoders_list.iter().position(|decoder| {
+            let lossless = parallel::all(&self.cd_entries, |cd| {
+                decoder.can_decode(&cd.file_name_raw) && decoder.can_decode(&cd.file_comment)
+            });
+            tracing::debug!(
+                encoding = decoder.encoding_name(),
+                lossless,
+                "tried the decoder"
+            );
+            return lossless;
+        });
+        match index {
+            Some(index) => tracing::info!(
+                encoding = decoders_list[index].encoding_name(),
+                "chose the decoder"
+            ),
+            None => tracing::info!("no decoder can decode all the names"),
+        }
+        return index;
+    }
+
+    /// Scores the decoders by how plausible the decoded file names & comments look and returns them sorted by score (descending).
+    ///
+    /// Unlike `get_filename_decoder_index`, a later decoder can win if the former ones produce garbage.
+    /// See `name_scoring::rank_decoders` for details.
+    ///
+    /// # Arguments
+    ///
+    /// * `decoders_list` - list of decoders; the former the higher priority on ties.
+    pub fn rank_filename_decoders(&self, decoders_list: &[&dyn IDecoder]) -> Vec<DecoderScore> {
+        let _span = tracing::info_span!("detect").entered();
+        let ranking = name_scoring::rank_decoders(
             decoders_list,
-            &(&self
+            &self
                 .cd_entries
                 .iter()
                 .flat_map(|cd| vec![&cd.file_name_raw, &cd.file_comment])
-                .collect::<Vec<&Vec<u8>>>()),
+                .collect::<Vec<&Vec<u8>>>(),
         );
+        for score in ranking.iter() {
+            tracing::debug!(
+                encoding = score.encoding_name.as_str(),
+                score = score.score,
+                lossless = score.lossless,
+                "scored the decoder"
+            );
+        }
+        return ranking;
     }
 
-    /// Returns a list of file names (including whether they are explicitly encoded in UTF-8).
+    /// Guesses the encoding of implicitly-encoded file names & comments statistically.
+    ///
+    /// The OS that likely created the archive (see `origin_hints`) is also taken into account.
+    ///
+    /// Returns candidates sorted by confidence (descending); see `encoding_detection::detect_encoding_candidates`.
+    pub fn detect_filename_encoding_candidates(&self) -> Vec<EncodingCandidate> {
+        let _span = tracing::info_span!("detect").entered();
+        let candidates = encoding_detection::detect_encoding_candidates_with_creator(
+            &self
+                .cd_entries
+                .iter()
+                .filter(|cd| !cd.is_encoded_in_utf8())
+                .flat_map(|cd| vec![&cd.file_name_raw, &cd.file_comment])
+                .collect::<Vec<&Vec<u8>>>(),
+            self.origin_hints().likely_creator(),
+        );
+        for candidate in candidates.iter() {
+            tracing::debug!(
+                encoding = candidate.decoder.encoding_name(),
+                confidence = candidate.confidence,
+                "detected the candidate"
+            );
+        }
+        return candidates;
+    }
+
+    /// Returns how the first `limit` implicitly-encoded non-ASCII file names are decoded in each of `decoders`
+    ///
+    /// Useful to let the user eyeball the right encoding when the detection is ambiguous.
     ///
     /// # Arguments
     ///
-    /// * `legacy_decoder` - used for implicitly-encoded file names.
-    pub fn get_file_names_list(&self, legacy_decoder: &dyn IDecoder) -> Vec<FileNameEntry> {
-        use FileNameEncodingType::*;
-        self.cd_entries
+    /// * `decoders` - candidate decoders (e.g. those of `detect_filename_encoding_candidates`)
+    /// * `limit` - maximum number of file names
+    pub fn preview_names(&self, decoders: &[&dyn IDecoder], limit: usize) -> Vec<NamePreview> {
+        return self
+            .cd_entries
             .iter()
-            .map(|cd| {
-                if cd.is_encoded_in_utf8() {
-                    let original_file_name = String::from_utf8_lossy(&*(cd.file_name_raw));
-                    let nfc_file_name = compose_from_hfs_nfd(&original_file_name);
-                    return FileNameEntry {
-                        encoding_type: if &original_file_name == &nfc_file_name {
-                            ExplicitRegularUTF8
-                        } else {
-                            ExplicitIrregularUTF8
+            .enumerate()
+            .filter(|(_, cd)| !cd.is_encoded_in_utf8() && !cd.file_name_raw.is_ascii())
+            .take(limit)
+            .map(|(index, cd)| NamePreview {
+                index,
+                raw: cd.file_name_raw.clone(),
+                decoded: decoders
+                    .iter()
+                    .map(
+                        |decoder| match decoder.to_string_lossless(&cd.file_name_raw) {
+                            Some(name) => DecodedName {
+                                name,
+                                lossless: true,
+                            },
+                            None => DecodedName {
+                                name: decoder.to_string_lossy(&cd.file_name_raw),
+                                lossless: false,
+                            },
                         },
-                        name: nfc_file_name,
-                    };
-                }
-                if let Some(ascii_file_name) = ASCII_DECODER.to_string_lossless(&cd.file_name_raw) {
-                    return FileNameEntry {
-                        encoding_type: ImplicitASCII,
-                        name: ascii_file_name,
-                    };
-                }
-                return FileNameEntry {
-                    encoding_type: ImplicitNonASCII,
-                    name: legacy_decoder.to_string_lossy(&cd.file_name_raw),
-                };
+                    )
+                    .collect(),
             })
-            .collect()
+            .collect();
     }
 
-    /// Changes encoding of file names in central directories in ZIP archive
-    ///
-    /// This affects only on `.cd_entries`; The contents of the original ZIP file will not be overwritten.
+    /// Returns hints on the OS that created the archive (from NTFS/Unix extra fields & "version made by").
+    pub fn origin_hints(&self) -> OriginHints {
+        return OriginHints::from_central_directories(&self.cd_entries);
+    }
+
+    /// Walks the archive from the head by signature and returns an iterator of its local file headers.
     ///
-    /// # Arguments
+    /// Independent of the central directory, so it also finds entries that are present locally but missing from it.
+    pub fn iter_local_headers(&mut self) -> LocalHeaderIter<'_, F> {
+        return local_headers::iter_local_headers(&mut self.file_handler);
+    }
+
+    /// Returns local file headers that no central directory refers to (e.g. entries lost by a truncated central directory).
+    pub fn find_orphan_local_headers(&mut self) -> anyhow::Result<Vec<LocalHeaderInfo>> {
+        let referred = self
+            .cd_entries
+            .iter()
+            .map(zip64::local_header_position)
+            .collect::<std::collections::HashSet<u64>>();
+        let mut orphans = Vec::new();
+        for header in self.iter_local_headers() {
+            let header = header?;
+            if !referred.contains(&header.position) {
+                orphans.push(header);
+            }
+        }
+        return Ok(orphans);
+    }
+
+    /// Appends central directory records for local entries that no central directory refers to.
     ///
-    /// * `legacy_decoder`: decoder for file names with implicit encoding
-    pub fn convert_central_directory_file_names(&mut self, legacy_decoder: &dyn IDecoder) {
-        self.cd_entries.iter_mut().for_each(|cd| {
-            if cd.is_encoded_in_utf8() {
-                let original_file_name = String::from_utf8_lossy(&cd.file_name_raw);
-                let nfc_file_name = compose_from_hfs_nfd(&original_file_name);
-                if original_file_name != nfc_file_name {
-                    cd.set_file_name_from_slice(&nfc_file_name.as_bytes().to_vec());
-                }
-                return;
+    /// Streamed entries (sizes only in the data descriptor) are skipped because their data cannot be copied yet.
+    pub fn recover_orphan_entries(&mut self) -> anyhow::Result<OrphanRecovery> {
+        let mut recovery = OrphanRecovery::default();
+        for header in self.find_orphan_local_headers()? {
+            if header.is_streamed {
+                recovery.skipped.push(header);
+                continue;
             }
-            cd.set_file_name_from_slice(
-                &legacy_decoder
-                    .to_string_lossy(&cd.file_name_raw)
-                    .as_bytes()
-                    .to_vec(),
-            );
-            cd.set_file_coment_from_slice(
-                &legacy_decoder
-                    .to_string_lossy(&cd.file_comment)
-                    .as_bytes()
-                    .to_vec(),
-            );
-            cd.set_utf8_encoded_flag();
-        });
+            self.cd_entries.push(header.to_central_directory()?);
+            recovery.recovered.push(header);
+        }
+        return Ok(recovery);
     }
 
-    /// Outputs the ZIP archive to the given handler.
+    /// Decompresses the entries, fixes wrong CRC-32 & uncompressed sizes in the central directory, and returns the changes.
     ///
-    /// File names in local file headers will be ignored. That in central directories are used instead.
+    /// The local headers of the checked entries take the values of the central directory when written,
+    /// and their data descriptors are dropped.  Encrypted entries, compression methods other than stored & deflated,
+    /// ZIP64 sizes, and entries whose local headers or data can't be read are skipped.
+    pub fn repair_metadata(&mut self) -> anyhow::Result<RepairReport> {
+        return self.repair_metadata_with_deadline(None, None);
+    }
+
+    /// Same as `repair_metadata` except that it gives up when the deadline has passed or the token is cancelled
+    ///
+    /// They are checked between entries and between the chunks of the decompressed data.
+    /// Returns `Err` containing `DeadlineExceeded` or `Cancelled` then (the repairs so far are kept).
     ///
     /// # Arguments
     ///
-    /// * `dest_handler` - The file handler representing for the output file.
-    pub fn output_archive_with_central_directory_file_names<G: WriteBytesExt>(
+    /// * `deadline` - deadline of the repair (`None` means no deadline)
+    /// * `cancellation` - token that aborts the repair (`None` means it can't be cancelled)
+    pub fn repair_metadata_with_deadline(
         &mut self,
-        dest_handler: &mut G,
-    ) -> anyhow::Result<()> {
-        // Writer can't get the current position, so we must record it by ourselves.
-        let mut pos: u64 = 0;
-        // Local header (including contents)
-        for cd in self.cd_entries.iter_mut() {
-            let mut local_header =
-                zip_local_file_header::ZipLocalFileHeader::from_central_directory(
+        deadline: Option<Instant>,
+        cancellation: Option<&CancellationToken>,
+    ) -> anyhow::Result<RepairReport> {
+        let interruption = Interruption {
+            deadline,
+            cancellation,
+        };
+        let mut report = RepairReport::default();
+        self.repaired_entries.clear();
+        for index in 0..self.cd_entries.len() {
+            let cd = &self.cd_entries[index];
+            let mut skip = |reason: String| report.skipped.push(SkippedRepair { index, reason });
+            if cd.is_encrypted_data() {
+                skip("encrypted".to_string());
+                continue;
+            }
+            // Broken streamed archives are what this is for, so a broken entry doesn't stop the others
+            let mut local_header = match LocalFileHeader::read_at(
+                &mut self.file_handler,
+                zip64::local_header_position(cd),
+            ) {
+                Ok(local_header) => local_header,
+                Err(e) => {
+                    skip(format!("local file header can't be read: {}", e));
+                    continue;
+                }
+            };
+            if let Err(e) = local_header.read_data_descriptor(&mut self.file_handler, cd) {
+                skip(format!("data descriptor can't be read: {}", e));
+                continue;
+            }
+            let mut payload =
+                std::io::Read::take(&mut self.file_handler, local_header.payload_size);
+            interruption.check("repairing the metadata")?;
+            let (crc32, uncompressed_size) = match repair::decompressed_crc32_and_size(
+                &mut payload,
+                cd.compression_method,
+                interruption,
+                "repairing the metadata",
+            )? {
+                Ok(Some(values)) => values,
+                Ok(None) => {
+                    skip(format!(
+                        "compression method {} is not supported",
+                        metadata::compression_method_name(cd.compression_method)
+                    ));
+                    continue;
+                }
+                Err(e) => {
+                    skip(format!("failed to decompress: {}", e));
+                    continue;
+                }
+            };
+            if payload.limit() > 0 {
+                skip(
+                    output::truncated_payload_error(
+                        local_header.payload_size,
+                        local_header.payload_size - payload.limit(),
+                    )
+                    .to_string(),
+                );
+                continue;
+            }
+            let old_uncompressed_size = zip64::uncompressed_size(cd);
+            let new_uncompressed_size = if old_uncompressed_size == uncompressed_size {
+                cd.uncompressed_size
+            } else {
+                match zip64::narrow(uncompressed_size, "uncompressed size") {
+                    // The placeholder for the ZIP64 extended information extra field is left as it is
+                    Ok(size) if cd.uncompressed_size != u32::MAX => size,
+                    _ => {
+                        skip("ZIP64 sizes are not repaired".to_string());
+                        continue;
+                    }
+                }
+            };
+            let repair = EntryRepair {
+                index,
+                crc32: Some((cd.crc32, crc32)).filter(|(old, new)| old != new),
+                uncompressed_size: Some((old_uncompressed_size, uncompressed_size))
+                    .filter(|(old, new)| old != new),
+                data_descriptor_dropped: local_header.has_data_descriptor()
+                    || cd.general_purpose_flags & DATA_DESCRIPTOR_FLAG_BIT != 0,
+            };
+            let cd = &mut self.cd_entries[index];
+            cd.crc32 = crc32;
+            cd.uncompressed_size = new_uncompressed_size;
+            cd.general_purpose_flags &= !DATA_DESCRIPTOR_FLAG_BIT;
+            self.repaired_entries.push(index);
+            if repair.crc32.is_some()
+                || repair.uncompressed_size.is_some()
+                || repair.data_descriptor_dropped
+            {
+                report.repaired.push(repair);
+            }
+        }
+        return Ok(report);
+    }
+
+    /// Finds the local headers of the entries whose offsets in the central directory don't point at any, and fixes the offsets.
+    ///
+    /// Some broken archivers write wrong offsets (e.g. without the size of data prepended to the archive).
+    /// The local header with the same file name nearest to the recorded offset is taken;
+    /// the neighborhood (`OFFSET_SEARCH_RADIUS` bytes) is searched first, and then the whole archive.
+    /// Returns the entries with wrong offsets, including those whose local headers are not found (left as they are).
+    pub fn repair_local_header_offsets(&mut self) -> anyhow::Result<Vec<OffsetRepair>> {
+        let archive_len = self.file_handler.seek(std::io::SeekFrom::End(0))?;
+        let mut broken = vec![];
+        let mut valid_positions = std::collections::HashSet::new();
+        for (index, cd) in self.cd_entries.iter().enumerate() {
+            let position = zip64::local_header_position(cd);
+            match local_headers::read_local_header_name(&mut self.file_handler, position) {
+                Ok(_) => {
+                    valid_positions.insert(position);
+                }
+                Err(_) => broken.push(index),
+            }
+        }
+        let mut repairs = vec![];
+        for index in broken {
+            let recorded_position = zip64::local_header_position(&self.cd_entries[index]);
+            let file_name_raw = &self.cd_entries[index].file_name_raw;
+            let neighborhood = recorded_position.saturating_sub(OFFSET_SEARCH_RADIUS)
+                ..recorded_position
+                    .saturating_add(OFFSET_SEARCH_RADIUS)
+                    .min(archive_len);
+            let mut found_position = local_headers::find_local_header(
+                &mut self.file_handler,
+                neighborhood,
+                file_name_raw,
+                recorded_position,
+                &valid_positions,
+            )?;
+            if found_position.is_none() {
+                found_position = local_headers::find_local_header(
                     &mut self.file_handler,
-                    cd,
+                    0..archive_len,
+                    file_name_raw,
+                    recorded_position,
+                    &valid_positions,
                 )?;
-            if local_header.file_name_length != cd.file_name_length {
-                local_header.set_file_name_from_slice(&cd.file_name_raw);
             }
-            // We don't have to consider the case that the UTF-8 flag only in the local file header is set (very rare & non-RFC)
-            if cd.is_encoded_in_utf8() {
-                local_header.set_utf8_encoded_flag();
+            if let Some(position) = found_position {
+                zip64::set_local_header_position(&mut self.cd_entries[index], position)?;
+                valid_positions.insert(position);
             }
-            cd.local_header_position = pos as u32;
-            pos += local_header.write(dest_handler)?;
+            repairs.push(OffsetRepair {
+                index,
+                recorded_position,
+                found_position,
+            });
         }
-        // Central directory
-        self.eocd.cd_starting_position = pos as u32;
-        let mut cd_new_size: u64 = 0;
-        for cd in self.cd_entries.iter_mut() {
-            cd_new_size += cd.write(dest_handler)?;
+        return Ok(repairs);
+    }
+
+    /// Returns the entries whose local file headers have different names from their central directories.
+    ///
+    /// Some archivers write different byte sequences to them, and extractors disagree on which one to use.
+    /// Entries whose local file headers can't be read are skipped (writing them fails later).
+    /// The UTF-8 flags set only in the local file headers are also honored (see `read_local_header_flags`).
+    pub fn find_name_mismatches(&mut self) -> anyhow::Result<Vec<NameMismatch>> {
+        let mut mismatches = Vec::new();
+        for index in 0..self.cd_entries.len() {
+            let cd = &self.cd_entries[index];
+            let (flags, local_header_name_raw) = match local_headers::read_local_header_name(
+                &mut self.file_handler,
+                zip64::local_header_position(cd),
+            ) {
+                Ok(header) => header,
+                Err(error) => {
+                    let error = EntryReadError::local_file_header(index, cd, error);
+                    tracing::warn!(%error, "skipped the name check of an unreadable local header");
+                    continue;
+                }
+            };
+            if local_header_name_raw != cd.file_name_raw {
+                mismatches.push(NameMismatch {
+                    index,
+                    central_directory_name_raw: cd.file_name_raw.clone(),
+                    local_header_name_raw,
+                });
+                continue;
+            }
+            self.honor_local_utf8_flag(index, flags, &local_header_name_raw);
         }
-        // EOCD
-        self.eocd.cd_size = cd_new_size as u32;
-        self.eocd.write(dest_handler)?;
-        return Ok(());
+        return Ok(mismatches);
     }
 
-    /// Returns `Err(ZipReadError)` if the archive has unsupported features (e.g. central directory encryption)
-    pub fn check_unsupported_zip_type(&self) -> Result<(), ZipReadError> {
-        return self.eocd.check_unsupported_zip_type();
+    /// Resolves the names that differ between local file headers and central directories and returns the mismatches.
+    ///
+    /// The output always has the same names in both headers; `policy` decides which one is converted and written.
+    /// Returns `Err` containing `NameMismatchError` without changing anything if `policy` is `Error` and there are mismatches.
+    ///
+    /// # Arguments
+    ///
+    /// * `policy` - which name to use
+    pub fn reconcile_name_mismatches(
+        &mut self,
+        policy: NameMismatchPolicy,
+    ) -> anyhow::Result<Vec<NameMismatch>> {
+        let mismatches = self.find_name_mismatches()?;
+        match policy {
+            NameMismatchPolicy::PreferCentralDirectory => {}
+            NameMismatchPolicy::PreferLocalHeader => {
+                for mismatch in mismatches.iter() {
+                    self.cd_entries[mismatch.index]
+                        .set_file_name_from_slice(&mismatch.local_header_name_raw);
+                }
+            }
+            NameMismatchPolicy::Error => {
+                if !mismatches.is_empty() {
+                    return Err(NameMismatchError { mismatches }.into());
+                }
+            }
+        }
+        return Ok(mismatches);
+    }
+
+    /// Returns a list of file names (including whether they are explicitly encoded in UTF-8).
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder` - used for implicitly-encoded file names.
+    pub fn get_file_names_list(&self, legacy_decoder: &dyn IDecoder) -> Vec<FileNameEntry> {
+        return parallel::map(&self.cd_entries, |cd| file_name_entry(cd, legacy_decoder));
+    }
+
+    /// Returns an iterator that decodes the file names one by one only when they are requested.
+    ///
+    /// Unlike `get_file_names_list`, this does not build the whole list at once,
+    /// so frontends can fill virtualized lists of archives with hundreds of thousands of entries.
+    /// The number of entries is available from `len()`, and `entry` decodes only the entry at an index.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder` - used for implicitly-encoded file names.
+    pub fn entries<'a>(
+        &'a self,
+        legacy_decoder: &'a dyn IDecoder,
+    ) -> impl ExactSizeIterator<Item = FileNameEntry> + DoubleEndedIterator + 'a {
+        return self
+            .cd_entries
+            .iter()
+            .map(move |cd| file_name_entry(cd, legacy_decoder));
+    }
+
+    /// Returns the file name of the entry at `index` (`None` if out of range)
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `legacy_decoder` - used for implicitly-encoded file names.
+    pub fn entry(&self, index: usize, legacy_decoder: &dyn IDecoder) -> Option<FileNameEntry> {
+        return self
+            .cd_entries
+            .get(index)
+            .map(|cd| file_name_entry(cd, legacy_decoder));
+    }
+
+    /// Returns the metadata (sizes, compression method, CRC, timestamps, flags, offsets, etc.) of the entry at `index`
+    /// (`None` if out of range)
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    pub fn entry_metadata(&self, index: usize) -> Option<EntryMetadata> {
+        return self
+            .cd_entries
+            .get(index)
+            .map(|cd| EntryMetadata::from_central_directory(index, cd));
+    }
+
+    /// Returns an iterator over the metadata of all the entries (see `entry_metadata`)
+    pub fn entries_metadata(&self) -> impl ExactSizeIterator<Item = EntryMetadata> + '_ {
+        return self
+            .cd_entries
+            .iter()
+            .enumerate()
+            .map(|(index, cd)| EntryMetadata::from_central_directory(index, cd));
+    }
+
+    /// Changes encoding of file names in central directories in ZIP archive
+    ///
+    /// This affects only on `.cd_entries`; The contents of the original ZIP file will not be overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder`: decoder for file names with implicit encoding
+    pub fn convert_central_directory_file_names(&mut self, legacy_decoder: &dyn IDecoder) {
+        self.convert_central_directory_file_names_with_options(
+            legacy_decoder,
+            &ConversionOptions::default(),
+        );
+    }
+
+    /// Changes encoding of file names in central directories in ZIP archive with options
+    ///
+    /// This affects only on `.cd_entries`; The contents of the original ZIP file will not be overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder`: decoder for file names with implicit encoding
+    /// * `options`: conversion options (e.g. transliteration)
+    pub fn convert_central_directory_file_names_with_options(
+        &mut self,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+    ) {
+        self.convert_central_directory_file_names_with_filter(legacy_decoder, options, |_| true);
+    }
+
+    /// Changes encoding of file names in central directories in ZIP archive only for the entries selected by `filter`
+    ///
+    /// The other entries are copied untouched (e.g. when only one subfolder has garbled names).
+    /// This affects only on `.cd_entries`; The contents of the original ZIP file will not be overwritten.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder`: decoder for file names with implicit encoding
+    /// * `options`: conversion options (e.g. transliteration)
+    /// * `filter`: predicate that receives the decoded file name (before the later stages) and returns `true` to convert the entry
+    pub fn convert_central_directory_file_names_with_filter<P: Fn(&str) -> bool>(
+        &mut self,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+        filter: P,
+    ) {
+        let _span =
+            tracing::info_span!("convert", encoding = legacy_decoder.encoding_name()).entered();
+        let traces = trace_entries(
+            &self.cd_entries,
+            legacy_decoder,
+            options,
+            self.rename_hook.as_deref(),
+        );
+        let emptied_entries = &mut self.emptied_entries;
+        emptied_entries.clear();
+        self.cd_entries
+            .iter_mut()
+            .zip(traces)
+            .enumerate()
+            .for_each(|(index, (cd, trace))| {
+                if !filter(&trace.stages[0].1) {
+                    tracing::trace!(index, "excluded by the filter");
+                    return;
+                }
+                tracing::trace!(
+                    index,
+                    decoded = trace.stages[0].1.as_str(),
+                    converted = trace.final_name(),
+                    "converted the name"
+                );
+                if trace.final_name().is_empty() {
+                    emptied_entries.push(index);
+                }
+                if let Some(made_by) = options.made_by {
+                    cd.version_made_by =
+                        (cd.version_made_by & 0x00ff) | ((made_by.code() as u16) << 8);
+                }
+                if cd.is_encoded_in_utf8() {
+                    // Keep the original bytes (even if they are invalid as UTF-8) unless a stage after decoding changes the name
+                    if (1..trace.stages.len()).any(|i| trace.is_changed_at(i)) {
+                        cd.set_file_name_from_slice(&trace.final_name().as_bytes().to_vec());
+                    }
+                    return;
+                }
+                cd.set_file_name_from_slice(&trace.final_name().as_bytes().to_vec());
+                let mut comment = legacy_decoder.to_string_lossy(&cd.file_comment);
+                if options.transliterate {
+                    comment = name_pipeline::transliterate(&comment);
+                }
+                cd.set_file_coment_from_slice(&comment.as_bytes().to_vec());
+                cd.set_utf8_encoded_flag();
+            });
+    }
+
+    /// Changes encoding of explicitly UTF-8 encoded file names & comments in central directories into a legacy encoding,
+    /// and clears their UTF-8 flags (general purpose flag #11)
+    ///
+    /// This is the reverse conversion for receivers that can only handle legacy encodings (e.g. old Windows).
+    /// Names are composed into NFC before encoding.  Implicitly-encoded entries are left as they are.
+    ///
+    /// Returns `Err(LossyConversionError)` listing the offending entries without changing anything
+    /// if any name or comment has characters that cannot be represented in the encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: encoder for the target legacy encoding
+    pub fn convert_central_directory_file_names_to_encoding(
+        &mut self,
+        encoder: &dyn IEncoder,
+    ) -> Result<(), LossyConversionError> {
+        let mut encoded = Vec::with_capacity(self.cd_entries.len());
+        let mut entries = vec![];
+        for (index, cd) in self.cd_entries.iter().enumerate() {
+            if !cd.is_encoded_in_utf8() {
+                encoded.push(None);
+                continue;
+            }
+            let name = compose_from_hfs_nfd(&String::from_utf8_lossy(&cd.file_name_raw));
+            let comment = String::from_utf8_lossy(&cd.file_comment);
+            match (
+                encoder.to_bytes_lossless(&name),
+                encoder.to_bytes_lossless(&comment),
+            ) {
+                (Some(name_raw), Some(comment_raw)) => encoded.push(Some((name_raw, comment_raw))),
+                _ => entries.push(LossyEntry {
+                    index,
+                    name,
+                    reasons: vec![LossReason::UnmappableCharacter],
+                }),
+            }
+        }
+        if !entries.is_empty() {
+            return Err(LossyConversionError { entries });
+        }
+        for (cd, encoded) in self.cd_entries.iter_mut().zip(encoded) {
+            if let Some((name_raw, comment_raw)) = encoded {
+                cd.set_file_name_from_slice(&name_raw);
+                cd.set_file_coment_from_slice(&comment_raw);
+                cd.general_purpose_flags &= !UTF8_FLAG_BIT;
+            }
+        }
+        return Ok(());
+    }
+
+    /// Encodes explicitly UTF-8 encoded file names & comments in central directories into a legacy encoding (e.g. CP437)
+    /// for ancient extractors, keeping the UTF-8 names in Info-ZIP Unicode Path extra fields (0x7075).
+    ///
+    /// Unlike `convert_central_directory_file_names_to_encoding`, characters that cannot be represented are replaced with `_`
+    /// because extractors that support the extra field still restore the exact names.
+    /// Their UTF-8 flags (general purpose flag #11) are cleared, and the extra fields are also written to the local file headers.
+    /// ASCII names and implicitly-encoded entries are left as they are.
+    ///
+    /// Returns the entries whose legacy names (or comments) lost characters, or `Err(FieldTooLongError)` if the extra field
+    /// of an entry would exceed 65535 bytes (the entries before it are already converted).
+    ///
+    /// # Arguments
+    ///
+    /// * `encoder`: encoder for the legacy encoding of the primary names
+    pub fn convert_central_directory_file_names_to_legacy_with_unicode_path(
+        &mut self,
+        encoder: &dyn IEncoder,
+    ) -> Result<Vec<LossyEntry>, FieldTooLongError> {
+        let mut lossy_entries = vec![];
+        for (index, cd) in self.cd_entries.iter_mut().enumerate() {
+            if !cd.is_encoded_in_utf8() {
+                continue;
+            }
+            let name = compose_from_hfs_nfd(&String::from_utf8_lossy(&cd.file_name_raw));
+            let comment = String::from_utf8_lossy(&cd.file_comment).into_owned();
+            if name.is_ascii() && comment.is_ascii() {
+                cd.general_purpose_flags &= !UTF8_FLAG_BIT;
+                continue;
+            }
+            let name_raw = encoder.to_bytes_lossy(&name);
+            let extra_field = extra_fields::replace_record(
+                &cd.extra_field,
+                UNICODE_PATH_EXTRA_FIELD_ID,
+                Some(&unicode_path::unicode_path_extra_field(&name_raw, &name)?),
+            );
+            cd.extra_field_length = field_length(extra_field.len(), "extra field")?;
+            if !encoder.can_encode(&name) || !encoder.can_encode(&comment) {
+                lossy_entries.push(LossyEntry {
+                    index,
+                    name: name.clone(),
+                    reasons: vec![LossReason::UnmappableCharacter],
+                });
+            }
+            cd.extra_field = extra_field;
+            cd.set_file_name_from_slice(&name_raw);
+            cd.set_file_coment_from_slice(&encoder.to_bytes_lossy(&comment));
+            cd.general_purpose_flags &= !UTF8_FLAG_BIT;
+        }
+        return Ok(lossy_entries);
+    }
+
+    /// Corrects the MS-DOS timestamps in the central directories (and the local file headers on output),
+    /// which are in the local time of the machine that created the archive.
+    ///
+    /// Entries with invalid MS-DOS timestamps are left as they are.
+    /// Returns `Err(FieldTooLongError)` if the extended timestamp extra field doesn't fit in that of an entry
+    /// (the entries before it are already corrected).
+    ///
+    /// # Arguments
+    ///
+    /// * `correction` - UTC offsets and whether to add extended timestamp extra fields
+    pub fn correct_timestamps(
+        &mut self,
+        correction: &TimestampCorrection,
+    ) -> Result<(), FieldTooLongError> {
+        for cd in self.cd_entries.iter_mut() {
+            let local_time =
+                match timestamps::dos_time_to_seconds(cd.last_mod_date, cd.last_mod_time) {
+                    Some(local_time) => local_time,
+                    None => continue,
+                };
+            let utc = local_time - correction.source_utc_offset as i64;
+            if correction.add_extended_timestamps
+                && extra_fields::find_record(&cd.extra_field, EXTENDED_TIMESTAMP_EXTRA_FIELD_ID)
+                    .is_none()
+            {
+                let extra_field = extra_fields::replace_record(
+                    &cd.extra_field,
+                    EXTENDED_TIMESTAMP_EXTRA_FIELD_ID,
+                    Some(&timestamps::extended_timestamp_extra_field(utc)),
+                );
+                cd.extra_field_length = field_length(extra_field.len(), "extra field")?;
+                cd.extra_field = extra_field;
+            }
+            if let Some(target_utc_offset) = correction.target_utc_offset {
+                (cd.last_mod_date, cd.last_mod_time) =
+                    timestamps::seconds_to_dos_time(utc + target_utc_offset as i64);
+            }
+        }
+        return Ok(());
+    }
+
+    /// Returns the size of the junk appended after the EOCD & the archive comment (e.g. by broken proxies)
+    ///
+    /// The junk is accepted up to `trailing_data::TRAILING_DATA_SEARCH_LIMIT` bytes and dropped from the output.
+    pub fn trailing_data_size(&self) -> u64 {
+        return self.trailing_data_size;
+    }
+
+    /// Returns `true` if any entry or the archive itself has a comment
+    pub fn has_comments(&self) -> bool {
+        return !self.eocd.comment.is_empty()
+            || self.cd_entries.iter().any(|cd| !cd.file_comment.is_empty());
+    }
+
+    /// Removes the comments of all the entries and the archive itself (e.g. mojibake ones left after the conversion)
+    pub fn strip_comments(&mut self) {
+        for cd in self.cd_entries.iter_mut() {
+            cd.set_file_coment_from_slice(&vec![]);
+        }
+        self.eocd.comment.clear();
+        self.eocd.comment_length = 0;
+    }
+
+    /// Returns the archive comment in the EOCD as it is
+    pub fn comment(&self) -> &[u8] {
+        return &self.eocd.comment;
+    }
+
+    /// Replaces the archive comment in the EOCD (e.g. a note on the provenance or the distribution in UTF-8)
+    ///
+    /// Returns `Err` (and leaves the comment as it is) if it is longer than 65535 bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `comment` - new comment (an empty one removes it)
+    pub fn set_comment(&mut self, comment: &[u8]) -> Result<(), CommentTooLongError> {
+        let comment_length = u16::try_from(comment.len()).map_err(|_| CommentTooLongError {
+            length: comment.len(),
+        })?;
+        self.eocd.comment = comment.to_vec();
+        self.eocd.comment_length = comment_length;
+        return Ok(());
+    }
+
+    /// Rewrites the Unix modes in the external file attributes of the central directories
+    /// (e.g. forces 0644 / 0755 and strips setuid), which are often garbage in archives created by Windows tools.
+    ///
+    /// Returns the number of changed entries.  Entries created in hosts without Unix modes (e.g. MS-DOS) are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `normalization` - permission bits to force and whether to strip the special bits
+    pub fn normalize_unix_permissions(&mut self, normalization: &PermissionNormalization) -> usize {
+        return self
+            .cd_entries
+            .iter_mut()
+            .map(|cd| attributes::normalize_unix_mode_of_entry(cd, normalization))
+            .filter(|changed| *changed)
+            .count();
+    }
+
+    /// Returns the plan of the conversion (old and new names of all the entries) without changing anything.
+    ///
+    /// Useful for frontends that show a before/after table.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder`: decoder for file names with implicit encoding
+    pub fn plan_conversion(&self, legacy_decoder: &dyn IDecoder) -> Vec<RenamePlanEntry> {
+        return self.plan_conversion_with_options(legacy_decoder, &ConversionOptions::default());
+    }
+
+    /// Returns the plan of the conversion with options without changing anything.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder`: decoder for file names with implicit encoding
+    /// * `options`: conversion options (e.g. transliteration)
+    pub fn plan_conversion_with_options(
+        &self,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+    ) -> Vec<RenamePlanEntry> {
+        return trace_entries(
+            &self.cd_entries,
+            legacy_decoder,
+            options,
+            self.rename_hook.as_deref(),
+        )
+        .into_iter()
+        .enumerate()
+        .map(|(index, trace)| {
+            // Same condition as `convert_central_directory_file_names_with_filter`
+            let changes = if trace.explicit_utf8 {
+                (1..trace.stages.len()).any(|i| trace.is_changed_at(i))
+            } else {
+                !trace.raw.is_ascii() || trace.final_name().as_bytes() != trace.raw
+            };
+            return RenamePlanEntry {
+                index,
+                current_name: trace.stages[0].1.clone(),
+                proposed_name: trace.final_name().to_string(),
+                changes,
+                raw: trace.raw,
+                explicit_utf8: trace.explicit_utf8,
+            };
+        })
+        .collect();
+    }
+
+    /// Returns `Err(LossyConversionError)` listing the offending entries if the conversion would lose any information.
+    ///
+    /// Lossy decoding, replacement characters, and transliteration of file names or comments are reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder`: decoder for file names with implicit encoding
+    /// * `options`: conversion options (e.g. transliteration)
+    pub fn check_lossless_conversion(
+        &self,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+    ) -> Result<(), LossyConversionError> {
+        let entries: Vec<LossyEntry> = self
+            .cd_entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, cd)| {
+                let trace = trace_entry(cd, legacy_decoder, options, self.rename_hook.as_deref());
+                let mut reasons = trace.losses.clone();
+                // Comments of explicitly UTF-8 encoded entries are left as they are
+                if !cd.is_encoded_in_utf8() {
+                    let comment = legacy_decoder.to_string_lossy(&cd.file_comment);
+                    if !legacy_decoder.can_decode(&cd.file_comment) {
+                        reasons.push(LossReason::LossyCommentDecode);
+                    } else if options.transliterate
+                        && name_pipeline::transliterate(&comment) != comment
+                        && !reasons.contains(&LossReason::Transliteration)
+                    {
+                        reasons.push(LossReason::Transliteration);
+                    }
+                }
+                if reasons.is_empty() {
+                    return None;
+                }
+                return Some(LossyEntry {
+                    index,
+                    name: trace.final_name().to_string(),
+                    reasons,
+                });
+            })
+            .collect();
+        if entries.is_empty() {
+            return Ok(());
+        }
+        return Err(LossyConversionError { entries });
+    }
+
+    /// Returns how the file name of the entry goes through each stage of the conversion.
+    ///
+    /// Returns `None` if `index` is out of range.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `legacy_decoder` - decoder for file names with implicit encoding
+    /// * `options` - conversion options (e.g. transliteration)
+    pub fn explain_file_name(
+        &self,
+        index: usize,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+    ) -> Option<NameTrace> {
+        let cd = self.cd_entries.get(index)?;
+        return Some(trace_entry(
+            cd,
+            legacy_decoder,
+            options,
+            self.rename_hook.as_deref(),
+        ));
+    }
+
+    /// Returns the entries whose decoded names can escape the extraction directory (e.g. `../a.txt`)
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder` - decoder for file names with implicit encoding
+    pub fn find_unsafe_paths(&self, legacy_decoder: &dyn IDecoder) -> Vec<path_safety::UnsafePath> {
+        return self
+            .entries(legacy_decoder)
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let reasons = path_safety::find_unsafe_path_reasons(&entry.name);
+                if reasons.is_empty() {
+                    return None;
+                }
+                return Some(path_safety::UnsafePath {
+                    index,
+                    name: entry.name,
+                    reasons,
+                });
+            })
+            .collect();
+    }
+
+    /// Returns the entries whose decoded names cannot be extracted as they are in Windows (e.g. `aux.txt`)
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder` - decoder for file names with implicit encoding
+    pub fn find_reserved_names(
+        &self,
+        legacy_decoder: &dyn IDecoder,
+    ) -> Vec<reserved_names::ReservedName> {
+        return self
+            .entries(legacy_decoder)
+            .enumerate()
+            .filter_map(|(index, entry)| {
+                let reasons = reserved_names::find_reserved_name_reasons(&entry.name);
+                if reasons.is_empty() {
+                    return None;
+                }
+                return Some(reserved_names::ReservedName {
+                    index,
+                    name: entry.name,
+                    reasons,
+                });
+            })
+            .collect();
+    }
+
+    /// Returns the plan entries of symbolic links that the conversion would rename
+    ///
+    /// Their targets are kept as they are, so links pointing to the old names may break.
+    ///
+    /// # Arguments
+    ///
+    /// * `legacy_decoder` - decoder for file names with implicit encoding
+    /// * `options` - conversion options (e.g. transliteration)
+    pub fn find_renamed_symlinks(
+        &self,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+    ) -> Vec<RenamePlanEntry> {
+        return self
+            .plan_conversion_with_options(legacy_decoder, options)
+            .into_iter()
+            .filter(|entry| {
+                entry.changes && attributes::is_symlink_entry(&self.cd_entries[entry.index])
+            })
+            .collect();
+    }
+
+    /// Returns the index of the first entry whose file name is `name` before or after the conversion.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - file name (or path) to search for
+    /// * `legacy_decoder` - decoder for file names with implicit encoding
+    /// * `options` - conversion options (e.g. transliteration)
+    pub fn find_entry_index(
+        &self,
+        name: &str,
+        legacy_decoder: &dyn IDecoder,
+        options: &ConversionOptions,
+    ) -> Option<usize> {
+        return self.cd_entries.iter().position(|cd| {
+            let trace = trace_entry(cd, legacy_decoder, options, self.rename_hook.as_deref());
+            trace
+                .stages
+                .iter()
+                .any(|(_, stage_name)| stage_name == name)
+        });
+    }
+
+    /// Outputs the ZIP archive to the given handler.
+    ///
+    /// File names in local file headers will be ignored. That in central directories are used instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest_handler` - The file handler representing for the output file.
+    pub fn output_archive_with_central_directory_file_names<G: WriteBytesExt>(
+        &mut self,
+        dest_handler: &mut G,
+    ) -> anyhow::Result<()> {
+        self.output_archive_with_options(dest_handler, &OutputOptions::default())?;
+        return Ok(());
+    }
+
+    /// Outputs the ZIP archive to the given handler with options and returns the report on the written entries.
+    ///
+    /// File names in local file headers will be ignored. That in central directories are used instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest_handler` - The file handler representing for the output file.
+    /// * `options` - output options (e.g. hashing of payloads)
+    pub fn output_archive_with_options<G: WriteBytesExt>(
+        &mut self,
+        dest_handler: &mut G,
+        options: &OutputOptions,
+    ) -> anyhow::Result<OutputReport> {
+        let mut sink = CountingSink::new(dest_handler);
+        if options.preserve_unchanged
+            && self.is_unchanged()?
+            && self.is_aligned(options.alignment)?
+        {
+            return self.copy_unchanged_archive(&mut sink, options);
+        }
+        return self.output_archive_to_sink(&mut sink, options);
+    }
+
+    /// Outputs the ZIP archive to a seekable writer, filling in the CRC-32 & sizes of each local header after its payload.
+    ///
+    /// Each local header is written first, and the writer seeks back to it once the payload has been written,
+    /// so the header describes the written payload even if the input has the values only in the data descriptor.
+    /// The data descriptors are dropped (general purpose flag #3 is cleared in the central directory too)
+    /// except for encrypted entries, whose password checks may depend on the flag.
+    ///
+    /// # Arguments
+    ///
+    /// * `dest_handler` - The file handler representing for the output file.
+    /// * `options` - output options (e.g. hashing of payloads)
+    pub fn output_archive_with_backfill<G: WriteBytesExt + std::io::Seek>(
+        &mut self,
+        dest_handler: &mut G,
+        options: &OutputOptions,
+    ) -> anyhow::Result<OutputReport> {
+        let mut sink = CountingSink::new(dest_handler);
+        let mut state = self.begin_output()?;
+        for index in 0..self.cd_entries.len() {
+            if !self.filter_entry(index, &mut state, options)? {
+                continue;
+            }
+            self.write_local_entry_with_backfill(index, &mut sink, &mut state, options)?;
+        }
+        return self.finish_output(&mut sink, state);
+    }
+
+    /// Same as `write_local_entry` except that the local header is rewritten after the payload (see `output_archive_with_backfill`)
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `sink` - destination of the archive
+    /// * `state` - state of the output in progress
+    /// * `options` - output options
+    fn write_local_entry_with_backfill<W: WriteBytesExt + std::io::Seek>(
+        &mut self,
+        index: usize,
+        sink: &mut CountingSink<W>,
+        state: &mut OutputState,
+        options: &OutputOptions,
+    ) -> anyhow::Result<()> {
+        let mut local_header = LocalFileHeader::read_for_entry(
+            &mut self.file_handler,
+            index,
+            &self.cd_entries[index],
+        )?;
+        self.honor_local_utf8_flag(
+            index,
+            local_header.general_purpose_flags,
+            &local_header.file_name_raw,
+        );
+        let cd = &mut self.cd_entries[index];
+        local_header.read_data_descriptor(&mut self.file_handler, cd)?;
+        let header_position = state.pos;
+        state.pos += write_local_header(cd, &mut local_header, sink, options.alignment)?;
+        let mut hasher = output::PayloadHasher::new(options.hash_payloads);
+        output::copy_payload(
+            &mut self.file_handler,
+            sink,
+            local_header.payload_size,
+            options,
+            &mut hasher,
+            &mut output::PayloadProgress::new(
+                &mut state.progress,
+                self.progress_callback.as_deref(),
+            ),
+        )?;
+        state.pos += local_header.payload_size;
+        // The payload is copied as it is, so the values in the central directory are those of the written payload
+        let keeps_data_descriptor = cd.is_encrypted_data() && local_header.has_data_descriptor();
+        let data_descriptor_size = local_header.data_descriptor_size;
+        local_header.fill_from_central_directory(cd)?;
+        if keeps_data_descriptor {
+            local_header.general_purpose_flags |= DATA_DESCRIPTOR_FLAG_BIT;
+            output::copy_payload(
+                &mut self.file_handler,
+                sink,
+                data_descriptor_size,
+                options,
+                &mut output::PayloadHasher::new(false),
+                &mut output::PayloadProgress::none(),
+            )?;
+            state.pos += data_descriptor_size;
+            local_header.data_descriptor_size = data_descriptor_size;
+        } else {
+            cd.general_purpose_flags &= !DATA_DESCRIPTOR_FLAG_BIT;
+        }
+        let mut header_bytes = Vec::with_capacity(local_header.size() as usize);
+        local_header.write(&mut header_bytes)?;
+        sink.overwrite_at(header_position, &header_bytes)?;
+        self.finish_local_entry(index, header_position, &local_header, hasher, state);
+        return Ok(());
+    }
+
+    /// Returns `true` if the archive has not been changed since it was read, so it can be written byte for byte.
+    ///
+    /// The central directory & the EOCD must serialize to the same bytes as in the input, the local headers must agree with
+    /// them on the file names & UTF-8 flags, and there must be nothing to drop (trailing data), repair, or filter.
+    /// Archives with ZIP64 records are never regarded as unchanged.
+    pub fn is_unchanged(&mut self) -> anyhow::Result<bool> {
+        if self.trailing_data_size > 0
+            || !self.repaired_entries.is_empty()
+            || !self.emptied_entries.is_empty()
+            || self.entry_filter.is_some()
+            || self.eocd.is_zip64()
+        {
+            return Ok(false);
+        }
+        let mut expected = vec![];
+        for cd in self.cd_entries.iter() {
+            cd.write(&mut expected)?;
+        }
+        let cd_start = self.eocd.cd_starting_position as u64;
+        if cd_start + expected.len() as u64 != self.eocd.starting_position_with_signature {
+            return Ok(false);
+        }
+        self.eocd.write(&mut expected)?;
+        self.file_handler.seek(std::io::SeekFrom::Start(cd_start))?;
+        let mut actual = vec![0u8; expected.len()];
+        if self.file_handler.read_exact(&mut actual).is_err() || actual != expected {
+            return Ok(false);
+        }
+        let mut previous_position = None;
+        for cd in self.cd_entries.iter() {
+            let position = zip64::local_header_position(cd);
+            // Entries are copied in the order of the central directory
+            if previous_position.is_some_and(|previous| previous >= position) {
+                return Ok(false);
+            }
+            previous_position = Some(position);
+            match local_headers::read_local_header_name(&mut self.file_handler, position) {
+                Ok((flags, name))
+                    if name == cd.file_name_raw
+                        && flags & UTF8_FLAG_BIT == cd.general_purpose_flags & UTF8_FLAG_BIT => {}
+                _ => return Ok(false),
+            }
+        }
+        return Ok(true);
+    }
+
+    /// Returns `true` if the data of all the stored entries begin at multiples of `alignment` (e.g. APKs after `zipalign`)
+    ///
+    /// Always `true` if `alignment` is `None`.
+    ///
+    /// # Arguments
+    ///
+    /// * `alignment` - alignment of the data in bytes (see `OutputOptions::alignment`)
+    pub fn is_aligned(&mut self, alignment: Option<u16>) -> anyhow::Result<bool> {
+        let alignment = match alignment {
+            Some(alignment) => alignment,
+            None => return Ok(true),
+        };
+        for (index, cd) in self.cd_entries.iter().enumerate() {
+            let local_header = LocalFileHeader::read_for_entry(&mut self.file_handler, index, cd)?;
+            if !alignment::is_aligned(&local_header, alignment) {
+                return Ok(false);
+            }
+        }
+        return Ok(true);
+    }
+
+    /// Copies the unchanged archive byte for byte (including gaps between entries) and returns the report on the entries
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - destination of the archive (not spanned)
+    /// * `options` - output options
+    fn copy_unchanged_archive<S: ArchiveSink>(
+        &mut self,
+        sink: &mut S,
+        options: &OutputOptions,
+    ) -> anyhow::Result<OutputReport> {
+        let mut state = self.begin_output()?;
+        for index in 0..self.cd_entries.len() {
+            options.interruption().check("writing the output archive")?;
+            let cd = &self.cd_entries[index];
+            let header_position = zip64::local_header_position(cd);
+            let mut local_header =
+                LocalFileHeader::read_for_entry(&mut self.file_handler, index, cd)?;
+            local_header.read_data_descriptor(&mut self.file_handler, cd)?;
+            // Data before the payload (the local header and any gap before it) as it is
+            let head_size = local_header.data_position.checked_sub(state.pos).ok_or(
+                ZipReadError::InvalidZipArchive {
+                    reason: format!("entry #{} overlaps the previous entry", index),
+                },
+            )?;
+            self.file_handler
+                .seek(std::io::SeekFrom::Start(state.pos))?;
+            output::copy_payload(
+                &mut self.file_handler,
+                sink,
+                head_size,
+                options,
+                &mut output::PayloadHasher::new(false),
+                &mut output::PayloadProgress::none(),
+            )?;
+            let mut hasher = output::PayloadHasher::new(options.hash_payloads);
+            output::copy_payload(
+                &mut self.file_handler,
+                sink,
+                local_header.payload_size,
+                options,
+                &mut hasher,
+                &mut output::PayloadProgress::new(
+                    &mut state.progress,
+                    self.progress_callback.as_deref(),
+                ),
+            )?;
+            output::copy_payload(
+                &mut self.file_handler,
+                sink,
+                local_header.data_descriptor_size,
+                options,
+                &mut output::PayloadHasher::new(false),
+                &mut output::PayloadProgress::none(),
+            )?;
+            state.pos = local_header.data_position
+                + local_header.payload_size
+                + local_header.data_descriptor_size;
+            state.written.push(true);
+            self.finish_local_entry(index, header_position, &local_header, hasher, &mut state);
+        }
+        // The central directory & the EOCD (and any gap before them)
+        let end_of_archive = self.eocd.starting_position_with_signature
+            + EOCD_FIXED_SIZE
+            + self.eocd.comment.len() as u64;
+        let tail_size =
+            end_of_archive
+                .checked_sub(state.pos)
+                .ok_or(ZipReadError::InvalidZipArchive {
+                    reason: "the last entry overlaps the central directory".to_string(),
+                })?;
+        self.file_handler
+            .seek(std::io::SeekFrom::Start(state.pos))?;
+        output::copy_payload(
+            &mut self.file_handler,
+            sink,
+            tail_size,
+            options,
+            &mut output::PayloadHasher::new(false),
+            &mut output::PayloadProgress::none(),
+        )?;
+        return Ok(state.report);
+    }
+
+    /// Outputs the ZIP archive to the given sink (e.g. `spanning::SpannedWriter` for multi-disk output) and returns the report on the written entries.
+    ///
+    /// Disk numbers and offsets in the central directory & the EOCD follow `sink.position()`.
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - destination of the archive
+    /// * `options` - output options (e.g. hashing of payloads)
+    pub fn output_archive_to_sink<S: ArchiveSink>(
+        &mut self,
+        sink: &mut S,
+        options: &OutputOptions,
+    ) -> anyhow::Result<OutputReport> {
+        let mut state = self.begin_output()?;
+        for index in 0..self.cd_entries.len() {
+            if !self.filter_entry(index, &mut state, options)? {
+                continue;
+            }
+            self.write_local_entry(index, sink, &mut state, options)?;
+        }
+        return self.finish_output(sink, state);
+    }
+
+    /// Checks whether the archive can be written and reports the initial progress
+    pub(crate) fn begin_output(&self) -> anyhow::Result<OutputState> {
+        self.feature_gate().check(Operation::Fix)?;
+        tracing::info!(n_entries = self.cd_entries.len(), "writing the archive");
+        let state = OutputState {
+            report: OutputReport::default(),
+            pos: 0,
+            written: Vec::with_capacity(self.cd_entries.len()),
+            progress: OutputProgress {
+                n_entries: self.cd_entries.len(),
+                total_bytes: self.cd_entries.iter().map(zip64::compressed_size).sum(),
+                ..Default::default()
+            },
+        };
+        self.report_progress(&state.progress);
+        return Ok(state);
+    }
+
+    fn report_progress(&self, progress: &OutputProgress) {
+        if let Some(callback) = self.progress_callback.as_deref() {
+            callback(progress);
+        }
+    }
+
+    /// Consults the entry filter and returns `true` if the entry is written
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `state` - state of the output in progress
+    /// * `options` - output options
+    pub(crate) fn filter_entry(
+        &mut self,
+        index: usize,
+        state: &mut OutputState,
+        options: &OutputOptions,
+    ) -> anyhow::Result<bool> {
+        options.interruption().check("writing the output archive")?;
+        let cd = &mut self.cd_entries[index];
+        let decision = match self.entry_filter.as_deref() {
+            _ if self.emptied_entries.binary_search(&index).is_ok() => EntryDecision::Skip,
+            Some(filter) => filter(&EntryInfo {
+                index,
+                file_name_raw: &cd.file_name_raw,
+                explicit_utf8: cd.is_encoded_in_utf8(),
+                compressed_size: zip64::compressed_size(cd),
+                uncompressed_size: zip64::uncompressed_size(cd),
+                encrypted: cd.is_encrypted_data(),
+                version_made_by: cd.version_made_by,
+                external_file_attributes: cd.external_file_attributes,
+            }),
+            None => EntryDecision::Keep,
+        };
+        match decision {
+            EntryDecision::Keep => {}
+            EntryDecision::Skip => {
+                state.written.push(false);
+                state.progress.entries_done += 1;
+                state.progress.total_bytes -= zip64::compressed_size(cd);
+                self.report_progress(&state.progress);
+                return Ok(false);
+            }
+            EntryDecision::Rename(name) => {
+                cd.set_file_name_from_slice(&name.into_bytes());
+                cd.set_utf8_encoded_flag();
+            }
+        }
+        state.written.push(true);
+        return Ok(true);
+    }
+
+    /// Writes the local header of the entry with the file name in the central directory, followed by the payload
+    ///
+    /// The payload is copied through a buffer of `options.chunk_size()` bytes, never read into memory as a whole.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `sink` - destination of the archive
+    /// * `state` - state of the output in progress
+    /// * `options` - output options
+    fn write_local_entry<S: ArchiveSink>(
+        &mut self,
+        index: usize,
+        sink: &mut S,
+        state: &mut OutputState,
+        options: &OutputOptions,
+    ) -> anyhow::Result<()> {
+        let mut local_header = LocalFileHeader::read_for_entry(
+            &mut self.file_handler,
+            index,
+            &self.cd_entries[index],
+        )?;
+        self.honor_local_utf8_flag(
+            index,
+            local_header.general_purpose_flags,
+            &local_header.file_name_raw,
+        );
+        let cd = &mut self.cd_entries[index];
+        local_header.read_data_descriptor(&mut self.file_handler, cd)?;
+        if self.repaired_entries.binary_search(&index).is_ok() {
+            local_header.fill_from_central_directory(cd)?;
+        }
+        let header_position = state.pos;
+        state.pos += write_local_header(cd, &mut local_header, sink, options.alignment)?;
+        let mut hasher = output::PayloadHasher::new(options.hash_payloads);
+        output::copy_payload(
+            &mut self.file_handler,
+            sink,
+            local_header.payload_size,
+            options,
+            &mut hasher,
+            &mut output::PayloadProgress::new(
+                &mut state.progress,
+                self.progress_callback.as_deref(),
+            ),
+        )?;
+        // The data descriptor is not a part of the payload; it is copied as it is (any of the 4 forms)
+        let trailer_size = local_header.data_descriptor_size;
+        output::copy_payload(
+            &mut self.file_handler,
+            sink,
+            trailer_size,
+            options,
+            &mut output::PayloadHasher::new(false),
+            &mut output::PayloadProgress::none(),
+        )?;
+        state.pos += local_header.payload_size + trailer_size;
+        self.finish_local_entry(index, header_position, &local_header, hasher, state);
+        return Ok(());
+    }
+
+    /// Records the written entry in the report and reports the progress
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `header_position` - position of the local header in the whole output
+    /// * `local_header` - written local header
+    /// * `hasher` - hasher fed with the payload
+    /// * `state` - state of the output in progress
+    pub(crate) fn finish_local_entry(
+        &self,
+        index: usize,
+        header_position: u64,
+        local_header: &LocalFileHeader,
+        hasher: output::PayloadHasher,
+        state: &mut OutputState,
+    ) {
+        let cd = &self.cd_entries[index];
+        state.report.entries.push(WrittenEntry {
+            index,
+            file_name_raw: cd.file_name_raw.clone(),
+            explicit_utf8: cd.is_encoded_in_utf8(),
+            local_header_position: header_position,
+            compressed_size: local_header.payload_size,
+            payload_sha256: hasher.finish(),
+        });
+        state.progress.entries_done += 1;
+        self.report_progress(&state.progress);
+    }
+
+    /// Writes the central directory & the EOCD and returns the report on the written entries
+    ///
+    /// # Arguments
+    ///
+    /// * `sink` - destination of the archive
+    /// * `state` - state of the output after all the local headers are written
+    pub(crate) fn finish_output<S: ArchiveSink>(
+        &mut self,
+        sink: &mut S,
+        state: OutputState,
+    ) -> anyhow::Result<OutputReport> {
+        let mut cd_new_size: u64 = 0;
+        // Disk where the central directory starts and that of each record
+        let mut cd_start: Option<(u16, u64)> = None;
+        let mut cd_disks = Vec::with_capacity(state.written.len());
+        for (cd, _) in self
+            .cd_entries
+            .iter()
+            .zip(state.written.iter())
+            .filter(|(_, is_written)| **is_written)
+        {
+            sink.keep_together(
+                CD_FIXED_SIZE
+                    + cd.file_name_raw.len() as u64
+                    + cd.extra_field.len() as u64
+                    + cd.file_comment.len() as u64,
+            )?;
+            let position = sink.position();
+            cd_start.get_or_insert(position);
+            cd_disks.push(position.0);
+            cd_new_size += cd.write(sink)?;
+        }
+        // EOCD
+        sink.keep_together(EOCD_FIXED_SIZE + self.eocd.comment.len() as u64)?;
+        let (eocd_disk, _) = sink.position();
+        let (cd_start_disk, cd_start_offset) = cd_start.unwrap_or_else(|| sink.position());
+        self.eocd.eocd_disk_index = eocd_disk;
+        self.eocd.cd_start_disk_index = cd_start_disk;
+        self.eocd.cd_starting_position =
+            zip64::narrow(cd_start_offset, "offset of the central directory")?;
+        self.eocd.n_cd_entries = zip64::narrow(cd_disks.len() as u64, "number of entries")?;
+        self.eocd.n_cd_entries_in_disk = zip64::narrow(
+            cd_disks.iter().filter(|disk| **disk == eocd_disk).count() as u64,
+            "number of entries",
+        )?;
+        self.eocd.cd_size = zip64::narrow(cd_new_size, "size of the central directory")?;
+        self.eocd.write(sink)?;
+        tracing::info!(
+            n_entries = cd_disks.len(),
+            cd_size = cd_new_size,
+            "wrote the central directory"
+        );
+        return Ok(state.report);
+    }
+
+    /// Returns `Err(ZipReadError)` if the archive has features that block fixing it (e.g. central directory encryption)
+    ///
+    /// Same as `self.feature_gate().check(Operation::Fix)` except for the error type.
+    pub fn check_unsupported_zip_type(&self) -> Result<(), ZipReadError> {
+        return self
+            .feature_gate()
+            .check(Operation::Fix)
+            .map(|_| ())
+            .map_err(|e| ZipReadError::UnsupportedZipArchive {
+                reason: e.to_string(),
+            });
+    }
+
+    /// Returns the registry of unsupported constructs in the archive, which decides what each operation can do
+    pub fn feature_gate(&self) -> FeatureGate {
+        return FeatureGate::from_structures(&self.eocd, &self.cd_entries);
     }
 }
@@ -0,0 +1,717 @@
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{Read, Seek, SeekFrom, Write};
+use zip_structs::zip_central_directory::ZipCDEntry;
+use zip_structs::zip_error::ZipReadError;
+
+use crate::error::EntryReadError;
+use crate::extra_fields;
+use crate::field_length;
+use crate::zip64::{self, narrow, Zip64Required};
+
+/// Signature of local file headers
+pub const LOCAL_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+/// Signature of central directory entries
+pub const CD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x01, 0x02];
+/// Signature of data descriptors (optional); also used as the marker of spanned archives at the head
+pub const DATA_DESCRIPTOR_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x07, 0x08];
+/// Marker of archives that were to be spanned but fit in a single segment
+const SINGLE_SEGMENT_MARKER: [u8; 4] = [0x50, 0x4b, 0x30, 0x30];
+/// Size of the fixed-length part of a local file header (including the signature)
+pub(crate) const LOCAL_HEADER_FIXED_SIZE: u64 = 30;
+/// General purpose flag bit #3: sizes & CRC-32 are in the data descriptor after the data
+pub(crate) const DATA_DESCRIPTOR_FLAG_BIT: u16 = 0x0008;
+/// Size of chunks read while searching for the end of streamed data
+const SCAN_CHUNK_SIZE: usize = 64 * 1024;
+/// Distance from the recorded offset within which a misplaced local header is searched for first
+pub const OFFSET_SEARCH_RADIUS: u64 = 64 * 1024;
+/// Size of the largest data descriptor (signature, CRC-32, and 64-bit sizes)
+pub(crate) const MAX_DATA_DESCRIPTOR_SIZE: u64 = 24;
+/// Forms of data descriptors (with the signature, with 8-byte sizes) in the order of preference
+const DATA_DESCRIPTOR_FORMS: [(bool, bool); 4] =
+    [(true, false), (true, true), (false, false), (false, true)];
+/// Same as `DATA_DESCRIPTOR_FORMS` for ZIP64 entries, whose descriptors should have 8-byte sizes
+const ZIP64_DATA_DESCRIPTOR_FORMS: [(bool, bool); 4] =
+    [(true, true), (true, false), (false, true), (false, false)];
+
+/// Metadata of a local file header found by walking the archive
+///
+/// The compressed data is not read into memory.
+#[derive(Clone, Debug)]
+pub struct LocalHeaderInfo {
+    /// Position of the signature of the local file header
+    pub position: u64,
+    pub version_required_to_extract: u16,
+    pub general_purpose_flags: u16,
+    pub compression_method: u16,
+    pub last_mod_time: u16,
+    pub last_mod_date: u16,
+    /// CRC-32 (taken from the data descriptor if the local header does not have it)
+    pub crc32: u32,
+    /// Compressed size (taken from the data descriptor or the ZIP64 extra field if the local header does not have it)
+    pub compressed_size: u64,
+    /// Uncompressed size (taken from the data descriptor or the ZIP64 extra field if the local header does not have it)
+    pub uncompressed_size: u64,
+    pub file_name_raw: Vec<u8>,
+    pub extra_field: Vec<u8>,
+    /// Position of the compressed data
+    pub data_position: u64,
+    /// Size of the data descriptor after the compressed data (0 if absent)
+    pub data_descriptor_size: u64,
+    /// `true` if the sizes are known only from the data descriptor (0 in the local header)
+    pub is_streamed: bool,
+}
+
+/// Local file header read without its payload (see `LocalFileHeader::read_at`)
+///
+/// Unlike `ZipLocalFileHeader`, the payload is not read into memory; it is copied chunk by chunk afterwards.
+#[derive(Clone, Debug)]
+pub(crate) struct LocalFileHeader {
+    pub version_required_to_extract: u16,
+    pub general_purpose_flags: u16,
+    pub compression_method: u16,
+    pub last_mod_time: u16,
+    pub last_mod_date: u16,
+    pub crc32: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub file_name_raw: Vec<u8>,
+    pub extra_field: Vec<u8>,
+    /// Position of the payload in the input
+    pub data_position: u64,
+    /// Size of the payload (taken from the central directory if the sizes are in the data descriptor)
+    pub payload_size: u64,
+    /// Size of the data descriptor following the payload (0 if absent; see `resolve_data_descriptor`)
+    pub data_descriptor_size: u64,
+}
+
+impl LocalFileHeader {
+    /// Reads the local file header at `position` and leaves `reader` at the head of the payload
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - file handler representing the archive
+    /// * `position` - position of the signature of the local file header
+    pub fn read_at<R: Read + Seek>(reader: &mut R, position: u64) -> Result<Self, ZipReadError> {
+        reader.seek(SeekFrom::Start(position))?;
+        let mut signature = [0u8; 4];
+        reader.read_exact(&mut signature)?;
+        if signature != LOCAL_HEADER_SIGNATURE {
+            return Err(ZipReadError::InvalidZipArchive {
+                reason: format!(
+                    "assumed local file header signature doesn't appear at position {}",
+                    position
+                ),
+            });
+        }
+        let version_required_to_extract = reader.read_u16::<LE>()?;
+        let general_purpose_flags = reader.read_u16::<LE>()?;
+        let compression_method = reader.read_u16::<LE>()?;
+        let last_mod_time = reader.read_u16::<LE>()?;
+        let last_mod_date = reader.read_u16::<LE>()?;
+        let crc32 = reader.read_u32::<LE>()?;
+        let compressed_size = reader.read_u32::<LE>()?;
+        let uncompressed_size = reader.read_u32::<LE>()?;
+        let file_name_length = reader.read_u16::<LE>()?;
+        let extra_field_length = reader.read_u16::<LE>()?;
+        let mut file_name_raw = vec![0u8; file_name_length as usize];
+        reader.read_exact(&mut file_name_raw)?;
+        let mut extra_field = vec![0u8; extra_field_length as usize];
+        reader.read_exact(&mut extra_field)?;
+        let payload_size = zip64::local_compressed_size(compressed_size, &extra_field);
+        return Ok(Self {
+            version_required_to_extract,
+            general_purpose_flags,
+            compression_method,
+            last_mod_time,
+            last_mod_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            file_name_raw,
+            extra_field,
+            data_position: position
+                + LOCAL_HEADER_FIXED_SIZE
+                + file_name_length as u64
+                + extra_field_length as u64,
+            payload_size,
+            data_descriptor_size: 0,
+        });
+    }
+
+    /// Reads the local file header of entry `index` (see `read_at`), telling which entry is broken on failure
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - file handler representing the archive
+    /// * `index` - index of the entry
+    /// * `cd` - central directory of the entry
+    pub fn read_for_entry<R: Read + Seek>(
+        reader: &mut R,
+        index: usize,
+        cd: &ZipCDEntry,
+    ) -> Result<Self, EntryReadError> {
+        return Self::read_at(reader, zip64::local_header_position(cd))
+            .map_err(|error| EntryReadError::local_file_header(index, cd, error));
+    }
+
+    /// Resolves the sizes of the payload & the data descriptor of an entry with general purpose flag #3
+    ///
+    /// The local header of such an entry may have 0 as the sizes, so the payload size is taken from the central directory.
+    /// Does nothing if the flag is not set.
+    ///
+    /// # Arguments
+    ///
+    /// * `cd` - central directory of the entry
+    /// * `descriptor_head` - bytes following the payload (up to `MAX_DATA_DESCRIPTOR_SIZE` bytes; shorter at the end of the input)
+    pub fn resolve_data_descriptor(&mut self, cd: &ZipCDEntry, descriptor_head: &[u8]) {
+        if !self.has_data_descriptor() {
+            return;
+        }
+        self.payload_size = zip64::compressed_size(cd);
+        self.data_descriptor_size = detect_data_descriptor_size(
+            descriptor_head,
+            self.payload_size,
+            zip64::uncompressed_size(cd),
+            zip64::has_zip64_extra_field(&self.extra_field),
+        );
+    }
+
+    /// Reads the bytes following the payload and resolves the data descriptor (see `resolve_data_descriptor`)
+    ///
+    /// Leaves `reader` at the head of the payload.
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - file handler representing the archive
+    /// * `cd` - central directory of the entry
+    pub fn read_data_descriptor<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        cd: &ZipCDEntry,
+    ) -> Result<(), ZipReadError> {
+        if !self.has_data_descriptor() {
+            return Ok(());
+        }
+        reader.seek(SeekFrom::Start(
+            self.data_position + zip64::compressed_size(cd),
+        ))?;
+        let mut head = [0u8; MAX_DATA_DESCRIPTOR_SIZE as usize];
+        let n_read = read_fully(reader, &mut head)?;
+        self.resolve_data_descriptor(cd, &head[..n_read]);
+        reader.seek(SeekFrom::Start(self.data_position))?;
+        return Ok(());
+    }
+
+    /// Takes the CRC-32 & sizes from the central directory and drops the data descriptor (for repaired entries)
+    ///
+    /// The sizes go to the ZIP64 extended information extra field if the header has it.
+    /// Returns `Err` if the sizes don't fit in the 32-bit fields otherwise.
+    ///
+    /// # Arguments
+    ///
+    /// * `cd` - central directory of the entry
+    pub fn fill_from_central_directory(&mut self, cd: &ZipCDEntry) -> Result<(), Zip64Required> {
+        let compressed_size = zip64::compressed_size(cd);
+        let uncompressed_size = zip64::uncompressed_size(cd);
+        self.crc32 = cd.crc32;
+        match extra_fields::find_data_range(&self.extra_field, zip64::ZIP64_EXTRA_FIELD_ID)
+            .filter(|range| range.len() >= 16)
+        {
+            Some(range) => {
+                // The record in local headers has both sizes (uncompressed first)
+                self.extra_field[range.start..range.start + 8]
+                    .copy_from_slice(&uncompressed_size.to_le_bytes());
+                self.extra_field[range.start + 8..range.start + 16]
+                    .copy_from_slice(&compressed_size.to_le_bytes());
+                self.compressed_size = u32::MAX;
+                self.uncompressed_size = u32::MAX;
+            }
+            None => {
+                self.compressed_size = narrow(compressed_size, "compressed size")?;
+                self.uncompressed_size = narrow(uncompressed_size, "uncompressed size")?;
+            }
+        }
+        self.general_purpose_flags &= !DATA_DESCRIPTOR_FLAG_BIT;
+        self.payload_size = compressed_size;
+        self.data_descriptor_size = 0;
+        return Ok(());
+    }
+
+    /// Returns `true` if general purpose flag #3 (data descriptor after the payload) is set
+    pub fn has_data_descriptor(&self) -> bool {
+        return self.general_purpose_flags & DATA_DESCRIPTOR_FLAG_BIT != 0;
+    }
+
+    /// Returns the size of the header (without the payload)
+    pub fn size(&self) -> u64 {
+        return LOCAL_HEADER_FIXED_SIZE
+            + self.file_name_raw.len() as u64
+            + self.extra_field.len() as u64;
+    }
+
+    /// Writes the header (without the payload) and returns the number of bytes written
+    ///
+    /// Returns `Err` (of the kind `InvalidInput`, writing nothing) if the name or the extra field exceeds 65535 bytes.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - destination
+    pub fn write<W: Write>(&self, writer: &mut W) -> std::io::Result<u64> {
+        let too_long = |e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e);
+        let file_name_length =
+            field_length(self.file_name_raw.len(), "file name").map_err(too_long)?;
+        let extra_field_length =
+            field_length(self.extra_field.len(), "extra field").map_err(too_long)?;
+        writer.write_all(&LOCAL_HEADER_SIGNATURE)?;
+        writer.write_u16::<LE>(self.version_required_to_extract)?;
+        writer.write_u16::<LE>(self.general_purpose_flags)?;
+        writer.write_u16::<LE>(self.compression_method)?;
+        writer.write_u16::<LE>(self.last_mod_time)?;
+        writer.write_u16::<LE>(self.last_mod_date)?;
+        writer.write_u32::<LE>(self.crc32)?;
+        writer.write_u32::<LE>(self.compressed_size)?;
+        writer.write_u32::<LE>(self.uncompressed_size)?;
+        writer.write_u16::<LE>(file_name_length)?;
+        writer.write_u16::<LE>(extra_field_length)?;
+        writer.write_all(&self.file_name_raw)?;
+        writer.write_all(&self.extra_field)?;
+        return Ok(self.size());
+    }
+}
+
+/// Returns the size of the data descriptor starting with `head` (12, 16, 20, or 24 bytes)
+///
+/// The signature is optional, and ZIP64 entries have 8-byte sizes.  The form whose sizes match the known ones is taken
+/// (a CRC-32 that happens to equal the signature does not fool it; 8-byte sizes are tried first for ZIP64 entries).
+/// If none matches, the signature decides and the sizes are 8 bytes only for entries with the ZIP64 extended information extra field.
+///
+/// # Arguments
+///
+/// * `head` - bytes following the payload (up to `MAX_DATA_DESCRIPTOR_SIZE` bytes)
+/// * `compressed_size` - size of the payload
+/// * `uncompressed_size` - uncompressed size of the entry
+/// * `zip64` - `true` if the local header has the ZIP64 extended information extra field
+pub(crate) fn detect_data_descriptor_size(
+    head: &[u8],
+    compressed_size: u64,
+    uncompressed_size: u64,
+    zip64: bool,
+) -> u64 {
+    let has_signature = head.starts_with(&DATA_DESCRIPTOR_SIGNATURE);
+    let forms = if zip64 {
+        ZIP64_DATA_DESCRIPTOR_FORMS
+    } else {
+        DATA_DESCRIPTOR_FORMS
+    };
+    for (signature, wide) in forms {
+        if signature && !has_signature {
+            continue;
+        }
+        let size = descriptor_size(signature, wide);
+        if head.len() < size as usize {
+            continue;
+        }
+        let (_, compressed, uncompressed) = parse_data_descriptor(head, size);
+        if compressed == compressed_size && uncompressed == uncompressed_size {
+            return size;
+        }
+    }
+    return descriptor_size(has_signature, zip64);
+}
+
+/// Returns the size of the data descriptor of the form
+///
+/// # Arguments
+///
+/// * `signature` - `true` if the descriptor starts with the signature
+/// * `wide` - `true` if the sizes are 8 bytes (ZIP64)
+fn descriptor_size(signature: bool, wide: bool) -> u64 {
+    return if signature { 4 } else { 0 } + if wide { 20 } else { 12 };
+}
+
+/// Returns (CRC-32, compressed size, uncompressed size) in the data descriptor of `size` bytes
+///
+/// # Arguments
+///
+/// * `descriptor` - data descriptor (at least `size` bytes)
+/// * `size` - size of the descriptor returned by `detect_data_descriptor_size` (12, 16, 20, or 24)
+fn parse_data_descriptor(descriptor: &[u8], size: u64) -> (u32, u64, u64) {
+    // Only the 16 & 24-byte forms have the signature
+    let start = if size == 16 || size == 24 { 4 } else { 0 };
+    let field = |offset: usize, len: usize| {
+        let mut bytes = [0u8; 8];
+        bytes[..len].copy_from_slice(&descriptor[start + offset..start + offset + len]);
+        u64::from_le_bytes(bytes)
+    };
+    let width = if size >= 20 { 8 } else { 4 };
+    return (field(0, 4) as u32, field(4, width), field(4 + width, width));
+}
+
+/// Result of resurrecting local entries missing from the central directory
+#[derive(Clone, Debug, Default)]
+pub struct OrphanRecovery {
+    /// Entries appended to the central directory
+    pub recovered: Vec<LocalHeaderInfo>,
+    /// Streamed entries left out because their data cannot be copied yet
+    pub skipped: Vec<LocalHeaderInfo>,
+}
+
+/// Entry whose offset in the central directory does not point at a local file header
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OffsetRepair {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Offset of the local header recorded in the central directory
+    pub recorded_position: u64,
+    /// Actual position of the local header (`None` if not found; the offset is left as it is)
+    pub found_position: Option<u64>,
+}
+
+impl LocalHeaderInfo {
+    /// Returns the position next to the entry (local header + data + data descriptor)
+    ///
+    /// Saturated because the ZIP64 sizes of broken archives can be anything.
+    pub fn end_position(&self) -> u64 {
+        return self
+            .data_position
+            .saturating_add(self.compressed_size)
+            .saturating_add(self.data_descriptor_size);
+    }
+
+    /// Returns `true` if general purpose flag #11 (UTF-8) is set
+    pub fn is_encoded_in_utf8(&self) -> bool {
+        return self.general_purpose_flags & 0x0800 != 0;
+    }
+
+    /// Builds a central directory record from the local file header
+    ///
+    /// Fields only in central directories (e.g. comments and attributes) are empty except the directory flag.
+    /// Returns `Err` if the position or the sizes don't fit in the non-ZIP64 format.
+    pub fn to_central_directory(&self) -> Result<ZipCDEntry, Zip64Required> {
+        return Ok(ZipCDEntry {
+            version_made_by: 0, // MS-DOS compatible
+            version_required_to_extract: self.version_required_to_extract,
+            general_purpose_flags: self.general_purpose_flags,
+            compression_method: self.compression_method,
+            last_mod_time: self.last_mod_time,
+            last_mod_date: self.last_mod_date,
+            crc32: self.crc32,
+            compressed_size: narrow(self.compressed_size, "compressed size")?,
+            uncompressed_size: narrow(self.uncompressed_size, "uncompressed size")?,
+            file_name_length: self.file_name_raw.len() as u16,
+            extra_field_length: self.extra_field.len() as u16,
+            file_comment_length: 0,
+            disk_number_start: 0,
+            internal_file_attributes: 0,
+            external_file_attributes: if self.file_name_raw.ends_with(b"/") {
+                0x0000_0010 // MS-DOS directory attribute
+            } else {
+                0
+            },
+            local_header_position: narrow(self.position, "offset of the local header")?,
+            file_name_raw: self.file_name_raw.clone(),
+            extra_field: self.extra_field.clone(),
+            file_comment: vec![],
+            starting_position_with_signature: 0,
+            starting_position_without_signature: 0,
+        });
+    }
+}
+
+/// Iterator of local file headers walking an archive from the head by signature
+///
+/// Independent of the central directory, so it finds entries missing from it (e.g. truncated archives).
+/// Stops at the first position that is not a local file header (usually the central directory).
+pub struct LocalHeaderIter<'a, R: Read + Seek> {
+    reader: &'a mut R,
+    position: u64,
+    finished: bool,
+}
+
+/// Walks an archive from the head and returns an iterator of its local file headers.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+pub fn iter_local_headers<R: Read + Seek>(reader: &mut R) -> LocalHeaderIter<'_, R> {
+    return LocalHeaderIter {
+        reader,
+        position: 0,
+        finished: false,
+    };
+}
+
+impl<R: Read + Seek> Iterator for LocalHeaderIter<'_, R> {
+    type Item = Result<LocalHeaderInfo, ZipReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let result = self.read_next();
+        match &result {
+            Ok(Some(header)) => self.position = header.end_position(),
+            _ => self.finished = true,
+        }
+        return result.transpose();
+    }
+}
+
+impl<R: Read + Seek> LocalHeaderIter<'_, R> {
+    fn read_next(&mut self) -> Result<Option<LocalHeaderInfo>, ZipReadError> {
+        self.reader.seek(SeekFrom::Start(self.position))?;
+        let mut signature = [0u8; 4];
+        if read_fully(self.reader, &mut signature)? < signature.len() {
+            return Ok(None);
+        }
+        // Markers of (single-segment) spanned archives
+        if self.position == 0
+            && (signature == DATA_DESCRIPTOR_SIGNATURE || signature == SINGLE_SEGMENT_MARKER)
+        {
+            self.position = 4;
+            return self.read_next();
+        }
+        if signature != LOCAL_HEADER_SIGNATURE {
+            return Ok(None);
+        }
+        let position = self.position;
+        let version_required_to_extract = self.reader.read_u16::<LE>()?;
+        let general_purpose_flags = self.reader.read_u16::<LE>()?;
+        let compression_method = self.reader.read_u16::<LE>()?;
+        let last_mod_time = self.reader.read_u16::<LE>()?;
+        let last_mod_date = self.reader.read_u16::<LE>()?;
+        let mut crc32 = self.reader.read_u32::<LE>()?;
+        let compressed_size_32 = self.reader.read_u32::<LE>()?;
+        let uncompressed_size_32 = self.reader.read_u32::<LE>()?;
+        let file_name_length = self.reader.read_u16::<LE>()?;
+        let extra_field_length = self.reader.read_u16::<LE>()?;
+        let mut file_name_raw = vec![0u8; file_name_length as usize];
+        self.reader.read_exact(&mut file_name_raw)?;
+        let mut extra_field = vec![0u8; extra_field_length as usize];
+        self.reader.read_exact(&mut extra_field)?;
+        let data_position = position
+            + LOCAL_HEADER_FIXED_SIZE
+            + file_name_length as u64
+            + extra_field_length as u64;
+        let mut compressed_size = zip64::local_compressed_size(compressed_size_32, &extra_field);
+        let mut uncompressed_size =
+            zip64::local_uncompressed_size(uncompressed_size_32, &extra_field);
+
+        let mut data_descriptor_size = 0;
+        let is_streamed =
+            general_purpose_flags & DATA_DESCRIPTOR_FLAG_BIT != 0 && compressed_size == 0;
+        if general_purpose_flags & DATA_DESCRIPTOR_FLAG_BIT != 0 {
+            let (data_size, descriptor_size) = if is_streamed {
+                // Streamed; the size is known only from the data descriptor
+                self.find_data_descriptor(
+                    data_position,
+                    zip64::has_zip64_extra_field(&extra_field),
+                )?
+                .ok_or(ZipReadError::InvalidZipArchive {
+                    reason: format!(
+                        "the data descriptor of the local file header at {} is not found",
+                        position
+                    ),
+                })?
+            } else {
+                self.reader
+                    .seek(SeekFrom::Start(data_position + compressed_size))?;
+                let mut head = [0u8; MAX_DATA_DESCRIPTOR_SIZE as usize];
+                let n_read = read_fully(self.reader, &mut head)?;
+                (
+                    compressed_size,
+                    detect_data_descriptor_size(
+                        &head[..n_read],
+                        compressed_size,
+                        uncompressed_size,
+                        zip64::has_zip64_extra_field(&extra_field),
+                    ),
+                )
+            };
+            data_descriptor_size = descriptor_size;
+            self.reader
+                .seek(SeekFrom::Start(data_position + data_size))?;
+            let mut descriptor = vec![0u8; descriptor_size as usize];
+            self.reader.read_exact(&mut descriptor)?;
+            (crc32, compressed_size, uncompressed_size) =
+                parse_data_descriptor(&descriptor, descriptor_size);
+        }
+        return Ok(Some(LocalHeaderInfo {
+            position,
+            version_required_to_extract,
+            general_purpose_flags,
+            compression_method,
+            last_mod_time,
+            last_mod_date,
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            file_name_raw,
+            extra_field,
+            data_position,
+            data_descriptor_size,
+            is_streamed,
+        }));
+    }
+
+    /// Searches for the data descriptor of streamed data and returns (size of the data, size of the descriptor).
+    ///
+    /// A data descriptor is recognized by the compressed size in it that matches the distance from `data_position`.
+    ///
+    /// # Arguments
+    ///
+    /// * `data_position` - position of the head of the compressed data
+    /// * `zip64` - `true` if the local header has the ZIP64 extended information extra field (8-byte sizes are preferred)
+    fn find_data_descriptor(
+        &mut self,
+        data_position: u64,
+        zip64: bool,
+    ) -> Result<Option<(u64, u64)>, ZipReadError> {
+        let forms = if zip64 {
+            ZIP64_DATA_DESCRIPTOR_FORMS
+        } else {
+            DATA_DESCRIPTOR_FORMS
+        };
+        self.reader.seek(SeekFrom::Start(data_position))?;
+        // `window[0]` is at `window_position`
+        let mut window: Vec<u8> = Vec::with_capacity(SCAN_CHUNK_SIZE * 2);
+        let mut window_position = data_position;
+        let mut chunk = vec![0u8; SCAN_CHUNK_SIZE];
+        // Index in `window` to be examined next
+        let mut i = 0;
+        loop {
+            let n_read = read_fully(self.reader, &mut chunk)?;
+            window.extend_from_slice(&chunk[..n_read]);
+            let at_end = n_read < chunk.len();
+            // A candidate needs up to 24 bytes after it (signature + ZIP64 descriptor) unless the input ends
+            while i + MAX_DATA_DESCRIPTOR_SIZE as usize <= window.len()
+                || (at_end && i < window.len())
+            {
+                let data_size = window_position + i as u64 - data_position;
+                for (signature, wide) in forms {
+                    let size = descriptor_size(signature, wide) as usize;
+                    // Descriptors without the signature are recognized by the next header following them
+                    let end = if signature { size } else { size + 4 };
+                    if i + end > window.len() {
+                        continue;
+                    }
+                    let candidate = &window[i..i + end];
+                    let matches = if signature {
+                        candidate.starts_with(&DATA_DESCRIPTOR_SIGNATURE)
+                    } else {
+                        candidate[size..] == LOCAL_HEADER_SIGNATURE
+                            || candidate[size..] == CD_SIGNATURE
+                    };
+                    if matches && parse_data_descriptor(candidate, size as u64).1 == data_size {
+                        return Ok(Some((data_size, size as u64)));
+                    }
+                }
+                i += 1;
+            }
+            if at_end {
+                return Ok(None);
+            }
+            // Keep only the tail that may contain the head of a candidate
+            window.drain(..i);
+            window_position += i as u64;
+            i = 0;
+        }
+    }
+}
+
+/// Searches `range` for the local header of the entry named `file_name_raw` and returns the one nearest to `around`
+///
+/// Candidates are found by the signature and confirmed by the file name.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+/// * `range` - area to be searched (positions of the signature)
+/// * `file_name_raw` - byte sequence of the file name in the central directory
+/// * `around` - recorded offset of the local header
+/// * `excluded` - positions of the local headers that belong to other entries
+pub(crate) fn find_local_header<R: Read + Seek>(
+    reader: &mut R,
+    range: std::ops::Range<u64>,
+    file_name_raw: &[u8],
+    around: u64,
+    excluded: &std::collections::HashSet<u64>,
+) -> Result<Option<u64>, ZipReadError> {
+    let mut found: Option<u64> = None;
+    let mut chunk = vec![0u8; SCAN_CHUNK_SIZE];
+    let mut chunk_position = range.start;
+    while chunk_position < range.end {
+        reader.seek(SeekFrom::Start(chunk_position))?;
+        let n_read = read_fully(reader, &mut chunk)?;
+        let mut candidates = vec![];
+        for i in 0..n_read.saturating_sub(LOCAL_HEADER_SIGNATURE.len() - 1) {
+            let position = chunk_position + i as u64;
+            if position < range.end
+                && chunk[i..i + LOCAL_HEADER_SIGNATURE.len()] == LOCAL_HEADER_SIGNATURE
+                && !excluded.contains(&position)
+            {
+                candidates.push(position);
+            }
+        }
+        for position in candidates {
+            if let Ok((_, name)) = read_local_header_name(reader, position) {
+                if name == file_name_raw
+                    && found.is_none_or(|best| position.abs_diff(around) < best.abs_diff(around))
+                {
+                    found = Some(position);
+                }
+            }
+        }
+        if n_read < chunk.len() {
+            break;
+        }
+        // The signature may straddle the chunks
+        chunk_position += (n_read - (LOCAL_HEADER_SIGNATURE.len() - 1)) as u64;
+    }
+    return Ok(found);
+}
+
+/// Reads the local file header at `position` and returns its (general purpose flags, file name)
+///
+/// The data are not read, so this is much cheaper than `ZipLocalFileHeader::from_central_directory`.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+/// * `position` - position of the signature of the local file header
+pub(crate) fn read_local_header_name<R: Read + Seek>(
+    reader: &mut R,
+    position: u64,
+) -> Result<(u16, Vec<u8>), ZipReadError> {
+    reader.seek(SeekFrom::Start(position))?;
+    let mut signature = [0u8; 4];
+    reader.read_exact(&mut signature)?;
+    if signature != LOCAL_HEADER_SIGNATURE {
+        return Err(ZipReadError::InvalidZipArchive {
+            reason: format!("no local file header is found at {}", position),
+        });
+    }
+    reader.seek(SeekFrom::Start(position + 6))?;
+    let general_purpose_flags = reader.read_u16::<LE>()?;
+    reader.seek(SeekFrom::Start(position + 26))?;
+    let file_name_length = reader.read_u16::<LE>()?;
+    reader.seek(SeekFrom::Start(position + LOCAL_HEADER_FIXED_SIZE))?;
+    let mut file_name_raw = vec![0u8; file_name_length as usize];
+    reader.read_exact(&mut file_name_raw)?;
+    return Ok((general_purpose_flags, file_name_raw));
+}
+
+/// Reads into `buf` as much as possible and returns the number of bytes read (less than `buf.len()` only at EOF)
+fn read_fully<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut n_read = 0;
+    while n_read < buf.len() {
+        match reader.read(&mut buf[n_read..]) {
+            Ok(0) => break,
+            Ok(n) => n_read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    return Ok(n_read);
+}
@@ -0,0 +1,122 @@
+//! Metadata of entries for frontends that show more than the file names (e.g. sizes and timestamps)
+
+use zip_structs::zip_central_directory::ZipCDEntry;
+
+use crate::encryption::{self, EntryEncryption};
+use crate::{attributes, timestamps, zip64};
+
+/// Metadata of an entry in the central directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntryMetadata {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the file name
+    pub file_name_raw: Vec<u8>,
+    /// Byte sequence of the file comment
+    pub file_comment_raw: Vec<u8>,
+    /// `true` if the file name & comment are explicitly encoded in UTF-8 (general purpose flag #11)
+    pub explicit_utf8: bool,
+    /// Size of the compressed (and possibly encrypted) payload
+    pub compressed_size: u64,
+    /// Size of the original content
+    pub uncompressed_size: u64,
+    /// Compression method (e.g. 0 = stored, 8 = deflated, 99 = AES)
+    pub compression_method: u16,
+    /// CRC-32 of the original content
+    pub crc32: u32,
+    /// MS-DOS date of the last modification (in the local time of the machine that created the archive)
+    pub last_mod_date: u16,
+    /// MS-DOS time of the last modification (in the local time of the machine that created the archive)
+    pub last_mod_time: u16,
+    /// Last modification in seconds since the Unix epoch regarding the local time as UTC (`None` if invalid)
+    pub modified_local: Option<i64>,
+    /// Last modification in seconds since the Unix epoch (UTC) from the extended timestamp extra field if any
+    pub modified_utc: Option<i64>,
+    /// General purpose bit flags
+    pub general_purpose_flags: u16,
+    /// "version made by" (the upper byte is the host system)
+    pub version_made_by: u16,
+    /// "version needed to extract"
+    pub version_required_to_extract: u16,
+    /// Internal file attributes
+    pub internal_file_attributes: u16,
+    /// External file attributes (MS-DOS attributes and Unix mode)
+    pub external_file_attributes: u32,
+    /// Number of the disk where the entry starts
+    pub disk_number_start: u16,
+    /// Offset of the local file header from the start of the archive
+    pub local_header_offset: u64,
+    /// Offset of the central directory of the entry from the start of the archive
+    pub central_directory_offset: u64,
+    /// `true` if the content is encrypted
+    pub encrypted: bool,
+    /// Encryption scheme (`None` if not encrypted)
+    pub encryption: Option<EntryEncryption>,
+    /// `true` if the entry represents a directory (see `attributes::is_directory`)
+    pub is_directory: bool,
+    /// `true` if the entry represents a symbolic link (see `attributes::is_symlink`)
+    pub is_symlink: bool,
+}
+
+impl EntryMetadata {
+    /// Collects the metadata from the central directory
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - 0-based index of the entry in the central directory
+    /// * `cd` - central directory of the entry
+    pub(crate) fn from_central_directory(index: usize, cd: &ZipCDEntry) -> Self {
+        return Self {
+            index,
+            file_name_raw: cd.file_name_raw.clone(),
+            file_comment_raw: cd.file_comment.clone(),
+            explicit_utf8: cd.is_encoded_in_utf8(),
+            compressed_size: zip64::compressed_size(cd),
+            uncompressed_size: zip64::uncompressed_size(cd),
+            compression_method: cd.compression_method,
+            crc32: cd.crc32,
+            last_mod_date: cd.last_mod_date,
+            last_mod_time: cd.last_mod_time,
+            modified_local: timestamps::dos_time_to_seconds(cd.last_mod_date, cd.last_mod_time),
+            modified_utc: timestamps::read_extended_timestamp(&cd.extra_field),
+            general_purpose_flags: cd.general_purpose_flags,
+            version_made_by: cd.version_made_by,
+            version_required_to_extract: cd.version_required_to_extract,
+            internal_file_attributes: cd.internal_file_attributes,
+            external_file_attributes: cd.external_file_attributes,
+            disk_number_start: cd.disk_number_start,
+            local_header_offset: zip64::local_header_position(cd),
+            central_directory_offset: cd.starting_position_with_signature,
+            encrypted: cd.is_encrypted_data(),
+            encryption: encryption::entry_encryption(cd),
+            is_directory: attributes::is_directory_entry(cd),
+            is_symlink: attributes::is_symlink_entry(cd),
+        };
+    }
+}
+
+/// Returns the name of a compression method (e.g. `deflate`), or `#<number>` for unknown ones
+///
+/// # Arguments
+///
+/// * `method` - compression method in the local header or the central directory
+pub fn compression_method_name(method: u16) -> String {
+    let name = match method {
+        0 => "store",
+        1 => "shrink",
+        6 => "implode",
+        8 => "deflate",
+        9 => "deflate64",
+        12 => "bzip2",
+        14 => "lzma",
+        93 => "zstd",
+        95 => "xz",
+        96 => "jpeg",
+        97 => "wavpack",
+        98 => "ppmd",
+        99 => "aes",
+        _ => return format!("#{}", method),
+    };
+    return name.to_string();
+}
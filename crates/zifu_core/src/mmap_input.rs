@@ -0,0 +1,48 @@
+//! Memory-mapped input archives (`mmap` feature)
+//!
+//! Parsing and copying read the archive through a byte slice instead of many small reads through `BufReader`,
+//! which pays off for very large archives.
+
+use std::fs::File;
+use std::io::Cursor;
+use std::path::Path;
+
+use memmap2::Mmap;
+
+use crate::InputZIPArchive;
+
+/// Reader over a memory-mapped archive
+pub type MmapReader = Cursor<Mmap>;
+
+/// Maps the file at `path` into memory and returns a reader over it
+///
+/// The file must not be truncated or modified by other processes while the reader is alive;
+/// otherwise reading it may crash the process (e.g. `SIGBUS`) or see inconsistent data.
+///
+/// # Arguments
+///
+/// * `path` - path to the file
+pub fn open_mmap<P: AsRef<Path>>(path: P) -> std::io::Result<MmapReader> {
+    let file = File::open(path)?;
+    // SAFETY: the file is opened read-only and the caller guarantees that nobody else modifies it (see above)
+    let map = unsafe { Mmap::map(&file)? };
+    return Ok(Cursor::new(map));
+}
+
+impl InputZIPArchive<MmapReader> {
+    /// Returns an initialized instance reading the archive at `path` through a memory map.
+    ///
+    /// The same restriction as `open_mmap` applies to the file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the input ZIP file
+    pub fn from_mmap<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        return Self::new(open_mmap(path)?);
+    }
+
+    /// Returns the whole archive as a byte slice
+    pub fn as_bytes(&self) -> &[u8] {
+        return self.file_handler.get_ref();
+    }
+}
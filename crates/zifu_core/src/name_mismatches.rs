@@ -0,0 +1,54 @@
+//! Detection and reconciliation of names that differ between local file headers and central directories
+
+/// Which name to use when the local file header and the central directory disagree
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NameMismatchPolicy {
+    /// Uses the name in the central directory (what most extractors show)
+    #[default]
+    PreferCentralDirectory,
+    /// Uses the name in the local file header (what streaming extractors show)
+    PreferLocalHeader,
+    /// Fails with `NameMismatchError`
+    Error,
+}
+
+/// Entry whose local file header has a different name from its central directory
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NameMismatch {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the file name in the central directory
+    pub central_directory_name_raw: Vec<u8>,
+    /// Byte sequence of the file name in the local file header
+    pub local_header_name_raw: Vec<u8>,
+}
+
+/// Error returned by `NameMismatchPolicy::Error` when some names differ between the headers
+#[derive(Clone, Debug)]
+pub struct NameMismatchError {
+    pub mismatches: Vec<NameMismatch>,
+}
+
+impl std::fmt::Display for NameMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} entries have different names in the local file headers and the central directory",
+            self.mismatches.len()
+        )?;
+        for mismatch in self.mismatches.iter() {
+            write!(
+                f,
+                "\n  #{}: {} (central directory) / {} (local file header)",
+                mismatch.index,
+                String::from_utf8_lossy(&mismatch.central_directory_name_raw),
+                String::from_utf8_lossy(&mismatch.local_header_name_raw)
+            )?;
+        }
+        return Ok(());
+    }
+}
+
+impl std::error::Error for NameMismatchError {}
@@ -0,0 +1,313 @@
+use deunicode::deunicode;
+use hfs_nfd::{compose_from_hfs_nfd, decompose_into_hfs_nfd};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::filename_decoder::IDecoder;
+use crate::origin_hints::HostSystem;
+use crate::path_length::truncate_long_path;
+use crate::path_safety::sanitize_path;
+use crate::rename_rules::{apply_rename_rules, RenameRule};
+use crate::reserved_names::{fix_reserved_names, trim_trailing_dots_and_spaces};
+use crate::target_os::TargetOS;
+use crate::top_directory::change_top_directory;
+
+/// Unicode normalization form that converted file names are normalized into
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NormalizationForm {
+    /// NFC (composes HFS+ NFD-like names); regular in Windows and Linux
+    #[default]
+    NFC,
+    /// HFS+ NFD-like decomposition used by macOS
+    NFD,
+    /// NFKC; also unifies compatibility characters (e.g. full-width alphabets & half-width katakana)
+    NFKC,
+    /// Keeps the normalization of the decoded names as it is
+    Preserve,
+}
+
+impl NormalizationForm {
+    /// Normalizes `s` into the form
+    pub fn normalize(&self, s: &str) -> String {
+        return match self {
+            NormalizationForm::NFC => compose_from_hfs_nfd(s),
+            NormalizationForm::NFD => decompose_into_hfs_nfd(&compose_from_hfs_nfd(s)),
+            NormalizationForm::NFKC => s.nfkc().collect(),
+            NormalizationForm::Preserve => s.to_string(),
+        };
+    }
+}
+
+/// Options for the conversion of file names (and comments)
+#[derive(Clone, Debug, Default)]
+pub struct ConversionOptions {
+    /// Transliterates names to pure ASCII (e.g. `テスト.txt` -> `tesuto.txt`) for receivers that cannot handle UTF-8 at all
+    pub transliterate: bool,
+    /// Rules that rewrite the names in order (e.g. `s/^New Folder\///`)
+    pub rename_rules: Vec<RenameRule>,
+    /// Unicode normalization form of the converted names (default: NFC)
+    pub normalization: NormalizationForm,
+    /// Rewrites unsafe paths (e.g. `../a.txt`, `/a.txt`, `C:\a.txt`) to safe relative ones
+    pub sanitize_paths: bool,
+    /// Appends `_` to names that cannot be extracted in Windows (e.g. `aux.txt` -> `aux_.txt`, `a.` -> `a._`)
+    pub fix_reserved_names: bool,
+    /// Trims dots and spaces at the end of the names as Windows does (e.g. `a.` -> `a`) instead of appending `_`
+    pub trim_trailing_dots: bool,
+    /// Replaces characters forbidden in the OS with `_` (e.g. `a:b.txt` -> `a_b.txt` for Windows)
+    pub substitute_forbidden_characters: Option<TargetOS>,
+    /// Rewrites the host system in "version made by" of all the entries (e.g. `HostSystem::Unix`)
+    /// because extractors interpret the external attributes differently per host system
+    pub made_by: Option<HostSystem>,
+    /// Removes the top-level directory (e.g. `"New Folder"`; see `top_directory::common_top_directory`) from the names
+    pub strip_top_directory: Option<String>,
+    /// Moves all the entries into the directory (e.g. `"docs"`)
+    pub add_top_directory: Option<String>,
+    /// Truncates names too long to be extracted, keeping the extensions (see `path_length::truncate_long_path`)
+    pub truncate_long_names: bool,
+}
+
+/// Stage of the pipeline that turns a raw file name into the one written to the output
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NameStage {
+    /// Decoded by UTF-8 (explicitly encoded) or by the legacy decoder (implicitly encoded)
+    Decoded,
+    /// Normalized into `ConversionOptions::normalization` (skipped if it is `Preserve`)
+    Normalized,
+    /// Transliterated to ASCII (only if `ConversionOptions::transliterate` is set)
+    Transliterated,
+    /// Rewritten by the rename rules (only if `ConversionOptions::rename_rules` is not empty)
+    Rewritten,
+    /// Rewritten to a safe relative path (only if `ConversionOptions::sanitize_paths` is set)
+    Sanitized,
+    /// Dots & spaces at the end of the components trimmed (only if `ConversionOptions::trim_trailing_dots` is set)
+    TrailingDotsTrimmed,
+    /// Fixed not to be reserved in Windows (only if `ConversionOptions::fix_reserved_names` is set)
+    ReservedNamesFixed,
+    /// Forbidden characters replaced (only if `ConversionOptions::substitute_forbidden_characters` is set)
+    Substituted,
+    /// Moved out of or into the top-level directory (only if `ConversionOptions::strip_top_directory` or `add_top_directory` is set)
+    TopDirectoryChanged,
+    /// Truncated to fit in the limits of the length (only if `ConversionOptions::truncate_long_names` is set)
+    Truncated,
+    /// Trailing `/` made consistent with whether the entry is a directory (only if it changes the name)
+    DirectoryNormalized,
+    /// Overridden by the rename hook (only if the hook returns a new name)
+    Renamed,
+}
+
+impl NameStage {
+    /// Returns the human-readable name of the stage
+    pub fn name(&self) -> &'static str {
+        return match self {
+            NameStage::Decoded => "decoded",
+            NameStage::Normalized => "normalized",
+            NameStage::Transliterated => "transliterated",
+            NameStage::Rewritten => "rewritten",
+            NameStage::Sanitized => "sanitized",
+            NameStage::TrailingDotsTrimmed => "trailing dots trimmed",
+            NameStage::ReservedNamesFixed => "reserved names fixed",
+            NameStage::Substituted => "substituted",
+            NameStage::TopDirectoryChanged => "top directory changed",
+            NameStage::Truncated => "truncated",
+            NameStage::DirectoryNormalized => "directory normalized",
+            NameStage::Renamed => "renamed",
+        };
+    }
+}
+
+/// Reason why the conversion of an entry loses information
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LossReason {
+    /// The file name cannot be decoded without error
+    LossyDecode,
+    /// The converted file name contains replacement characters (U+FFFD)
+    ReplacementCharacter,
+    /// The file name (or comment) is transliterated to ASCII
+    Transliteration,
+    /// Compatibility characters in the file name are unified by NFKC
+    CompatibilityNormalization,
+    /// The file comment cannot be decoded without error
+    LossyCommentDecode,
+    /// The file name (or comment) has characters that cannot be represented in the target legacy encoding
+    UnmappableCharacter,
+}
+
+impl LossReason {
+    /// Returns the human-readable description of the reason
+    pub fn description(&self) -> &'static str {
+        return match self {
+            LossReason::LossyDecode => "the file name cannot be decoded without error",
+            LossReason::ReplacementCharacter => {
+                "the file name contains replacement characters (U+FFFD)"
+            }
+            LossReason::Transliteration => "the file name is transliterated to ASCII",
+            LossReason::CompatibilityNormalization => {
+                "compatibility characters in the file name are unified by NFKC"
+            }
+            LossReason::LossyCommentDecode => "the file comment cannot be decoded without error",
+            LossReason::UnmappableCharacter => {
+                "the file name or comment has characters that cannot be represented in the target encoding"
+            }
+        };
+    }
+}
+
+/// Record of how a file name went through the pipeline
+#[derive(Clone, Debug)]
+pub struct NameTrace {
+    /// Byte sequence of the file name in the input archive
+    pub raw: Vec<u8>,
+    /// `true` if the name is explicitly encoded in UTF-8 (general purpose flag #11)
+    pub explicit_utf8: bool,
+    /// Outputs of the stages in the applied order
+    pub stages: Vec<(NameStage, String)>,
+    /// Reasons why the stages lose information (empty if the conversion is lossless)
+    pub losses: Vec<LossReason>,
+}
+
+impl NameTrace {
+    /// Returns the name after all the stages (written to the output)
+    pub fn final_name(&self) -> &str {
+        return self
+            .stages
+            .last()
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_default();
+    }
+
+    /// Returns `true` if the stage changed the name that the previous stage output
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - index of the stage in `stages`
+    pub fn is_changed_at(&self, index: usize) -> bool {
+        return match index {
+            0 => false,
+            _ => self.stages[index - 1].1 != self.stages[index].1,
+        };
+    }
+}
+
+/// Passes a file name through the pipeline and returns the record of every stage.
+///
+/// # Arguments
+///
+/// * `raw` - byte sequence of the file name
+/// * `explicit_utf8` - `true` if general purpose flag #11 is set for the file name
+/// * `legacy_decoder` - decoder for file names with implicit encoding
+/// * `options` - options that enable optional stages
+pub fn trace_file_name(
+    raw: &[u8],
+    explicit_utf8: bool,
+    legacy_decoder: &dyn IDecoder,
+    options: &ConversionOptions,
+) -> NameTrace {
+    let mut stages = Vec::with_capacity(3);
+    let mut losses = vec![];
+    let decoded = if explicit_utf8 {
+        String::from_utf8_lossy(raw).into_owned()
+    } else {
+        legacy_decoder.to_string_lossy(raw)
+    };
+    let is_lossless_decode = if explicit_utf8 {
+        std::str::from_utf8(raw).is_ok()
+    } else {
+        legacy_decoder.can_decode(raw)
+    };
+    if !is_lossless_decode {
+        losses.push(LossReason::LossyDecode);
+    }
+    stages.push((NameStage::Decoded, decoded));
+    if options.normalization != NormalizationForm::Preserve {
+        let before = &stages.last().unwrap().1;
+        let normalized = options.normalization.normalize(before);
+        // NFKC is lossy only if it does more than NFC does
+        if options.normalization == NormalizationForm::NFKC
+            && normalized != NormalizationForm::NFC.normalize(before)
+        {
+            losses.push(LossReason::CompatibilityNormalization);
+        }
+        stages.push((NameStage::Normalized, normalized));
+    }
+    if options.transliterate {
+        let transliterated = transliterate(&stages.last().unwrap().1);
+        if transliterated != stages.last().unwrap().1 {
+            losses.push(LossReason::Transliteration);
+        }
+        stages.push((NameStage::Transliterated, transliterated));
+    }
+    if !options.rename_rules.is_empty() {
+        let rewritten = apply_rename_rules(&options.rename_rules, &stages.last().unwrap().1);
+        stages.push((NameStage::Rewritten, rewritten));
+    }
+    if options.sanitize_paths {
+        let sanitized = sanitize_path(&stages.last().unwrap().1);
+        stages.push((NameStage::Sanitized, sanitized));
+    }
+    if options.trim_trailing_dots {
+        let trimmed = trim_trailing_dots_and_spaces(&stages.last().unwrap().1);
+        stages.push((NameStage::TrailingDotsTrimmed, trimmed));
+    }
+    if options.fix_reserved_names {
+        let fixed = fix_reserved_names(&stages.last().unwrap().1);
+        stages.push((NameStage::ReservedNamesFixed, fixed));
+    }
+    if let Some(target_os) = options.substitute_forbidden_characters {
+        let substituted = target_os.substitute_forbidden_characters(&stages.last().unwrap().1, '_');
+        stages.push((NameStage::Substituted, substituted));
+    }
+    if options.strip_top_directory.is_some() || options.add_top_directory.is_some() {
+        let changed = change_top_directory(
+            &stages.last().unwrap().1,
+            options.strip_top_directory.as_deref(),
+            options.add_top_directory.as_deref(),
+        );
+        stages.push((NameStage::TopDirectoryChanged, changed));
+    }
+    if options.truncate_long_names {
+        let truncated = truncate_long_path(&stages.last().unwrap().1);
+        stages.push((NameStage::Truncated, truncated));
+    }
+    if stages.last().unwrap().1.contains('\u{FFFD}') {
+        losses.push(LossReason::ReplacementCharacter);
+    }
+    return NameTrace {
+        raw: raw.to_vec(),
+        explicit_utf8,
+        stages,
+        losses,
+    };
+}
+
+/// Callback that overrides converted file names (e.g. manual corrections in a GUI)
+///
+/// Receives the byte sequence of the file name in the input archive and the converted name,
+/// and returns the name to be written instead, or `None` to keep the converted one.
+pub type RenameHook = dyn Fn(&[u8], &str) -> Option<String> + Send;
+
+/// Passes the traced file name to the rename hook and appends the `Renamed` stage if the hook overrides it
+///
+/// # Arguments
+///
+/// * `trace` - record returned by `trace_file_name`
+/// * `hook` - rename hook
+pub(crate) fn apply_rename_hook(trace: &mut NameTrace, hook: &RenameHook) {
+    let renamed = match hook(&trace.raw, trace.final_name()) {
+        Some(renamed) => renamed,
+        None => return,
+    };
+    // The hook may have fixed (or introduced) replacement characters
+    trace
+        .losses
+        .retain(|loss| *loss != LossReason::ReplacementCharacter);
+    if renamed.contains('\u{FFFD}') {
+        trace.losses.push(LossReason::ReplacementCharacter);
+    }
+    trace.stages.push((NameStage::Renamed, renamed));
+}
+
+/// Transliterates a string to pure ASCII (e.g. `テスト` -> `tesuto`)
+///
+/// Used for comments as well as for file names.
+pub fn transliterate(s: &str) -> String {
+    return deunicode(s);
+}
@@ -0,0 +1,220 @@
+//! Heuristic plausibility scores of decoded file names
+//!
+//! `decide_decoder` takes the first decoder that decodes all the names without error, but short names are often valid
+//! in many encodings (e.g. most Shift_JIS names are also valid in GBK and Big5).  The scores tell which of the results
+//! looks like real text: common scripts (kana, hanzi/kanji, hangul, alphabets) are rewarded and characters rarely seen in
+//! file names (box drawing, halfwidth katakana, C1 controls, private use, etc.) are penalized.
+//!
+//! Shift_JIS, GBK, and Big5 names are often decoded "successfully" in the others, producing ideographs nobody uses.
+//! To tell them apart, ideographs are rewarded fully only if they are in the small tables of common kanji/hanzi,
+//! and kana (especially runs of katakana) are rewarded as Japanese.  Hangul syllables (EUC-KR) are treated likewise.
+
+use std::collections::HashSet;
+
+use lazy_static::lazy_static;
+
+use crate::filename_decoder::IDecoder;
+
+/// Ideographs common in file names, regardless of the language
+static COMMON_IDEOGRAPHS: &[&str] = &[
+    // Shared by Japanese, Simplified Chinese, and Traditional Chinese
+    "一二三四五六七八九十百千人大小中上下日月年本文字名子女男山川水火木金土天生出入手目口心力正方新古分合同作品表用工明高和公司内外",
+    "真料理科研究告信部加第最多少的是不有在我他你好家地成可能自定其事所如此物原期次相式前全半今先元受取名命唱商",
+    // Japanese
+    "回図案版画像曲集歌楽映影写真旅行春夏秋冬朝夜時間週末期末試験問題答練習作業提出資料報告議事録稿原",
+    "々円駅読込売広実会議説明内予様御気動録音声届払請求見積履歴職務経帳覧検査結果発申館鉄県都市区町村書類楽譜",
+    // Simplified Chinese
+    "这们个说时来对发会学国过还没后里开关见长问现实点动题经机业电话门车书报东为图档资视频华乐网页简历设计记录头数据库软件课讲义复级项目组织务单备份专论划总结负责传统演试卷",
+    // Traditional Chinese
+    "這們個說時來對發會學國過還沒後裡開關見長問現實點動題經機業電話門車書報東為圖檔資視頻華樂網頁簡歷設計記錄頭數據庫軟體課講義複級項組織務單備專論劃總結負責傳統演試卷",
+];
+
+/// Hangul syllables common in Korean; misdecoded CJK names often produce other syllables of the 11,172
+static COMMON_HANGUL: &str = "가각간갈감강개거건걸검게겨격견결경계고곡공과관광교구국군굴권귀규그극근글금기길김나날남내너넣네년노놀누눈뉴느는늘니다단달담당대더던덕데도독동되된될두드득들등디따때또뜻라락란람랑래략량러런럼렇레려력련렬령례로록론료루류률르른를름리린림립마막만많말망매머먹메며면명모목몰무문물미민밀바박반받발밤방배백버번벌범법베변별병보복본봉부북분불비빌사산살삼상새생서석선설섬성세소속손송수숙순술쉬스슬습승시식신실심십아악안알암압앞애야약양어억언얼엄업없에여역연열영예오온올와완왕외요용우운울움원월위유육윤은을음응의이익인일임입있자작잔장재저적전절점정제조족존종좌주죽준중즈즉증지직진질집차착찰참창채책처천철첫청체초총최추축출충취측치친칙카코크키타탁탄태터테토통투트특티파판팔패퍼편평포표품프플피필하학한할함합항해행향허험혁현협형호혹화확환활황회효후훈휴흐흔희히";
+
+lazy_static! {
+    static ref COMMON_IDEOGRAPH_SET: HashSet<char> = COMMON_IDEOGRAPHS
+        .iter()
+        .flat_map(|row| row.chars())
+        .collect();
+    static ref COMMON_HANGUL_SET: HashSet<char> = COMMON_HANGUL.chars().collect();
+}
+
+/// Score of a sequence that the decoder cannot decode without error (lower than any decodable one)
+const UNDECODABLE_SCORE: f64 = -2.0;
+
+/// Score of a decoder returned by `rank_decoders`
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DecoderScore {
+    /// Index of the decoder in the array passed to `rank_decoders`
+    pub index: usize,
+    /// Name of the encoding of the decoder
+    pub encoding_name: String,
+    /// Mean score of the non-ASCII sequences (between -2 and 1.5; the higher the more plausible)
+    pub score: f64,
+    /// `true` if the decoder decodes all the sequences without error
+    pub lossless: bool,
+}
+
+/// Returns the plausibility of a non-ASCII character in file names between -1 and 1
+///
+/// # Arguments
+///
+/// * `c` - non-ASCII character
+fn char_score(c: char) -> f64 {
+    return match c {
+        // Hiragana and katakana
+        '\u{3040}'..='\u{30FF}' => 1.0,
+        // Hangul syllables
+        '\u{AC00}'..='\u{D7A3}' if COMMON_HANGUL_SET.contains(&c) => 1.0,
+        '\u{AC00}'..='\u{D7A3}' => 0.0,
+        // CJK unified ideographs; uncommon ones are typical of a name decoded in the wrong CJK encoding
+        '\u{4E00}'..='\u{9FFF}' if COMMON_IDEOGRAPH_SET.contains(&c) => 1.0,
+        '\u{4E00}'..='\u{9FFF}' => 0.25,
+        // CJK symbols & punctuation and fullwidth ASCII
+        '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF5E}' => 0.5,
+        // × & ÷ in the middle of Latin-1 letters
+        '\u{00D7}' | '\u{00F7}' => -0.5,
+        // Letters of alphabets (Latin-1, Latin Extended-A, Greek, Cyrillic, Hebrew, Arabic, and Thai)
+        '\u{00C0}'..='\u{017F}'
+        | '\u{0370}'..='\u{04FF}'
+        | '\u{0590}'..='\u{06FF}'
+        | '\u{0E00}'..='\u{0E7F}' => 0.5,
+        // Halfwidth katakana, CJK extension A, CJK compatibility ideographs, and hangul jamo
+        '\u{FF61}'..='\u{FF9F}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{1100}'..='\u{11FF}'
+        | '\u{3130}'..='\u{318F}' => -0.5,
+        // C1 controls, Latin-1 symbols, daggers & per mille etc., box drawing, block elements, private use, and replacement characters
+        '\u{0080}'..='\u{00BF}'
+        | '\u{0192}'
+        | '\u{2020}'..='\u{2022}'
+        | '\u{2030}'
+        | '\u{2500}'..='\u{259F}'
+        | '\u{E000}'..='\u{F8FF}'
+        | '\u{FFFD}' => -1.0,
+        _ => 0.0,
+    };
+}
+
+/// Returns the bonus for the extension of a file name
+///
+/// Short alphanumeric extensions (e.g. `.txt`) are rewarded; those containing non-ASCII characters are penalized
+/// because they usually mean that a multibyte character swallowed the dot or the bytes around it.
+///
+/// # Arguments
+///
+/// * `name` - decoded file name
+fn extension_score(name: &str) -> f64 {
+    let base_name = name.trim_end_matches('/').rsplit('/').next().unwrap_or("");
+    let extension = match base_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => extension,
+        _ => return 0.0,
+    };
+    if !extension.is_ascii() {
+        return -0.5;
+    }
+    if (1..=5).contains(&extension.len()) && extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return 0.25;
+    }
+    return 0.0;
+}
+
+/// Returns the bonus for kana, which appear only in Japanese names
+///
+/// Misdecoded GBK or Big5 names may contain a kana or two (e.g. halfwidth ones), but hardly ever hiragana or runs of katakana.
+///
+/// # Arguments
+///
+/// * `name` - decoded file name
+fn kana_score(name: &str) -> f64 {
+    let is_katakana = |c: char| matches!(c, '\u{30A1}'..='\u{30FA}' | '\u{30FC}');
+    let chars = name.chars().collect::<Vec<_>>();
+    if chars.iter().any(|c| matches!(c, '\u{3041}'..='\u{3096}'))
+        || chars
+            .windows(2)
+            .any(|pair| is_katakana(pair[0]) && is_katakana(pair[1]))
+    {
+        return 0.25;
+    }
+    return 0.0;
+}
+
+/// Returns the plausibility of a decoded name; the mean score of the non-ASCII characters plus the extension & kana bonuses
+///
+/// Returns 0 for ASCII-only names.
+///
+/// # Arguments
+///
+/// * `name` - decoded file name or comment
+pub fn score_name(name: &str) -> f64 {
+    let scores = name
+        .chars()
+        .filter(|c| !c.is_ascii())
+        .map(char_score)
+        .collect::<Vec<_>>();
+    if scores.is_empty() {
+        return 0.0;
+    }
+    return scores.iter().sum::<f64>() / scores.len() as f64
+        + extension_score(name)
+        + kana_score(name);
+}
+
+/// Scores `decoders` by how plausible the decoded `strings` look and returns them sorted by score (descending)
+///
+/// Ties keep the order of `decoders`, so it can be used as a drop-in replacement of `decide_decoder`
+/// by taking the first lossless one.
+///
+/// # Arguments
+///
+/// * `decoders` - encoding candidates.  The smaller the index, the higher the priority on ties
+/// * `strings` - sequences that may represent strings (e.g. file names)
+pub fn rank_decoders<T>(decoders: &[&dyn IDecoder], strings: &[T]) -> Vec<DecoderScore>
+where
+    T: AsRef<[u8]>,
+{
+    let non_ascii_strings = strings
+        .iter()
+        .map(|s| s.as_ref())
+        .filter(|s| !s.is_ascii())
+        .collect::<Vec<_>>();
+    let mut ranking = decoders
+        .iter()
+        .enumerate()
+        .map(|(index, decoder)| {
+            let mut lossless = strings
+                .iter()
+                .filter(|s| s.as_ref().is_ascii())
+                .all(|s| decoder.can_decode(s.as_ref()));
+            let total = non_ascii_strings
+                .iter()
+                .map(|s| match decoder.to_string_lossless(s) {
+                    Some(name) => score_name(&name),
+                    None => {
+                        lossless = false;
+                        UNDECODABLE_SCORE
+                    }
+                })
+                .sum::<f64>();
+            DecoderScore {
+                index,
+                encoding_name: decoder.encoding_name().to_string(),
+                score: if non_ascii_strings.is_empty() {
+                    0.0
+                } else {
+                    total / non_ascii_strings.len() as f64
+                },
+                lossless,
+            }
+        })
+        .collect::<Vec<_>>();
+    ranking.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    return ranking;
+}
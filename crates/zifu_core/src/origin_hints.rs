@@ -0,0 +1,369 @@
+use zip_structs::zip_central_directory::ZipCDEntry;
+
+/// Header ID of the NTFS extra field (modification/access/creation times in FILETIME)
+const NTFS_EXTRA_FIELD_ID: u16 = 0x000A;
+/// Header IDs of extra fields written only by Unix archivers (PKWARE Unix, Info-ZIP Unix types 1 & 2 / new Unix)
+const UNIX_EXTRA_FIELD_IDS: &[u16] = &[0x000D, 0x5855, 0x7855, 0x7875];
+/// Header ID of the extended timestamp extra field ("UT"), written by Info-ZIP and macOS
+const EXTENDED_TIMESTAMP_EXTRA_FIELD_ID: u16 = 0x5455;
+/// Prefix of the AppleDouble entries added by macOS Archive Utility
+const MACOSX_METADATA_PREFIX: &[u8] = b"__MACOSX/";
+/// Seconds between 1601-01-01 (epoch of FILETIME) and 1970-01-01 (Unix epoch)
+const FILETIME_UNIX_EPOCH_DIFF_SECONDS: i64 = 11_644_473_600;
+
+/// Host system in the upper byte of "version made by" in central directories
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum HostSystem {
+    /// MS-DOS and OS/2 (FAT / VFAT / FAT32); also used by Windows Explorer and 7-Zip
+    MSDOS,
+    /// UNIX (incl. Linux)
+    Unix,
+    /// Windows NTFS
+    NTFS,
+    /// OS X (Darwin)
+    MacOS,
+    /// Others
+    Other(u8),
+}
+
+impl HostSystem {
+    pub(crate) fn from_version_made_by(version_made_by: u16) -> Self {
+        return match version_made_by >> 8 {
+            0 => HostSystem::MSDOS,
+            3 => HostSystem::Unix,
+            10 => HostSystem::NTFS,
+            19 => HostSystem::MacOS,
+            other => HostSystem::Other(other as u8),
+        };
+    }
+
+    /// Returns the value in the upper byte of "version made by"
+    pub fn code(self) -> u8 {
+        return match self {
+            HostSystem::MSDOS => 0,
+            HostSystem::Unix => 3,
+            HostSystem::NTFS => 10,
+            HostSystem::MacOS => 19,
+            HostSystem::Other(code) => code,
+        };
+    }
+
+    /// Returns `true` if implicitly-encoded names from the host system are likely in UTF-8
+    ///
+    /// UNIX-like hosts write names in the locale charset, which is UTF-8 nowadays, and OS X writes UTF-8 (in NFD).
+    pub fn prefers_utf8(self) -> bool {
+        return matches!(self, HostSystem::Unix | HostSystem::MacOS);
+    }
+
+    /// Returns the human-readable name of the host system (e.g. `MS-DOS`)
+    pub fn name(self) -> String {
+        return match self {
+            HostSystem::MSDOS => "MS-DOS".to_string(),
+            HostSystem::Unix => "UNIX".to_string(),
+            HostSystem::NTFS => "NTFS".to_string(),
+            HostSystem::MacOS => "OS X".to_string(),
+            HostSystem::Other(code) => format!("host #{}", code),
+        };
+    }
+}
+
+/// Returns "version made by" in a human-readable form (e.g. `MS-DOS 6.3`)
+///
+/// # Arguments
+///
+/// * `version_made_by` - "version made by" of the central directory
+pub fn format_version_made_by(version_made_by: u16) -> String {
+    let version = version_made_by & 0x00ff;
+    return format!(
+        "{} {}.{}",
+        HostSystem::from_version_made_by(version_made_by).name(),
+        version / 10,
+        version % 10
+    );
+}
+
+/// Archiver that likely created the archive
+///
+/// Guessed from the typical "version made by" and extra fields of each archiver, so other archivers that happen to write
+/// the same ones are mistaken for it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CreatingTool {
+    /// 7-Zip (and p7zip); writes version 6.3
+    SevenZip,
+    /// Info-ZIP (`zip` command); writes version 3.0 with the extended timestamp extra fields
+    InfoZip,
+    /// WinRAR; writes version 3.1 on Windows
+    WinRAR,
+    /// Archive Utility (and Finder) in macOS; writes version 2.1 on UNIX and `__MACOSX/` entries
+    MacArchiveUtility,
+    /// Compressed folders in Windows Explorer; writes version 2.0 on MS-DOS without extra fields
+    WindowsExplorer,
+}
+
+impl CreatingTool {
+    /// Returns the name of the archiver
+    pub fn name(self) -> &'static str {
+        return match self {
+            CreatingTool::SevenZip => "7-Zip",
+            CreatingTool::InfoZip => "Info-ZIP",
+            CreatingTool::WinRAR => "WinRAR",
+            CreatingTool::MacArchiveUtility => "macOS Archive Utility",
+            CreatingTool::WindowsExplorer => "Windows Explorer",
+        };
+    }
+}
+
+/// Operating system that likely created the archive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreatorSystem {
+    /// Windows; file names are likely to be encoded in the ANSI/OEM code page
+    Windows,
+    /// UNIX-like OS (incl. macOS); file names are likely to be encoded in UTF-8
+    Unix,
+    /// Not enough evidence
+    Unknown,
+}
+
+/// Hints on the OS that created the archive, collected from extra fields & "version made by"
+#[derive(Clone, Debug, Default)]
+pub struct OriginHints {
+    /// Number of the entries
+    pub n_entries: usize,
+    /// Number of the entries with NTFS extra fields (0x000A)
+    pub n_ntfs_extra_fields: usize,
+    /// Number of the entries with Unix extra fields (e.g. 0x7875)
+    pub n_unix_extra_fields: usize,
+    /// Number of the entries made by MS-DOS or NTFS hosts
+    pub n_dos_hosts: usize,
+    /// Number of the entries made by UNIX or OS X hosts
+    pub n_unix_hosts: usize,
+    /// Latest modification time in the NTFS extra fields (seconds since the Unix epoch)
+    pub latest_ntfs_modification: Option<i64>,
+    /// Number of the entries with extended timestamp extra fields (0x5455)
+    pub n_extended_timestamp_fields: usize,
+    /// Number of the entries with any extra fields
+    pub n_entries_with_extra_fields: usize,
+    /// Number of the AppleDouble entries in `__MACOSX/`
+    pub n_macosx_entries: usize,
+    /// Most common "version made by" (the first one in ties; `None` if there are no entries)
+    pub version_made_by: Option<u16>,
+}
+
+impl OriginHints {
+    /// Collects hints from central directories
+    ///
+    /// # Arguments
+    ///
+    /// * `cd_entries` - central directories of the archive
+    pub(crate) fn from_central_directories(cd_entries: &[ZipCDEntry]) -> Self {
+        let mut hints = OriginHints {
+            n_entries: cd_entries.len(),
+            ..Default::default()
+        };
+        let mut version_counts: Vec<(u16, usize)> = vec![];
+        for cd in cd_entries {
+            match version_counts
+                .iter_mut()
+                .find(|(version, _)| *version == cd.version_made_by)
+            {
+                Some((_, count)) => *count += 1,
+                None => version_counts.push((cd.version_made_by, 1)),
+            }
+            if cd.file_name_raw.starts_with(MACOSX_METADATA_PREFIX) {
+                hints.n_macosx_entries += 1;
+            }
+            if !cd.extra_field.is_empty() {
+                hints.n_entries_with_extra_fields += 1;
+            }
+            match HostSystem::from_version_made_by(cd.version_made_by) {
+                HostSystem::MSDOS | HostSystem::NTFS => hints.n_dos_hosts += 1,
+                HostSystem::Unix | HostSystem::MacOS => hints.n_unix_hosts += 1,
+                HostSystem::Other(_) => {}
+            }
+            let mut has_unix_field = false;
+            let mut has_extended_timestamp = false;
+            for (id, data) in iter_extra_fields(&cd.extra_field) {
+                if id == NTFS_EXTRA_FIELD_ID {
+                    hints.n_ntfs_extra_fields += 1;
+                    if let Some(modified) = ntfs_modification_time(data) {
+                        hints.latest_ntfs_modification = Some(
+                            hints
+                                .latest_ntfs_modification
+                                .map_or(modified, |latest| latest.max(modified)),
+                        );
+                    }
+                } else if UNIX_EXTRA_FIELD_IDS.contains(&id) {
+                    has_unix_field = true;
+                } else if id == EXTENDED_TIMESTAMP_EXTRA_FIELD_ID {
+                    has_extended_timestamp = true;
+                }
+            }
+            if has_unix_field {
+                hints.n_unix_extra_fields += 1;
+            }
+            if has_extended_timestamp {
+                hints.n_extended_timestamp_fields += 1;
+            }
+        }
+        // `max_by_key` would take the last one in ties
+        hints.version_made_by = version_counts
+            .iter()
+            .fold(None, |most: Option<&(u16, usize)>, current| match most {
+                Some(most) if most.1 >= current.1 => Some(most),
+                _ => Some(current),
+            })
+            .map(|(version, _)| *version);
+        return hints;
+    }
+
+    /// Returns the OS that likely created the archive
+    ///
+    /// Extra fields are stronger evidence than "version made by" because Windows archivers often claim MS-DOS.
+    pub fn likely_creator(&self) -> CreatorSystem {
+        if self.n_ntfs_extra_fields > self.n_unix_extra_fields {
+            return CreatorSystem::Windows;
+        }
+        if self.n_unix_extra_fields > self.n_ntfs_extra_fields {
+            return CreatorSystem::Unix;
+        }
+        if self.n_dos_hosts > self.n_unix_hosts {
+            return CreatorSystem::Windows;
+        }
+        if self.n_unix_hosts > self.n_dos_hosts {
+            return CreatorSystem::Unix;
+        }
+        return CreatorSystem::Unknown;
+    }
+
+    /// Returns the host system in the most common "version made by" (`None` if the archive has no entries)
+    pub fn host_system(&self) -> Option<HostSystem> {
+        return self.version_made_by.map(HostSystem::from_version_made_by);
+    }
+
+    /// Returns the archiver that likely created the archive (`None` if unknown)
+    pub fn likely_tool(&self) -> Option<CreatingTool> {
+        let version_made_by = self.version_made_by?;
+        let host = HostSystem::from_version_made_by(version_made_by);
+        let is_windows_host = host == HostSystem::MSDOS || host == HostSystem::NTFS;
+        if self.n_macosx_entries > 0 || (host == HostSystem::Unix && version_made_by & 0xff == 21) {
+            return Some(CreatingTool::MacArchiveUtility);
+        }
+        return match version_made_by & 0xff {
+            63 => Some(CreatingTool::SevenZip),
+            30 if self.n_extended_timestamp_fields > 0 => Some(CreatingTool::InfoZip),
+            31 if is_windows_host => Some(CreatingTool::WinRAR),
+            20 if host == HostSystem::MSDOS && self.n_entries_with_extra_fields == 0 => {
+                Some(CreatingTool::WindowsExplorer)
+            }
+            _ => None,
+        };
+    }
+
+    /// Returns a summary of the archiver & OS that likely created the archive
+    /// (e.g. `created by ~7-Zip on Windows (version made by: MS-DOS 6.3)`)
+    ///
+    /// `~` marks the guesses.  Returns `None` if the archive has no entries.
+    pub fn creation_summary(&self) -> Option<String> {
+        let version_made_by = format_version_made_by(self.version_made_by?);
+        let tool = self.likely_tool();
+        let os = match (tool, self.likely_creator()) {
+            (Some(CreatingTool::MacArchiveUtility), _) => Some("macOS"),
+            (_, CreatorSystem::Windows) => Some("Windows"),
+            (_, CreatorSystem::Unix) => Some("a UNIX-like OS"),
+            (_, CreatorSystem::Unknown) => None,
+        };
+        let origin = match (tool, os) {
+            (Some(tool), Some(os)) => format!("created by ~{} on {}", tool.name(), os),
+            (Some(tool), None) => format!("created by ~{}", tool.name()),
+            (None, Some(os)) => format!("created on ~{}", os),
+            (None, None) => "created by an unknown archiver".to_string(),
+        };
+        return Some(format!("{} (version made by: {})", origin, version_made_by));
+    }
+
+    /// Returns a human-readable description of the hints (e.g. `likely created on Windows NTFS at 2021-01-01 00:00:00 UTC`)
+    ///
+    /// Returns `None` if there is not enough evidence.
+    pub fn description(&self) -> Option<String> {
+        return match self.likely_creator() {
+            CreatorSystem::Windows if self.n_ntfs_extra_fields > 0 => {
+                Some(match self.latest_ntfs_modification {
+                    Some(modified) => format!(
+                        "likely created on Windows NTFS at {}",
+                        format_unix_time_utc(modified)
+                    ),
+                    None => "likely created on Windows NTFS".to_string(),
+                })
+            }
+            CreatorSystem::Windows => Some("likely created on Windows (MS-DOS host)".to_string()),
+            CreatorSystem::Unix => Some("likely created on a UNIX-like OS".to_string()),
+            CreatorSystem::Unknown => None,
+        };
+    }
+}
+
+/// Returns an iterator of (header ID, data) in an extra field
+///
+/// Stops at a truncated record.
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+pub fn iter_extra_fields(extra_field: &[u8]) -> impl Iterator<Item = (u16, &[u8])> {
+    let mut rest = extra_field;
+    return std::iter::from_fn(move || {
+        if rest.len() < 4 {
+            return None;
+        }
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let data = rest.get(4..4 + size)?;
+        rest = &rest[4 + size..];
+        return Some((id, data));
+    });
+}
+
+/// Returns the modification time in the NTFS extra field (seconds since the Unix epoch)
+///
+/// # Arguments
+///
+/// * `data` - data of the NTFS extra field (without the header ID & size)
+fn ntfs_modification_time(data: &[u8]) -> Option<i64> {
+    // 4 bytes reserved, then attributes (tag + size + data)
+    let mut rest = data.get(4..)?;
+    while rest.len() >= 4 {
+        let tag = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let attribute = rest.get(4..4 + size)?;
+        if tag == 0x0001 && size >= 8 {
+            let mut filetime = [0u8; 8];
+            filetime.copy_from_slice(&attribute[..8]);
+            // FILETIME is in 100 ns
+            let seconds = (u64::from_le_bytes(filetime) / 10_000_000) as i64;
+            return Some(seconds - FILETIME_UNIX_EPOCH_DIFF_SECONDS);
+        }
+        rest = &rest[4 + size..];
+    }
+    return None;
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD hh:mm:ss UTC`
+///
+/// # Arguments
+///
+/// * `seconds` - seconds since the Unix epoch
+pub fn format_unix_time_utc(seconds: i64) -> String {
+    let (year, month, day) = crate::timestamps::civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    return format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    );
+}
@@ -0,0 +1,287 @@
+use crate::cancellation::CancellationToken;
+use crate::deadline::Interruption;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use std::time::Instant;
+
+/// Size of the buffer through which payloads are copied unless `OutputOptions::chunk_size` is set
+pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+
+/// Options for writing the output archive
+///
+/// Payloads are never read into memory as a whole; they are copied through a buffer of `chunk_size` bytes,
+/// so the working set while writing is bounded by the chunk size, the largest header (< 200 KiB),
+/// and the central directory (kept in memory from the start).
+#[derive(Clone, Debug, Default)]
+pub struct OutputOptions {
+    /// Computes SHA-256 of the compressed payload of each entry while copying it
+    pub hash_payloads: bool,
+    /// Gives up writing when the deadline has passed (checked between entries and between the chunks of their payloads)
+    pub deadline: Option<Instant>,
+    /// Gives up writing when the token is cancelled (checked between entries and between the chunks of their payloads)
+    pub cancellation: Option<CancellationToken>,
+    /// Size of the buffer through which payloads are copied (`None` means `DEFAULT_CHUNK_SIZE`)
+    pub chunk_size: Option<usize>,
+    /// Copies the input byte for byte if nothing is changed (see `InputZIPArchive::is_unchanged`) instead of rewriting it,
+    /// which would e.g. drop gaps between entries and add extended timestamps to the local headers.
+    /// Spanned and asynchronous output always rewrite the archive.
+    pub preserve_unchanged: bool,
+    /// Pads the local headers of stored entries so that their data begin at multiples of this (e.g. 4 for APKs;
+    /// see `alignment`).  Unchanged archives are copied as they are only if they are aligned.
+    pub alignment: Option<u16>,
+}
+
+impl OutputOptions {
+    /// Returns the size of the buffer through which payloads are copied (at least 1 byte)
+    pub fn chunk_size(&self) -> usize {
+        return self.chunk_size.unwrap_or(DEFAULT_CHUNK_SIZE).max(1);
+    }
+
+    /// Returns the deadline & cancellation token checked while writing
+    pub(crate) fn interruption(&self) -> Interruption<'_> {
+        return Interruption {
+            deadline: self.deadline,
+            cancellation: self.cancellation.as_ref(),
+        };
+    }
+}
+
+/// Record of an entry written to the output archive
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WrittenEntry {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the file name written to the output
+    pub file_name_raw: Vec<u8>,
+    /// `true` if the file name is explicitly encoded in UTF-8 in the output
+    pub explicit_utf8: bool,
+    /// Position of the local file header in the output
+    pub local_header_position: u64,
+    /// Size of the compressed (and possibly encrypted) payload
+    pub compressed_size: u64,
+    /// SHA-256 of the compressed payload (only if `OutputOptions::hash_payloads` is set)
+    pub payload_sha256: Option<[u8; 32]>,
+}
+
+impl WrittenEntry {
+    /// Returns the file name for display (decoded as UTF-8 with replacement characters)
+    pub fn file_name(&self) -> String {
+        return String::from_utf8_lossy(&self.file_name_raw).into_owned();
+    }
+
+    /// Returns SHA-256 of the payload in lowercase hexadecimal
+    pub fn payload_sha256_hex(&self) -> Option<String> {
+        return self
+            .payload_sha256
+            .map(|hash| hash.iter().map(|b| format!("{:02x}", b)).collect());
+    }
+}
+
+/// Report on the written output archive
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputReport {
+    /// Written entries in the order of the central directory
+    pub entries: Vec<WrittenEntry>,
+}
+
+/// State of the output in progress shared by the entries
+pub(crate) struct OutputState {
+    pub report: OutputReport,
+    /// Position in the whole output (across disks)
+    pub pos: u64,
+    /// Whether each entry is written (decided by the entry filter)
+    pub written: Vec<bool>,
+    pub progress: OutputProgress,
+}
+
+/// SHA-256 of a payload computed chunk by chunk (does nothing unless enabled)
+pub(crate) struct PayloadHasher(Option<Sha256>);
+
+impl PayloadHasher {
+    /// Returns a new hasher
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - `OutputOptions::hash_payloads`
+    pub fn new(enabled: bool) -> Self {
+        return Self(if enabled { Some(Sha256::new()) } else { None });
+    }
+
+    /// Feeds the next chunk of the payload
+    pub fn update(&mut self, chunk: &[u8]) {
+        if let Some(hasher) = self.0.as_mut() {
+            hasher.update(chunk);
+        }
+    }
+
+    /// Returns SHA-256 of the chunks if enabled
+    pub fn finish(self) -> Option<[u8; 32]> {
+        return self.0.map(|hasher| hasher.finalize().into());
+    }
+}
+
+/// Progress advanced chunk by chunk while a payload is copied (does nothing for the data that are not payloads)
+///
+/// Reporting each chunk lets the callback follow a single huge entry instead of waiting until it is finished.
+pub(crate) struct PayloadProgress<'a>(
+    Option<(&'a mut OutputProgress, Option<&'a ProgressCallback>)>,
+);
+
+impl<'a> PayloadProgress<'a> {
+    /// Returns the progress of a payload
+    ///
+    /// # Arguments
+    ///
+    /// * `progress` - progress of the whole output
+    /// * `callback` - progress callback called after each chunk
+    pub fn new(progress: &'a mut OutputProgress, callback: Option<&'a ProgressCallback>) -> Self {
+        return Self(Some((progress, callback)));
+    }
+
+    /// Returns the progress that ignores the copied bytes (e.g. of local headers or data descriptors)
+    pub fn none() -> Self {
+        return Self(None);
+    }
+
+    /// Adds the size of the copied chunk and reports the progress
+    pub fn advance(&mut self, n_bytes: u64) {
+        if let Some((progress, callback)) = self.0.as_mut() {
+            progress.bytes_copied += n_bytes;
+            if let Some(callback) = callback {
+                callback(progress);
+            }
+        }
+    }
+}
+
+/// Returns the error for a payload shorter than its header says
+///
+/// # Arguments
+///
+/// * `expected` - compressed size in the local header
+/// * `got` - number of bytes available
+pub(crate) fn truncated_payload_error(expected: u64, got: u64) -> std::io::Error {
+    return std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        format!(
+            "compressed size is invalid (expected from length value field: {} / got {})",
+            expected, got
+        ),
+    );
+}
+
+/// Copies `size` bytes from `reader` to `writer` through a buffer of `options.chunk_size()` bytes
+///
+/// The bytes are also fed to `hasher`, and `progress` advances after each chunk.
+/// The deadline & cancellation of `options` are checked before each chunk, so a huge payload can't hold the output.
+/// Returns `Err` (`UnexpectedEof`) if `reader` ends before `size` bytes.
+///
+/// # Arguments
+///
+/// * `reader` - input positioned at the head of the payload
+/// * `writer` - destination
+/// * `size` - number of bytes to copy
+/// * `options` - output options (chunk size, deadline, and cancellation)
+/// * `hasher` - hasher of the payload
+/// * `progress` - progress of the output
+pub(crate) fn copy_payload<R: Read, W: Write>(
+    reader: &mut R,
+    writer: &mut W,
+    size: u64,
+    options: &OutputOptions,
+    hasher: &mut PayloadHasher,
+    progress: &mut PayloadProgress,
+) -> anyhow::Result<()> {
+    let interruption = options.interruption();
+    let mut buffer = vec![0u8; (options.chunk_size() as u64).min(size).max(1) as usize];
+    let mut remaining = size;
+    while remaining > 0 {
+        interruption.check("writing the output archive")?;
+        let len = (buffer.len() as u64).min(remaining) as usize;
+        let n_read = reader.read(&mut buffer[..len])?;
+        if n_read == 0 {
+            return Err(truncated_payload_error(size, size - remaining).into());
+        }
+        writer.write_all(&buffer[..n_read])?;
+        hasher.update(&buffer[..n_read]);
+        progress.advance(n_read as u64);
+        remaining -= n_read as u64;
+    }
+    return Ok(());
+}
+
+/// Information on an entry passed to the entry filter before it is written
+#[derive(Clone, Debug)]
+pub struct EntryInfo<'a> {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Byte sequence of the (converted) file name
+    pub file_name_raw: &'a [u8],
+    /// `true` if the file name is explicitly encoded in UTF-8
+    pub explicit_utf8: bool,
+    /// Size of the compressed (and possibly encrypted) payload
+    pub compressed_size: u64,
+    /// Size of the original content
+    pub uncompressed_size: u64,
+    /// `true` if the content is encrypted
+    pub encrypted: bool,
+    /// "version made by" (the upper byte is the host system)
+    pub version_made_by: u16,
+    /// External file attributes (MS-DOS attributes and Unix mode)
+    pub external_file_attributes: u32,
+}
+
+impl EntryInfo<'_> {
+    /// Returns the file name for display (decoded as UTF-8 with replacement characters)
+    pub fn file_name(&self) -> String {
+        return String::from_utf8_lossy(self.file_name_raw).into_owned();
+    }
+
+    /// Returns `true` if the entry represents a directory (see `attributes::is_directory`)
+    pub fn is_directory(&self) -> bool {
+        return crate::attributes::is_directory(
+            self.file_name_raw,
+            self.uncompressed_size,
+            self.version_made_by,
+            self.external_file_attributes,
+        );
+    }
+
+    /// Returns `true` if the entry represents a symbolic link (see `attributes::is_symlink`)
+    pub fn is_symlink(&self) -> bool {
+        return crate::attributes::is_symlink(self.version_made_by, self.external_file_attributes);
+    }
+}
+
+/// Decision of the entry filter on an entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EntryDecision {
+    /// Writes the entry as it is
+    Keep,
+    /// Leaves the entry out of the output
+    Skip,
+    /// Writes the entry with the new name (explicitly encoded in UTF-8)
+    Rename(String),
+}
+
+/// Callback that observes each entry and decides whether (and under which name) it is written
+pub type EntryFilter = dyn Fn(&EntryInfo) -> EntryDecision + Send;
+
+/// Progress of writing the output archive passed to the progress callback
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OutputProgress {
+    /// Number of the entries processed so far (including skipped ones)
+    pub entries_done: usize,
+    /// Number of all the entries
+    pub n_entries: usize,
+    /// Size of the compressed payloads copied so far
+    pub bytes_copied: u64,
+    /// Size of all the compressed payloads (skipped entries are subtracted as they are found)
+    pub total_bytes: u64,
+}
+
+/// Callback that observes the progress of writing the output archive
+pub type ProgressCallback = dyn Fn(&OutputProgress) + Send;
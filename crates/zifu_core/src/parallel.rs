@@ -0,0 +1,64 @@
+//! Per-entry processing that runs in parallel with `rayon` if the `rayon` feature is enabled (sequentially otherwise)
+//!
+//! Decoding, normalizing, and diagnosing the names of tens of thousands of entries are independent of each other.
+//! The results keep the order of the entries either way.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+/// Returns `true` if this build processes entries in parallel
+pub fn is_enabled() -> bool {
+    return cfg!(feature = "rayon");
+}
+
+/// Applies `f` to all the items and returns the results in the same order
+///
+/// # Arguments
+///
+/// * `items` - items (e.g. central directory entries)
+/// * `f` - function applied to each item
+pub(crate) fn map<T, R, M>(items: &[T], f: M) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    M: Fn(&T) -> R + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.par_iter().map(f).collect();
+    #[cfg(not(feature = "rayon"))]
+    return items.iter().map(f).collect();
+}
+
+/// Returns `true` if `predicate` returns `true` for any of the items
+///
+/// # Arguments
+///
+/// * `items` - items (e.g. central directory entries)
+/// * `predicate` - predicate applied to each item
+pub(crate) fn any<T, P>(items: &[T], predicate: P) -> bool
+where
+    T: Sync,
+    P: Fn(&T) -> bool + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.par_iter().any(predicate);
+    #[cfg(not(feature = "rayon"))]
+    return items.iter().any(predicate);
+}
+
+/// Returns `true` if `predicate` returns `true` for all the items
+///
+/// # Arguments
+///
+/// * `items` - items (e.g. file names)
+/// * `predicate` - predicate applied to each item
+pub(crate) fn all<T, P>(items: &[T], predicate: P) -> bool
+where
+    T: Sync,
+    P: Fn(&T) -> bool + Sync + Send,
+{
+    #[cfg(feature = "rayon")]
+    return items.par_iter().all(predicate);
+    #[cfg(not(feature = "rayon"))]
+    return items.iter().all(predicate);
+}
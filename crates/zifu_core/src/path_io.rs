@@ -0,0 +1,61 @@
+//! Reading & writing archives by their paths
+//!
+//! The common case of the library (open a file, convert the names, write another file) doesn't need the plumbing of
+//! `BufReader`, `BufWriter`, flushing, and syncing at the call sites.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use byteorder::ReadBytesExt;
+
+use crate::{InputZIPArchive, OutputOptions, OutputReport};
+
+/// Reader over an archive opened by `InputZIPArchive::from_path`
+pub type FileReader = BufReader<File>;
+
+impl InputZIPArchive<FileReader> {
+    /// Returns an initialized instance reading the archive at `path` through `BufReader`.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the input ZIP file
+    pub fn from_path<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        return Self::new(BufReader::new(File::open(path)?));
+    }
+}
+
+impl<F> InputZIPArchive<F>
+where
+    F: ReadBytesExt + std::io::Seek,
+{
+    /// Writes the archive to a new file at `path` and returns the report on the written entries.
+    ///
+    /// The file is flushed and synced to the storage before this returns.
+    /// On failure, the partially written file is removed (an existing file at `path` is lost anyway because it is truncated first).
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - path to the output ZIP file
+    /// * `options` - output options (e.g. hashing of payloads)
+    pub fn write_to_path<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: &OutputOptions,
+    ) -> anyhow::Result<OutputReport> {
+        let path = path.as_ref();
+        let mut writer = BufWriter::new(File::create(path)?);
+        let result = self
+            .output_archive_with_options(&mut writer, options)
+            .and_then(|report| {
+                writer.flush()?;
+                writer.get_ref().sync_all()?;
+                return Ok(report);
+            });
+        if result.is_err() {
+            drop(writer);
+            let _ = std::fs::remove_file(path);
+        }
+        return result;
+    }
+}
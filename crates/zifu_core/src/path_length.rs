@@ -0,0 +1,139 @@
+//! Detection and truncation of names too long to be extracted (e.g. in Windows by default)
+//!
+//! Converting names from 2-byte legacy encodings to UTF-8 can triple their byte lengths,
+//! so names that were extracted fine can exceed the limits of file systems after the conversion.
+
+/// Maximum length of a path component in bytes (most file systems on Linux & macOS)
+pub const MAX_COMPONENT_BYTES: usize = 255;
+/// Maximum length of a path in UTF-16 code units (`MAX_PATH` in Windows without long path support)
+pub const MAX_PATH_UTF16_UNITS: usize = 260;
+
+/// Reason why a name is too long
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PathLengthReason {
+    /// A component of the name exceeds `MAX_COMPONENT_BYTES` bytes in UTF-8
+    ComponentTooLong,
+    /// The name exceeds `MAX_PATH_UTF16_UNITS` characters (UTF-16 code units)
+    PathTooLong,
+}
+
+impl PathLengthReason {
+    /// Returns the human-readable description of the reason
+    pub fn description(&self) -> &'static str {
+        return match self {
+            PathLengthReason::ComponentTooLong => "a component of the name exceeds 255 bytes",
+            PathLengthReason::PathTooLong => {
+                "the name exceeds 260 characters (the limit in Windows by default)"
+            }
+        };
+    }
+}
+
+/// Entry whose converted name is too long
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LongPath {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Converted name of the entry
+    pub name: String,
+    pub reasons: Vec<PathLengthReason>,
+}
+
+/// Returns the length of `s` in UTF-16 code units
+fn utf16_len(s: &str) -> usize {
+    return s.chars().map(char::len_utf16).sum();
+}
+
+/// Returns the reasons why the name is too long (empty if it is not)
+///
+/// The path of the extraction directory is not counted, so names close to the limit can still fail.
+///
+/// # Arguments
+///
+/// * `path` - converted name of the entry
+pub fn find_path_length_reasons(path: &str) -> Vec<PathLengthReason> {
+    let mut reasons = vec![];
+    if path
+        .split('/')
+        .any(|component| component.len() > MAX_COMPONENT_BYTES)
+    {
+        reasons.push(PathLengthReason::ComponentTooLong);
+    }
+    if utf16_len(path) > MAX_PATH_UTF16_UNITS {
+        reasons.push(PathLengthReason::PathTooLong);
+    }
+    return reasons;
+}
+
+/// Returns the entries whose converted names are too long
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive (`InputZIPArchive::plan_conversion_with_options`)
+pub fn find_long_paths(plan: &[crate::RenamePlanEntry]) -> Vec<LongPath> {
+    return plan
+        .iter()
+        .filter_map(|entry| {
+            let reasons = find_path_length_reasons(&entry.proposed_name);
+            if reasons.is_empty() {
+                return None;
+            }
+            return Some(LongPath {
+                index: entry.index,
+                name: entry.proposed_name.clone(),
+                reasons,
+            });
+        })
+        .collect();
+}
+
+/// Shortens the stem of `component` keeping its extension (e.g. `.txt`) until it fits in `max_bytes` bytes and `max_units`
+/// UTF-16 code units
+///
+/// The stem keeps at least one character, so the result can still exceed the limits.
+fn truncate_component(component: &str, max_bytes: usize, max_units: usize) -> String {
+    // Extensions longer than 16 bytes are more likely parts of the names
+    let (stem, extension) = match component.rfind('.') {
+        Some(i) if i > 0 && component.len() - i <= 16 => component.split_at(i),
+        _ => (component, ""),
+    };
+    let mut stem: Vec<char> = stem.chars().collect();
+    while stem.len() > 1
+        && (stem.iter().map(|c| c.len_utf8()).sum::<usize>() + extension.len() > max_bytes
+            || stem.iter().map(|c| c.len_utf16()).sum::<usize>() + utf16_len(extension) > max_units)
+    {
+        stem.pop();
+    }
+    return stem.into_iter().collect::<String>() + extension;
+}
+
+/// Truncates the components of the name to fit in the limits, keeping their extensions (e.g. `.txt`)
+///
+/// Every component is cut down to `MAX_COMPONENT_BYTES` bytes, and then the last one is shortened further
+/// if the whole name exceeds `MAX_PATH_UTF16_UNITS` characters.  Names within the limits are returned as they are.
+///
+/// # Arguments
+///
+/// * `path` - converted name of the entry
+pub fn truncate_long_path(path: &str) -> String {
+    let mut components: Vec<String> = path
+        .split('/')
+        .map(|component| {
+            if component.len() <= MAX_COMPONENT_BYTES {
+                return component.to_string();
+            }
+            return truncate_component(component, MAX_COMPONENT_BYTES, usize::MAX);
+        })
+        .collect();
+    let total_units = utf16_len(&components.join("/"));
+    if total_units > MAX_PATH_UTF16_UNITS {
+        // The trailing empty component of directories (`dir/`) cannot be shortened
+        if let Some(last) = components.iter_mut().rev().find(|c| !c.is_empty()) {
+            let max_units = utf16_len(last).saturating_sub(total_units - MAX_PATH_UTF16_UNITS);
+            *last = truncate_component(last, MAX_COMPONENT_BYTES, max_units);
+        }
+    }
+    return components.join("/");
+}
@@ -0,0 +1,112 @@
+//! Detection and sanitization of entry names that can escape the extraction directory (e.g. `../a`, `/a`, `C:\a`)
+
+/// Reason why an entry name is unsafe to extract
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UnsafePathReason {
+    /// The name has `..` components (e.g. `../a.txt`)
+    ParentDirectory,
+    /// The name starts with a separator (e.g. `/etc/passwd`)
+    Absolute,
+    /// The name starts with a drive letter (e.g. `C:\a.txt`)
+    DriveLetter,
+}
+
+impl UnsafePathReason {
+    /// Returns the human-readable description of the reason
+    pub fn description(&self) -> &'static str {
+        return match self {
+            UnsafePathReason::ParentDirectory => "the path has `..` components",
+            UnsafePathReason::Absolute => "the path is absolute",
+            UnsafePathReason::DriveLetter => "the path starts with a drive letter",
+        };
+    }
+}
+
+/// Entry whose name is unsafe to extract
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UnsafePath {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Decoded name of the entry
+    pub name: String,
+    pub reasons: Vec<UnsafePathReason>,
+}
+
+/// Returns `true` if `c` separates path components (`\` is a separator in Windows)
+fn is_separator(c: char) -> bool {
+    return c == '/' || c == '\\';
+}
+
+/// Returns `true` if the path starts with a drive letter (e.g. `C:`)
+fn has_drive_letter(path: &str) -> bool {
+    let bytes = path.as_bytes();
+    return bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+}
+
+/// Returns the reasons why the path is unsafe to extract (empty if safe)
+///
+/// # Arguments
+///
+/// * `path` - decoded name of the entry
+pub fn find_unsafe_path_reasons(path: &str) -> Vec<UnsafePathReason> {
+    let mut reasons = vec![];
+    if path.split(is_separator).any(|component| component == "..") {
+        reasons.push(UnsafePathReason::ParentDirectory);
+    }
+    if path.starts_with(is_separator) {
+        reasons.push(UnsafePathReason::Absolute);
+    }
+    if has_drive_letter(path) {
+        reasons.push(UnsafePathReason::DriveLetter);
+    }
+    return reasons;
+}
+
+/// Returns `true` if the raw (undecoded) name is unsafe to extract
+///
+/// Only `/` is treated as a separator because `\` (0x5C) can be a part of a multibyte character (e.g. `表` in Shift_JIS).
+///
+/// # Arguments
+///
+/// * `raw` - byte sequence of the name
+pub fn is_unsafe_raw_path(raw: &[u8]) -> bool {
+    return raw.starts_with(b"/")
+        || (raw.len() >= 2 && raw[0].is_ascii_alphabetic() && raw[1] == b':')
+        || raw
+            .split(|&b| b == b'/')
+            .any(|component| component == b"..");
+}
+
+/// Rewrites the path to a safe relative one (e.g. `/a/../b` -> `a/b`, `C:\a\b` -> `a/b`)
+///
+/// Drive letters, leading separators, and `.` & `..` components are removed, and `\` is replaced with `/`.
+/// The trailing `/` of directories is kept.  Safe paths are returned as they are.
+///
+/// # Arguments
+///
+/// * `path` - decoded name of the entry
+pub fn sanitize_path(path: &str) -> String {
+    if find_unsafe_path_reasons(path).is_empty() {
+        return path.to_string();
+    }
+    let without_drive = if has_drive_letter(path) {
+        &path[2..]
+    } else {
+        path
+    };
+    let components: Vec<&str> = without_drive
+        .split(is_separator)
+        .filter(|component| !component.is_empty() && *component != "." && *component != "..")
+        .collect();
+    if components.is_empty() {
+        return "_".to_string();
+    }
+    let is_directory = path.ends_with(is_separator);
+    return format!(
+        "{}{}",
+        components.join("/"),
+        if is_directory { "/" } else { "" }
+    );
+}
@@ -0,0 +1,180 @@
+//! User-defined rules that rewrite decoded file names (e.g. `s/^New Folder\///`)
+//!
+//! The rules are applied in order after the normalization & transliteration and before the sanitization,
+//! so they see the names as the receivers will and cannot reintroduce unsafe paths.
+
+use regex::{NoExpand, Regex};
+
+/// Rule that replaces the parts of a file name that match a regular expression
+#[derive(Clone, Debug)]
+pub struct RenameRule {
+    /// Pattern to search for
+    pattern: Regex,
+    /// Replacement (`$1`, `${name}`, and `$$` are expanded unless `literal` is set)
+    replacement: String,
+    /// `true` if the replacement is inserted as it is
+    literal: bool,
+    /// `true` if all the matches are replaced; only the first one otherwise
+    global: bool,
+}
+
+/// Error returned when a rename rule cannot be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RenameRuleError {
+    /// The expression is not like `s/old/new/` (or has unknown flags)
+    Syntax { expression: String },
+    /// The pattern is not a valid regular expression
+    Pattern { pattern: String, message: String },
+}
+
+impl std::fmt::Display for RenameRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return match self {
+            RenameRuleError::Syntax { expression } => write!(
+                f,
+                "`{}` is not a substitution like s/old/new/ (flags: g, i)",
+                expression
+            ),
+            RenameRuleError::Pattern { pattern, message } => {
+                write!(
+                    f,
+                    "`{}` is not a valid regular expression: {}",
+                    pattern, message
+                )
+            }
+        };
+    }
+}
+
+impl std::error::Error for RenameRuleError {}
+
+/// Splits the body of a substitution by `delimiter` (`\` + `delimiter` stands for the delimiter itself)
+///
+/// # Arguments
+///
+/// * `body` - substitution without the leading `s`
+/// * `delimiter` - the first character of `body`
+fn split_substitution(body: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut().unwrap().push(next),
+                Some(next) => {
+                    parts.last_mut().unwrap().push(c);
+                    parts.last_mut().unwrap().push(next);
+                }
+                None => parts.last_mut().unwrap().push(c),
+            }
+        } else if c == delimiter {
+            parts.push(String::new());
+        } else {
+            parts.last_mut().unwrap().push(c);
+        }
+    }
+    return parts;
+}
+
+impl RenameRule {
+    /// Returns a rule that replaces the first match of `pattern` with `replacement`
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - regular expression (syntax of the `regex` crate)
+    /// * `replacement` - replacement (`$1` & `${name}` refer to the groups)
+    pub fn new(pattern: &str, replacement: &str) -> Result<Self, RenameRuleError> {
+        return Ok(Self {
+            pattern: Regex::new(pattern).map_err(|e| RenameRuleError::Pattern {
+                pattern: pattern.to_string(),
+                message: e.to_string(),
+            })?,
+            replacement: replacement.to_string(),
+            literal: false,
+            global: false,
+        });
+    }
+
+    /// Returns a rule that replaces all the occurrences of `from` with `to` without any special characters
+    ///
+    /// # Arguments
+    ///
+    /// * `from` - string to search for
+    /// * `to` - string to be inserted
+    pub fn literal(from: &str, to: &str) -> Self {
+        return Self {
+            pattern: Regex::new(&regex::escape(from)).unwrap(),
+            replacement: to.to_string(),
+            literal: true,
+            global: true,
+        };
+    }
+
+    /// Parses a sed-like substitution such as `s/old/new/`, `s/old/new/g`, or `s|a/b|c|i`
+    ///
+    /// Any character after `s` can be the delimiter.  The flags are `g` (replaces all the matches) and `i` (ignores case).
+    ///
+    /// # Arguments
+    ///
+    /// * `expression` - substitution
+    pub fn parse(expression: &str) -> Result<Self, RenameRuleError> {
+        let syntax_error = || RenameRuleError::Syntax {
+            expression: expression.to_string(),
+        };
+        let body = expression.strip_prefix('s').ok_or_else(syntax_error)?;
+        let delimiter = body.chars().next().ok_or_else(syntax_error)?;
+        if delimiter.is_alphanumeric() || delimiter == '\\' || delimiter.is_whitespace() {
+            return Err(syntax_error());
+        }
+        let parts = split_substitution(&body[delimiter.len_utf8()..], delimiter);
+        let (pattern, replacement, flags) = match parts.as_slice() {
+            [pattern, replacement, flags] => (pattern, replacement, flags),
+            _ => return Err(syntax_error()),
+        };
+        let mut global = false;
+        let mut ignore_case = false;
+        for flag in flags.chars() {
+            match flag {
+                'g' => global = true,
+                'i' => ignore_case = true,
+                _ => return Err(syntax_error()),
+            }
+        }
+        let mut rule = if ignore_case {
+            Self::new(&format!("(?i:{})", pattern), replacement)?
+        } else {
+            Self::new(pattern, replacement)?
+        };
+        rule.global = global;
+        return Ok(rule);
+    }
+
+    /// Returns `name` rewritten by the rule
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - decoded file name
+    pub fn apply(&self, name: &str) -> String {
+        let limit = if self.global { 0 } else { 1 };
+        let rewritten = if self.literal {
+            self.pattern
+                .replacen(name, limit, NoExpand(&self.replacement))
+        } else {
+            self.pattern
+                .replacen(name, limit, self.replacement.as_str())
+        };
+        return rewritten.into_owned();
+    }
+}
+
+/// Returns `name` rewritten by all the rules in order
+///
+/// # Arguments
+///
+/// * `rules` - rules to be applied
+/// * `name` - decoded file name
+pub fn apply_rename_rules(rules: &[RenameRule], name: &str) -> String {
+    return rules
+        .iter()
+        .fold(name.to_string(), |name, rule| rule.apply(&name));
+}
@@ -0,0 +1,109 @@
+//! Recomputation of CRC-32 & uncompressed sizes by decompressing the entries (`InputZIPArchive::repair_metadata`)
+//!
+//! Streamed archives often have zeros (or garbage) in these fields and keep the real values only in the data descriptors.
+//! The recomputed values are written to the central directory and to the local headers, and the data descriptors are
+//! dropped, so the output describes itself without relying on them.
+
+use std::io::Read;
+
+use flate2::read::DeflateDecoder;
+
+use crate::deadline::Interruption;
+
+/// Compression method "stored" (no compression)
+const STORED_METHOD: u16 = 0;
+/// Compression method "deflated"
+const DEFLATED_METHOD: u16 = 8;
+/// Size of the buffer through which decompressed data are read
+const DECOMPRESSION_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Entry whose metadata were changed by `InputZIPArchive::repair_metadata`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EntryRepair {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// CRC-32 in the central directory before & after the repair (`None` if it was correct)
+    pub crc32: Option<(u32, u32)>,
+    /// Uncompressed size in the central directory before & after the repair (`None` if it was correct)
+    pub uncompressed_size: Option<(u64, u64)>,
+    /// `true` if the data descriptor is dropped (general purpose flag #3 is cleared)
+    pub data_descriptor_dropped: bool,
+}
+
+/// Entry left untouched by `InputZIPArchive::repair_metadata`
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SkippedRepair {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Why the entry can't be repaired (e.g. `encrypted`)
+    pub reason: String,
+}
+
+/// Result of `InputZIPArchive::repair_metadata`
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RepairReport {
+    /// Entries whose metadata were changed
+    pub repaired: Vec<EntryRepair>,
+    /// Entries that could not be decompressed (encrypted, unsupported compression methods, broken data, etc.)
+    pub skipped: Vec<SkippedRepair>,
+}
+
+/// Returns a reader of the decompressed data of the payload
+///
+/// Returns `None` if the compression method is not supported (only stored and deflated entries are).
+///
+/// # Arguments
+///
+/// * `payload` - compressed payload (limited to its size)
+/// * `compression_method` - compression method of the entry
+pub(crate) fn decompressing_reader<'a, R: Read + 'a>(
+    payload: R,
+    compression_method: u16,
+) -> Option<Box<dyn Read + 'a>> {
+    return match compression_method {
+        STORED_METHOD => Some(Box::new(payload)),
+        DEFLATED_METHOD => Some(Box::new(DeflateDecoder::new(payload))),
+        _ => None,
+    };
+}
+
+/// Decompresses the payload and returns (CRC-32, size) of the decompressed data
+///
+/// Returns `Ok(Ok(None))` if the compression method is not supported (only stored and deflated entries are),
+/// `Ok(Err)` if the payload can't be read or decompressed, and `Err` if `interruption` stops it (checked before each chunk).
+///
+/// # Arguments
+///
+/// * `payload` - compressed payload (limited to its size)
+/// * `compression_method` - compression method of the entry
+/// * `interruption` - deadline & cancellation of the operation
+/// * `during` - operation in progress (e.g. `"repairing the metadata"`)
+pub(crate) fn decompressed_crc32_and_size<R: Read>(
+    payload: R,
+    compression_method: u16,
+    interruption: Interruption,
+    during: &'static str,
+) -> anyhow::Result<std::io::Result<Option<(u32, u64)>>> {
+    let mut reader = match decompressing_reader(payload, compression_method) {
+        Some(reader) => reader,
+        None => return Ok(Ok(None)),
+    };
+    let mut hasher = crc32fast::Hasher::new();
+    let mut size = 0;
+    let mut chunk = vec![0u8; DECOMPRESSION_CHUNK_SIZE];
+    loop {
+        interruption.check(during)?;
+        let n_read = match reader.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Ok(Err(e)),
+        };
+        hasher.update(&chunk[..n_read]);
+        size += n_read as u64;
+    }
+    return Ok(Ok(Some((hasher.finalize(), size))));
+}
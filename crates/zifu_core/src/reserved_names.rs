@@ -0,0 +1,127 @@
+//! Detection and fixing of names that cannot be extracted as they are in Windows (e.g. `CON`, `aux.txt`, `a.`)
+
+/// Device names reserved in Windows regardless of the extension (compared case-insensitively)
+const DEVICE_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Reason why a path component cannot be extracted as it is in Windows
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ReservedNameReason {
+    /// The component is a device name (e.g. `CON`, `aux.txt`)
+    DeviceName,
+    /// The component ends with dots or spaces, which Windows strips (e.g. `a.`, `b `)
+    TrailingDotOrSpace,
+}
+
+impl ReservedNameReason {
+    /// Returns the human-readable description of the reason
+    pub fn description(&self) -> &'static str {
+        return match self {
+            ReservedNameReason::DeviceName => "the name is reserved for a device in Windows",
+            ReservedNameReason::TrailingDotOrSpace => {
+                "the name ends with a dot or a space, which Windows strips"
+            }
+        };
+    }
+}
+
+/// Entry whose name cannot be extracted as it is in Windows
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReservedName {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Decoded name of the entry
+    pub name: String,
+    pub reasons: Vec<ReservedNameReason>,
+}
+
+/// Returns `true` if the component is a device name with or without extensions (e.g. `con`, `AUX.tar.gz`)
+fn is_device_name(component: &str) -> bool {
+    let stem = component.split('.').next().unwrap_or_default();
+    // Windows ignores spaces before the extension (e.g. `CON .txt`)
+    let stem = stem.trim_end_matches(' ');
+    return DEVICE_NAMES
+        .iter()
+        .any(|device_name| device_name.eq_ignore_ascii_case(stem));
+}
+
+/// Returns `true` if the component ends with a dot or a space (`.` and `..` are excluded)
+fn has_trailing_dot_or_space(component: &str) -> bool {
+    return component != "."
+        && component != ".."
+        && (component.ends_with('.') || component.ends_with(' '));
+}
+
+/// Returns the reasons why the path cannot be extracted as it is in Windows (empty if it can)
+///
+/// Every component of the path is checked.  The name may be decoded lossily
+/// because only ASCII characters matter.
+///
+/// # Arguments
+///
+/// * `path` - decoded name of the entry
+pub fn find_reserved_name_reasons(path: &str) -> Vec<ReservedNameReason> {
+    let mut reasons = vec![];
+    let components: Vec<&str> = path.split('/').filter(|c| !c.is_empty()).collect();
+    if components.iter().any(|c| is_device_name(c)) {
+        reasons.push(ReservedNameReason::DeviceName);
+    }
+    if components.iter().any(|c| has_trailing_dot_or_space(c)) {
+        reasons.push(ReservedNameReason::TrailingDotOrSpace);
+    }
+    return reasons;
+}
+
+/// Trims dots and spaces at the end of each component of the path as Windows does (e.g. `a./b ` -> `a/b`)
+///
+/// Components that consist only of dots and spaces (except `.` and `..`) become `_`.
+///
+/// # Arguments
+///
+/// * `path` - decoded name of the entry
+pub fn trim_trailing_dots_and_spaces(path: &str) -> String {
+    return path
+        .split('/')
+        .map(|component| {
+            if !has_trailing_dot_or_space(component) {
+                return component;
+            }
+            return match component.trim_end_matches(['.', ' ']) {
+                "" => "_",
+                trimmed => trimmed,
+            };
+        })
+        .collect::<Vec<&str>>()
+        .join("/");
+}
+
+/// Appends `_` to the problematic components of the path (e.g. `con/aux.txt` -> `con_/aux_.txt`, `a.` -> `a._`)
+///
+/// Names that can be extracted in Windows are returned as they are.
+///
+/// # Arguments
+///
+/// * `path` - decoded name of the entry
+pub fn fix_reserved_names(path: &str) -> String {
+    return path
+        .split('/')
+        .map(|component| {
+            if has_trailing_dot_or_space(component) {
+                return format!("{}_", component);
+            }
+            if is_device_name(component) {
+                // Before the extension so that the file type is kept (e.g. `aux.txt` -> `aux_.txt`)
+                return match component.find('.') {
+                    Some(i) => format!("{}_{}", &component[..i], &component[i..]),
+                    None => format!("{}_", component),
+                };
+            }
+            return component.to_string();
+        })
+        .collect::<Vec<String>>()
+        .join("/");
+}
@@ -0,0 +1,183 @@
+//! Choice between the Windows (CP932) and the strict JIS X 0208 mappings of Shift_JIS
+//!
+//! `encoding_rs::SHIFT_JIS` follows the WHATWG Encoding Standard, which is Windows-31J (CP932):
+//! it has the NEC & IBM extensions and maps e.g. 0x8160 to U+FF5E (fullwidth tilde).
+//! macOS and Unix tools follow JIS X 0208 instead and map it to U+301C (wave dash).
+
+use crate::filename_decoder::IDecoder;
+use crate::filename_encoder::IEncoder;
+
+/// Name of the strict JIS X 0208 variant (accepted by `from_encoding_name` case-insensitively)
+pub const STRICT_SHIFT_JIS_NAME: &str = "Shift_JIS-strict";
+
+/// Characters mapped differently (JIS X 0208, Windows-31J)
+const JIS_WINDOWS_PAIRS: &[(char, char)] = &[
+    ('\u{301C}', '\u{FF5E}'), // 0x8160 wave dash / fullwidth tilde
+    ('\u{2016}', '\u{2225}'), // 0x8161 double vertical line / parallel to
+    ('\u{2212}', '\u{FF0D}'), // 0x817C minus sign / fullwidth hyphen-minus
+    ('\u{00A2}', '\u{FFE0}'), // 0x8191 cent sign
+    ('\u{00A3}', '\u{FFE1}'), // 0x8192 pound sign
+    ('\u{00AC}', '\u{FFE2}'), // 0x81CA not sign
+];
+
+/// Mapping of Shift_JIS
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ShiftJISMapping {
+    /// Windows-31J (CP932) used by Windows Explorer and most archivers in Japan, with the NEC & IBM extensions
+    #[default]
+    Windows31J,
+    /// JIS X 0208 only (e.g. 0x8160 is U+301C), used by macOS and Unix tools; the vendor extensions are invalid
+    JIS,
+}
+
+/// Returns `true` if the lead byte starts a double-byte character
+fn is_lead_byte(byte: u8) -> bool {
+    return matches!(byte, 0x81..=0x9F | 0xE0..=0xFC);
+}
+
+/// Returns `true` if the lead byte belongs to rows outside JIS X 0208
+/// (NEC special characters, NEC-selected IBM extensions, user-defined area, and IBM extensions)
+fn is_vendor_lead_byte(byte: u8) -> bool {
+    return matches!(byte, 0x87 | 0xED..=0xFC);
+}
+
+/// Returns `true` if the byte sequence has no double-byte characters outside JIS X 0208
+///
+/// # Arguments
+///
+/// * `input` - byte sequence in Shift_JIS
+fn has_only_jis_x0208_characters(input: &[u8]) -> bool {
+    let mut i = 0;
+    while i < input.len() {
+        if is_lead_byte(input[i]) {
+            if is_vendor_lead_byte(input[i]) {
+                return false;
+            }
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    return true;
+}
+
+/// Replaces the characters of one mapping with those of the other (see `JIS_WINDOWS_PAIRS`)
+///
+/// # Arguments
+///
+/// * `input` - string to be converted
+/// * `to_jis` - `true` to convert Windows-31J characters into JIS X 0208 ones
+fn swap_mapping(input: &str, to_jis: bool) -> String {
+    return input
+        .chars()
+        .map(|c| {
+            JIS_WINDOWS_PAIRS
+                .iter()
+                .find(|(jis, windows)| c == if to_jis { *windows } else { *jis })
+                .map_or(c, |(jis, windows)| if to_jis { *jis } else { *windows })
+        })
+        .collect();
+}
+
+/// Shift_JIS decoder with the strict JIS X 0208 mapping
+struct StrictShiftJISDecoder {}
+
+/// Shift_JIS encoder with the strict JIS X 0208 mapping
+struct StrictShiftJISEncoder {}
+
+impl IDecoder for StrictShiftJISDecoder {
+    fn to_string_lossless(&self, input: &[u8]) -> Option<String> {
+        if !has_only_jis_x0208_characters(input) {
+            return None;
+        }
+        let (result, _, met_invalid_char) = encoding_rs::SHIFT_JIS.decode(input);
+        if met_invalid_char {
+            return None;
+        }
+        return Some(swap_mapping(&result, true));
+    }
+    fn to_string_lossy(&self, input: &[u8]) -> String {
+        if let Some(result) = self.to_string_lossless(input) {
+            return result;
+        }
+        // Decode character by character so that the vendor extensions become replacement characters
+        let mut result = String::new();
+        let mut i = 0;
+        while i < input.len() {
+            let length = if is_lead_byte(input[i]) { 2 } else { 1 };
+            let end = (i + length).min(input.len());
+            match self.to_string_lossless(&input[i..end]) {
+                Some(decoded) => result.push_str(&decoded),
+                None => result.push('\u{FFFD}'),
+            }
+            i = end;
+        }
+        return result;
+    }
+    fn encoding_name(&self) -> &str {
+        return STRICT_SHIFT_JIS_NAME;
+    }
+}
+
+impl IEncoder for StrictShiftJISEncoder {
+    fn to_bytes_lossless(&self, input: &str) -> Option<Vec<u8>> {
+        // The Windows-31J characters are not in JIS X 0208
+        if input
+            .chars()
+            .any(|c| JIS_WINDOWS_PAIRS.iter().any(|(_, windows)| c == *windows))
+        {
+            return None;
+        }
+        let input = swap_mapping(input, false);
+        let (result, _, met_unmappable_char) = encoding_rs::SHIFT_JIS.encode(&input);
+        if met_unmappable_char || !has_only_jis_x0208_characters(&result) {
+            return None;
+        }
+        return Some(result.into_owned());
+    }
+    fn encoding_name(&self) -> &str {
+        return STRICT_SHIFT_JIS_NAME;
+    }
+}
+
+/// Returns `true` if `name` is a name of the strict variant (e.g. `sjis-strict`)
+///
+/// # Arguments
+///
+/// * `name` - encoding name
+pub(crate) fn is_strict_shift_jis_name(name: &str) -> bool {
+    return ["shift_jis-strict", "sjis-strict"].contains(&name.to_lowercase().as_str());
+}
+
+impl dyn IDecoder {
+    /// Returns a Shift_JIS decoder with the mapping
+    ///
+    /// # Arguments
+    ///
+    /// * `mapping` - Windows-31J (same as `from_encoding_name("sjis")`) or strict JIS X 0208
+    pub fn shift_jis(mapping: ShiftJISMapping) -> Box<dyn IDecoder> {
+        return match mapping {
+            ShiftJISMapping::Windows31J => {
+                Box::new(crate::filename_decoder::LegacyEncodingDecoder {
+                    decoder: encoding_rs::SHIFT_JIS,
+                })
+            }
+            ShiftJISMapping::JIS => Box::new(StrictShiftJISDecoder {}),
+        };
+    }
+}
+
+impl dyn IEncoder {
+    /// Returns a Shift_JIS encoder with the mapping
+    ///
+    /// # Arguments
+    ///
+    /// * `mapping` - Windows-31J (same as `from_encoding_name("sjis")`) or strict JIS X 0208
+    pub fn shift_jis(mapping: ShiftJISMapping) -> Box<dyn IEncoder> {
+        return match mapping {
+            ShiftJISMapping::Windows31J => <dyn IEncoder>::from_encoding_name("shift_jis").unwrap(),
+            ShiftJISMapping::JIS => Box::new(StrictShiftJISEncoder {}),
+        };
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::local_headers::DATA_DESCRIPTOR_SIGNATURE;
+
+/// Destination of the output archive that knows where each byte lands
+pub trait ArchiveSink: Write {
+    /// Returns the 0-based disk number and the offset in the disk of the next byte
+    fn position(&self) -> (u16, u64);
+    /// Moves on to the next disk if `len` bytes do not fit in the current one
+    ///
+    /// Called before each header so that headers are never split across disks.
+    ///
+    /// # Arguments
+    ///
+    /// * `len` - size of the header to be written next
+    fn keep_together(&mut self, len: u64) -> std::io::Result<()>;
+}
+
+/// Single-disk sink over any writer (writers can't get the current position, so it is counted here)
+pub(crate) struct CountingSink<'a, W: Write> {
+    inner: &'a mut W,
+    position: u64,
+}
+
+impl<'a, W: Write> CountingSink<'a, W> {
+    pub(crate) fn new(inner: &'a mut W) -> Self {
+        return Self { inner, position: 0 };
+    }
+}
+
+impl<W: Write> Write for CountingSink<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n_written = self.inner.write(buf)?;
+        self.position += n_written as u64;
+        return Ok(n_written);
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+impl<W: Write + Seek> CountingSink<'_, W> {
+    /// Overwrites the bytes written at `position` (counted from the head of the output) and returns to the end
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - position of the bytes to be overwritten
+    /// * `bytes` - new bytes (must not go past the end)
+    pub(crate) fn overwrite_at(&mut self, position: u64, bytes: &[u8]) -> std::io::Result<()> {
+        let end = self.inner.stream_position()?;
+        let head = end - self.position;
+        self.inner.seek(SeekFrom::Start(head + position))?;
+        self.inner.write_all(bytes)?;
+        self.inner.seek(SeekFrom::Start(end))?;
+        return Ok(());
+    }
+}
+
+impl<W: Write> ArchiveSink for CountingSink<'_, W> {
+    fn position(&self) -> (u16, u64) {
+        return (0, self.position);
+    }
+    fn keep_together(&mut self, _len: u64) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+/// Sink that splits the archive into disks (volumes) of at most `span_size` bytes
+///
+/// The first disk starts with the spanning signature (`PK\x07\x08`).
+pub struct SpannedWriter<W: Write, F: FnMut(u16) -> std::io::Result<W>> {
+    open_disk: F,
+    current: W,
+    disk: u16,
+    offset: u64,
+    span_size: u64,
+}
+
+impl<W: Write, F: FnMut(u16) -> std::io::Result<W>> SpannedWriter<W, F> {
+    /// Opens the first disk and writes the spanning signature.
+    ///
+    /// # Arguments
+    ///
+    /// * `span_size` - maximum size of each disk
+    /// * `open_disk` - callback that creates the disk of the given 0-based number
+    pub fn new(span_size: u64, mut open_disk: F) -> std::io::Result<Self> {
+        let current = open_disk(0)?;
+        let mut writer = Self {
+            open_disk,
+            current,
+            disk: 0,
+            offset: 0,
+            span_size,
+        };
+        writer.write_all(&DATA_DESCRIPTOR_SIGNATURE)?;
+        return Ok(writer);
+    }
+
+    /// Returns the number of disks opened so far
+    pub fn n_disks(&self) -> u16 {
+        return self.disk + 1;
+    }
+
+    /// Flushes the last disk and returns the number of disks
+    pub fn finish(mut self) -> std::io::Result<u16> {
+        self.current.flush()?;
+        return Ok(self.n_disks());
+    }
+
+    fn next_disk(&mut self) -> std::io::Result<()> {
+        self.current.flush()?;
+        let disk = self
+            .disk
+            .checked_add(1)
+            .ok_or_else(|| std::io::Error::other("too many disks"))?;
+        self.current = (self.open_disk)(disk)?;
+        self.disk = disk;
+        self.offset = 0;
+        return Ok(());
+    }
+}
+
+impl<W: Write, F: FnMut(u16) -> std::io::Result<W>> Write for SpannedWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        // Open the next disk lazily not to leave an empty disk at the end
+        if self.offset >= self.span_size {
+            self.next_disk()?;
+        }
+        let len = buf.len().min((self.span_size - self.offset) as usize);
+        let n_written = self.current.write(&buf[..len])?;
+        self.offset += n_written as u64;
+        return Ok(n_written);
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.current.flush();
+    }
+}
+
+impl<W: Write, F: FnMut(u16) -> std::io::Result<W>> ArchiveSink for SpannedWriter<W, F> {
+    fn position(&self) -> (u16, u64) {
+        if self.offset >= self.span_size {
+            return (self.disk.saturating_add(1), 0);
+        }
+        return (self.disk, self.offset);
+    }
+    fn keep_together(&mut self, len: u64) -> std::io::Result<()> {
+        if self.offset > 0 && self.offset + len > self.span_size {
+            self.next_disk()?;
+        }
+        return Ok(());
+    }
+}
@@ -0,0 +1,93 @@
+//! Validation of converted names against characters forbidden in the OS of the receiver
+
+/// OS where the converted archive is extracted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TargetOS {
+    /// Forbids `<>:"\|?*` and control characters (U+0000–U+001F)
+    Windows,
+    /// Forbids `:` (shown as `/` in Finder) and NUL
+    MacOS,
+    /// Forbids NUL only
+    Linux,
+}
+
+impl TargetOS {
+    /// Returns `true` if the character cannot be used in a path component in the OS (`/` is a separator in every OS)
+    pub fn forbids(&self, c: char) -> bool {
+        return match self {
+            TargetOS::Windows => {
+                (c.is_ascii_control() && c != '\u{7F}')
+                    || matches!(c, '<' | '>' | ':' | '"' | '\\' | '|' | '?' | '*')
+            }
+            TargetOS::MacOS => c == ':' || c == '\0',
+            TargetOS::Linux => c == '\0',
+        };
+    }
+
+    /// Returns the characters in the path forbidden in the OS without duplicates in the order of appearance
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - converted name of the entry
+    pub fn find_forbidden_characters(&self, path: &str) -> Vec<char> {
+        let mut characters = vec![];
+        for c in path.chars().filter(|&c| self.forbids(c)) {
+            if !characters.contains(&c) {
+                characters.push(c);
+            }
+        }
+        return characters;
+    }
+
+    /// Replaces the characters forbidden in the OS with `replacement` (e.g. `a:b?.txt` -> `a_b_.txt`)
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - converted name of the entry
+    /// * `replacement` - character substituted for the forbidden ones
+    pub fn substitute_forbidden_characters(&self, path: &str, replacement: char) -> String {
+        return path
+            .chars()
+            .map(|c| if self.forbids(c) { replacement } else { c })
+            .collect();
+    }
+}
+
+/// Entry whose converted name has characters forbidden in the target OS
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IllegalName {
+    /// 0-based index of the entry in the central directory
+    pub index: usize,
+    /// Converted name of the entry
+    pub name: String,
+    /// Forbidden characters in the name
+    pub characters: Vec<char>,
+}
+
+/// Returns the entries whose converted names have characters forbidden in the target OS
+///
+/// # Arguments
+///
+/// * `plan` - rename plan of the archive (`InputZIPArchive::plan_conversion_with_options`)
+/// * `target_os` - OS where the archive is extracted
+pub fn find_illegal_names(
+    plan: &[crate::RenamePlanEntry],
+    target_os: TargetOS,
+) -> Vec<IllegalName> {
+    return plan
+        .iter()
+        .filter_map(|entry| {
+            let characters = target_os.find_forbidden_characters(&entry.proposed_name);
+            if characters.is_empty() {
+                return None;
+            }
+            return Some(IllegalName {
+                index: entry.index,
+                name: entry.proposed_name.clone(),
+                characters,
+            });
+        })
+        .collect();
+}
@@ -0,0 +1,175 @@
+//! Correction of MS-DOS timestamps, which are in the local time of the machine that created the archive
+
+use crate::extra_fields;
+
+/// Header ID of the extended timestamp extra field ("UT"; modification time etc. in UTC)
+pub const EXTENDED_TIMESTAMP_EXTRA_FIELD_ID: u16 = 0x5455;
+/// Flag of the extended timestamp extra field indicating that the modification time is present
+const MODIFICATION_TIME_FLAG: u8 = 0x01;
+/// Latest time that MS-DOS timestamps can represent (2107-12-31 23:59:58) in seconds since the Unix epoch
+const DOS_TIME_MAX: i64 = 4_354_819_198;
+/// Earliest time that MS-DOS timestamps can represent (1980-01-01 00:00:00) in seconds since the Unix epoch
+const DOS_TIME_MIN: i64 = 315_532_800;
+
+/// How to correct the timestamps of the entries (see `InputZIPArchive::correct_timestamps`)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimestampCorrection {
+    /// UTC offset of the machine that created the archive in seconds (e.g. `9 * 3600` for JST)
+    pub source_utc_offset: i32,
+    /// Rewrites the MS-DOS timestamps into the local time of this UTC offset in seconds (e.g. that of the receivers)
+    pub target_utc_offset: Option<i32>,
+    /// Adds extended timestamp extra fields (0x5455) with the modification times in UTC to the entries without them
+    pub add_extended_timestamps: bool,
+}
+
+/// Returns days since the Unix epoch of the civil date (Howard Hinnant's algorithm)
+pub(crate) fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    return era * 146_097 + doe - 719_468;
+}
+
+/// Returns the civil date (year, month, day) of days since the Unix epoch (Howard Hinnant's algorithm)
+pub(crate) fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    return (year, month, day);
+}
+
+/// Returns the MS-DOS timestamp as seconds since the Unix epoch regarding its local time as UTC
+///
+/// Returns `None` if the date is invalid (e.g. month 0 written by some archivers for unknown times).
+///
+/// # Arguments
+///
+/// * `date` - MS-DOS date (`last_mod_date`)
+/// * `time` - MS-DOS time (`last_mod_time`)
+pub fn dos_time_to_seconds(date: u16, time: u16) -> Option<i64> {
+    let (year, month, day) = (
+        1980 + (date >> 9) as i64,
+        ((date >> 5) & 0x0f) as i64,
+        (date & 0x1f) as i64,
+    );
+    let (hour, minute, second) = (
+        (time >> 11) as i64,
+        ((time >> 5) & 0x3f) as i64,
+        (time & 0x1f) as i64 * 2,
+    );
+    if !(1..=12).contains(&month) || day == 0 || hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    return Some(days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second);
+}
+
+/// Formats seconds since the Unix epoch as `YYYY-MM-DD hh:mm` without the time zone (e.g. MS-DOS times in unknown local time)
+///
+/// # Arguments
+///
+/// * `seconds` - seconds since the Unix epoch (e.g. `dos_time_to_seconds`)
+pub fn format_local_minutes(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    return format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60
+    );
+}
+
+/// Returns the MS-DOS timestamp (date, time) of seconds since the Unix epoch
+///
+/// Times out of the range of MS-DOS timestamps (1980-2107) are clamped, and odd seconds are rounded down.
+///
+/// # Arguments
+///
+/// * `seconds` - seconds since the Unix epoch (in the local time to be stored)
+pub fn seconds_to_dos_time(seconds: i64) -> (u16, u16) {
+    let seconds = seconds.clamp(DOS_TIME_MIN, DOS_TIME_MAX);
+    let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+    let time = seconds.rem_euclid(86400);
+    let date = (((year - 1980) << 9) | (month << 5) | day) as u16;
+    let time = (((time / 3600) << 11) | ((time % 3600 / 60) << 5) | (time % 60 / 2)) as u16;
+    return (date, time);
+}
+
+/// Returns the whole extended timestamp extra field record (including the header ID & size) with only the modification time
+///
+/// The same record is valid for both local file headers and central directories.
+///
+/// # Arguments
+///
+/// * `modification_time` - modification time in seconds since the Unix epoch (UTC)
+pub fn extended_timestamp_extra_field(modification_time: i64) -> Vec<u8> {
+    let mut data = Vec::with_capacity(5);
+    data.push(MODIFICATION_TIME_FLAG);
+    data.extend_from_slice(&(modification_time as u32).to_le_bytes());
+    // 5 bytes always fit in a record
+    return extra_fields::build_record(EXTENDED_TIMESTAMP_EXTRA_FIELD_ID, &data).unwrap();
+}
+
+/// Returns the modification time in the extended timestamp extra field in seconds since the Unix epoch (UTC)
+///
+/// Returns `None` if the extra fields have no such record or it lacks the modification time.
+///
+/// # Arguments
+///
+/// * `extra_field` - extra fields of the central directory or the local file header
+pub fn read_extended_timestamp(extra_field: &[u8]) -> Option<i64> {
+    let record = extra_fields::find_record(extra_field, EXTENDED_TIMESTAMP_EXTRA_FIELD_ID)?;
+    let data = &record[4..];
+    if data.len() < 5 || data[0] & MODIFICATION_TIME_FLAG == 0 {
+        return None;
+    }
+    return Some(u32::from_le_bytes([data[1], data[2], data[3], data[4]]) as i64);
+}
+
+/// Parses a UTC offset (e.g. `+09:00`, `-0530`, `+9`, or `Z`) and returns it in seconds
+///
+/// # Arguments
+///
+/// * `offset` - UTC offset
+pub fn parse_utc_offset(offset: &str) -> Option<i32> {
+    if offset == "Z" || offset == "z" {
+        return Some(0);
+    }
+    let (sign, rest) = match offset.as_bytes().first()? {
+        b'+' => (1, &offset[1..]),
+        b'-' => (-1, &offset[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = match rest.split_once(':') {
+        Some((hours, minutes)) => (hours, minutes),
+        None if rest.len() == 4 => rest.split_at(2),
+        None => (rest, "0"),
+    };
+    if !(1..=2).contains(&hours.len())
+        || minutes.len() > 2
+        || !hours
+            .bytes()
+            .chain(minutes.bytes())
+            .all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    if hours > 14 || minutes > 59 {
+        return None;
+    }
+    return Some(sign * (hours * 3600 + minutes * 60));
+}
@@ -0,0 +1,49 @@
+//! Stripping and adding the top-level directory that wraps all the entries
+//!
+//! Archives often wrap everything in a folder that the receivers don't want (e.g. `New Folder/`), or put everything at the
+//! root so that extracting them scatters files in the current directory.  Both are fixed by rewriting the head of each name.
+
+/// Returns the top-level directory (without `/`) that all the names are in, or `None` if there is no such directory
+///
+/// The names of the directory itself (e.g. `top/`) don't count as entries outside the directory.
+///
+/// # Arguments
+///
+/// * `names` - converted names of all the entries
+pub fn common_top_directory<'a, I: IntoIterator<Item = &'a str>>(names: I) -> Option<String> {
+    let mut top: Option<&str> = None;
+    for name in names {
+        let head = match name.split_once('/') {
+            Some((head, _)) if !head.is_empty() => head,
+            _ => return None,
+        };
+        match top {
+            Some(top) if top != head => return None,
+            _ => top = Some(head),
+        }
+    }
+    return top.map(str::to_string);
+}
+
+/// Returns `name` moved out of `strip` and/or into `add`
+///
+/// The directory entry of `strip` itself becomes empty unless `add` is given (it is left out of the output).
+///
+/// # Arguments
+///
+/// * `name` - converted name of an entry
+/// * `strip` - top-level directory (without `/`) to be removed from the head of the name
+/// * `add` - directory (without the trailing `/`) to be prepended to the name
+pub fn change_top_directory(name: &str, strip: Option<&str>, add: Option<&str>) -> String {
+    let stripped = match strip {
+        Some(strip) => match name.strip_prefix(strip) {
+            Some(rest) if rest.starts_with('/') => &rest[1..],
+            _ => name,
+        },
+        None => name,
+    };
+    return match add {
+        Some(add) => format!("{}/{}", add.trim_end_matches('/'), stripped),
+        None => stripped.to_string(),
+    };
+}
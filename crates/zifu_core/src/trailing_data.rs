@@ -0,0 +1,142 @@
+//! End of central directory records followed by junk
+//!
+//! `ZipEOCD::from_reader` accepts an EOCD only if its comment ends exactly at the end of the file, so an archive with data
+//! appended after the comment (e.g. by broken proxies or download managers) is rejected as if it had no EOCD at all.
+//! `read_eocd` falls back to the last EOCD in the tail whose comment fits in the file and that points at a central directory
+//! before it.  The bytes after its comment are reported as trailing data; they are never written to the output.
+
+use byteorder::{ReadBytesExt, LE};
+use std::io::{Read, Seek, SeekFrom};
+use zip_structs::{zip_eocd::ZipEOCD, zip_error::ZipReadError};
+
+/// Maximum size of trailing data after the EOCD & its comment that `read_eocd` accepts
+pub const TRAILING_DATA_SEARCH_LIMIT: u64 = 64 * 1024;
+/// Signature of the EOCD
+const EOCD_SIGNATURE: [u8; 4] = [b'P', b'K', 0x05, 0x06];
+/// Signature of the central directory entries
+const CD_ENTRY_SIGNATURE: [u8; 4] = [b'P', b'K', 0x01, 0x02];
+/// Signature of the ZIP64 EOCD locator
+const ZIP64_EOCD_LOCATOR_SIGNATURE: [u8; 4] = [b'P', b'K', 0x06, 0x07];
+/// Size of the ZIP64 EOCD locator
+const ZIP64_EOCD_LOCATOR_SIZE: u64 = 20;
+/// Size of the EOCD without the comment
+const EOCD_FIXED_SIZE: usize = 22;
+
+/// Parses the EOCD at the head of `bytes` if its comment fits in them
+///
+/// # Arguments
+///
+/// * `bytes` - bytes from the EOCD signature to the end of the file
+/// * `position` - position of the EOCD signature in the file
+fn parse_eocd(mut bytes: &[u8], position: u64) -> Option<ZipEOCD> {
+    let available_comment_length = bytes.len().checked_sub(EOCD_FIXED_SIZE)?;
+    bytes = &bytes[EOCD_SIGNATURE.len()..];
+    let eocd_disk_index = bytes.read_u16::<LE>().ok()?;
+    let cd_start_disk_index = bytes.read_u16::<LE>().ok()?;
+    let n_cd_entries_in_disk = bytes.read_u16::<LE>().ok()?;
+    let n_cd_entries = bytes.read_u16::<LE>().ok()?;
+    let cd_size = bytes.read_u32::<LE>().ok()?;
+    let cd_starting_position = bytes.read_u32::<LE>().ok()?;
+    let comment_length = bytes.read_u16::<LE>().ok()?;
+    if comment_length as usize > available_comment_length {
+        return None;
+    }
+    return Some(ZipEOCD {
+        eocd_disk_index,
+        cd_start_disk_index,
+        n_cd_entries_in_disk,
+        n_cd_entries,
+        cd_size,
+        cd_starting_position,
+        comment_length,
+        comment: bytes[..comment_length as usize].to_vec(),
+        starting_position_with_signature: position,
+        starting_position_without_signature: position + EOCD_SIGNATURE.len() as u64,
+    });
+}
+
+/// Returns `true` if the signature at `position` is `signature`
+fn has_signature_at<R: ReadBytesExt + Seek>(
+    reader: &mut R,
+    position: u64,
+    signature: &[u8; 4],
+) -> std::io::Result<bool> {
+    reader.seek(SeekFrom::Start(position))?;
+    let mut actual = [0u8; 4];
+    return match reader.read_exact(&mut actual) {
+        Ok(()) => Ok(&actual == signature),
+        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(e) => Err(e),
+    };
+}
+
+/// Returns `true` if the EOCD points at a central directory that ends before it (or at a ZIP64 EOCD locator just before it)
+///
+/// Rules out signatures that appear in the trailing data or in the comments by chance.
+fn points_at_central_directory<R: ReadBytesExt + Seek>(
+    reader: &mut R,
+    eocd: &ZipEOCD,
+) -> std::io::Result<bool> {
+    if eocd.is_zip64() {
+        return match eocd
+            .starting_position_with_signature
+            .checked_sub(ZIP64_EOCD_LOCATOR_SIZE)
+        {
+            Some(position) => has_signature_at(reader, position, &ZIP64_EOCD_LOCATOR_SIGNATURE),
+            None => Ok(false),
+        };
+    }
+    let cd_end = eocd.cd_starting_position as u64 + eocd.cd_size as u64;
+    if cd_end > eocd.starting_position_with_signature {
+        return Ok(false);
+    }
+    if eocd.n_cd_entries == 0 {
+        return Ok(eocd.cd_size == 0);
+    }
+    return has_signature_at(
+        reader,
+        eocd.cd_starting_position as u64,
+        &CD_ENTRY_SIGNATURE,
+    );
+}
+
+/// Reads the EOCD, accepting up to `TRAILING_DATA_SEARCH_LIMIT` bytes of junk after its comment
+///
+/// Returns the EOCD and the size of the trailing data (0 for well-formed archives).
+/// If no EOCD is found even with the junk, the error of `ZipEOCD::from_reader` is returned.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+pub(crate) fn read_eocd<R: ReadBytesExt + Seek>(
+    reader: &mut R,
+) -> Result<(ZipEOCD, u64), ZipReadError> {
+    let strict_error = match ZipEOCD::from_reader(reader) {
+        Ok(eocd) => return Ok((eocd, 0)),
+        Err(e) => e,
+    };
+    let archive_len = reader.seek(SeekFrom::End(0))?;
+    let window_start = archive_len
+        .saturating_sub(EOCD_FIXED_SIZE as u64 + u16::MAX as u64 + TRAILING_DATA_SEARCH_LIMIT);
+    reader.seek(SeekFrom::Start(window_start))?;
+    let mut window = vec![];
+    Read::take(&mut *reader, archive_len - window_start).read_to_end(&mut window)?;
+    // The last candidate is the most likely one; junk rarely contains a consistent EOCD
+    for offset in (0..window.len().saturating_sub(EOCD_FIXED_SIZE - 1)).rev() {
+        if window[offset..offset + EOCD_SIGNATURE.len()] != EOCD_SIGNATURE {
+            continue;
+        }
+        let eocd = match parse_eocd(&window[offset..], window_start + offset as u64) {
+            Some(eocd) => eocd,
+            None => continue,
+        };
+        if !points_at_central_directory(reader, &eocd)? {
+            continue;
+        }
+        let end_of_comment = eocd.starting_position_with_signature
+            + EOCD_FIXED_SIZE as u64
+            + eocd.comment_length as u64;
+        return Ok((eocd, archive_len - end_of_comment));
+    }
+    return Err(strict_error);
+}
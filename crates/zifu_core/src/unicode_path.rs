@@ -0,0 +1,45 @@
+//! Info-ZIP Unicode Path extra field (0x7075), which carries the UTF-8 name next to a legacy-encoded one
+
+use crate::extra_fields;
+use crate::origin_hints::iter_extra_fields;
+use crate::FieldTooLongError;
+
+/// Header ID of the Info-ZIP Unicode Path extra field
+pub const UNICODE_PATH_EXTRA_FIELD_ID: u16 = 0x7075;
+/// Version of the Unicode Path extra field (only 1 is defined)
+const UNICODE_PATH_VERSION: u8 = 1;
+
+/// Returns the whole Unicode Path extra field record (including the header ID & size)
+///
+/// Returns `Err(FieldTooLongError)` if the name is too long for a record (more than 65530 bytes).
+///
+/// # Arguments
+///
+/// * `name_raw` - byte sequence of the (legacy-encoded) file name in the header; its CRC-32 is stored
+/// * `name` - name to be stored in UTF-8
+pub fn unicode_path_extra_field(name_raw: &[u8], name: &str) -> Result<Vec<u8>, FieldTooLongError> {
+    let mut data = Vec::with_capacity(1 + 4 + name.len());
+    data.push(UNICODE_PATH_VERSION);
+    data.extend_from_slice(&crc32fast::hash(name_raw).to_le_bytes());
+    data.extend_from_slice(name.as_bytes());
+    return extra_fields::build_record(UNICODE_PATH_EXTRA_FIELD_ID, &data);
+}
+
+/// Returns the UTF-8 name in the Unicode Path extra field
+///
+/// Returns `None` if there is no such field, or if it is stale (the CRC-32 does not match `name_raw`, e.g. renamed by another tool).
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+/// * `name_raw` - byte sequence of the file name in the header
+pub fn read_unicode_path(extra_field: &[u8], name_raw: &[u8]) -> Option<String> {
+    let (_, data) = iter_extra_fields(extra_field)
+        .find(|(id, data)| *id == UNICODE_PATH_EXTRA_FIELD_ID && data.len() >= 5)?;
+    if data[0] != UNICODE_PATH_VERSION
+        || u32::from_le_bytes([data[1], data[2], data[3], data[4]]) != crc32fast::hash(name_raw)
+    {
+        return None;
+    }
+    return String::from_utf8(data[5..].to_vec()).ok();
+}
@@ -0,0 +1,375 @@
+use crate::cancellation::CancellationToken;
+use crate::deadline::Interruption;
+use crate::feature_gate::{FeatureGate, Operation};
+use crate::local_headers::LocalFileHeader;
+use crate::output::{self, OutputReport};
+use crate::{repair, zip64};
+use byteorder::ReadBytesExt;
+use std::time::Instant;
+use zip_structs::{
+    zip_central_directory::{ZipCDEntry, UTF8_FLAG_BIT},
+    zip_eocd::ZipEOCD,
+    zip_error::ZipReadError,
+};
+
+/// Operation reported by `DeadlineExceeded` & `Cancelled` from the verification
+const VERIFYING: &str = "verifying the written archive";
+
+/// Size of the fixed-length part of a central directory entry (including the signature)
+pub(crate) const CD_FIXED_SIZE: u64 = 46;
+/// Size of the fixed-length part of the end of central directory (including the signature)
+pub(crate) const EOCD_FIXED_SIZE: u64 = 22;
+
+/// Error that represents problems found in the structure of a ZIP archive
+#[derive(Clone, Debug)]
+pub struct VerificationError {
+    /// Human-readable descriptions of the problems
+    pub problems: Vec<String>,
+}
+
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the archive has {} structural problems",
+            self.problems.len()
+        )?;
+        for problem in self.problems.iter() {
+            write!(f, "\n  - {}", problem)?;
+        }
+        return Ok(());
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// Reads the central directory entries the EOCD points at
+///
+/// Same as `ZipCDEntry::all_from_eocd`, which overflows (and panics in debug builds) if the entries run past the EOCD.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+/// * `eocd` - EOCD of the archive
+pub fn read_central_directory<R: ReadBytesExt + std::io::Seek>(
+    reader: &mut R,
+    eocd: &ZipEOCD,
+) -> Result<Vec<ZipCDEntry>, ZipReadError> {
+    reader.seek(std::io::SeekFrom::Start(eocd.cd_starting_position as u64))?;
+    let mut cd_entries = vec![];
+    for _ in 0..eocd.n_cd_entries {
+        cd_entries.push(ZipCDEntry::read_and_generate_from_signature(reader)?);
+    }
+    let end_pos = reader.stream_position()?;
+    if end_pos != eocd.starting_position_with_signature {
+        return Err(ZipReadError::UnsupportedZipArchive {
+            reason: format!(
+                "the central directory ends at {}, not at the end of central directory ({})",
+                end_pos, eocd.starting_position_with_signature
+            ),
+        });
+    }
+    return Ok(cd_entries);
+}
+
+/// Re-parses a ZIP archive strictly and cross-checks its structure.
+///
+/// Use this for archives just written by `InputZIPArchive` to catch writer bugs before they are shipped.
+/// In addition to parsing, the following are checked:
+///
+/// - entry counts in the EOCD match the number of central directory entries
+/// - the central directory size in the EOCD matches the actual size
+/// - local header offsets are monotonically increasing and point at valid local headers before the central directory
+/// - file names and UTF-8 flags in local headers match those in the central directory
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+pub fn verify_archive_structure<R: ReadBytesExt + std::io::Seek>(
+    reader: &mut R,
+) -> Result<(), VerificationError> {
+    return check_structure(reader).map(|_| ());
+}
+
+/// Body of `verify_archive_structure` that returns the central directory entries on success
+fn check_structure<R: ReadBytesExt + std::io::Seek>(
+    reader: &mut R,
+) -> Result<Vec<ZipCDEntry>, VerificationError> {
+    let single = |problem: String| VerificationError {
+        problems: vec![problem],
+    };
+    let eocd = ZipEOCD::from_reader(reader).map_err(|e| single(e.to_string()))?;
+    // Only archives zifu can write are verified
+    FeatureGate::from_structures(&eocd, &[])
+        .check(Operation::Fix)
+        .map_err(|e| single(e.to_string()))?;
+    let cd_entries = read_central_directory(reader, &eocd).map_err(|e| single(e.to_string()))?;
+
+    let mut problems = vec![];
+    if eocd.n_cd_entries as usize != cd_entries.len()
+        || eocd.n_cd_entries_in_disk as usize != cd_entries.len()
+    {
+        problems.push(format!(
+            "entry counts in the EOCD ({} / {} in the disk) do not match the number of central directory entries ({})",
+            eocd.n_cd_entries,
+            eocd.n_cd_entries_in_disk,
+            cd_entries.len()
+        ));
+    }
+    let actual_cd_size: u64 = cd_entries
+        .iter()
+        .map(|cd| {
+            CD_FIXED_SIZE
+                + cd.file_name_length as u64
+                + cd.extra_field_length as u64
+                + cd.file_comment_length as u64
+        })
+        .sum();
+    if eocd.cd_size as u64 != actual_cd_size
+        || eocd.cd_starting_position as u64 + actual_cd_size
+            != eocd.starting_position_with_signature
+    {
+        problems.push(format!(
+            "central directory size in the EOCD ({}) does not match the actual size ({})",
+            eocd.cd_size, actual_cd_size
+        ));
+    }
+
+    // End of the previous entry (local header + data + data descriptor)
+    let mut previous_end: u64 = 0;
+    for (i, cd) in cd_entries.iter().enumerate() {
+        let position = zip64::local_header_position(cd);
+        if position < previous_end {
+            problems.push(format!(
+                "local header of entry #{} (at {}) overlaps the previous entry (ending at {})",
+                i, position, previous_end
+            ));
+        }
+        if position >= eocd.cd_starting_position as u64 {
+            problems.push(format!(
+                "local header of entry #{} (at {}) is not before the central directory",
+                i, position
+            ));
+            continue;
+        }
+        let local_header =
+            match LocalFileHeader::read_at(reader, position).and_then(|mut header| {
+                header.read_data_descriptor(reader, cd)?;
+                return Ok(header);
+            }) {
+                Ok(header) => header,
+                Err(e) => {
+                    problems.push(format!("entry #{}: {}", i, e));
+                    continue;
+                }
+            };
+        if local_header.file_name_raw != cd.file_name_raw {
+            problems.push(format!(
+                "file name of entry #{} differs between the local header and the central directory",
+                i
+            ));
+        }
+        if (local_header.general_purpose_flags & UTF8_FLAG_BIT)
+            != (cd.general_purpose_flags & UTF8_FLAG_BIT)
+        {
+            problems.push(format!(
+                "UTF-8 flag (general purpose bit #11) of entry #{} differs between the local header and the central directory",
+                i
+            ));
+        }
+        previous_end = local_header.data_position
+            + local_header.payload_size
+            + local_header.data_descriptor_size;
+    }
+    if previous_end > eocd.cd_starting_position as u64 {
+        problems.push(format!(
+            "the last entry (ending at {}) overlaps the central directory (starting at {})",
+            previous_end, eocd.cd_starting_position
+        ));
+    }
+
+    if problems.is_empty() {
+        return Ok(cd_entries);
+    }
+    return Err(VerificationError { problems });
+}
+
+/// Re-parses an archive just written by `InputZIPArchive` and checks that it is what the writer reported.
+///
+/// In addition to `verify_archive_structure`, the central directory must list the written entries
+/// (the same number of entries, file names, and local header offsets as `report`).
+/// With `check_crc32`, stored & deflated entries are decompressed and their CRC-32 are compared with those in the central
+/// directory; encrypted entries and the other compression methods are skipped.
+/// Run this before replacing the original with the output, so that a buggy write never destroys the only copy.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the written archive
+/// * `report` - report returned by the output method that wrote the archive
+/// * `check_crc32` - `true` to decompress the entries and check their CRC-32
+pub fn verify_written_archive<R: ReadBytesExt + std::io::Seek>(
+    reader: &mut R,
+    report: &OutputReport,
+    check_crc32: bool,
+) -> Result<(), VerificationError> {
+    return match verify_written_entries(reader, report, check_crc32, Interruption::default()) {
+        Ok(result) => result,
+        // Never happens without a deadline or a cancellation token
+        Err(e) => Err(VerificationError {
+            problems: vec![e.to_string()],
+        }),
+    };
+}
+
+/// Same as `verify_written_archive` except that it gives up when the deadline has passed or the token is cancelled
+///
+/// They are checked between entries and between the chunks of the decompressed data.
+/// Returns `Err` containing `VerificationError` if the archive has problems,
+/// or `DeadlineExceeded` or `Cancelled` if the verification is interrupted.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the written archive
+/// * `report` - report returned by the output method that wrote the archive
+/// * `check_crc32` - `true` to decompress the entries and check their CRC-32
+/// * `deadline` - deadline of the verification (`None` means no deadline)
+/// * `cancellation` - token that aborts the verification (`None` means it can't be cancelled)
+pub fn verify_written_archive_with_deadline<R: ReadBytesExt + std::io::Seek>(
+    reader: &mut R,
+    report: &OutputReport,
+    check_crc32: bool,
+    deadline: Option<Instant>,
+    cancellation: Option<&CancellationToken>,
+) -> anyhow::Result<()> {
+    let interruption = Interruption {
+        deadline,
+        cancellation,
+    };
+    verify_written_entries(reader, report, check_crc32, interruption)??;
+    return Ok(());
+}
+
+/// Body of `verify_written_archive` that returns `Err` only if `interruption` stops it
+fn verify_written_entries<R: ReadBytesExt + std::io::Seek>(
+    reader: &mut R,
+    report: &OutputReport,
+    check_crc32: bool,
+    interruption: Interruption,
+) -> anyhow::Result<Result<(), VerificationError>> {
+    let cd_entries = match check_structure(reader) {
+        Ok(cd_entries) => cd_entries,
+        Err(e) => return Ok(Err(e)),
+    };
+    let mut problems = vec![];
+    if cd_entries.len() != report.entries.len() {
+        problems.push(format!(
+            "the central directory has {} entries but {} entries were written",
+            cd_entries.len(),
+            report.entries.len()
+        ));
+    }
+    for (i, (cd, written)) in cd_entries.iter().zip(report.entries.iter()).enumerate() {
+        interruption.check(VERIFYING)?;
+        if cd.file_name_raw != written.file_name_raw {
+            problems.push(format!(
+                "file name of entry #{} differs from the written one",
+                i
+            ));
+        }
+        let position = zip64::local_header_position(cd);
+        if position != written.local_header_position {
+            problems.push(format!(
+                "local header of entry #{} is recorded at {} but was written at {}",
+                i, position, written.local_header_position
+            ));
+        }
+        if !check_crc32 || cd.is_encrypted_data() {
+            continue;
+        }
+        let header = match LocalFileHeader::read_at(reader, position).and_then(|mut header| {
+            header.read_data_descriptor(reader, cd)?;
+            return Ok(header);
+        }) {
+            Ok(header) => header,
+            Err(e) => {
+                problems.push(format!("entry #{}: {}", i, e));
+                continue;
+            }
+        };
+        let mut payload = std::io::Read::take(&mut *reader, header.payload_size);
+        let result = repair::decompressed_crc32_and_size(
+            &mut payload,
+            cd.compression_method,
+            interruption,
+            VERIFYING,
+        )?;
+        match result {
+            Ok(_) if payload.limit() > 0 => problems.push(format!(
+                "entry #{}: {}",
+                i,
+                output::truncated_payload_error(
+                    header.payload_size,
+                    header.payload_size - payload.limit(),
+                )
+            )),
+            Ok(Some((crc32, _))) if crc32 != cd.crc32 => problems.push(format!(
+                "CRC-32 of entry #{} is {:08X} but {:08X} is recorded",
+                i, crc32, cd.crc32
+            )),
+            Ok(_) => {}
+            Err(e) => problems.push(format!("entry #{}: {}", i, e)),
+        }
+    }
+    if problems.is_empty() {
+        return Ok(Ok(()));
+    }
+    return Ok(Err(VerificationError { problems }));
+}
+
+/// Re-opens a ZIP archive with the `zip` crate as an independent reader and returns the names of the entries.
+///
+/// This is an in-process equivalent of `7z t`; the contents of unencrypted entries are decompressed
+/// to check their CRC-32, and encrypted ones are only enumerated.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+#[cfg(feature = "zip-crate-check")]
+pub fn verify_with_zip_crate<R: std::io::Read + std::io::Seek>(
+    reader: R,
+) -> Result<Vec<String>, VerificationError> {
+    let single = |problem: String| VerificationError {
+        problems: vec![problem],
+    };
+    let mut archive = zip::ZipArchive::new(reader).map_err(|e| single(e.to_string()))?;
+    let mut names = Vec::with_capacity(archive.len());
+    let mut problems = vec![];
+    for i in 0..archive.len() {
+        let encrypted = match archive.by_index_raw(i) {
+            Ok(file) => {
+                names.push(file.name().to_string());
+                file.encrypted()
+            }
+            Err(e) => {
+                problems.push(format!("entry #{}: {}", i, e));
+                continue;
+            }
+        };
+        if encrypted {
+            continue;
+        }
+        let result = archive
+            .by_index(i)
+            .map_err(|e| e.to_string())
+            .and_then(|mut file| {
+                std::io::copy(&mut file, &mut std::io::sink()).map_err(|e| e.to_string())
+            });
+        if let Err(e) = result {
+            problems.push(format!("entry #{}: {}", i, e));
+        }
+    }
+    if problems.is_empty() {
+        return Ok(names);
+    }
+    return Err(VerificationError { problems });
+}
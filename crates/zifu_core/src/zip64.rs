@@ -0,0 +1,332 @@
+//! 64-bit sizes & offsets of archives
+//!
+//! `zip_structs` stores offsets and sizes as the 32-bit fields of the format.  zifu handles them as `u64` via the accessors here
+//! (resolving the ZIP64 extended information extra field) and narrows them back only when writing, with `narrow`,
+//! so that an archive over 4 GiB results in `Err(Zip64Required)` instead of silently truncated offsets.
+
+use byteorder::{ReadBytesExt, LE};
+use std::convert::{TryFrom, TryInto};
+use std::io::{Seek, SeekFrom};
+use zip_structs::{zip_central_directory::ZipCDEntry, zip_eocd::ZipEOCD, zip_error::ZipReadError};
+
+use crate::encryption::{StrongAlgorithm, StrongEncryptionInfo};
+use crate::extra_fields;
+use crate::origin_hints::iter_extra_fields;
+
+/// Header ID of the ZIP64 extended information extra field
+pub const ZIP64_EXTRA_FIELD_ID: u16 = 0x0001;
+/// Value of a 32-bit field whose actual value is in the ZIP64 extended information extra field
+const ZIP64_PLACEHOLDER: u32 = u32::MAX;
+/// "Version needed to extract" of entries with the ZIP64 extended information extra field (4.5)
+const ZIP64_VERSION_REQUIRED_TO_EXTRACT: u16 = 45;
+
+/// Error returned when a value does not fit in its 32-bit (or 16-bit) field of the non-ZIP64 format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Zip64Required {
+    /// Field to be written (e.g. `"offset of the local header"`)
+    pub field: &'static str,
+    /// Value that does not fit in the field
+    pub value: u64,
+}
+
+impl std::fmt::Display for Zip64Required {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return write!(
+            f,
+            "{} ({}) is too large for the non-ZIP64 format",
+            self.field, self.value
+        );
+    }
+}
+
+impl std::error::Error for Zip64Required {}
+
+/// Narrows a 64-bit value to its field, or returns `Err(Zip64Required)` if it doesn't fit
+///
+/// # Arguments
+///
+/// * `value` - offset, size, or count
+/// * `field` - name of the field for the error message
+pub(crate) fn narrow<T: TryFrom<u64>>(value: u64, field: &'static str) -> Result<T, Zip64Required> {
+    return T::try_from(value).map_err(|_| Zip64Required { field, value });
+}
+
+/// Returns the 64-bit values in the ZIP64 extended information extra field in its order
+/// (uncompressed size, compressed size, offset of the local header), or `None` for each absent one
+///
+/// Only the values whose 32-bit fields are `0xFFFFFFFF` are stored in the extra field.
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of an entry
+/// * `placeholders` - whether each 32-bit field is `0xFFFFFFFF`
+fn read_zip64_extra_field(extra_field: &[u8], placeholders: [bool; 3]) -> [Option<u64>; 3] {
+    let mut values = [None; 3];
+    let data = match iter_extra_fields(extra_field).find(|(id, _)| *id == ZIP64_EXTRA_FIELD_ID) {
+        Some((_, data)) => data,
+        None => return values,
+    };
+    let mut rest = data;
+    for (value, is_placeholder) in values.iter_mut().zip(placeholders) {
+        if !is_placeholder {
+            continue;
+        }
+        if rest.len() < 8 {
+            break;
+        }
+        *value = Some(u64::from_le_bytes(rest[..8].try_into().unwrap()));
+        rest = &rest[8..];
+    }
+    return values;
+}
+
+/// Returns the 64-bit values of the entry in the ZIP64 extended information extra field (see `read_zip64_extra_field`)
+fn zip64_values(cd: &ZipCDEntry) -> [Option<u64>; 3] {
+    return read_zip64_extra_field(
+        &cd.extra_field,
+        [
+            cd.uncompressed_size == ZIP64_PLACEHOLDER,
+            cd.compressed_size == ZIP64_PLACEHOLDER,
+            cd.local_header_position == ZIP64_PLACEHOLDER,
+        ],
+    );
+}
+
+/// Returns the size of the original content of the entry
+///
+/// # Arguments
+///
+/// * `cd` - central directory record of the entry
+pub fn uncompressed_size(cd: &ZipCDEntry) -> u64 {
+    return zip64_values(cd)[0].unwrap_or(cd.uncompressed_size as u64);
+}
+
+/// Returns the size of the compressed (and possibly encrypted) payload of the entry
+///
+/// # Arguments
+///
+/// * `cd` - central directory record of the entry
+pub fn compressed_size(cd: &ZipCDEntry) -> u64 {
+    return zip64_values(cd)[1].unwrap_or(cd.compressed_size as u64);
+}
+
+/// Returns the offset of the local header of the entry (in its disk)
+///
+/// # Arguments
+///
+/// * `cd` - central directory record of the entry
+pub fn local_header_position(cd: &ZipCDEntry) -> u64 {
+    return zip64_values(cd)[2].unwrap_or(cd.local_header_position as u64);
+}
+
+/// Sets the offset of the local header of the entry (e.g. moved by renamed entries before it)
+///
+/// If the offset is in the ZIP64 extended information extra field, the value there is rewritten in place
+/// (the 32-bit field stays `0xFFFFFFFF`).  Offsets that don't fit in 32 bits are moved to the extra field too.
+///
+/// # Arguments
+///
+/// * `cd` - central directory record of the entry
+/// * `offset` - new offset of the local header (in its disk)
+pub(crate) fn set_local_header_position(
+    cd: &mut ZipCDEntry,
+    offset: u64,
+) -> Result<(), ZipReadError> {
+    let in_extra_field = cd.local_header_position == ZIP64_PLACEHOLDER;
+    if !in_extra_field && offset < ZIP64_PLACEHOLDER as u64 {
+        cd.local_header_position = offset as u32;
+        return Ok(());
+    }
+    // The offset follows the sizes stored in the extra field
+    let offset_in_data = 8 * [cd.uncompressed_size, cd.compressed_size]
+        .iter()
+        .filter(|size| **size == ZIP64_PLACEHOLDER)
+        .count();
+    let malformed = || ZipReadError::InvalidZipArchive {
+        reason: format!(
+            "ZIP64 extended information extra field of {} is too short",
+            String::from_utf8_lossy(&cd.file_name_raw)
+        ),
+    };
+    match extra_fields::find_data_range(&cd.extra_field, ZIP64_EXTRA_FIELD_ID) {
+        Some(range) if in_extra_field => {
+            if range.len() < offset_in_data + 8 {
+                return Err(malformed());
+            }
+            let start = range.start + offset_in_data;
+            cd.extra_field[start..start + 8].copy_from_slice(&offset.to_le_bytes());
+        }
+        Some(range) => {
+            if range.len() < offset_in_data {
+                return Err(malformed());
+            }
+            let start = range.start + offset_in_data;
+            cd.extra_field.splice(start..start, offset.to_le_bytes());
+            let size = (range.len() + 8) as u16;
+            cd.extra_field[range.start - 2..range.start].copy_from_slice(&size.to_le_bytes());
+        }
+        None => {
+            if offset_in_data > 0 {
+                return Err(malformed());
+            }
+            cd.extra_field
+                // 8 bytes always fit in a record
+                .extend_from_slice(
+                    &extra_fields::build_record(ZIP64_EXTRA_FIELD_ID, &offset.to_le_bytes())
+                        .unwrap(),
+                );
+        }
+    }
+    cd.local_header_position = ZIP64_PLACEHOLDER;
+    cd.extra_field_length = cd.extra_field.len() as u16;
+    cd.version_required_to_extract = cd
+        .version_required_to_extract
+        .max(ZIP64_VERSION_REQUIRED_TO_EXTRACT);
+    return Ok(());
+}
+
+/// Returns `true` if the extra field has the ZIP64 extended information extra field
+///
+/// The data descriptor of such an entry has 8-byte sizes.
+///
+/// # Arguments
+///
+/// * `extra_field` - whole extra field of a local header
+pub(crate) fn has_zip64_extra_field(extra_field: &[u8]) -> bool {
+    return iter_extra_fields(extra_field).any(|(id, _)| id == ZIP64_EXTRA_FIELD_ID);
+}
+
+/// Returns the uncompressed size in a local header (`uncompressed_size` is `0xFFFFFFFF` for ZIP64 entries)
+///
+/// The ZIP64 extended information extra field in local headers always has both sizes.
+///
+/// # Arguments
+///
+/// * `uncompressed_size` - 32-bit uncompressed size in the local header
+/// * `extra_field` - whole extra field of the local header
+pub(crate) fn local_uncompressed_size(uncompressed_size: u32, extra_field: &[u8]) -> u64 {
+    if uncompressed_size != ZIP64_PLACEHOLDER {
+        return uncompressed_size as u64;
+    }
+    return read_zip64_extra_field(extra_field, [true, true, false])[0]
+        .unwrap_or(uncompressed_size as u64);
+}
+
+/// Returns the compressed size in a local header (`compressed_size` is `0xFFFFFFFF` for ZIP64 entries)
+///
+/// The ZIP64 extended information extra field in local headers always has both sizes.
+///
+/// # Arguments
+///
+/// * `compressed_size` - 32-bit compressed size in the local header
+/// * `extra_field` - whole extra field of the local header
+pub(crate) fn local_compressed_size(compressed_size: u32, extra_field: &[u8]) -> u64 {
+    if compressed_size != ZIP64_PLACEHOLDER {
+        return compressed_size as u64;
+    }
+    return read_zip64_extra_field(extra_field, [true, true, false])[1]
+        .unwrap_or(compressed_size as u64);
+}
+
+/// Signature of the ZIP64 end of central directory record
+const ZIP64_EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x06];
+/// Signature of the ZIP64 end of central directory locator
+const ZIP64_EOCD_LOCATOR_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x06, 0x07];
+/// Size of the ZIP64 end of central directory locator (just before the EOCD)
+const ZIP64_EOCD_LOCATOR_SIZE: u64 = 20;
+/// Size of the version 1 ZIP64 end of central directory record (without the leading 12 bytes)
+const ZIP64_EOCD_V1_SIZE: u64 = 44;
+/// Size of the version 2 record up to the encryption flags (method, sizes, algorithm ID, bit length, and flags)
+const ZIP64_EOCD_V2_SIZE: u64 = ZIP64_EOCD_V1_SIZE + 24;
+/// "Version needed to extract" of the version 2 record (6.2, central directory encryption)
+const ZIP64_EOCD_V2_VERSION: u16 = 62;
+
+/// Fields of the ZIP64 end of central directory record needed to read the central directory
+#[derive(Clone, Debug)]
+pub(crate) struct Zip64EOCD {
+    /// Position of the signature of the record (the central directory ends here)
+    pub position: u64,
+    /// Number of the central directory entries (all the disks)
+    pub n_cd_entries: u64,
+    /// Position of the central directory
+    pub cd_starting_position: u64,
+    /// Encryption of the central directory (only in the version 2 record)
+    pub cd_encryption: Option<StrongEncryptionInfo>,
+}
+
+/// Reads the ZIP64 end of central directory record located just before `eocd`
+///
+/// Returns `None` if the archive is not ZIP64.
+///
+/// # Arguments
+///
+/// * `reader` - file handler representing the archive
+/// * `eocd` - (32-bit) end of central directory of the archive
+pub(crate) fn read_zip64_eocd<R: ReadBytesExt + Seek>(
+    reader: &mut R,
+    eocd: &ZipEOCD,
+) -> Result<Option<Zip64EOCD>, ZipReadError> {
+    if !eocd.is_zip64() {
+        return Ok(None);
+    }
+    let locator_position = match eocd
+        .starting_position_with_signature
+        .checked_sub(ZIP64_EOCD_LOCATOR_SIZE)
+    {
+        Some(position) => position,
+        None => return Ok(None),
+    };
+    reader.seek(SeekFrom::Start(locator_position))?;
+    let mut signature = [0u8; 4];
+    reader.read_exact(&mut signature)?;
+    if signature != ZIP64_EOCD_LOCATOR_SIGNATURE {
+        // 0xFFFF entries etc. in a 32-bit archive by chance
+        return Ok(None);
+    }
+    let _disk_of_record = reader.read_u32::<LE>()?;
+    let position = reader.read_u64::<LE>()?;
+    reader.seek(SeekFrom::Start(position))?;
+    reader.read_exact(&mut signature)?;
+    if signature != ZIP64_EOCD_SIGNATURE {
+        return Err(ZipReadError::InvalidZipArchive {
+            reason: format!(
+                "ZIP64 end of central directory record is not found at {}",
+                position
+            ),
+        });
+    }
+    let size_of_record = reader.read_u64::<LE>()?;
+    let _version_made_by = reader.read_u16::<LE>()?;
+    let version_required_to_extract = reader.read_u16::<LE>()?;
+    let _disk_index = reader.read_u32::<LE>()?;
+    let _cd_start_disk_index = reader.read_u32::<LE>()?;
+    let _n_cd_entries_in_disk = reader.read_u64::<LE>()?;
+    let n_cd_entries = reader.read_u64::<LE>()?;
+    let _cd_size = reader.read_u64::<LE>()?;
+    let cd_starting_position = reader.read_u64::<LE>()?;
+    let mut cd_encryption = None;
+    if version_required_to_extract & 0xff >= ZIP64_EOCD_V2_VERSION
+        && size_of_record >= ZIP64_EOCD_V2_SIZE
+    {
+        let _compression_method = reader.read_u16::<LE>()?;
+        let _compressed_size = reader.read_u64::<LE>()?;
+        let _original_size = reader.read_u64::<LE>()?;
+        let algorithm_id = reader.read_u16::<LE>()?;
+        let bit_length = reader.read_u16::<LE>()?;
+        let flags = reader.read_u16::<LE>()?;
+        // Algorithm ID 0 means that the central directory is not encrypted
+        if algorithm_id != 0 {
+            cd_encryption = Some(StrongEncryptionInfo {
+                algorithm: StrongAlgorithm::from_id(algorithm_id),
+                bit_length,
+                flags,
+            });
+        }
+    }
+    return Ok(Some(Zip64EOCD {
+        position,
+        n_cd_entries,
+        cd_starting_position,
+        cd_encryption,
+    }));
+}
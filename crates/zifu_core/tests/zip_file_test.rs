@@ -1,15 +1,80 @@
+#![allow(
+    clippy::needless_return,
+    clippy::get_first,
+    clippy::redundant_pattern_matching,
+    clippy::useless_vec,
+    clippy::ptr_arg,
+    clippy::needless_borrows_for_generic_args
+)]
+
 use std::{
+    cell::Cell,
     fs::File,
     io::Cursor,
-    io::{BufReader, BufWriter, Read, Seek, SeekFrom},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
     path::PathBuf,
     process::Command,
+    rc::Rc,
+    time::{Duration, Instant},
 };
 
 use tempfile::tempdir;
 use zifu_core::{
+    attributes::{
+        is_directory, is_symlink, normalize_trailing_slash, normalize_unix_mode, unix_mode,
+        PermissionNormalization,
+    },
+    cancellation::{CancellationToken, Cancelled},
+    collisions::{find_collisions, resolve_collisions_by_suffix, NameCollision},
+    create::{create_archive, create_zip_file, CreateOptions},
+    deadline::DeadlineExceeded,
+    duplicates::{deduplicate, find_duplicates, DuplicateKind},
+    encodings::{list_encodings, search_encodings, EncodingGroup},
+    encryption::{
+        read_aes_extra_field, read_strong_encryption_extra_field, AesInfo, AesStrength,
+        EntryEncryption, StrongAlgorithm, StrongEncryptionInfo, UnsupportedEncryptionError,
+        UnsupportedEncryptionScheme, AES_COMPRESSION_METHOD,
+    },
+    error::{EntryReadError, EntryStructure},
+    extract::{extract_zip_file, ExtractOptions},
+    feature_gate::{Operation, UnsupportedFeature},
     filename_decoder::{self, IDecoder, UTF8NFCDecoder},
-    FileNameEncodingType, InputZIPArchive,
+    filename_encoder::IEncoder,
+    fix::{fix_zip_bytes, fix_zip_file, FixError, FixOptions},
+    local_headers::{self, OffsetRepair},
+    metadata::compression_method_name,
+    name_mismatches::{NameMismatchError, NameMismatchPolicy},
+    name_pipeline::{trace_file_name, LossReason, NameStage, NormalizationForm},
+    origin_hints::{
+        format_unix_time_utc, format_version_made_by, CreatingTool, CreatorSystem, HostSystem,
+    },
+    path_length::{
+        find_long_paths, find_path_length_reasons, truncate_long_path, PathLengthReason,
+        MAX_COMPONENT_BYTES, MAX_PATH_UTF16_UNITS,
+    },
+    path_safety::{find_unsafe_path_reasons, is_unsafe_raw_path, sanitize_path, UnsafePathReason},
+    rename_rules::{apply_rename_rules, RenameRule, RenameRuleError},
+    repair::{EntryRepair, SkippedRepair},
+    reserved_names::{
+        find_reserved_name_reasons, fix_reserved_names, trim_trailing_dots_and_spaces,
+        ReservedNameReason,
+    },
+    shift_jis::ShiftJISMapping,
+    spanning::SpannedWriter,
+    target_os::{find_illegal_names, TargetOS},
+    timestamps::{
+        dos_time_to_seconds, extended_timestamp_extra_field, format_local_minutes,
+        parse_utc_offset, seconds_to_dos_time, TimestampCorrection,
+    },
+    top_directory::{change_top_directory, common_top_directory},
+    trailing_data::TRAILING_DATA_SEARCH_LIMIT,
+    unicode_path::read_unicode_path,
+    verification::{
+        read_central_directory, verify_archive_structure, verify_written_archive,
+        verify_written_archive_with_deadline,
+    },
+    zip64, ConversionOptions, EntryDecision, FileNameEncodingType, InputZIPArchive, OutputOptions,
+    OutputProgress, RenamePlanEntry, ZifuError,
 };
 
 fn open_bufreader(path: &str) -> anyhow::Result<BufReader<File>> {
@@ -202,6 +267,12 @@ fn zipcrypto_convert_test() -> anyhow::Result<()> {
     } else {
         let mut dump = Cursor::new(Vec::<u8>::new());
         before.output_archive_with_central_directory_file_names(&mut dump)?;
+        #[cfg(feature = "zip-crate-check")]
+        assert_eq!(
+            zifu_core::verification::verify_with_zip_crate(dump)?,
+            vec!["テスト.txt"],
+            "the zip crate reads the converted name"
+        );
     }
 
     Ok(())
@@ -272,11 +343,63 @@ fn aes256_convert_test() -> anyhow::Result<()> {
     } else {
         let mut dump = Cursor::new(Vec::<u8>::new());
         before.output_archive_with_central_directory_file_names(&mut dump)?;
+        #[cfg(feature = "zip-crate-check")]
+        assert_eq!(
+            zifu_core::verification::verify_with_zip_crate(dump)?,
+            vec!["テスト.txt"],
+            "the zip crate reads the converted name"
+        );
     }
 
     Ok(())
 }
 
+#[test]
+fn aes_metadata_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/aes256_sjis.zip")?)?;
+    let aes = EntryEncryption::Aes(Some(AesInfo {
+        vendor_version: 2,
+        strength: AesStrength::Aes256,
+        compression_method: 0,
+    }));
+    let metadata = zip.entry_metadata(0).unwrap();
+    assert_eq!(metadata.compression_method, AES_COMPRESSION_METHOD);
+    assert_eq!(metadata.encryption, Some(aes.clone()));
+    assert_eq!(aes.description(), "AES-256 encrypted, method store");
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    assert_eq!(zip.entry(0, &*sjis_decoder).unwrap().encryption, Some(aes));
+
+    // The AES extra field and method 99 are passed through untouched
+    let aes_record = |bytes: &[u8]| {
+        let start = bytes
+            .windows(4)
+            .position(|window| window == [0x01, 0x99, 7, 0])
+            .unwrap();
+        return bytes[start..start + 11].to_vec();
+    };
+    let original = std::fs::read("tests/assets/aes256_sjis.zip")?;
+    zip.convert_central_directory_file_names(&*sjis_decoder);
+    let mut output = vec![];
+    zip.output_archive_with_central_directory_file_names(&mut output)?;
+    assert_eq!(aes_record(&output), aes_record(&original));
+    let converted = InputZIPArchive::new(Cursor::new(output))?;
+    let converted_metadata = converted.entry_metadata(0).unwrap();
+    assert_eq!(
+        converted_metadata.compression_method,
+        AES_COMPRESSION_METHOD
+    );
+    assert_eq!(converted_metadata.encryption, metadata.encryption);
+    assert_eq!(converted_metadata.crc32, metadata.crc32);
+
+    assert_eq!(
+        read_aes_extra_field(&[0x01, 0x99, 7, 0, 2, 0, b'A', b'E', 9, 8, 0]),
+        None
+    );
+    assert_eq!(compression_method_name(8), "deflate");
+    assert_eq!(compression_method_name(1234), "#1234");
+    Ok(())
+}
+
 #[test]
 fn macos_finder_emulate_test() -> anyhow::Result<()> {
     static FILE_NAME: &str = "ほげふがぴよ.txt";
@@ -404,3 +527,3459 @@ fn implicit_utf8_test() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn statistical_detection_test() -> anyhow::Result<()> {
+    let sjis = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let candidates = sjis.detect_filename_encoding_candidates();
+    assert_eq!(
+        candidates
+            .get(0)
+            .ok_or(anyhow::anyhow!("at least one candidate"))?
+            .decoder
+            .encoding_name(),
+        "Shift_JIS",
+        "Shift-JIS wins"
+    );
+    let confidence_sum: f64 = candidates.iter().map(|c| c.confidence).sum();
+    assert!(
+        (confidence_sum - 1.0).abs() < 1e-9,
+        "sum of confidences is 1"
+    );
+
+    let utf8 = InputZIPArchive::new(open_bufreader("tests/assets/implicit_utf8.zip")?)?;
+    let candidates = utf8.detect_filename_encoding_candidates();
+    assert_eq!(
+        candidates
+            .get(0)
+            .ok_or(anyhow::anyhow!("at least one candidate"))?
+            .decoder
+            .encoding_name(),
+        "UTF-8",
+        "UTF-8 wins"
+    );
+
+    let explicit = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let candidates = explicit.detect_filename_encoding_candidates();
+    assert_eq!(candidates.len(), 1, "only ASCII is left");
+    assert_eq!(candidates[0].decoder.encoding_name(), "ASCII");
+
+    Ok(())
+}
+
+#[test]
+fn cjk_disambiguation_test() -> anyhow::Result<()> {
+    use zifu_core::encoding_detection::detect_encoding_candidates;
+    use zifu_core::name_scoring::score_name;
+    assert!(score_name("テスト.txt") > score_name("僥僗僩.txt"));
+    assert!(score_name("资料.doc") > score_name("訧蹋.doc"));
+    let cases = [
+        ("Shift_JIS", vec!["テスト.txt"]),
+        ("Shift_JIS", vec!["資料.pdf"]),
+        ("GBK", vec!["资料.doc"]),
+        ("GBK", vec!["简历.doc"]),
+        ("Big5", vec!["資料.doc"]),
+        ("Big5", vec!["簡歷.doc"]),
+        ("EUC-KR", vec!["자료.hwp"]),
+        ("EUC-KR", vec!["이력서.doc"]),
+    ];
+    for (encoding_name, names) in cases.iter() {
+        let encoder = <dyn IEncoder>::from_encoding_name(encoding_name).unwrap();
+        let raw_names = names
+            .iter()
+            .map(|name| encoder.to_bytes_lossless(name).unwrap())
+            .collect::<Vec<_>>();
+        let candidates = detect_encoding_candidates(&raw_names);
+        assert_eq!(
+            candidates[0].decoder.encoding_name(),
+            *encoding_name,
+            "{:?}",
+            names
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn explain_file_name_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/mac_finder_emulate.zip")?)?;
+    let decoder = <dyn IDecoder>::utf8();
+    let options = ConversionOptions::default();
+    let index = zip
+        .find_entry_index("ほげふがぴよ.txt", &*decoder, &options)
+        .ok_or(anyhow::anyhow!("entry is found by its NFC name"))?;
+    let trace = zip
+        .explain_file_name(index, &*decoder, &options)
+        .ok_or(anyhow::anyhow!("index is in range"))?;
+    assert!(trace.explicit_utf8, "explicitly encoded in UTF-8");
+    assert_eq!(trace.stages[0].0, NameStage::Decoded);
+    assert!(!trace.is_changed_at(0), "first stage is never a change");
+    assert_eq!(trace.stages[1].0, NameStage::Normalized);
+    assert!(trace.is_changed_at(1), "HFS+ NFD is composed");
+    assert_eq!(trace.final_name(), "ほげふがぴよ.txt");
+    assert!(
+        zip.explain_file_name(index + 1, &*decoder, &options)
+            .is_none(),
+        "out of range"
+    );
+    Ok(())
+}
+
+#[test]
+fn transliterate_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    zip.convert_central_directory_file_names_with_options(
+        &*sjis_decoder,
+        &ConversionOptions {
+            transliterate: true,
+            ..Default::default()
+        },
+    );
+    let names_list = zip.get_file_names_list(&*sjis_decoder);
+    assert_eq!(names_list[0].name, "tesuto.txt", "transliterated to ASCII");
+    assert!(
+        zip.diagnose_file_name_encoding().is_universal_archive(),
+        "archive is universal after application"
+    );
+    Ok(())
+}
+
+#[test]
+fn strict_lossless_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    assert!(
+        zip.check_lossless_conversion(&*sjis_decoder, &ConversionOptions::default())
+            .is_ok(),
+        "Shift-JIS decodes the names losslessly"
+    );
+
+    let error = zip
+        .check_lossless_conversion(&*<dyn IDecoder>::ascii(), &ConversionOptions::default())
+        .expect_err("ASCII cannot decode the names");
+    assert_eq!(error.entries.len(), 1);
+    assert_eq!(error.entries[0].index, 0);
+    assert_eq!(
+        error.entries[0].reasons,
+        vec![LossReason::LossyDecode, LossReason::ReplacementCharacter]
+    );
+
+    let error = zip
+        .check_lossless_conversion(
+            &*sjis_decoder,
+            &ConversionOptions {
+                transliterate: true,
+                ..Default::default()
+            },
+        )
+        .expect_err("transliteration loses information");
+    assert_eq!(error.entries[0].reasons, vec![LossReason::Transliteration]);
+    Ok(())
+}
+
+#[test]
+fn normalization_form_test() -> anyhow::Result<()> {
+    static NFC_NAME: &str = "ほげふがぴよ.txt";
+    let decoder = <dyn IDecoder>::utf8();
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/mac_finder_emulate.zip")?)?;
+    zip.convert_central_directory_file_names_with_options(
+        &*decoder,
+        &ConversionOptions {
+            normalization: NormalizationForm::Preserve,
+            ..Default::default()
+        },
+    );
+    assert!(
+        zip.diagnose_file_name_encoding()
+            .has_non_nfc_explicit_utf8_names,
+        "HFS+ NFD is preserved"
+    );
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    zip.convert_central_directory_file_names_with_options(
+        &*decoder,
+        &ConversionOptions {
+            normalization: NormalizationForm::NFD,
+            ..Default::default()
+        },
+    );
+    assert!(
+        !zip.diagnose_file_name_encoding()
+            .has_non_nfc_explicit_utf8_names,
+        "names without (de)composable characters are unchanged"
+    );
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/mac_finder_emulate.zip")?)?;
+    zip.convert_central_directory_file_names(&*decoder);
+    zip.convert_central_directory_file_names_with_options(
+        &*decoder,
+        &ConversionOptions {
+            normalization: NormalizationForm::NFD,
+            ..Default::default()
+        },
+    );
+    assert!(
+        zip.diagnose_file_name_encoding()
+            .has_non_nfc_explicit_utf8_names,
+        "NFC is decomposed into NFD again"
+    );
+    assert_eq!(zip.get_file_names_list(&*decoder)[0].name, NFC_NAME);
+
+    assert_eq!(NormalizationForm::NFKC.normalize("ＡＢＣ.txt"), "ABC.txt");
+    assert_eq!(
+        NormalizationForm::NFKC.normalize("ＡＢＣｶﾞｲﾄﾞ（１）.txt"),
+        "ABCガイド(1).txt",
+        "half-width katakana are widened, and full-width ASCII is narrowed"
+    );
+    let trace = trace_file_name(
+        "ｶﾞｲﾄﾞ.txt".as_bytes(),
+        true,
+        &*decoder,
+        &ConversionOptions {
+            normalization: NormalizationForm::NFKC,
+            ..Default::default()
+        },
+    );
+    assert_eq!(trace.final_name(), "ガイド.txt");
+    assert_eq!(trace.losses, vec![LossReason::CompatibilityNormalization]);
+    Ok(())
+}
+
+#[test]
+fn verify_archive_structure_test() -> anyhow::Result<()> {
+    for path in [
+        "tests/assets/after.zip",
+        "tests/assets/before.zip",
+        "tests/assets/zipcrypto_sjis.zip",
+    ] {
+        verify_archive_structure(&mut open_bufreader(path)?)?;
+    }
+
+    // Same length names must be replaced in local headers too
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    zip.convert_central_directory_file_names_with_options(
+        &*sjis_decoder,
+        &ConversionOptions {
+            transliterate: true,
+            ..Default::default()
+        },
+    );
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_central_directory_file_names(&mut dump)?;
+    verify_archive_structure(&mut dump)?;
+
+    // Corrupt the local header position of the first central directory entry
+    let mut broken = read_all(&mut File::open("tests/assets/after.zip")?)?;
+    let cd_position = broken
+        .windows(4)
+        .position(|w| w == [0x50, 0x4b, 0x01, 0x02])
+        .ok_or(anyhow::anyhow!("central directory exists"))?;
+    broken[cd_position + 42] = 1;
+    let error = verify_archive_structure(&mut Cursor::new(broken))
+        .expect_err("broken local header position is detected");
+    assert!(!error.problems.is_empty());
+    Ok(())
+}
+
+#[test]
+fn verify_written_archive_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    let report = zip.output_archive_with_options(&mut dump, &OutputOptions::default())?;
+    verify_written_archive(&mut dump, &report, true)?;
+
+    // An entry lost while writing
+    let mut missing = report.clone();
+    missing.entries.pop();
+    let error = verify_written_archive(&mut dump, &missing, false)
+        .expect_err("the missing entry is detected");
+    assert_eq!(error.problems.len(), 1);
+
+    // Corrupt the CRC-32 in the central directory; detected only with `check_crc32`
+    let mut broken = dump.into_inner();
+    let cd_position = broken
+        .windows(4)
+        .position(|w| w == [0x50, 0x4b, 0x01, 0x02])
+        .ok_or(anyhow::anyhow!("central directory exists"))?;
+    broken[cd_position + 16] ^= 0xFF;
+    let mut broken = Cursor::new(broken);
+    verify_written_archive(&mut broken, &report, false)?;
+    let error = verify_written_archive(&mut broken, &report, true)
+        .expect_err("the wrong CRC-32 is detected");
+    assert!(error.problems[0].contains("CRC-32 of entry #0"));
+    Ok(())
+}
+
+#[cfg(feature = "zip-crate-check")]
+#[test]
+fn zip_crate_check_test() -> anyhow::Result<()> {
+    let mut before = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    before.convert_central_directory_file_names(&*sjis_decoder);
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    before.output_archive_with_central_directory_file_names(&mut dump)?;
+    assert_eq!(
+        zifu_core::verification::verify_with_zip_crate(dump)?,
+        vec!["テスト.txt"],
+        "the zip crate reads the converted name and the content"
+    );
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "oem-cp")]
+fn reverse_conversion_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let sjis_encoder = <dyn IEncoder>::from_encoding_name("sjis").ok_or(anyhow::anyhow!(
+        "`sjis` is not suitable encoding name for `IEncoder::from_encoding_name`"
+    ))?;
+    zip.convert_central_directory_file_names_to_encoding(&*sjis_encoder)?;
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_central_directory_file_names(&mut dump)?;
+    verify_archive_structure(&mut dump)?;
+
+    let reversed = InputZIPArchive::new(dump)?;
+    assert!(
+        !reversed
+            .diagnose_file_name_encoding()
+            .is_universal_archive(),
+        "UTF-8 flag is cleared"
+    );
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    let names_list = reversed.get_file_names_list(&*sjis_decoder);
+    assert!(matches!(
+        names_list[0].encoding_type,
+        FileNameEncodingType::ImplicitNonASCII
+    ));
+    assert_eq!(names_list[0].name, "テスト.txt");
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let error = zip
+        .convert_central_directory_file_names_to_encoding(
+            &*<dyn IEncoder>::from_encoding_name("cp437").unwrap(),
+        )
+        .expect_err("CP437 cannot represent Japanese");
+    assert_eq!(
+        error.entries[0].reasons,
+        vec![LossReason::UnmappableCharacter]
+    );
+    assert!(
+        zip.diagnose_file_name_encoding().is_universal_archive(),
+        "nothing is changed on failure"
+    );
+    assert!(<dyn IEncoder>::from_encoding_name("utf-16le").is_none());
+    Ok(())
+}
+
+#[test]
+fn payload_hash_test() -> anyhow::Result<()> {
+    let options = OutputOptions {
+        hash_payloads: true,
+        ..Default::default()
+    };
+    let mut original = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let original_report = original.output_archive_with_options(&mut std::io::sink(), &options)?;
+
+    let mut converted = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    converted.convert_central_directory_file_names(&*sjis_decoder);
+    let converted_report =
+        converted.output_archive_with_options(&mut std::io::sink(), &OutputOptions::default())?;
+    assert_eq!(converted_report.entries[0].payload_sha256, None);
+    let mut converted = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    converted.convert_central_directory_file_names(&*sjis_decoder);
+    let converted_report = converted.output_archive_with_options(&mut std::io::sink(), &options)?;
+
+    assert_eq!(converted_report.entries.len(), 1);
+    assert_eq!(converted_report.entries[0].file_name(), "テスト.txt");
+    assert!(converted_report.entries[0].explicit_utf8);
+    assert!(converted_report.entries[0].payload_sha256.is_some());
+    assert_eq!(
+        original_report.entries[0].payload_sha256, converted_report.entries[0].payload_sha256,
+        "fixing names does not alter the payload"
+    );
+    assert_eq!(
+        converted_report.entries[0]
+            .payload_sha256_hex()
+            .map(|hex| hex.len()),
+        Some(64)
+    );
+    Ok(())
+}
+
+#[test]
+fn chunked_output_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let mut reports = vec![];
+    for chunk_size in [None, Some(1), Some(7)] {
+        let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+        zip.convert_central_directory_file_names(&*sjis_decoder);
+        let options = OutputOptions {
+            hash_payloads: true,
+            chunk_size,
+            ..Default::default()
+        };
+        let mut output = vec![];
+        reports.push(zip.output_archive_with_options(&mut output, &options)?);
+        assert_eq!(output, std::fs::read("tests/assets/after.zip")?);
+    }
+    assert!(reports
+        .iter()
+        .all(|report| report.entries[0].payload_sha256 == reports[0].entries[0].payload_sha256));
+    assert_eq!(
+        OutputOptions {
+            chunk_size: Some(0),
+            ..Default::default()
+        }
+        .chunk_size(),
+        1
+    );
+    Ok(())
+}
+
+#[test]
+fn filtered_conversion_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").ok_or(
+        anyhow::anyhow!("`sjis` is not suitable encoding name for `IDecoder::from_encoding_name`"),
+    )?;
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    zip.convert_central_directory_file_names_with_filter(
+        &*sjis_decoder,
+        &ConversionOptions::default(),
+        |name| name.starts_with("docs/"),
+    );
+    assert!(
+        !zip.diagnose_file_name_encoding().is_universal_archive(),
+        "unselected entries are untouched"
+    );
+
+    zip.convert_central_directory_file_names_with_filter(
+        &*sjis_decoder,
+        &ConversionOptions::default(),
+        |name| name == "テスト.txt",
+    );
+    assert!(
+        zip.diagnose_file_name_encoding().is_universal_archive(),
+        "selected entries are converted"
+    );
+    Ok(())
+}
+
+#[test]
+fn rename_hook_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    // ASCII decoder gets the name wrong
+    let decoder = <dyn IDecoder>::ascii();
+    zip.set_rename_hook(|raw, _| {
+        <dyn IDecoder>::from_encoding_name("sjis")?.to_string_lossless(raw)
+    });
+    let trace = zip
+        .explain_file_name(0, &*decoder, &ConversionOptions::default())
+        .ok_or(anyhow::anyhow!("entry #0 exists"))?;
+    assert_eq!(trace.stages.last().unwrap().0, NameStage::Renamed);
+    assert_eq!(trace.final_name(), "テスト.txt");
+    assert_eq!(trace.losses, vec![LossReason::LossyDecode]);
+
+    zip.convert_central_directory_file_names(&*decoder);
+    assert_eq!(
+        zip.get_file_names_list(&*decoder)[0].name,
+        "テスト.txt",
+        "the hook overrides the name"
+    );
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    zip.set_rename_hook(|_, _| None);
+    zip.convert_central_directory_file_names(&*decoder);
+    assert_eq!(zip.get_file_names_list(&*decoder)[0].name, "テスト.txt");
+    Ok(())
+}
+
+#[test]
+fn entry_filter_test() -> anyhow::Result<()> {
+    let decoder = <dyn IDecoder>::utf8();
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    zip.set_entry_filter(|info| {
+        assert_eq!(info.index, 0);
+        assert_eq!(info.file_name(), "テスト.txt");
+        assert!(!info.is_directory());
+        return EntryDecision::Rename(format!("renamed/{}", info.file_name()));
+    });
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    let report = zip.output_archive_with_options(&mut dump, &OutputOptions::default())?;
+    assert_eq!(report.entries[0].file_name(), "renamed/テスト.txt");
+    verify_archive_structure(&mut dump)?;
+    let renamed = InputZIPArchive::new(dump)?;
+    assert_eq!(
+        renamed.get_file_names_list(&*decoder)[0].name,
+        "renamed/テスト.txt"
+    );
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    zip.set_entry_filter(|_| EntryDecision::Skip);
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    let report = zip.output_archive_with_options(&mut dump, &OutputOptions::default())?;
+    assert!(report.entries.is_empty());
+    verify_archive_structure(&mut dump)?;
+    let skipped = InputZIPArchive::new(dump)?;
+    assert!(skipped.get_file_names_list(&*decoder).is_empty());
+    Ok(())
+}
+
+#[test]
+fn plan_conversion_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/mac_finder_emulate.zip")?)?;
+    let plan = zip.plan_conversion(&*<dyn IDecoder>::utf8());
+    assert_eq!(plan.len(), 1);
+    assert!(plan[0].explicit_utf8);
+    assert!(plan[0].changes, "NFD is composed");
+    assert_ne!(plan[0].current_name, plan[0].proposed_name);
+    assert_eq!(plan[0].proposed_name, "ほげふがぴよ.txt");
+    assert!(
+        !zip.diagnose_file_name_encoding().is_universal_archive(),
+        "planning does not change anything"
+    );
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let plan = zip.plan_conversion(&*<dyn IDecoder>::utf8());
+    assert!(!plan[0].changes);
+    assert_eq!(plan[0].current_name, plan[0].proposed_name);
+    Ok(())
+}
+
+#[test]
+fn deadline_test() -> anyhow::Result<()> {
+    let passed = Some(Instant::now());
+    let error =
+        InputZIPArchive::new_with_deadline(open_bufreader("tests/assets/after.zip")?, passed)
+            .err()
+            .ok_or(anyhow::anyhow!("the deadline has passed"))?;
+    assert!(error.downcast_ref::<DeadlineExceeded>().is_some());
+
+    let future = Some(Instant::now() + Duration::from_secs(3600));
+    let mut zip =
+        InputZIPArchive::new_with_deadline(open_bufreader("tests/assets/after.zip")?, future)?;
+    zip.output_archive_with_options(
+        &mut std::io::sink(),
+        &OutputOptions {
+            deadline: future,
+            ..Default::default()
+        },
+    )?;
+    let error = zip
+        .output_archive_with_options(
+            &mut std::io::sink(),
+            &OutputOptions {
+                deadline: passed,
+                ..Default::default()
+            },
+        )
+        .expect_err("the deadline has passed");
+    assert!(error.downcast_ref::<DeadlineExceeded>().is_some());
+    Ok(())
+}
+
+#[test]
+fn origin_hints_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/7zip_extra_field.zip")?)?;
+    let hints = zip.origin_hints();
+    assert_eq!(hints.n_ntfs_extra_fields, 1);
+    assert_eq!(hints.likely_creator(), CreatorSystem::Windows);
+    assert!(hints.latest_ntfs_modification.is_some());
+    assert!(hints
+        .description()
+        .unwrap()
+        .starts_with("likely created on Windows NTFS at "));
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/implicit_utf8.zip")?)?;
+    let hints = zip.origin_hints();
+    assert_eq!(hints.n_unix_extra_fields, 1);
+    assert_eq!(hints.likely_creator(), CreatorSystem::Unix);
+
+    assert_eq!(hints.likely_tool(), Some(CreatingTool::InfoZip));
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert_eq!(zip.origin_hints().likely_creator(), CreatorSystem::Windows);
+    assert_eq!(zip.origin_hints().host_system(), Some(HostSystem::MSDOS));
+    assert_eq!(
+        zip.origin_hints().likely_tool(),
+        Some(CreatingTool::WindowsExplorer)
+    );
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/7zip_extra_field.zip")?)?;
+    assert_eq!(
+        zip.origin_hints().likely_tool(),
+        Some(CreatingTool::SevenZip)
+    );
+    assert_eq!(
+        zip.origin_hints().creation_summary().unwrap(),
+        "created by ~7-Zip on Windows (version made by: MS-DOS 6.3)"
+    );
+    let zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&[
+        b"a.txt".to_vec(),
+        b"__MACOSX/._a.txt".to_vec(),
+    ])))?;
+    assert_eq!(
+        zip.origin_hints().likely_tool(),
+        Some(CreatingTool::MacArchiveUtility)
+    );
+    let zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&[])))?;
+    assert_eq!(zip.origin_hints().creation_summary(), None);
+    assert_eq!(format_version_made_by(0x0315), "UNIX 2.1");
+    assert_eq!(format_version_made_by(0x0a3f), "NTFS 6.3");
+    assert_eq!(format_version_made_by(0x1e14), "host #30 2.0");
+
+    assert_eq!(format_unix_time_utc(0), "1970-01-01 00:00:00 UTC");
+    assert_eq!(format_unix_time_utc(951_782_400), "2000-02-29 00:00:00 UTC");
+    Ok(())
+}
+
+/// Returns a stored local entry streamed with a data descriptor (sizes & CRC-32 are 0 in the local header)
+fn streamed_local_entry(name: &[u8], content: &[u8], descriptor_signature: bool) -> Vec<u8> {
+    return streamed_local_entry_with_descriptor(name, content, descriptor_signature, false);
+}
+
+/// Returns a stored local entry streamed with a data descriptor of the form
+///
+/// ZIP64 entries (`wide`) have the ZIP64 extended information extra field and a descriptor with 8-byte sizes.
+fn streamed_local_entry_with_descriptor(
+    name: &[u8],
+    content: &[u8],
+    descriptor_signature: bool,
+    wide: bool,
+) -> Vec<u8> {
+    let version = if wide { 45 } else { 20 };
+    let mut entry = vec![
+        0x50, 0x4b, 0x03, 0x04, version, 0, 0x08, 0x08, 0, 0, 0, 0, 0, 0,
+    ];
+    entry.extend_from_slice(&[0; 4]);
+    let extra: &[u8] = if wide {
+        entry.extend_from_slice(&[0xff; 8]);
+        &[1, 0, 16, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    } else {
+        entry.extend_from_slice(&[0; 8]);
+        &[]
+    };
+    entry.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    entry.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+    entry.extend_from_slice(name);
+    entry.extend_from_slice(extra);
+    entry.extend_from_slice(content);
+    if descriptor_signature {
+        entry.extend_from_slice(&[0x50, 0x4b, 0x07, 0x08]);
+    }
+    // CRC-32 is not checked while walking
+    entry.extend_from_slice(&0x12345678u32.to_le_bytes());
+    for _ in 0..2 {
+        if wide {
+            entry.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        } else {
+            entry.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        }
+    }
+    return entry;
+}
+
+/// Returns a stored local entry whose local header carries the sizes
+fn stored_local_entry(name: &[u8], content: &[u8]) -> Vec<u8> {
+    let mut entry = vec![0x50, 0x4b, 0x03, 0x04, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    // CRC-32 is not checked while walking
+    entry.extend_from_slice(&0x12345678u32.to_le_bytes());
+    entry.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    entry.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    entry.extend_from_slice(&[0, 0]);
+    entry.extend_from_slice(name);
+    entry.extend_from_slice(content);
+    return entry;
+}
+
+/// Inserts local entries unknown to the central directory just before it and returns the archive & the position of the entries
+fn insert_before_central_directory(path: &str, entries: &[u8]) -> anyhow::Result<(Vec<u8>, usize)> {
+    let original = std::fs::read(path)?;
+    let cd_offset = InputZIPArchive::new(Cursor::new(original.clone()))?
+        .iter_local_headers()
+        .last()
+        .unwrap()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?
+        .end_position() as usize;
+    let mut bytes = original[..cd_offset].to_vec();
+    bytes.extend_from_slice(entries);
+    bytes.extend_from_slice(&original[cd_offset..]);
+    // Offset of the start of the central directory in the EOCD (no comment)
+    let eocd = bytes.len() - 22;
+    bytes[eocd + 16..eocd + 20]
+        .copy_from_slice(&((cd_offset + entries.len()) as u32).to_le_bytes());
+    return Ok((bytes, cd_offset));
+}
+
+/// Returns an archive of empty stored entries with the names (implicitly encoded)
+///
+/// The ZIP64 end of central directory record is added for 65535 or more entries.
+fn synthetic_archive(names: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = vec![];
+    let mut offsets = vec![];
+    for name in names.iter() {
+        offsets.push(bytes.len() as u32);
+        bytes.extend_from_slice(&stored_local_entry(name, b""));
+    }
+    let cd_offset = bytes.len() as u32;
+    for (name, offset) in names.iter().zip(offsets) {
+        bytes.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&0x12345678u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(name);
+    }
+    let cd_size = bytes.len() as u32 - cd_offset;
+    let n_entries = if names.len() >= u16::MAX as usize {
+        let zip64_eocd_offset = bytes.len() as u64;
+        bytes.extend_from_slice(&[0x50, 0x4b, 0x06, 0x06]);
+        bytes.extend_from_slice(&44u64.to_le_bytes());
+        bytes.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&(names.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(names.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(cd_size as u64).to_le_bytes());
+        bytes.extend_from_slice(&(cd_offset as u64).to_le_bytes());
+        bytes.extend_from_slice(&[0x50, 0x4b, 0x06, 0x07, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&zip64_eocd_offset.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        u16::MAX
+    } else {
+        names.len() as u16
+    };
+    bytes.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+    bytes.extend_from_slice(&n_entries.to_le_bytes());
+    bytes.extend_from_slice(&n_entries.to_le_bytes());
+    bytes.extend_from_slice(&cd_size.to_le_bytes());
+    bytes.extend_from_slice(&cd_offset.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+    return bytes;
+}
+
+/// Returns `count` distinct names in Shift_JIS (e.g. `フォルダ12/テスト345.txt`)
+fn numbered_sjis_names(count: usize) -> Vec<Vec<u8>> {
+    let encoder = <dyn IEncoder>::from_encoding_name("sjis").unwrap();
+    return (0..count)
+        .map(|i| {
+            encoder
+                .to_bytes_lossless(&format!("フォルダ{}/テスト{}.txt", i / 100, i))
+                .unwrap()
+        })
+        .collect();
+}
+
+#[test]
+fn parallel_processing_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let utf8_decoder = <dyn filename_decoder::IDecoder>::utf8();
+    let mut zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&numbered_sjis_names(1000))))?;
+    // The results keep the order of the entries even if processed in parallel
+    let names = zip.get_file_names_list(&*sjis_decoder);
+    assert_eq!(names.len(), 1000);
+    assert!(names
+        .iter()
+        .zip(zip.entries(&*sjis_decoder))
+        .all(|(a, b)| a.name == b.name));
+    assert_eq!(names[123].name, "フォルダ1/テスト123.txt");
+    assert!(
+        zip.diagnose_file_name_encoding()
+            .has_implicit_non_ascii_names
+    );
+    assert_eq!(
+        zip.get_filename_decoder_index(&[&*utf8_decoder, &*sjis_decoder]),
+        Some(1)
+    );
+    let plan = zip.plan_conversion(&*sjis_decoder);
+    assert_eq!(plan[999].proposed_name, "フォルダ9/テスト999.txt");
+    zip.convert_central_directory_file_names(&*sjis_decoder);
+    assert_eq!(
+        zip.get_file_names_list(&*utf8_decoder)[456].name,
+        "フォルダ4/テスト456.txt"
+    );
+    Ok(())
+}
+
+/// Archive of stored entries filled with zeros, generated on the fly so that the test itself uses little memory
+struct ZeroFilledArchive {
+    /// Start, bytes (`None` for zeros), and length of each segment
+    segments: Vec<(u64, Option<Vec<u8>>, u64)>,
+    len: u64,
+    position: u64,
+}
+
+impl ZeroFilledArchive {
+    /// Returns an archive of entries of `entry_size` zeros with the names (implicitly encoded)
+    fn new(names: &[Vec<u8>], entry_size: u32) -> Self {
+        let mut archive = Self {
+            segments: vec![],
+            len: 0,
+            position: 0,
+        };
+        let mut offsets = vec![];
+        for name in names.iter() {
+            offsets.push(archive.len as u32);
+            let mut header = stored_local_entry(name, b"");
+            header[14..18].copy_from_slice(&0u32.to_le_bytes());
+            header[18..22].copy_from_slice(&entry_size.to_le_bytes());
+            header[22..26].copy_from_slice(&entry_size.to_le_bytes());
+            archive.push(Some(header));
+            archive.push_zeros(entry_size as u64);
+        }
+        let cd_offset = archive.len as u32;
+        let mut cd = vec![];
+        for (name, offset) in names.iter().zip(offsets) {
+            cd.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            cd.extend_from_slice(&0u32.to_le_bytes());
+            cd.extend_from_slice(&entry_size.to_le_bytes());
+            cd.extend_from_slice(&entry_size.to_le_bytes());
+            cd.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            cd.extend_from_slice(&[0; 12]);
+            cd.extend_from_slice(&offset.to_le_bytes());
+            cd.extend_from_slice(name);
+        }
+        let mut eocd = vec![0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0];
+        eocd.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        eocd.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        eocd.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+        eocd.extend_from_slice(&cd_offset.to_le_bytes());
+        eocd.extend_from_slice(&[0, 0]);
+        archive.push(Some(cd));
+        archive.push(Some(eocd));
+        return archive;
+    }
+
+    fn push(&mut self, bytes: Option<Vec<u8>>) {
+        let len = bytes.as_ref().map_or(0, |bytes| bytes.len() as u64);
+        self.segments.push((self.len, bytes, len));
+        self.len += len;
+    }
+
+    fn push_zeros(&mut self, len: u64) {
+        self.segments.push((self.len, None, len));
+        self.len += len;
+    }
+}
+
+impl Read for ZeroFilledArchive {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|(start, _, len)| (*start..*start + *len).contains(&self.position));
+        let (start, bytes, len) = match segment {
+            Some(segment) => segment,
+            None => return Ok(0),
+        };
+        let offset = self.position - start;
+        let n = (buf.len() as u64).min(len - offset) as usize;
+        match bytes {
+            Some(bytes) => buf[..n].copy_from_slice(&bytes[offset as usize..offset as usize + n]),
+            None => buf[..n].fill(0),
+        }
+        self.position += n as u64;
+        return Ok(n);
+    }
+}
+
+impl Seek for ZeroFilledArchive {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = match pos {
+            SeekFrom::Start(position) => position,
+            SeekFrom::End(delta) => self.len.checked_add_signed(delta).unwrap(),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta).unwrap(),
+        };
+        return Ok(self.position);
+    }
+}
+
+/// Returns the peak resident set size of this process in KiB
+#[cfg(target_os = "linux")]
+fn peak_rss_kib() -> u64 {
+    let status = std::fs::read_to_string("/proc/self/status").unwrap();
+    return status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+        .unwrap();
+}
+
+/// Fixes an archive of almost 4 GiB and checks the peak RSS
+///
+/// 4 GiB is the limit of the output because ZIP64 archives are read but never written (offsets and sizes must fit in 32 bits).
+///
+/// Run with `cargo test --release -p zifu_core -- --ignored bounded_memory`
+#[cfg(target_os = "linux")]
+#[test]
+#[ignore]
+fn bounded_memory_output_test() -> anyhow::Result<()> {
+    let entry_size = 1_900_000_000;
+    let mut zip =
+        InputZIPArchive::new(ZeroFilledArchive::new(&numbered_sjis_names(2), entry_size))?;
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    let options = OutputOptions {
+        hash_payloads: true,
+        ..Default::default()
+    };
+    let report = zip.output_archive_with_options(&mut std::io::sink(), &options)?;
+    assert_eq!(report.entries[1].compressed_size, entry_size as u64);
+    let peak_rss_kib = peak_rss_kib();
+    eprintln!("peak RSS: {} KiB", peak_rss_kib);
+    assert!(peak_rss_kib < 64 * 1024);
+    Ok(())
+}
+
+#[test]
+fn too_long_converted_name_test() -> anyhow::Result<()> {
+    // 2 bytes per character in Shift_JIS but 3 in UTF-8
+    let name = "テ".repeat(30000);
+    let sjis = <dyn IEncoder>::from_encoding_name("sjis").unwrap();
+    let mut zip =
+        InputZIPArchive::new(Cursor::new(synthetic_archive(
+            &[sjis.to_bytes_lossy(&name)],
+        )))?;
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    let mut output = vec![];
+    let error = zip
+        .output_archive_with_options(&mut output, &OutputOptions::default())
+        .unwrap_err();
+    assert!(error
+        .to_string()
+        .contains("the file name is too long (90000 bytes; 65535 at most)"));
+    // Nothing is written for the broken header
+    assert!(output.is_empty());
+    Ok(())
+}
+
+/// Reader or writer that records the largest buffer passed to a single call
+struct LargestCallRecorder<T> {
+    inner: T,
+    largest: Rc<Cell<usize>>,
+}
+
+impl<T: Read> Read for LargestCallRecorder<T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.largest.set(self.largest.get().max(buf.len()));
+        return self.inner.read(buf);
+    }
+}
+
+impl<T: Seek> Seek for LargestCallRecorder<T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        return self.inner.seek(pos);
+    }
+}
+
+impl<T: Write> Write for LargestCallRecorder<T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.largest.set(self.largest.get().max(buf.len()));
+        return self.inner.write(buf);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        return self.inner.flush();
+    }
+}
+
+#[test]
+fn bounded_chunk_output_test() -> anyhow::Result<()> {
+    let entry_size = 100_000;
+    let chunk_size = 4096;
+    let largest_read = Rc::new(Cell::new(0));
+    let mut zip = InputZIPArchive::new(LargestCallRecorder {
+        inner: ZeroFilledArchive::new(&numbered_sjis_names(2), entry_size),
+        largest: largest_read.clone(),
+    })?;
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    // Only the output is bounded (e.g. the EOCD is searched for in a larger buffer)
+    largest_read.set(0);
+    let largest_write = Rc::new(Cell::new(0));
+    let mut writer = LargestCallRecorder {
+        inner: std::io::sink(),
+        largest: largest_write.clone(),
+    };
+    let options = OutputOptions {
+        chunk_size: Some(chunk_size),
+        hash_payloads: true,
+        ..Default::default()
+    };
+    let report = zip.output_archive_with_options(&mut writer, &options)?;
+    assert_eq!(report.entries[1].compressed_size, entry_size as u64);
+    assert!(largest_read.get() > 0);
+    assert!(largest_read.get() <= chunk_size);
+    assert!(largest_write.get() > 0);
+    assert!(largest_write.get() <= chunk_size);
+    Ok(())
+}
+
+/// Run with `cargo test --release -p zifu_core --features rayon -- --ignored many_entries` to compare with the sequential build
+#[test]
+#[ignore]
+fn many_entries_benchmark_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let bytes = synthetic_archive(&numbered_sjis_names(100_000));
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let start = Instant::now();
+    let names = zip.get_file_names_list(&*sjis_decoder);
+    let diagnosis = zip.diagnose_file_name_encoding();
+    zip.convert_central_directory_file_names(&*sjis_decoder);
+    eprintln!(
+        "{} entries (parallel: {}): {:?}",
+        names.len(),
+        zifu_core::parallel::is_enabled(),
+        start.elapsed()
+    );
+    assert!(diagnosis.has_implicit_non_ascii_names);
+    Ok(())
+}
+
+#[test]
+fn local_headers_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let headers = zip.iter_local_headers().collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(headers.len(), 1);
+    assert_eq!(headers[0].position, 0);
+    assert_eq!(headers[0].file_name_raw, "テスト.txt".as_bytes());
+    assert!(zip.find_orphan_local_headers()?.is_empty());
+
+    // Streamed entries followed by the next local header or by nothing
+    let mut bytes = streamed_local_entry(b"a.txt", b"PK\x07\x08 fake sig", false);
+    bytes.extend(streamed_local_entry(b"b.txt", b"hello", true));
+    let mut cursor = Cursor::new(bytes);
+    let headers = local_headers::iter_local_headers(&mut cursor)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    assert_eq!(headers.len(), 2);
+    assert_eq!(headers[0].compressed_size, 13);
+    assert_eq!(headers[0].data_descriptor_size, 12);
+    assert_eq!(headers[0].crc32, 0x12345678);
+    assert_eq!(headers[1].position, headers[0].end_position());
+    assert_eq!(headers[1].file_name_raw, b"b.txt");
+    assert_eq!(headers[1].uncompressed_size, 5);
+    assert_eq!(headers[1].data_descriptor_size, 16);
+
+    // An entry lost from the central directory
+    let orphan = streamed_local_entry(b"lost.txt", b"lost", true);
+    let (bytes, cd_offset) = insert_before_central_directory("tests/assets/after.zip", &orphan)?;
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let orphans = zip.find_orphan_local_headers()?;
+    assert_eq!(orphans.len(), 1);
+    assert_eq!(orphans[0].position, cd_offset as u64);
+    assert_eq!(orphans[0].file_name_raw, b"lost.txt");
+    Ok(())
+}
+
+#[test]
+fn data_descriptor_forms_test() -> anyhow::Result<()> {
+    // (signature, 8-byte sizes) of the data descriptors: 12, 16, 20, and 24 bytes
+    let forms = [(false, false), (true, false), (false, true), (true, true)];
+    let content = b"hello";
+    let mut bytes = vec![];
+    let mut cd = vec![];
+    for (i, (signature, wide)) in forms.iter().enumerate() {
+        let name = format!("{}.txt", i);
+        let offset = bytes.len() as u32;
+        bytes.extend(streamed_local_entry_with_descriptor(
+            name.as_bytes(),
+            content,
+            *signature,
+            *wide,
+        ));
+        cd.extend_from_slice(&[
+            0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0x08, 0x08, 0, 0, 0, 0, 0, 0,
+        ]);
+        cd.extend_from_slice(&0x12345678u32.to_le_bytes());
+        cd.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        cd.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        cd.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        cd.extend_from_slice(&[0; 12]);
+        cd.extend_from_slice(&offset.to_le_bytes());
+        cd.extend_from_slice(name.as_bytes());
+    }
+    let cd_offset = bytes.len() as u32;
+    bytes.extend_from_slice(&cd);
+    bytes.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0, 4, 0, 4, 0]);
+    bytes.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&cd_offset.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+
+    let walk = |bytes: Vec<u8>| -> anyhow::Result<Vec<(u64, Vec<u8>)>> {
+        let mut cursor = Cursor::new(bytes);
+        let headers = local_headers::iter_local_headers(&mut cursor)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        let bytes = cursor.into_inner();
+        return Ok(headers
+            .iter()
+            .map(|header| {
+                assert_eq!(header.compressed_size, 5);
+                assert_eq!(header.uncompressed_size, 5);
+                assert_eq!(header.crc32, 0x12345678);
+                let descriptor_position = (header.data_position + header.compressed_size) as usize;
+                (
+                    header.data_descriptor_size,
+                    bytes[descriptor_position..header.end_position() as usize].to_vec(),
+                )
+            })
+            .collect());
+    };
+    let descriptors = walk(bytes.clone())?;
+    assert_eq!(
+        descriptors
+            .iter()
+            .map(|(size, _)| *size)
+            .collect::<Vec<_>>(),
+        vec![12, 16, 20, 24]
+    );
+
+    // The descriptors are copied as they are
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::utf8());
+    let mut output = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut output)?;
+    assert_eq!(walk(output.into_inner())?, descriptors);
+    Ok(())
+}
+
+#[test]
+fn repair_metadata_test() -> anyhow::Result<()> {
+    use std::io::Write;
+
+    let deflated_content = b"hello hello hello";
+    let mut encoder = flate2::write::DeflateEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(deflated_content)?;
+    let deflated = encoder.finish()?;
+    // #0: streamed & stored, #1: deflated with a wrong uncompressed size, #2: LZMA (not supported)
+    let mut entries = vec![(
+        streamed_local_entry(b"stored.txt", b"hello", true),
+        b"stored.txt".to_vec(),
+        0x0808u16,
+        0u16,
+        5u32,
+        5u32,
+    )];
+    let mut deflated_entry = stored_local_entry(b"deflated.txt", &deflated);
+    deflated_entry[8] = 8;
+    entries.push((
+        deflated_entry,
+        b"deflated.txt".to_vec(),
+        0,
+        8,
+        deflated.len() as u32,
+        0,
+    ));
+    let mut lzma_entry = stored_local_entry(b"lzma.txt", b"xyz");
+    lzma_entry[8] = 14;
+    entries.push((lzma_entry, b"lzma.txt".to_vec(), 0, 14, 3, 3));
+    let mut bytes = vec![];
+    let mut cd = vec![];
+    for (local, name, flags, method, compressed_size, uncompressed_size) in entries.iter() {
+        cd.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0]);
+        cd.extend_from_slice(&flags.to_le_bytes());
+        cd.extend_from_slice(&method.to_le_bytes());
+        cd.extend_from_slice(&[0; 4]);
+        // CRC-32 is wrong in all the entries
+        cd.extend_from_slice(&0x12345678u32.to_le_bytes());
+        cd.extend_from_slice(&compressed_size.to_le_bytes());
+        cd.extend_from_slice(&uncompressed_size.to_le_bytes());
+        cd.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        cd.extend_from_slice(&[0; 12]);
+        cd.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+        cd.extend_from_slice(name);
+        bytes.extend_from_slice(local);
+    }
+    let cd_offset = bytes.len() as u32;
+    bytes.extend_from_slice(&cd);
+    bytes.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0, 3, 0, 3, 0]);
+    bytes.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&cd_offset.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+
+    // A broken local header only skips its entry
+    let mut broken = bytes.clone();
+    broken[entries[0].0.len()] = b'X';
+    let report = InputZIPArchive::new(Cursor::new(broken))?.repair_metadata()?;
+    assert_eq!(report.repaired.len(), 1);
+    assert_eq!(report.skipped.len(), 2);
+    assert_eq!(report.skipped[0].index, 1);
+    assert!(report.skipped[0]
+        .reason
+        .starts_with("local file header can't be read: "));
+
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let report = zip.repair_metadata()?;
+    assert_eq!(
+        report.repaired,
+        vec![
+            EntryRepair {
+                index: 0,
+                crc32: Some((0x12345678, crc32fast::hash(b"hello"))),
+                uncompressed_size: None,
+                data_descriptor_dropped: true,
+            },
+            EntryRepair {
+                index: 1,
+                crc32: Some((0x12345678, crc32fast::hash(deflated_content))),
+                uncompressed_size: Some((0, deflated_content.len() as u64)),
+                data_descriptor_dropped: false,
+            },
+        ]
+    );
+    assert_eq!(
+        report.skipped,
+        vec![SkippedRepair {
+            index: 2,
+            reason: "compression method lzma is not supported".to_string(),
+        }]
+    );
+
+    let mut output = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut output)?;
+    let headers = local_headers::iter_local_headers(&mut output)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    assert_eq!(headers[0].general_purpose_flags & 0x0008, 0);
+    assert_eq!(headers[0].data_descriptor_size, 0);
+    assert_eq!(headers[0].crc32, crc32fast::hash(b"hello"));
+    assert_eq!(headers[0].compressed_size, 5);
+    assert_eq!(headers[1].crc32, crc32fast::hash(deflated_content));
+    assert_eq!(headers[1].uncompressed_size, deflated_content.len() as u64);
+    assert_eq!(headers[2].crc32, 0x12345678, "left untouched");
+    let repaired = InputZIPArchive::new(output)?;
+    let metadata = repaired.entry_metadata(1).unwrap();
+    assert_eq!(metadata.crc32, crc32fast::hash(deflated_content));
+    assert_eq!(metadata.uncompressed_size, deflated_content.len() as u64);
+    Ok(())
+}
+
+#[test]
+fn repair_local_header_offsets_test() -> anyhow::Result<()> {
+    let names = vec![b"a.txt".to_vec(), b"b.txt".to_vec(), b"c.txt".to_vec()];
+    let cd_offset = 105;
+    // Offsets of #1 & #2 point at the middle of #1 and far beyond the archive
+    let mut bytes = synthetic_archive(&names);
+    bytes[cd_offset + 51 + 42..cd_offset + 51 + 46].copy_from_slice(&42u32.to_le_bytes());
+    bytes[cd_offset + 102 + 42..cd_offset + 102 + 46].copy_from_slice(&0xFFFFFF00u32.to_le_bytes());
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    assert_eq!(
+        zip.repair_local_header_offsets()?,
+        vec![
+            OffsetRepair {
+                index: 1,
+                recorded_position: 42,
+                found_position: Some(35),
+            },
+            OffsetRepair {
+                index: 2,
+                recorded_position: 0xFFFFFF00,
+                found_position: Some(70),
+            },
+        ]
+    );
+    assert!(zip.repair_local_header_offsets()?.is_empty());
+
+    // Data prepended without fixing the offsets of the entries (only the EOCD is fixed)
+    let mut bytes = vec![0u8; 100];
+    bytes.extend(synthetic_archive(&names));
+    let eocd = bytes.len() - 22;
+    bytes[eocd + 16..eocd + 20].copy_from_slice(&(cd_offset as u32 + 100).to_le_bytes());
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let repairs = zip.repair_local_header_offsets()?;
+    assert_eq!(
+        repairs
+            .iter()
+            .map(|repair| repair.found_position)
+            .collect::<Vec<_>>(),
+        vec![Some(100), Some(135), Some(170)]
+    );
+    let mut output = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut output)?;
+    Ok(())
+}
+
+#[test]
+fn trailing_data_test() -> anyhow::Result<()> {
+    let original = std::fs::read("tests/assets/after.zip")?;
+    assert_eq!(
+        InputZIPArchive::new(Cursor::new(original.clone()))?.trailing_data_size(),
+        0
+    );
+
+    // An HTML error page appended by a proxy, containing a fake EOCD signature
+    let mut bytes = original.clone();
+    bytes.extend_from_slice(b"<html>PK\x05\x06 502 Bad Gateway</html>\r\n");
+    let junk_size = (bytes.len() - original.len()) as u64;
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    assert_eq!(zip.trailing_data_size(), junk_size);
+    let mut output = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut output)?;
+    assert_eq!(output.into_inner(), original);
+
+    // Junk beyond the limit is not searched
+    let mut bytes = original;
+    bytes.resize(
+        bytes.len() + TRAILING_DATA_SEARCH_LIMIT as usize + u16::MAX as usize + 1,
+        0,
+    );
+    assert!(InputZIPArchive::new(Cursor::new(bytes)).is_err());
+    Ok(())
+}
+
+#[test]
+fn preserve_unchanged_test() -> anyhow::Result<()> {
+    let options = OutputOptions {
+        preserve_unchanged: true,
+        hash_payloads: true,
+        ..Default::default()
+    };
+    // A gap between the last entry and the central directory, which a rewrite drops
+    let (bytes, _) = insert_before_central_directory("tests/assets/after.zip", b"padding")?;
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes.clone()))?;
+    assert!(zip.is_unchanged()?);
+    let mut rewritten = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut rewritten, &OutputOptions::default())?;
+    assert_ne!(rewritten.into_inner(), bytes);
+
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes.clone()))?;
+    let mut copied = Cursor::new(Vec::<u8>::new());
+    let report = zip.output_archive_with_options(&mut copied, &options)?;
+    assert_eq!(copied.into_inner(), bytes);
+    assert_eq!(report.entries.len(), 1);
+    assert_eq!(report.entries[0].local_header_position, 0);
+    assert!(report.entries[0].payload_sha256.is_some());
+
+    // Converted names are written as usual
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    zip.convert_central_directory_file_names(&*sjis_decoder);
+    assert!(!zip.is_unchanged()?);
+    let mut converted = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut converted, &options)?;
+    assert_ne!(
+        converted.into_inner(),
+        std::fs::read("tests/assets/before.zip")?
+    );
+    Ok(())
+}
+
+#[test]
+fn alignment_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert!(zip.is_aligned(None)?);
+    assert!(!zip.is_aligned(Some(4096))?);
+    for alignment in [2, 4, 7, 4096] {
+        let options = OutputOptions {
+            alignment: Some(alignment),
+            ..Default::default()
+        };
+        let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+        zip.convert_central_directory_file_names(&*sjis_decoder);
+        let mut aligned = Cursor::new(Vec::<u8>::new());
+        zip.output_archive_with_options(&mut aligned, &options)?;
+        verify_archive_structure(&mut aligned)?;
+        let mut zip = InputZIPArchive::new(Cursor::new(aligned.get_ref().clone()))?;
+        assert!(zip.is_aligned(Some(alignment))?, "aligned to {}", alignment);
+
+        // Padding is replaced instead of piling up
+        let mut realigned = Cursor::new(Vec::<u8>::new());
+        zip.output_archive_with_options(&mut realigned, &options)?;
+        assert_eq!(realigned.into_inner(), aligned.into_inner());
+    }
+    Ok(())
+}
+
+#[test]
+fn recover_orphans_test() -> anyhow::Result<()> {
+    let mut orphans = stored_local_entry("ロスト.txt".as_bytes(), b"lost");
+    orphans.extend(streamed_local_entry(b"streamed.txt", b"streamed", true));
+    let (bytes, cd_offset) = insert_before_central_directory("tests/assets/after.zip", &orphans)?;
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let recovery = zip.recover_orphan_entries()?;
+    assert_eq!(recovery.recovered.len(), 1);
+    assert_eq!(recovery.recovered[0].position, cd_offset as u64);
+    assert_eq!(recovery.skipped.len(), 1);
+    assert_eq!(recovery.skipped[0].file_name_raw, b"streamed.txt");
+
+    let utf8_decoder = <dyn IDecoder>::utf8();
+    zip.convert_central_directory_file_names(&*utf8_decoder);
+    let mut dump = Cursor::new(Vec::<u8>::new());
+    zip.output_archive_with_options(&mut dump, &OutputOptions::default())?;
+    verify_archive_structure(&mut dump)?;
+    let recovered = InputZIPArchive::new(dump)?;
+    let names = recovered.get_file_names_list(&*utf8_decoder);
+    assert_eq!(names.len(), 2);
+    assert_eq!(names[1].name, "ロスト.txt");
+    assert!(recovered
+        .diagnose_file_name_encoding()
+        .is_universal_archive());
+    Ok(())
+}
+
+#[test]
+fn backfill_output_test() -> anyhow::Result<()> {
+    let content = b"hello";
+    let name = b"streamed.txt";
+    let mut bytes = streamed_local_entry(name, content, true);
+    let cd_offset = bytes.len() as u32;
+    let mut cd = vec![
+        0x50, 0x4b, 0x01, 0x02, 20, 0, 20, 0, 0x08, 0x08, 0, 0, 0, 0, 0, 0,
+    ];
+    cd.extend_from_slice(&0x12345678u32.to_le_bytes());
+    cd.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    cd.extend_from_slice(&(content.len() as u32).to_le_bytes());
+    cd.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    cd.extend_from_slice(&[0; 16]);
+    cd.extend_from_slice(name);
+    bytes.extend_from_slice(&cd);
+    bytes.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0, 1, 0, 1, 0]);
+    bytes.extend_from_slice(&(cd.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&cd_offset.to_le_bytes());
+    bytes.extend_from_slice(&[0, 0]);
+
+    // The writer doesn't start at its head
+    let mut output = Cursor::new(b"SFX".to_vec());
+    output.set_position(3);
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let report = zip.output_archive_with_backfill(&mut output, &OutputOptions::default())?;
+    assert_eq!(report.entries[0].local_header_position, 0);
+    let written = output.into_inner()[3..].to_vec();
+    verify_archive_structure(&mut Cursor::new(written.clone()))?;
+    let headers = local_headers::iter_local_headers(&mut Cursor::new(written))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    assert_eq!(headers.len(), 1);
+    assert!(!headers[0].is_streamed);
+    assert_eq!(headers[0].general_purpose_flags & 0x08, 0);
+    assert_eq!(headers[0].data_descriptor_size, 0);
+    assert_eq!(headers[0].crc32, 0x12345678);
+    assert_eq!(headers[0].compressed_size, content.len() as u64);
+    assert_eq!(headers[0].uncompressed_size, content.len() as u64);
+    Ok(())
+}
+
+#[test]
+fn path_io_test() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let output_path = dir.path().join("after.zip");
+    let mut zip = InputZIPArchive::from_path("tests/assets/before.zip")?;
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    let report = zip.write_to_path(&output_path, &OutputOptions::default())?;
+    assert_eq!(report.entries.len(), 1);
+    let names = InputZIPArchive::from_path(&output_path)?
+        .get_file_names_list(&*<dyn IDecoder>::utf8())
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["テスト.txt"]);
+
+    // No partially written file is left
+    let broken_path = dir.path().join("broken.zip");
+    let mut zip = InputZIPArchive::from_path("tests/assets/before.zip")?;
+    let options = OutputOptions {
+        deadline: Some(Instant::now()),
+        ..Default::default()
+    };
+    assert!(zip.write_to_path(&broken_path, &options).is_err());
+    assert!(!broken_path.exists());
+    assert!(InputZIPArchive::from_path(dir.path().join("missing.zip")).is_err());
+    Ok(())
+}
+
+#[test]
+fn fix_zip_test() -> anyhow::Result<()> {
+    let names = |bytes: Vec<u8>| -> anyhow::Result<Vec<String>> {
+        return Ok(InputZIPArchive::new(Cursor::new(bytes))?
+            .get_file_names_list(&*<dyn IDecoder>::utf8())
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect());
+    };
+    let before = std::fs::read("tests/assets/before.zip")?;
+    let fixed = fix_zip_bytes(&before, &FixOptions::default())?;
+    assert_eq!(names(fixed)?, vec!["テスト.txt"]);
+    let after = std::fs::read("tests/assets/after.zip")?;
+    assert_eq!(fix_zip_bytes(&after, &FixOptions::default())?, after);
+    let error = fix_zip_bytes(
+        &before,
+        &FixOptions {
+            encoding: Some("no-such-encoding".to_string()),
+            ..Default::default()
+        },
+    )
+    .expect_err("the encoding name is invalid");
+    assert!(error.downcast_ref::<FixError>().is_some());
+
+    let dir = tempdir()?;
+    let path = dir.path().join("before.zip");
+    std::fs::write(&path, &before)?;
+    let options = FixOptions {
+        encoding: Some("sjis".to_string()),
+        ..Default::default()
+    };
+    assert!(fix_zip_file(&path, &options)?);
+    assert_eq!(names(std::fs::read(&path)?)?, vec!["テスト.txt"]);
+    assert!(!fix_zip_file(&path, &options)?);
+    assert_eq!(std::fs::read_dir(dir.path())?.count(), 1);
+    Ok(())
+}
+
+#[test]
+fn spanned_output_test() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let span_size = 64;
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let volume_path = |disk: u16| dir.path().join(format!("out.z{:02}", disk + 1));
+    let mut writer = SpannedWriter::new(span_size, |disk| File::create(volume_path(disk)))?;
+    zip.output_archive_to_sink(&mut writer, &OutputOptions::default())?;
+    let n_disks = writer.finish()?;
+    assert!(n_disks > 1);
+    let volumes = (0..n_disks)
+        .map(|disk| std::fs::read(volume_path(disk)))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert!(volumes
+        .iter()
+        .all(|volume| volume.len() as u64 <= span_size));
+    assert_eq!(&volumes[0][..4], b"PK\x07\x08");
+
+    let u16_at = |bytes: &[u8], i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]);
+    let u32_at = |bytes: &[u8], i: usize| {
+        u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+    };
+    let last = volumes.last().unwrap();
+    let eocd = &last[last.len() - 22..];
+    assert_eq!(&eocd[..4], b"PK\x05\x06");
+    assert_eq!(u16_at(eocd, 4), n_disks - 1);
+    assert_eq!(u16_at(eocd, 10), 1);
+    let cd_volume = &volumes[u16_at(eocd, 6) as usize];
+    let cd = &cd_volume[u32_at(eocd, 16) as usize..];
+    assert_eq!(&cd[..4], b"PK\x01\x02");
+    let local_volume = &volumes[u16_at(cd, 34) as usize];
+    let local_header = &local_volume[u32_at(cd, 42) as usize..];
+    assert_eq!(&local_header[..4], b"PK\x03\x04");
+    Ok(())
+}
+
+#[test]
+fn progress_callback_test() -> anyhow::Result<()> {
+    let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::<OutputProgress>::new()));
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let sink = log.clone();
+    zip.set_progress_callback(move |progress| sink.lock().unwrap().push(progress.clone()));
+    let report =
+        zip.output_archive_with_options(&mut std::io::sink(), &OutputOptions::default())?;
+    let log = log.lock().unwrap();
+    assert_eq!(
+        log.len(),
+        3,
+        "before the first entry, after each chunk, and after each entry"
+    );
+    assert_eq!(log[0].entries_done, 0);
+    assert_eq!(log[0].bytes_copied, 0);
+    assert_eq!(log[1].entries_done, 0);
+    assert_eq!(log[1].bytes_copied, report.entries[0].compressed_size);
+    assert_eq!(log[2].entries_done, 1);
+    assert_eq!(log[2].n_entries, 1);
+    assert_eq!(log[2].bytes_copied, log[2].total_bytes);
+
+    // A single entry reports its progress chunk by chunk
+    let log = std::sync::Arc::new(std::sync::Mutex::new(Vec::<OutputProgress>::new()));
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let sink = log.clone();
+    zip.set_progress_callback(move |progress| sink.lock().unwrap().push(progress.clone()));
+    let options = OutputOptions {
+        chunk_size: Some(2),
+        ..Default::default()
+    };
+    zip.output_archive_with_options(&mut std::io::sink(), &options)?;
+    let log = log.lock().unwrap();
+    let during_entry = log
+        .iter()
+        .filter(|progress| progress.entries_done == 0)
+        .map(|progress| progress.bytes_copied)
+        .collect::<Vec<_>>();
+    assert_eq!(during_entry, vec![0, 2, 4, 6, 8, 9]);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "oem-cp")]
+fn capabilities_test() -> anyhow::Result<()> {
+    let capabilities = zifu_core::capabilities();
+    assert!(capabilities.zip64_read);
+    assert!(!capabilities.zip64_write);
+    assert!(capabilities.encryption_pass_through);
+    assert_eq!(
+        capabilities.features.contains(&"zip-crate-check"),
+        cfg!(feature = "zip-crate-check")
+    );
+    // Every listed encoding is accepted
+    for encoding in capabilities.encodings.iter() {
+        assert!(
+            <dyn IDecoder>::from_encoding_name(encoding).is_some(),
+            "{}",
+            encoding
+        );
+    }
+    assert!(capabilities
+        .encodings
+        .iter()
+        .any(|name| name == "Shift_JIS"));
+    assert!(capabilities.encodings.iter().any(|name| name == "CP437"));
+    Ok(())
+}
+
+#[test]
+fn cancellation_test() -> anyhow::Result<()> {
+    let token = CancellationToken::new();
+    let options = OutputOptions {
+        cancellation: Some(token.clone()),
+        ..Default::default()
+    };
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    zip.output_archive_with_options(&mut std::io::sink(), &options)?;
+
+    token.cancel();
+    let error = zip
+        .output_archive_with_options(&mut std::io::sink(), &options)
+        .expect_err("cancelled");
+    assert!(error.downcast_ref::<Cancelled>().is_some());
+
+    // Checked between the chunks of a single payload too
+    let token = CancellationToken::new();
+    let options = OutputOptions {
+        cancellation: Some(token.clone()),
+        chunk_size: Some(2),
+        ..Default::default()
+    };
+    let copied = std::sync::Arc::new(std::sync::atomic::AtomicU64::new(0));
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let (callback_token, callback_copied) = (token.clone(), copied.clone());
+    zip.set_progress_callback(move |progress| {
+        callback_copied.store(progress.bytes_copied, std::sync::atomic::Ordering::SeqCst);
+        if progress.bytes_copied > 0 {
+            callback_token.cancel();
+        }
+    });
+    let error = zip
+        .output_archive_with_options(&mut std::io::sink(), &options)
+        .expect_err("cancelled");
+    assert!(error.downcast_ref::<Cancelled>().is_some());
+    assert_eq!(copied.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+    // The repair & the verification decompress the payloads, and they are interrupted as well
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let error = zip
+        .repair_metadata_with_deadline(None, Some(&token))
+        .expect_err("cancelled");
+    assert!(error.downcast_ref::<Cancelled>().is_some());
+    let mut output = Cursor::new(Vec::new());
+    let report = zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_written_archive_with_deadline(&mut output, &report, true, None, None)?;
+    let error =
+        verify_written_archive_with_deadline(&mut output, &report, true, None, Some(&token))
+            .expect_err("cancelled");
+    assert!(error.downcast_ref::<Cancelled>().is_some());
+    let error = verify_written_archive_with_deadline(
+        &mut output,
+        &report,
+        true,
+        Some(Instant::now()),
+        None,
+    )
+    .expect_err("the deadline has passed");
+    assert!(error.downcast_ref::<DeadlineExceeded>().is_some());
+    Ok(())
+}
+
+/// Converts a small archive (without a comment) into the ZIP64 format
+fn to_zip64(original: &[u8]) -> Vec<u8> {
+    let eocd = original.len() - 22;
+    let u16_at = |i: usize| u16::from_le_bytes([original[i], original[i + 1]]) as u64;
+    let u32_at = |i: usize| {
+        u32::from_le_bytes([
+            original[i],
+            original[i + 1],
+            original[i + 2],
+            original[i + 3],
+        ]) as u64
+    };
+    let (n_entries, cd_size, cd_offset) = (u16_at(eocd + 10), u32_at(eocd + 12), u32_at(eocd + 16));
+    let mut bytes = original[..eocd].to_vec();
+    let record_position = bytes.len() as u64;
+    bytes.extend_from_slice(b"PK\x06\x06");
+    bytes.extend_from_slice(&44u64.to_le_bytes());
+    bytes.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    for value in [n_entries, n_entries, cd_size, cd_offset] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(b"PK\x06\x07\x00\x00\x00\x00");
+    bytes.extend_from_slice(&record_position.to_le_bytes());
+    bytes.extend_from_slice(&1u32.to_le_bytes());
+    bytes.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    bytes.extend_from_slice(&[0xff; 12]);
+    bytes.extend_from_slice(&[0, 0]);
+    return bytes;
+}
+
+/// Sink that pretends to be already `position` bytes into the output
+struct OffsetSink {
+    position: u64,
+}
+
+impl std::io::Write for OffsetSink {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.position += buf.len() as u64;
+        return Ok(buf.len());
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+impl zifu_core::spanning::ArchiveSink for OffsetSink {
+    fn position(&self) -> (u16, u64) {
+        return (0, self.position);
+    }
+    fn keep_together(&mut self, _len: u64) -> std::io::Result<()> {
+        return Ok(());
+    }
+}
+
+#[test]
+fn zip64_offsets_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let mut cd = zip
+        .iter_local_headers()
+        .next()
+        .unwrap()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?
+        .to_central_directory()?;
+    assert_eq!(zip64::local_header_position(&cd), 0);
+    // Uncompressed size & offset in the ZIP64 extended information extra field
+    cd.uncompressed_size = u32::MAX;
+    cd.local_header_position = u32::MAX;
+    let mut data = 5_000_000_000u64.to_le_bytes().to_vec();
+    data.extend_from_slice(&4_500_000_000u64.to_le_bytes());
+    cd.extra_field = [&[0x01, 0x00, 16, 0][..], &data].concat();
+    assert_eq!(zip64::uncompressed_size(&cd), 5_000_000_000);
+    assert_eq!(zip64::compressed_size(&cd), cd.compressed_size as u64);
+    assert_eq!(zip64::local_header_position(&cd), 4_500_000_000);
+
+    // Offsets over 4 GiB are not truncated silently; those of local headers move to the ZIP64 extra field,
+    // but that of the central directory needs the ZIP64 end of central directory record
+    let result = zip.output_archive_to_sink(
+        &mut OffsetSink { position: 1 << 32 },
+        &OutputOptions::default(),
+    );
+    let error = result.unwrap_err();
+    let zip64_required = error
+        .downcast_ref::<zip64::Zip64Required>()
+        .expect("Zip64Required");
+    assert_eq!(zip64_required.field, "offset of the central directory");
+    assert_eq!(zip64_required.value, (1 << 32) + 40 + 9);
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let mut sink = OffsetSink {
+        position: u32::MAX as u64 - 20,
+    };
+    let error = zip
+        .output_archive_to_sink(&mut sink, &OutputOptions::default())
+        .unwrap_err();
+    assert_eq!(
+        error
+            .downcast_ref::<zip64::Zip64Required>()
+            .map(|e| e.field),
+        Some("offset of the central directory")
+    );
+    Ok(())
+}
+
+/// Moves the offsets of the local headers in the central directory of `synthetic_archive` to ZIP64 extended information extra fields
+fn with_zip64_offsets(original: &[u8]) -> Vec<u8> {
+    use std::convert::TryInto;
+
+    let eocd = original.len() - 22;
+    let cd_offset = u32::from_le_bytes(original[eocd + 16..eocd + 20].try_into().unwrap()) as usize;
+    let mut bytes = original[..cd_offset].to_vec();
+    let mut position = cd_offset;
+    while position < eocd {
+        let name_length = u16::from_le_bytes([original[position + 28], original[position + 29]]);
+        let end = position + 46 + name_length as usize;
+        let mut record = original[position..end].to_vec();
+        let offset = u32::from_le_bytes(record[42..46].try_into().unwrap()) as u64;
+        record[30..32].copy_from_slice(&16u16.to_le_bytes());
+        record[42..46].copy_from_slice(&u32::MAX.to_le_bytes());
+        record.extend_from_slice(&[0x01, 0x00, 8, 0]);
+        record.extend_from_slice(&offset.to_le_bytes());
+        // Unknown record after it, which must be kept
+        record.extend_from_slice(&[0xfe, 0xca, 0, 0]);
+        bytes.extend_from_slice(&record);
+        position = end;
+    }
+    let cd_size = (bytes.len() - cd_offset) as u32;
+    bytes.extend_from_slice(&original[eocd..eocd + 12]);
+    bytes.extend_from_slice(&cd_size.to_le_bytes());
+    bytes.extend_from_slice(&original[eocd + 16..]);
+    return bytes;
+}
+
+#[test]
+fn zip64_extra_field_offsets_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let bytes = with_zip64_offsets(&synthetic_archive(&numbered_sjis_names(3)));
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    assert_eq!(zip.entry_metadata(2).unwrap().local_header_offset, 2 * 51);
+    // Names in UTF-8 are longer, so the 2nd and later entries move
+    zip.convert_central_directory_file_names(&*sjis_decoder);
+    let mut output = vec![];
+    let report = zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    assert!(report.entries[2].local_header_position > 2 * 51);
+    assert!(verify_archive_structure(&mut Cursor::new(&output)).is_ok());
+    // The other records in the extra fields are kept
+    assert_eq!(
+        output
+            .windows(4)
+            .filter(|window| *window == [0xfe, 0xca, 0, 0])
+            .count(),
+        3
+    );
+    let written = InputZIPArchive::new(Cursor::new(output))?;
+    for entry in report.entries.iter() {
+        let metadata = written.entry_metadata(entry.index).unwrap();
+        assert_eq!(metadata.local_header_offset, entry.local_header_position);
+    }
+    Ok(())
+}
+
+#[test]
+fn feature_gate_test() -> anyhow::Result<()> {
+    let zip64 = to_zip64(&std::fs::read("tests/assets/after.zip")?);
+    let mut zip = InputZIPArchive::new(Cursor::new(zip64))?;
+    let gate = zip.feature_gate();
+    assert_eq!(gate.features(), &[UnsupportedFeature::Zip64]);
+    // Listing works even before fixing does
+    assert_eq!(
+        gate.check(Operation::List)?,
+        vec![UnsupportedFeature::Zip64]
+    );
+    assert_eq!(
+        zip.get_file_names_list(&*<dyn IDecoder>::utf8())[0].name,
+        "テスト.txt"
+    );
+    let error = gate
+        .check(Operation::Fix)
+        .expect_err("ZIP64 can't be written");
+    assert_eq!(error.feature, UnsupportedFeature::Zip64);
+    assert!(zip.check_unsupported_zip_type().is_err());
+    assert!(zip
+        .output_archive_with_options(&mut std::io::sink(), &OutputOptions::default())
+        .is_err());
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/zipcrypto_sjis.zip")?)?;
+    let gate = zip.feature_gate();
+    assert_eq!(gate.features(), &[UnsupportedFeature::EncryptedEntries]);
+    assert!(gate.check(Operation::Fix)?.is_empty(), "passed through");
+    assert!(gate.check(Operation::Extract).is_err());
+    Ok(())
+}
+
+#[test]
+fn unsupported_encryption_test() -> anyhow::Result<()> {
+    let names = vec![b"a.txt".to_vec(), b"b.txt".to_vec(), b"c.txt".to_vec()];
+    let local_offsets = [0, 35, 70];
+    let cd_offset = 105;
+    // Entries #0 & #2 are strongly encrypted (flags #0 & #6)
+    let mut strong = synthetic_archive(&names);
+    for i in [0, 2] {
+        strong[cd_offset + 51 * i + 8] = 0x41;
+    }
+    let zip = InputZIPArchive::new(Cursor::new(strong))?;
+    let gate = zip.feature_gate();
+    assert_eq!(
+        gate.features(),
+        &[UnsupportedFeature::StronglyEncryptedEntries]
+    );
+    assert!(gate.check(Operation::Fix)?.is_empty(), "passed through");
+    let error = gate
+        .check(Operation::Extract)
+        .expect_err("strongly encrypted entries can't be extracted");
+    assert_eq!(
+        error.encryptions,
+        vec![UnsupportedEncryptionError {
+            scheme: UnsupportedEncryptionScheme::StrongEncryption(None),
+            entries: vec![0, 2],
+        }]
+    );
+    assert!(error
+        .to_string()
+        .ends_with("strong encryption (PKWARE) is not supported; used by 2 entries (#0, #2)"));
+
+    // The strong encryption header: format 2, AES-256, 256 bits, and password
+    let extra = [0x17, 0, 8, 0, 2, 0, 0x10, 0x66, 0, 1, 1, 0];
+    let info = read_strong_encryption_extra_field(&extra).unwrap();
+    assert_eq!(
+        info,
+        StrongEncryptionInfo {
+            algorithm: StrongAlgorithm::Aes256,
+            bit_length: 256,
+            flags: 1,
+        }
+    );
+    assert_eq!(
+        EntryEncryption::Strong(Some(info)).description(),
+        "strongly encrypted (PKWARE, AES-256)"
+    );
+
+    // The central directory is encrypted (flag #13 & the archive extra data record)
+    let mut encrypted_cd = synthetic_archive(&names);
+    for offset in local_offsets[..2].iter() {
+        encrypted_cd[offset + 6] = 0x41;
+        encrypted_cd[offset + 7] = 0x20;
+    }
+    encrypted_cd[cd_offset..cd_offset + 4].copy_from_slice(b"PK\x06\x08");
+    let error = InputZIPArchive::new(Cursor::new(encrypted_cd))
+        .err()
+        .expect("the central directory can't be read");
+    let error = error.downcast::<UnsupportedEncryptionError>()?;
+    assert_eq!(
+        error.scheme,
+        UnsupportedEncryptionScheme::CentralDirectory(None)
+    );
+    assert_eq!(error.entries, vec![0, 1]);
+    match zip_structs::zip_error::ZipReadError::from(error) {
+        zip_structs::zip_error::ZipReadError::UnsupportedZipArchive { reason } => assert_eq!(
+            reason,
+            "central directory encryption (PKWARE) is not supported; the file names can't be read without the key (entries: #0, #1)"
+        ),
+        e => panic!("unexpected error: {}", e),
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_archive_test() -> anyhow::Result<()> {
+    use zifu_core::AsyncInputZIPArchive;
+
+    let file = tokio::io::BufReader::new(tokio::fs::File::open("tests/assets/before.zip").await?);
+    let mut zip = AsyncInputZIPArchive::new(file).await?;
+    assert!(!zip
+        .archive()
+        .diagnose_file_name_encoding()
+        .is_universal_archive());
+    zip.archive_mut().convert_central_directory_file_names(
+        &*<dyn IDecoder>::from_encoding_name("sjis").unwrap(),
+    );
+    let mut output = vec![];
+    // Futures must be `Send` to be spawned by web services
+    fn assert_send<T: Send>(future: T) -> T {
+        return future;
+    }
+    assert_send(zip.output_archive_with_options(&mut output, &OutputOptions::default())).await?;
+    assert_eq!(output, std::fs::read("tests/assets/after.zip")?);
+    Ok(())
+}
+
+#[cfg(feature = "mmap")]
+#[test]
+fn mmap_input_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::from_mmap("tests/assets/before.zip")?;
+    assert_eq!(zip.as_bytes(), std::fs::read("tests/assets/before.zip")?);
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    let mut output = vec![];
+    zip.output_archive_with_central_directory_file_names(&mut output)?;
+    assert_eq!(output, std::fs::read("tests/assets/after.zip")?);
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let names: Vec<zifu_core::FileNameEntry> = serde_json::from_str(&serde_json::to_string(
+        &zip.get_file_names_list(&*sjis_decoder),
+    )?)?;
+    assert_eq!(names[0].name, "テスト.txt");
+    assert!(matches!(
+        names[0].encoding_type,
+        FileNameEncodingType::ImplicitNonASCII
+    ));
+    let diagnosis = serde_json::to_value(zip.diagnose_file_name_encoding())?;
+    assert_eq!(diagnosis["has_implicit_non_ascii_names"], true);
+    let plan = serde_json::to_value(zip.plan_conversion(&*sjis_decoder))?;
+    assert_eq!(plan[0]["proposed_name"], "テスト.txt");
+    assert_eq!(plan[0]["changes"], true);
+    Ok(())
+}
+
+#[test]
+fn collisions_test() -> anyhow::Result<()> {
+    let entry = |index: usize, raw: &[u8], proposed_name: &str| {
+        return RenamePlanEntry {
+            index,
+            raw: raw.to_vec(),
+            explicit_utf8: true,
+            current_name: String::from_utf8_lossy(raw).into_owned(),
+            proposed_name: proposed_name.to_string(),
+            changes: true,
+        };
+    };
+    let plan = vec![
+        // NFC & NFD of "が.txt"
+        entry(0, "が.txt".as_bytes(), "が.txt"),
+        entry(1, "か\u{3099}.txt".as_bytes(), "が.txt"),
+        entry(2, b"a.txt", "a.txt"),
+        // Already duplicated in the input archive
+        entry(3, b"a.txt", "a.txt"),
+        entry(4, "か\u{3099}.txt".as_bytes(), "が.txt"),
+        entry(5, b"dir/", "d/"),
+        entry(6, b"DIR/", "d/"),
+        entry(7, "が (2).txt".as_bytes(), "が (2).txt"),
+    ];
+    let collisions = find_collisions(&plan);
+    assert_eq!(
+        collisions,
+        vec![
+            NameCollision {
+                name: "が.txt".to_string(),
+                indices: vec![0, 1, 4],
+            },
+            NameCollision {
+                name: "d/".to_string(),
+                indices: vec![5, 6],
+            },
+        ]
+    );
+    let renamed = resolve_collisions_by_suffix(&plan, &collisions);
+    assert_eq!(renamed.len(), 2);
+    // "が (2).txt" is taken
+    assert_eq!(renamed["か\u{3099}.txt".as_bytes()], "が (3).txt");
+    assert_eq!(renamed[b"DIR/".as_slice()], "d (2)/");
+    Ok(())
+}
+
+#[test]
+fn path_safety_test() -> anyhow::Result<()> {
+    assert_eq!(find_unsafe_path_reasons("dir/a.txt"), vec![]);
+    assert_eq!(find_unsafe_path_reasons("dir/..a.txt"), vec![]);
+    assert_eq!(
+        find_unsafe_path_reasons("../a.txt"),
+        vec![UnsafePathReason::ParentDirectory]
+    );
+    assert_eq!(
+        find_unsafe_path_reasons("/etc/passwd"),
+        vec![UnsafePathReason::Absolute]
+    );
+    assert_eq!(
+        find_unsafe_path_reasons("C:\\..\\a.txt"),
+        vec![
+            UnsafePathReason::ParentDirectory,
+            UnsafePathReason::DriveLetter
+        ]
+    );
+    // 0x5C is the trail byte of "表" in Shift_JIS, not a separator
+    assert!(!is_unsafe_raw_path(b"\x95\x5C/a.txt"));
+    assert!(is_unsafe_raw_path(b"a/../../b.txt"));
+
+    assert_eq!(sanitize_path("dir/a.txt"), "dir/a.txt");
+    assert_eq!(sanitize_path("/a/../b.txt"), "a/b.txt");
+    assert_eq!(sanitize_path("C:\\dir\\a.txt"), "dir/a.txt");
+    assert_eq!(sanitize_path("../dir/"), "dir/");
+    assert_eq!(sanitize_path("/"), "_");
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert!(!zip.diagnose_file_name_encoding().has_unsafe_paths);
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    assert!(zip.find_unsafe_paths(&*sjis_decoder).is_empty());
+    let options = ConversionOptions {
+        sanitize_paths: true,
+        ..Default::default()
+    };
+    let trace = zip.explain_file_name(0, &*sjis_decoder, &options).unwrap();
+    assert_eq!(trace.stages.last().unwrap().0, NameStage::Sanitized);
+    assert_eq!(trace.final_name(), "テスト.txt");
+    Ok(())
+}
+
+#[test]
+fn rename_rules_test() -> anyhow::Result<()> {
+    let rule = RenameRule::parse(r"s/^New Folder\///")?;
+    assert_eq!(rule.apply("New Folder/a.txt"), "a.txt");
+    assert_eq!(rule.apply("b/New Folder/a.txt"), "b/New Folder/a.txt");
+    assert_eq!(RenameRule::parse("s/a/b/")?.apply("aaa"), "baa");
+    assert_eq!(RenameRule::parse("s/a/b/g")?.apply("aaa"), "bbb");
+    assert_eq!(RenameRule::parse("s|A/|x/|gi")?.apply("a/A/"), "x/x/");
+    assert_eq!(
+        RenameRule::parse(r"s/(\d+)_(.+)/${2}_$1/")?.apply("01_a.txt"),
+        "a.txt_01"
+    );
+    assert_eq!(RenameRule::literal("$1.", "$2").apply("$1.$1."), "$2$2");
+    assert_eq!(
+        RenameRule::parse("s/a/b/x").unwrap_err(),
+        RenameRuleError::Syntax {
+            expression: "s/a/b/x".to_string()
+        }
+    );
+    assert!(RenameRule::parse("s/a/b").is_err());
+    assert!(RenameRule::parse("sab").is_err());
+    assert!(matches!(
+        RenameRule::parse("s/(/b/"),
+        Err(RenameRuleError::Pattern { .. })
+    ));
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let options = ConversionOptions {
+        rename_rules: vec![RenameRule::parse(r"s/\.txt$/.md/")?],
+        sanitize_paths: true,
+        ..Default::default()
+    };
+    let trace = zip.explain_file_name(0, &*sjis_decoder, &options).unwrap();
+    assert!(trace
+        .stages
+        .iter()
+        .any(|(stage, name)| *stage == NameStage::Rewritten && name == "テスト.md"));
+    assert_eq!(trace.final_name(), "テスト.md");
+    // Rules never reintroduce unsafe paths
+    assert_eq!(
+        apply_rename_rules(&[RenameRule::literal("テスト", "../x")], "テスト.txt"),
+        "../x.txt"
+    );
+    let options = ConversionOptions {
+        rename_rules: vec![RenameRule::literal("テスト", "../x")],
+        sanitize_paths: true,
+        ..Default::default()
+    };
+    let trace = zip.explain_file_name(0, &*sjis_decoder, &options).unwrap();
+    assert_eq!(trace.final_name(), "x.txt");
+    Ok(())
+}
+
+#[test]
+fn top_directory_test() -> anyhow::Result<()> {
+    assert_eq!(
+        common_top_directory(vec!["top/", "top/a.txt", "top/b/c.txt"]),
+        Some("top".to_string())
+    );
+    assert_eq!(common_top_directory(vec!["top/a.txt", "b.txt"]), None);
+    assert_eq!(common_top_directory(vec!["top/a.txt", "other/b.txt"]), None);
+    assert_eq!(common_top_directory(vec!["/a.txt"]), None);
+    assert_eq!(common_top_directory(Vec::<&str>::new()), None);
+    assert_eq!(
+        change_top_directory("top/a.txt", Some("top"), None),
+        "a.txt"
+    );
+    assert_eq!(change_top_directory("top/", Some("top"), None), "");
+    assert_eq!(
+        change_top_directory("topper/a.txt", Some("top"), None),
+        "topper/a.txt"
+    );
+    assert_eq!(
+        change_top_directory("a.txt", None, Some("new/")),
+        "new/a.txt"
+    );
+    assert_eq!(
+        change_top_directory("top/", Some("top"), Some("new")),
+        "new/"
+    );
+
+    let names = vec![
+        b"top/".to_vec(),
+        b"top/a.txt".to_vec(),
+        b"top/b/c.txt".to_vec(),
+    ];
+    let decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let mut zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&names)))?;
+    let options = ConversionOptions {
+        strip_top_directory: Some("top".to_string()),
+        ..Default::default()
+    };
+    let trace = zip.explain_file_name(1, &*decoder, &options).unwrap();
+    assert_eq!(
+        trace.stages.last().unwrap().0,
+        NameStage::TopDirectoryChanged
+    );
+    assert_eq!(trace.final_name(), "a.txt");
+    zip.convert_central_directory_file_names_with_options(&*decoder, &options);
+    let mut output = Cursor::new(vec![]);
+    zip.output_archive_with_central_directory_file_names(&mut output)?;
+    output.seek(SeekFrom::Start(0))?;
+    let written = InputZIPArchive::new(output)?;
+    let written_names: Vec<String> = written
+        .get_file_names_list(&*decoder)
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    assert_eq!(
+        written_names,
+        vec!["a.txt", "b/c.txt"],
+        "top/ itself is left out"
+    );
+
+    let mut zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&names)))?;
+    zip.convert_central_directory_file_names_with_options(
+        &*decoder,
+        &ConversionOptions {
+            strip_top_directory: Some("top".to_string()),
+            add_top_directory: Some("new".to_string()),
+            ..Default::default()
+        },
+    );
+    let mut output = Cursor::new(vec![]);
+    zip.output_archive_with_central_directory_file_names(&mut output)?;
+    output.seek(SeekFrom::Start(0))?;
+    let written_names: Vec<String> = InputZIPArchive::new(output)?
+        .get_file_names_list(&*decoder)
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    assert_eq!(written_names, vec!["new/", "new/a.txt", "new/b/c.txt"]);
+    Ok(())
+}
+
+#[test]
+fn reserved_names_test() -> anyhow::Result<()> {
+    assert_eq!(find_reserved_name_reasons("dir/a.txt"), vec![]);
+    assert_eq!(find_reserved_name_reasons("console.txt"), vec![]);
+    assert_eq!(find_reserved_name_reasons("COM10"), vec![]);
+    assert_eq!(
+        find_reserved_name_reasons("aux.txt"),
+        vec![ReservedNameReason::DeviceName]
+    );
+    assert_eq!(
+        find_reserved_name_reasons("Con/a. "),
+        vec![
+            ReservedNameReason::DeviceName,
+            ReservedNameReason::TrailingDotOrSpace
+        ]
+    );
+    assert_eq!(find_reserved_name_reasons("dir/"), vec![]);
+
+    assert_eq!(fix_reserved_names("dir/a.txt"), "dir/a.txt");
+    assert_eq!(fix_reserved_names("con/aux.tar.gz"), "con_/aux_.tar.gz");
+    assert_eq!(fix_reserved_names("a./b "), "a._/b _");
+    assert_eq!(fix_reserved_names("nul/"), "nul_/");
+    assert_eq!(trim_trailing_dots_and_spaces("a./b .txt"), "a/b .txt");
+    assert_eq!(trim_trailing_dots_and_spaces("a. . /b "), "a/b");
+    assert_eq!(trim_trailing_dots_and_spaces("../.../dir/"), "../_/dir/");
+    assert_eq!(trim_trailing_dots_and_spaces("dir/a.txt"), "dir/a.txt");
+
+    let names = vec![b"a./".to_vec(), b"a./con.".to_vec()];
+    let zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&names)))?;
+    let diagnosis = zip.diagnose_file_name_encoding();
+    assert!(diagnosis.has_windows_reserved_names);
+    assert!(diagnosis.has_trailing_dots_or_spaces);
+    let decoder = <dyn IDecoder>::ascii();
+    let options = ConversionOptions {
+        trim_trailing_dots: true,
+        fix_reserved_names: true,
+        ..Default::default()
+    };
+    let trace = zip.explain_file_name(1, &*decoder, &options).unwrap();
+    assert_eq!(trace.stages[2].0, NameStage::TrailingDotsTrimmed);
+    assert_eq!(trace.stages[2].1, "a/con");
+    assert_eq!(
+        trace.final_name(),
+        "a/con_",
+        "trimming can expose device names"
+    );
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert!(!zip.diagnose_file_name_encoding().has_windows_reserved_names);
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    assert!(zip.find_reserved_names(&*sjis_decoder).is_empty());
+    Ok(())
+}
+
+#[test]
+fn path_length_test() -> anyhow::Result<()> {
+    // 100 characters of 3 bytes each in UTF-8 (only 200 bytes in Shift_JIS)
+    let long_stem = "あ".repeat(100);
+    let long_name = format!("dir/{}.txt", long_stem);
+    assert_eq!(find_path_length_reasons("dir/a.txt"), vec![]);
+    assert_eq!(
+        find_path_length_reasons(&long_name),
+        vec![PathLengthReason::ComponentTooLong]
+    );
+    assert_eq!(
+        find_path_length_reasons(&format!("{}a.txt", "dir/".repeat(70))),
+        vec![PathLengthReason::PathTooLong]
+    );
+
+    let truncated = truncate_long_path(&long_name);
+    assert!(truncated.starts_with("dir/あ"));
+    assert!(truncated.ends_with(".txt"), "the extension is kept");
+    assert!(truncated.len() - "dir/".len() <= MAX_COMPONENT_BYTES);
+    assert_eq!(find_path_length_reasons(&truncated), vec![]);
+    assert_eq!(truncate_long_path("dir/a.txt"), "dir/a.txt");
+
+    let long_directory = format!("{}/", "b".repeat(300));
+    assert_eq!(
+        truncate_long_path(&long_directory),
+        format!("{}/", "b".repeat(255))
+    );
+    let long_path = format!("{}/{}.txt", "c".repeat(200), "e".repeat(100));
+    let truncated = truncate_long_path(&long_path);
+    assert_eq!(truncated.chars().count(), MAX_PATH_UTF16_UNITS);
+    assert!(truncated.ends_with("e.txt"));
+
+    let encoder = <dyn IEncoder>::from_encoding_name("sjis").unwrap();
+    let names = vec![
+        encoder.to_bytes_lossless(&long_name).unwrap(),
+        b"a.txt".to_vec(),
+    ];
+    let zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&names)))?;
+    let decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let long_paths =
+        find_long_paths(&zip.plan_conversion_with_options(&*decoder, &Default::default()));
+    assert_eq!(long_paths.len(), 1);
+    assert_eq!(long_paths[0].index, 0);
+    let trace = zip
+        .explain_file_name(
+            0,
+            &*decoder,
+            &ConversionOptions {
+                truncate_long_names: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(trace.stages.last().unwrap().0, NameStage::Truncated);
+    assert_eq!(trace.final_name(), truncate_long_path(&long_name));
+    Ok(())
+}
+
+#[test]
+fn target_os_test() -> anyhow::Result<()> {
+    assert_eq!(
+        TargetOS::Windows.find_forbidden_characters("dir/a:b?.txt:"),
+        vec![':', '?']
+    );
+    assert_eq!(
+        TargetOS::MacOS.find_forbidden_characters("a?b:c"),
+        vec![':']
+    );
+    assert_eq!(TargetOS::Linux.find_forbidden_characters("a?b:c"), vec![]);
+    assert!(TargetOS::Windows.forbids('\u{1}'));
+    assert_eq!(
+        TargetOS::Windows.substitute_forbidden_characters("dir/a<b>.txt", '_'),
+        "dir/a_b_.txt"
+    );
+
+    let plan = vec![RenamePlanEntry {
+        index: 0,
+        raw: b"a:b.txt".to_vec(),
+        explicit_utf8: false,
+        current_name: "a:b.txt".to_string(),
+        proposed_name: "a:b.txt".to_string(),
+        changes: false,
+    }];
+    assert_eq!(find_illegal_names(&plan, TargetOS::Linux), vec![]);
+    let illegal_names = find_illegal_names(&plan, TargetOS::Windows);
+    assert_eq!(illegal_names.len(), 1);
+    assert_eq!(illegal_names[0].characters, vec![':']);
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+    let options = ConversionOptions {
+        substitute_forbidden_characters: Some(TargetOS::Windows),
+        ..Default::default()
+    };
+    let trace = zip.explain_file_name(0, &*sjis_decoder, &options).unwrap();
+    assert_eq!(trace.stages.last().unwrap().0, NameStage::Substituted);
+    assert_eq!(trace.final_name(), "テスト.txt");
+    Ok(())
+}
+
+#[test]
+fn duplicates_test() -> anyhow::Result<()> {
+    let entry = |index: usize, name: &str| {
+        return RenamePlanEntry {
+            index,
+            raw: name.as_bytes().to_vec(),
+            explicit_utf8: false,
+            current_name: name.to_string(),
+            proposed_name: name.to_string(),
+            changes: false,
+        };
+    };
+    let plan = vec![
+        entry(0, "a.txt"),
+        entry(1, "b.txt"),
+        entry(2, "A.txt"),
+        entry(3, "b.txt"),
+        entry(4, "a (2).txt"),
+        entry(5, "c.txt"),
+    ];
+    let duplicates = find_duplicates(&plan);
+    assert_eq!(duplicates.len(), 2);
+    assert_eq!(duplicates[0].kind, DuplicateKind::Identical);
+    assert_eq!(duplicates[0].indices, vec![1, 3]);
+    assert_eq!(duplicates[1].kind, DuplicateKind::CaseInsensitive);
+    assert_eq!(duplicates[1].name, "a.txt");
+    assert_eq!(duplicates[1].indices, vec![0, 2]);
+
+    let deduplication = deduplicate(&plan, &duplicates);
+    assert_eq!(deduplication.skipped, vec![1]);
+    assert_eq!(deduplication.renamed.len(), 1);
+    // `a (2).txt` is already taken
+    assert_eq!(deduplication.renamed[&b"A.txt".to_vec()], "A (3).txt");
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let diagnosis = zip.diagnose_file_name_encoding();
+    assert!(!diagnosis.has_duplicate_names);
+    assert!(!diagnosis.has_case_insensitive_duplicates);
+    Ok(())
+}
+
+#[test]
+fn directory_entry_test() -> anyhow::Result<()> {
+    // MS-DOS host
+    assert!(is_directory(b"dir/", 0, 0x0014, 0x00));
+    assert!(is_directory(b"dir", 0, 0x0014, 0x10));
+    assert!(!is_directory(b"dir", 0, 0x0014, 0x20));
+    // Unix host
+    assert!(is_directory(b"dir", 0, 0x031e, 0o040755 << 16));
+    assert!(!is_directory(b"file", 0, 0x031e, 0o100644 << 16));
+    assert!(!is_directory(b"file", 9, 0x031e, 0o040755 << 16));
+    // The Unix mode is ignored for MS-DOS hosts
+    assert_eq!(unix_mode(0x0014, 0o040755 << 16), None);
+
+    assert_eq!(normalize_trailing_slash("dir", true), "dir/");
+    assert_eq!(normalize_trailing_slash("dir//", true), "dir/");
+    assert_eq!(normalize_trailing_slash("dir\\", true), "dir/");
+    assert_eq!(normalize_trailing_slash("file/", false), "file");
+    assert_eq!(normalize_trailing_slash("file.txt", false), "file.txt");
+
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let names = zip.get_file_names_list(&*sjis_decoder);
+    assert!(names.iter().all(|entry| !entry.is_directory));
+    Ok(())
+}
+
+#[test]
+fn symlink_entry_test() -> anyhow::Result<()> {
+    assert!(is_symlink(0x031e, 0o120777 << 16));
+    assert!(!is_symlink(0x031e, 0o100644 << 16));
+    assert!(!is_symlink(0x0014, 0o120777 << 16));
+
+    // Mark the only entry of before.zip as a symbolic link created in Unix
+    let mut bytes = std::fs::read("tests/assets/before.zip")?;
+    let eocd = bytes.len() - 22;
+    let u32_at = |bytes: &[u8], i: usize| {
+        u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]) as usize
+    };
+    let cd_offset = u32_at(&bytes, eocd + 16);
+    bytes[cd_offset + 5] = 3;
+    bytes[cd_offset + 38..cd_offset + 42].copy_from_slice(&((0o120777u32) << 16).to_le_bytes());
+    // Content of the first local header
+    let payload = |bytes: &[u8]| {
+        let u16_at = |i: usize| u16::from_le_bytes([bytes[i], bytes[i + 1]]) as usize;
+        let size = u32_at(bytes, 18);
+        let start = 30 + u16_at(26) + u16_at(28);
+        return bytes[start..start + size].to_vec();
+    };
+    let original_payload = payload(&bytes);
+
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let names = zip.get_file_names_list(&*sjis_decoder);
+    assert!(names[0].is_symlink);
+    assert!(!names[0].is_directory);
+    let renamed = zip.find_renamed_symlinks(&*sjis_decoder, &ConversionOptions::default());
+    assert_eq!(renamed.len(), 1);
+    assert_eq!(renamed[0].proposed_name, "テスト.txt");
+
+    zip.set_entry_filter(|entry| {
+        assert!(entry.is_symlink());
+        return EntryDecision::Keep;
+    });
+    zip.convert_central_directory_file_names(&*sjis_decoder);
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    assert_eq!(payload(&output), original_payload, "the target is kept");
+    Ok(())
+}
+
+#[test]
+fn lazy_entries_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let mut entries = zip.entries(&*sjis_decoder);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(
+        entries.next().map(|entry| entry.name).as_deref(),
+        Some("テスト.txt")
+    );
+    assert_eq!(entries.len(), 0);
+    assert!(entries.next().is_none());
+    assert_eq!(
+        zip.entry(0, &*sjis_decoder)
+            .map(|entry| entry.name)
+            .as_deref(),
+        Some("テスト.txt")
+    );
+    assert!(zip.entry(1, &*sjis_decoder).is_none());
+    Ok(())
+}
+
+#[test]
+fn preview_names_test() -> anyhow::Result<()> {
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let utf8_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("utf-8").unwrap();
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let previews = zip.preview_names(&[&*sjis_decoder, &*utf8_decoder], 10);
+    assert_eq!(previews.len(), 1);
+    assert_eq!(previews[0].index, 0);
+    assert_eq!(previews[0].decoded[0].name, "テスト.txt");
+    assert!(previews[0].decoded[0].lossless);
+    assert!(!previews[0].decoded[1].lossless);
+    assert!(zip.preview_names(&[&*sjis_decoder], 0).is_empty());
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    assert!(zip.preview_names(&[&*sjis_decoder], 10).is_empty());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "oem-cp")]
+fn rank_decoders_test() -> anyhow::Result<()> {
+    use zifu_core::name_scoring::{rank_decoders, score_name};
+    assert_eq!(score_name("readme.txt"), 0.0);
+    assert!(score_name("テスト.txt") > score_name("ﾃｽﾄ.txt"));
+    assert!(score_name("テスト.txt") > score_name("âeâXâg.txt"));
+    let cp437_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("cp437").unwrap();
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let utf8_decoder = <dyn filename_decoder::IDecoder>::utf8();
+    let decoders = [&*cp437_decoder, &*sjis_decoder, &*utf8_decoder];
+    // "テスト.txt" in Shift_JIS and UTF-8; CP437 decodes both without error
+    let ranking = rank_decoders(&decoders, &[b"\x83e\x83X\x83g.txt"]);
+    assert_eq!(ranking[0].encoding_name, "Shift_JIS");
+    assert_eq!(ranking[0].index, 1);
+    assert!(ranking[0].lossless);
+    assert!(!ranking[2].lossless);
+    let ranking = rank_decoders(&decoders, &["テスト.txt".as_bytes()]);
+    assert_eq!(ranking[0].encoding_name, "UTF-8");
+    // Ties keep the order
+    let ranking = rank_decoders(&decoders, &[b"readme.txt"]);
+    assert_eq!(ranking[0].index, 0);
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let ranking = zip.rank_filename_decoders(&decoders);
+    assert_eq!(ranking[0].encoding_name, "Shift_JIS");
+    Ok(())
+}
+
+#[test]
+fn entry_metadata_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert_eq!(zip.entries_metadata().len(), 1);
+    let metadata = zip.entry_metadata(0).unwrap();
+    assert_eq!(metadata.index, 0);
+    assert_eq!(metadata.file_name_raw, b"\x83e\x83X\x83g.txt".to_vec());
+    assert!(!metadata.explicit_utf8);
+    assert!(!metadata.encrypted);
+    assert!(!metadata.is_directory);
+    assert_eq!(metadata.local_header_offset, 0);
+    assert!(metadata.central_directory_offset > 0);
+    assert_eq!(
+        metadata.modified_local,
+        dos_time_to_seconds(metadata.last_mod_date, metadata.last_mod_time)
+    );
+    assert_eq!(metadata.modified_utc, None);
+    assert!(zip.entry_metadata(1).is_none());
+
+    zip.correct_timestamps(&TimestampCorrection {
+        source_utc_offset: 9 * 3600,
+        target_utc_offset: None,
+        add_extended_timestamps: true,
+    })?;
+    let metadata = zip.entries_metadata().next().unwrap();
+    assert_eq!(
+        metadata.modified_utc,
+        metadata.modified_local.map(|local| local - 9 * 3600)
+    );
+    Ok(())
+}
+
+#[test]
+fn set_comment_test() -> anyhow::Result<()> {
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert!(zip.comment().is_empty());
+    let comment = "zifuで修正済み";
+    zip.set_comment(comment.as_bytes())?;
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut Cursor::new(&output))?;
+    assert!(output.ends_with(comment.as_bytes()));
+    let mut zip = InputZIPArchive::new(Cursor::new(output))?;
+    assert_eq!(zip.comment(), comment.as_bytes());
+
+    let error = zip
+        .set_comment(&vec![b'a'; 65536])
+        .expect_err("the comment is too long");
+    assert_eq!(error.length, 65536);
+    assert_eq!(zip.comment(), comment.as_bytes(), "the comment is kept");
+    zip.set_comment(b"")?;
+    assert!(!zip.has_comments());
+    Ok(())
+}
+
+#[test]
+fn strip_comments_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert!(!zip.has_comments());
+
+    // Append an archive comment to before.zip
+    let mut bytes = std::fs::read("tests/assets/before.zip")?;
+    let eocd = bytes.len() - 22;
+    let comment = b"\x83R\x83\x81\x83\x93\x83g";
+    bytes[eocd + 20..eocd + 22].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(comment);
+
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    assert!(zip.has_comments());
+    zip.strip_comments();
+    assert!(!zip.has_comments());
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut Cursor::new(&output))?;
+    assert_eq!(&output[output.len() - 2..], &[0, 0], "no archive comment");
+    let zip = InputZIPArchive::new(Cursor::new(output))?;
+    assert!(!zip.has_comments());
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "oem-cp")]
+fn list_encodings_test() {
+    let encodings = list_encodings();
+    for encoding in encodings.iter() {
+        for label in std::iter::once(&encoding.name).chain(encoding.labels.iter()) {
+            assert!(
+                <dyn IDecoder>::from_encoding_name(label).is_some(),
+                "{} is accepted",
+                label
+            );
+        }
+    }
+    assert!(encodings.iter().any(|encoding| encoding.name == "CP437"));
+    assert!(
+        !encodings.iter().any(|encoding| encoding.name == "CP866"),
+        "handled by encoding_rs as IBM866"
+    );
+    let japanese = search_encodings("JIS");
+    assert!(japanese.iter().any(|encoding| encoding.name == "Shift_JIS"));
+    assert!(japanese
+        .iter()
+        .all(|encoding| encoding.group == EncodingGroup::Japanese));
+    assert!(search_encodings("cyrillic").len() > 3);
+    assert!(search_encodings("no-such-encoding").is_empty());
+}
+
+#[test]
+fn shift_jis_mapping_test() {
+    // 0x8160 (wave dash) + 0x8740 (circled digit one from the NEC special characters)
+    let bytes = b"\x81\x60\x87\x40";
+    let windows = <dyn IDecoder>::shift_jis(ShiftJISMapping::Windows31J);
+    assert_eq!(
+        windows.to_string_lossless(bytes).as_deref(),
+        Some("\u{FF5E}①")
+    );
+    let cp932 = <dyn IDecoder>::from_encoding_name("cp932").unwrap();
+    assert_eq!(cp932.to_string_lossy(bytes), "\u{FF5E}①");
+
+    let strict = <dyn IDecoder>::from_encoding_name("sjis-strict").unwrap();
+    assert_eq!(strict.encoding_name(), "Shift_JIS-strict");
+    assert_eq!(strict.to_string_lossless(bytes), None);
+    assert_eq!(strict.to_string_lossy(bytes), "\u{301C}\u{FFFD}");
+    assert_eq!(
+        strict.to_string_lossless(b"\x81\x60\x83e").as_deref(),
+        Some("\u{301C}テ")
+    );
+
+    let strict_encoder = <dyn IEncoder>::shift_jis(ShiftJISMapping::JIS);
+    assert_eq!(
+        strict_encoder.to_bytes_lossless("\u{301C}テ"),
+        Some(b"\x81\x60\x83e".to_vec())
+    );
+    assert_eq!(strict_encoder.to_bytes_lossless("\u{FF5E}"), None);
+    assert_eq!(strict_encoder.to_bytes_lossless("①"), None);
+    let windows_encoder = <dyn IEncoder>::from_encoding_name("cp932").unwrap();
+    assert_eq!(
+        windows_encoder.to_bytes_lossless("\u{FF5E}①"),
+        Some(bytes.to_vec())
+    );
+}
+
+#[test]
+#[cfg(feature = "oem-cp")]
+fn native_fallback_chain_test() {
+    let chain = <dyn IDecoder>::native_fallback_chain();
+    let names: Vec<&str> = chain
+        .iter()
+        .map(|decoder| decoder.encoding_name())
+        .collect();
+    assert_eq!(names.last(), Some(&"CP437"), "CP437 is the last resort");
+    assert!(
+        names
+            .iter()
+            .enumerate()
+            .all(|(i, name)| !names[..i].contains(name)),
+        "no duplicates: {:?}",
+        names
+    );
+    assert_eq!(
+        names[0],
+        <dyn IDecoder>::native_oem_encoding().encoding_name()
+    );
+
+    let names_for_host = |host| -> Vec<String> {
+        return <dyn IDecoder>::native_fallback_chain_for_host(host)
+            .iter()
+            .map(|decoder| decoder.encoding_name().to_string())
+            .collect();
+    };
+    assert_eq!(names_for_host(Some(HostSystem::MSDOS)), names);
+    // The ANSI code page comes first for NTFS hosts, but the same code pages are tried
+    let mut ntfs_names = names_for_host(Some(HostSystem::NTFS));
+    assert_eq!(ntfs_names.last().map(String::as_str), Some("CP437"));
+    ntfs_names.sort();
+    let mut sorted_names = names.clone();
+    sorted_names.sort();
+    assert_eq!(ntfs_names, sorted_names);
+
+    assert!(HostSystem::Unix.prefers_utf8());
+    assert!(HostSystem::MacOS.prefers_utf8());
+    assert!(!HostSystem::MSDOS.prefers_utf8());
+    assert!(!HostSystem::NTFS.prefers_utf8());
+}
+
+#[test]
+fn made_by_test() -> anyhow::Result<()> {
+    assert_eq!(HostSystem::Unix.code(), 3);
+    assert_eq!(HostSystem::Other(30).code(), 30);
+
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    zip.convert_central_directory_file_names_with_options(
+        &*sjis_decoder,
+        &ConversionOptions {
+            made_by: Some(HostSystem::Unix),
+            ..Default::default()
+        },
+    );
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+
+    let mut zip = InputZIPArchive::new(Cursor::new(output))?;
+    zip.set_entry_filter(|entry| {
+        assert_eq!(entry.version_made_by >> 8, 3, "host system is rewritten");
+        return EntryDecision::Keep;
+    });
+    zip.output_archive_with_options(&mut Vec::new(), &OutputOptions::default())?;
+    Ok(())
+}
+
+#[test]
+fn unix_permission_normalization_test() -> anyhow::Result<()> {
+    let normalization = PermissionNormalization {
+        file_mode: Some(0o644),
+        directory_mode: Some(0o755),
+        strip_special_bits: true,
+    };
+    assert_eq!(
+        normalize_unix_mode(0o104777, false, &normalization),
+        0o100644
+    );
+    assert_eq!(normalize_unix_mode(0, true, &normalization), 0o040755);
+    assert_eq!(
+        normalize_unix_mode(0o120777, false, &normalization),
+        0o120777
+    );
+    let strip_only = PermissionNormalization {
+        strip_special_bits: true,
+        ..Default::default()
+    };
+    assert_eq!(normalize_unix_mode(0o106755, false, &strip_only), 0o100755);
+
+    // Mark the only entry of before.zip as a setuid file created in Unix
+    let mut bytes = std::fs::read("tests/assets/before.zip")?;
+    let eocd = bytes.len() - 22;
+    let cd_offset = u32::from_le_bytes([
+        bytes[eocd + 16],
+        bytes[eocd + 17],
+        bytes[eocd + 18],
+        bytes[eocd + 19],
+    ]) as usize;
+    bytes[cd_offset + 5] = 3;
+    bytes[cd_offset + 38..cd_offset + 42].copy_from_slice(&((0o104777u32) << 16).to_le_bytes());
+
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes.clone()))?;
+    assert_eq!(zip.normalize_unix_permissions(&normalization), 1);
+    assert_eq!(zip.normalize_unix_permissions(&normalization), 0);
+    zip.set_entry_filter(|entry| {
+        assert_eq!(
+            unix_mode(entry.version_made_by, entry.external_file_attributes),
+            Some(0o100644)
+        );
+        return EntryDecision::Keep;
+    });
+    zip.output_archive_with_options(&mut Vec::new(), &OutputOptions::default())?;
+
+    // Entries created in MS-DOS have no Unix modes
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    assert_eq!(zip.normalize_unix_permissions(&normalization), 0);
+    Ok(())
+}
+
+#[test]
+fn name_mismatches_test() -> anyhow::Result<()> {
+    let zip = |policy: Option<NameMismatchPolicy>| -> anyhow::Result<_> {
+        // Rename `テスト.txt` to `テスト.txx` only in the local file header
+        let mut bytes = std::fs::read("tests/assets/before.zip")?;
+        let name_length = u16::from_le_bytes([bytes[26], bytes[27]]) as usize;
+        bytes[30 + name_length - 1] = b'x';
+        let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+        if let Some(policy) = policy {
+            zip.reconcile_name_mismatches(policy)?;
+        }
+        return Ok(zip);
+    };
+    let sjis_decoder = <dyn filename_decoder::IDecoder>::from_encoding_name("sjis").unwrap();
+
+    let mismatches = zip(None)?.find_name_mismatches()?;
+    assert_eq!(mismatches.len(), 1);
+    assert_eq!(mismatches[0].index, 0);
+    assert!(mismatches[0].central_directory_name_raw.ends_with(b".txt"));
+    assert!(mismatches[0].local_header_name_raw.ends_with(b".txx"));
+
+    let names =
+        zip(Some(NameMismatchPolicy::PreferCentralDirectory))?.get_file_names_list(&*sjis_decoder);
+    assert_eq!(names[0].name, "テスト.txt");
+    let mut preferring_local = zip(Some(NameMismatchPolicy::PreferLocalHeader))?;
+    assert_eq!(
+        preferring_local.get_file_names_list(&*sjis_decoder)[0].name,
+        "テスト.txx"
+    );
+    assert!(preferring_local.find_name_mismatches()?.is_empty());
+
+    let error = zip(Some(NameMismatchPolicy::Error)).err().unwrap();
+    assert_eq!(
+        error
+            .downcast_ref::<NameMismatchError>()
+            .unwrap()
+            .mismatches
+            .len(),
+        1
+    );
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    assert!(zip.find_name_mismatches()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn utf8_flag_only_in_local_header_test() -> anyhow::Result<()> {
+    // Set the UTF-8 flag only in the local file header of `テスト.txt`
+    let mut bytes = std::fs::read("tests/assets/implicit_utf8.zip")?;
+    bytes[7] |= 0x08;
+    // The constructor reads only the central directory
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes.clone()))?;
+    assert!(zip.utf8_flags_only_in_local_headers().is_empty());
+    assert!(
+        !zip.diagnose_file_name_encoding()
+            .has_utf8_flags_only_in_local_headers
+    );
+    assert_eq!(zip.read_local_header_flags(), &[0]);
+    let diagnosis = zip.diagnose_file_name_encoding();
+    assert!(diagnosis.has_utf8_flags_only_in_local_headers);
+    assert!(!diagnosis.has_implicit_non_ascii_names);
+    assert!(!diagnosis.is_universal_archive());
+    let names = zip.get_file_names_list(&*<dyn IDecoder>::utf8());
+    assert_eq!(names[0].name, "テスト.txt");
+    assert!(matches!(
+        names[0].encoding_type,
+        FileNameEncodingType::ExplicitRegularUTF8
+    ));
+
+    zip.convert_central_directory_file_names(&*<dyn IDecoder>::utf8());
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut Cursor::new(&output))?;
+    let fixed = InputZIPArchive::new(Cursor::new(output))?;
+    assert!(fixed.utf8_flags_only_in_local_headers().is_empty());
+    assert!(fixed.diagnose_file_name_encoding().is_universal_archive());
+
+    // The output honors the flag of the name left unconverted without `read_local_header_flags`
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes.clone()))?;
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    assert_eq!(zip.utf8_flags_only_in_local_headers(), &[0]);
+    let mut fixed = InputZIPArchive::new(Cursor::new(output))?;
+    assert!(fixed.diagnose_file_name_encoding().is_universal_archive());
+    assert!(fixed.read_local_header_flags().is_empty());
+
+    // `find_name_mismatches` honors it while reading the headers
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    assert!(zip.find_name_mismatches()?.is_empty());
+    assert_eq!(zip.utf8_flags_only_in_local_headers(), &[0]);
+    Ok(())
+}
+
+#[cfg(feature = "tokio")]
+#[tokio::test]
+async fn async_utf8_flag_only_in_local_header_test() -> anyhow::Result<()> {
+    use zifu_core::AsyncInputZIPArchive;
+
+    let mut bytes = std::fs::read("tests/assets/implicit_utf8.zip")?;
+    bytes[7] |= 0x08;
+    // Same as the synchronous output, which reads the same local headers
+    let mut zip = AsyncInputZIPArchive::new(Cursor::new(bytes.clone())).await?;
+    let mut output = vec![];
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())
+        .await?;
+    assert_eq!(zip.archive().utf8_flags_only_in_local_headers(), &[0]);
+    let mut expected = vec![];
+    InputZIPArchive::new(Cursor::new(bytes))?
+        .output_archive_with_options(&mut expected, &OutputOptions::default())?;
+    assert_eq!(output, expected);
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "oem-cp")]
+fn legacy_with_unicode_path_test() -> anyhow::Result<()> {
+    let cp437 = <dyn IEncoder>::from_encoding_name("cp437").unwrap();
+    assert_eq!(cp437.to_bytes_lossy("aé中.txt"), b"a\x82_.txt");
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    let lossy_entries =
+        zip.convert_central_directory_file_names_to_legacy_with_unicode_path(&*cp437)?;
+    assert_eq!(lossy_entries.len(), 1);
+    assert_eq!(lossy_entries[0].name, "テスト.txt");
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut Cursor::new(&output))?;
+
+    let converted = InputZIPArchive::new(Cursor::new(&output))?;
+    let names = converted.get_file_names_list(&*<dyn IDecoder>::utf8());
+    assert_eq!(names[0].name, "___.txt");
+    assert!(matches!(
+        names[0].encoding_type,
+        FileNameEncodingType::ImplicitASCII
+    ));
+    let local_header = local_headers::iter_local_headers(&mut Cursor::new(&output))
+        .next()
+        .unwrap()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    assert_eq!(
+        read_unicode_path(&local_header.extra_field, &local_header.file_name_raw).as_deref(),
+        Some("テスト.txt")
+    );
+    assert_eq!(
+        read_unicode_path(&local_header.extra_field, b"other.txt"),
+        None
+    );
+
+    let sjis = <dyn IEncoder>::from_encoding_name("sjis").unwrap();
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/after.zip")?)?;
+    assert!(zip
+        .convert_central_directory_file_names_to_legacy_with_unicode_path(&*sjis)?
+        .is_empty());
+    let names = zip.get_file_names_list(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    assert_eq!(names[0].name, "テスト.txt");
+
+    // The Unicode Path record of a 65532-byte name doesn't fit in the 16-bit size
+    let name = "é".repeat(32766);
+    let mut bytes = synthetic_archive(&[name.as_bytes().to_vec()]);
+    let cd_offset = stored_local_entry(name.as_bytes(), b"").len();
+    bytes[cd_offset + 9] |= 0x08;
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let error = zip
+        .convert_central_directory_file_names_to_legacy_with_unicode_path(&*sjis)
+        .unwrap_err();
+    assert_eq!(error.field, "extra field record");
+    assert_eq!(error.length, 5 + name.len());
+    Ok(())
+}
+
+#[test]
+fn timestamp_correction_test() -> anyhow::Result<()> {
+    assert_eq!(parse_utc_offset("+09:00"), Some(9 * 3600));
+    assert_eq!(parse_utc_offset("-0530"), Some(-(5 * 3600 + 30 * 60)));
+    assert_eq!(parse_utc_offset("+9"), Some(9 * 3600));
+    assert_eq!(parse_utc_offset("Z"), Some(0));
+    assert_eq!(parse_utc_offset("09:00"), None);
+    assert_eq!(parse_utc_offset("+09:-1"), None);
+
+    // 2021-10-17 12:34:56
+    let (date, time) = seconds_to_dos_time(1_634_474_096);
+    assert_eq!(dos_time_to_seconds(date, time), Some(1_634_474_096));
+    assert_eq!(
+        format_unix_time_utc(1_634_474_096),
+        "2021-10-17 12:34:56 UTC"
+    );
+    // Odd seconds are rounded down, and out-of-range times are clamped
+    let (date, time) = seconds_to_dos_time(1_634_474_097);
+    assert_eq!(dos_time_to_seconds(date, time), Some(1_634_474_096));
+    assert_eq!(seconds_to_dos_time(0), (0x0021, 0));
+    assert_eq!(format_local_minutes(1_634_474_096), "2021-10-17 12:34");
+    assert_eq!(format_local_minutes(-60), "1969-12-31 23:59");
+    assert_eq!(dos_time_to_seconds(0, 0), None);
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    let original = local_headers::iter_local_headers(&mut Cursor::new(&output))
+        .next()
+        .unwrap()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    let original_time =
+        dos_time_to_seconds(original.last_mod_date, original.last_mod_time).unwrap();
+
+    let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+    zip.correct_timestamps(&TimestampCorrection {
+        source_utc_offset: 9 * 3600,
+        target_utc_offset: Some(0),
+        add_extended_timestamps: true,
+    })?;
+    let mut output = Vec::new();
+    zip.output_archive_with_options(&mut output, &OutputOptions::default())?;
+    verify_archive_structure(&mut Cursor::new(&output))?;
+    let corrected = local_headers::iter_local_headers(&mut Cursor::new(&output))
+        .next()
+        .unwrap()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    assert_eq!(
+        dos_time_to_seconds(corrected.last_mod_date, corrected.last_mod_time),
+        Some(original_time - 9 * 3600)
+    );
+    let mut expected_extra_field = original.extra_field.clone();
+    expected_extra_field.extend(extended_timestamp_extra_field(original_time - 9 * 3600));
+    assert_eq!(corrected.extra_field, expected_extra_field);
+
+    // The extended timestamp doesn't fit in the 16-bit length of the extra field
+    let mut bytes = synthetic_archive(&[b"a.txt".to_vec()]);
+    let cd_offset = stored_local_entry(b"a.txt", b"").len();
+    // 2021-01-01 00:00:00
+    bytes[cd_offset + 14..cd_offset + 16]
+        .copy_from_slice(&((41u16 << 9) | (1 << 5) | 1).to_le_bytes());
+    bytes[cd_offset + 30..cd_offset + 32].copy_from_slice(&65530u16.to_le_bytes());
+    let mut extra_field = vec![0xfe, 0xca];
+    extra_field.extend_from_slice(&65526u16.to_le_bytes());
+    extra_field.resize(65530, 0);
+    let extra_field_position = cd_offset + 46 + b"a.txt".len();
+    bytes.splice(extra_field_position..extra_field_position, extra_field);
+    // Size of the central directory in the EOCD (no comment)
+    let cd_size = (bytes.len() - 22 - cd_offset) as u32;
+    let cd_size_position = bytes.len() - 10;
+    bytes[cd_size_position..cd_size_position + 4].copy_from_slice(&cd_size.to_le_bytes());
+    let mut zip = InputZIPArchive::new(Cursor::new(bytes))?;
+    let error = zip
+        .correct_timestamps(&TimestampCorrection {
+            source_utc_offset: 9 * 3600,
+            target_utc_offset: None,
+            add_extended_timestamps: true,
+        })
+        .unwrap_err();
+    assert_eq!(error.field, "extra field");
+    assert_eq!(error.length, 65530 + 9);
+    Ok(())
+}
+
+#[test]
+fn extract_test() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let dest = dir.path().join("out");
+    let options = ExtractOptions {
+        encoding: Some("sjis".to_string()),
+        ..Default::default()
+    };
+    let report = extract_zip_file("tests/assets/before.zip", &dest, &options)?;
+    assert_eq!(report.extracted.len(), 1);
+    assert_eq!(report.extracted[0].name, "テスト.txt");
+    assert!(report.skipped.is_empty());
+    let content = std::fs::read(dest.join("テスト.txt"))?;
+    assert_eq!(report.extracted[0].size, content.len() as u64);
+
+    // Existing files are kept unless overwriting is allowed
+    std::fs::write(dest.join("テスト.txt"), b"edited")?;
+    let report = extract_zip_file("tests/assets/before.zip", &dest, &options)?;
+    assert!(report.extracted.is_empty());
+    assert_eq!(report.skipped[0].reason, "the file already exists");
+    assert_eq!(std::fs::read(dest.join("テスト.txt"))?, b"edited");
+    let options = ExtractOptions {
+        overwrite: true,
+        ..options
+    };
+    extract_zip_file("tests/assets/before.zip", &dest, &options)?;
+    assert_eq!(std::fs::read(dest.join("テスト.txt"))?, content);
+
+    // Unsafe names stay in the destination, and broken entries (wrong CRC-32 here) are not left
+    let archive = dir.path().join("unsafe.zip");
+    std::fs::write(
+        &archive,
+        synthetic_archive(&[b"dir/".to_vec(), b"../evil.txt".to_vec()]),
+    )?;
+    let report = extract_zip_file(&archive, &dest, &options)?;
+    assert_eq!(report.extracted.len(), 1);
+    assert!(dest.join("dir").is_dir());
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].index, 1);
+    assert_eq!(report.skipped[0].name, sanitize_path("../evil.txt"));
+    assert!(report.skipped[0].reason.starts_with("CRC-32 mismatch"));
+    assert!(!dir.path().join("evil.txt").exists());
+    assert!(!dest.join(&report.skipped[0].name).exists());
+
+    // A broken local header only skips its entry
+    let mut broken = synthetic_archive(&[b"dir2/".to_vec(), b"broken.txt".to_vec()]);
+    broken[stored_local_entry(b"dir2/", b"").len()] = b'X';
+    std::fs::write(&archive, broken)?;
+    let report = extract_zip_file(&archive, &dest, &options)?;
+    assert_eq!(report.extracted.len(), 1);
+    assert!(dest.join("dir2").is_dir());
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].index, 1);
+    assert!(report.skipped[0]
+        .reason
+        .starts_with("local file header can't be read: "));
+    assert!(!dest.join("broken.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn create_test() -> anyhow::Result<()> {
+    let dir = tempdir()?;
+    let source = dir.path().join("source");
+    // Decomposed as in macOS file systems
+    std::fs::create_dir_all(source.join("フ\u{309a}ロジェクト/empty"))?;
+    std::fs::write(
+        source.join("フ\u{309a}ロジェクト/メモ.txt"),
+        "テスト".repeat(100),
+    )?;
+    std::fs::write(source.join("a.txt"), b"")?;
+    let path = source.join("out.zip");
+    let report = create_zip_file(&path, &source, &CreateOptions::default())?;
+    let names = report
+        .entries
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect::<Vec<_>>();
+    // The archive itself is left out
+    assert_eq!(
+        names,
+        vec![
+            "a.txt",
+            "プロジェクト/",
+            "プロジェクト/empty/",
+            "プロジェクト/メモ.txt"
+        ]
+    );
+    assert!(report.skipped.is_empty());
+    assert_eq!(report.entries[3].size, "テスト".len() as u64 * 100);
+    assert!(report.entries[3].compressed_size < report.entries[3].size);
+
+    verify_archive_structure(&mut open_bufreader(path.to_str().unwrap())?)?;
+    let zip = InputZIPArchive::from_path(&path)?;
+    let entries = zip.get_file_names_list(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    assert!(entries.iter().all(|entry| matches!(
+        entry.encoding_type,
+        FileNameEncodingType::ExplicitRegularUTF8
+    )));
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.is_directory)
+            .collect::<Vec<_>>(),
+        vec![false, true, true, false]
+    );
+    for header in local_headers::iter_local_headers(&mut open_bufreader(path.to_str().unwrap())?) {
+        let header = header.map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        assert_eq!(header.general_purpose_flags & 0x0800, 0x0800);
+        assert!(read_unicode_path(&header.extra_field, &header.file_name_raw).is_none());
+    }
+
+    // Round trip
+    let extracted = dir.path().join("extracted");
+    let extract_report = extract_zip_file(&path, &extracted, &ExtractOptions::default())?;
+    assert!(extract_report.skipped.is_empty());
+    assert_eq!(
+        std::fs::read(extracted.join("プロジェクト/メモ.txt"))?,
+        "テスト".repeat(100).as_bytes()
+    );
+    assert!(extracted.join("プロジェクト/empty").is_dir());
+
+    let mut output = Cursor::new(vec![]);
+    let options = CreateOptions {
+        unicode_path_fields: true,
+    };
+    create_archive(&mut output, source.join("フ\u{309a}ロジェクト"), &options)?;
+    output.set_position(0);
+    let header = local_headers::iter_local_headers(&mut output)
+        .nth(1)
+        .unwrap()
+        .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+    assert_eq!(header.file_name_raw, "メモ.txt".as_bytes());
+    assert_eq!(
+        read_unicode_path(&header.extra_field, &header.file_name_raw),
+        Some("メモ.txt".to_string())
+    );
+    Ok(())
+}
+
+#[test]
+fn undecodable_name_entry_test() -> anyhow::Result<()> {
+    let zip = InputZIPArchive::new(Cursor::new(synthetic_archive(&[
+        b"ok.txt".to_vec(),
+        b"\x83e\x83X\x83g.txt".to_vec(),
+        b"\x83e\xff.txt".to_vec(),
+    ])))?;
+    let entries = zip.get_file_names_list(&*<dyn IDecoder>::from_encoding_name("sjis").unwrap());
+    assert_eq!(
+        entries
+            .iter()
+            .map(|entry| entry.lossless)
+            .collect::<Vec<_>>(),
+        vec![true, true, false]
+    );
+    assert_eq!(entries[2].file_name_raw, b"\x83e\xff.txt");
+    assert!(entries[2].name.contains('\u{FFFD}'));
+    assert!(!zip.entry(1, &*<dyn IDecoder>::ascii()).unwrap().lossless);
+    Ok(())
+}
+
+/// Subscriber that records the spans and the messages of the events
+#[derive(Default)]
+struct RecordingSubscriber {
+    spans: std::sync::Mutex<Vec<&'static str>>,
+    messages: std::sync::Mutex<Vec<String>>,
+}
+
+impl tracing::field::Visit for &RecordingSubscriber {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.messages.lock().unwrap().push(format!("{:?}", value));
+        }
+    }
+}
+
+impl tracing::Subscriber for RecordingSubscriber {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        return true;
+    }
+    fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span.metadata().name());
+        return tracing::span::Id::from_u64(spans.len() as u64);
+    }
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+    fn event(&self, event: &tracing::Event<'_>) {
+        let mut visitor = self;
+        event.record(&mut visitor);
+    }
+    fn enter(&self, _: &tracing::span::Id) {}
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[test]
+fn tracing_events_test() -> anyhow::Result<()> {
+    let subscriber = std::sync::Arc::new(RecordingSubscriber::default());
+    tracing::subscriber::with_default(subscriber.clone(), || -> anyhow::Result<()> {
+        let mut zip = InputZIPArchive::new(open_bufreader("tests/assets/before.zip")?)?;
+        let sjis = <dyn IDecoder>::from_encoding_name("sjis").unwrap();
+        let index = zip.get_filename_decoder_index(&[&*<dyn IDecoder>::ascii(), &*sjis]);
+        assert_eq!(index, Some(1));
+        zip.convert_central_directory_file_names(&*sjis);
+        zip.output_archive_with_central_directory_file_names(&mut Cursor::new(vec![]))?;
+        Ok(())
+    })?;
+    assert_eq!(
+        *subscriber.spans.lock().unwrap(),
+        vec!["parse", "detect", "convert"]
+    );
+    let messages = subscriber.messages.lock().unwrap();
+    for message in [
+        "read the central directory",
+        "chose the decoder",
+        "converted the name",
+        "wrote the central directory",
+    ] {
+        assert!(messages.iter().any(|m| m == message), "{}", message);
+    }
+    Ok(())
+}
+
+#[test]
+fn zifu_error_test() -> anyhow::Result<()> {
+    let error = ZifuError::from(
+        InputZIPArchive::new(Cursor::new(b"not a zip archive".to_vec()))
+            .err()
+            .ok_or(anyhow::anyhow!("the bytes are not a ZIP archive"))?,
+    );
+    assert!(matches!(error, ZifuError::Parse(_)), "{:?}", error);
+
+    let error = ZifuError::from(
+        fix_zip_bytes(
+            &std::fs::read("tests/assets/before.zip")?,
+            &FixOptions {
+                encoding: Some("no-such-encoding".to_string()),
+                ..Default::default()
+            },
+        )
+        .expect_err("the encoding name is invalid"),
+    );
+    assert!(
+        matches!(
+            error,
+            ZifuError::Encoding(FixError::InvalidEncodingName { .. })
+        ),
+        "{:?}",
+        error
+    );
+
+    let passed = Instant::now() - Duration::from_secs(1);
+    let error = ZifuError::from(
+        InputZIPArchive::new_with_deadline(open_bufreader("tests/assets/after.zip")?, Some(passed))
+            .err()
+            .ok_or(anyhow::anyhow!("the deadline has passed"))?,
+    );
+    assert!(
+        matches!(error, ZifuError::DeadlineExceeded(_)),
+        "{:?}",
+        error
+    );
+
+    let error = ZifuError::from(anyhow::Error::from(std::io::Error::from(
+        std::io::ErrorKind::NotFound,
+    )));
+    assert!(matches!(error, ZifuError::Io(_)), "{:?}", error);
+    let error = ZifuError::from(zip_structs::zip_error::ZipReadError::IOError(
+        std::io::Error::from(std::io::ErrorKind::UnexpectedEof),
+    ));
+    assert!(matches!(error, ZifuError::Io(_)), "{:?}", error);
+    let error = ZifuError::from(anyhow::anyhow!("something else"));
+    assert!(matches!(error, ZifuError::Other(_)), "{:?}", error);
+    assert_eq!(error.to_string(), "something else");
+    Ok(())
+}
+
+#[test]
+fn entry_read_error_test() -> anyhow::Result<()> {
+    let before = std::fs::read("tests/assets/before.zip")?;
+    let options = FixOptions {
+        encoding: Some("sjis".to_string()),
+        ..Default::default()
+    };
+
+    // The local file header of the only entry is at 0
+    let mut broken = before.clone();
+    broken[0] = 0;
+    let error = fix_zip_bytes(&broken, &options)
+        .err()
+        .ok_or(anyhow::anyhow!("the local file header is broken"))?;
+    let error = error
+        .downcast_ref::<EntryReadError>()
+        .ok_or(anyhow::anyhow!("{:?}", error))?;
+    assert_eq!(error.structure, EntryStructure::LocalFileHeader);
+    assert_eq!(error.index, 0);
+    assert_eq!(error.position, 0);
+    // The name has been converted before the header is read
+    assert_eq!(error.name_raw.as_deref(), Some("テスト.txt".as_bytes()));
+    assert!(error.to_string().starts_with(
+        r#"failed to read the local file header of entry #0 ("テスト.txt") at position 0: "#
+    ));
+
+    // The central directory follows the header (30 bytes), the name (10 bytes), and the data (9 bytes)
+    let mut broken = before.clone();
+    broken[49] = 0;
+    let error = InputZIPArchive::new(Cursor::new(broken))
+        .err()
+        .ok_or(anyhow::anyhow!("the central directory is broken"))?;
+    match ZifuError::from(error) {
+        ZifuError::Entry(error) => {
+            assert_eq!(error.structure, EntryStructure::CentralDirectory);
+            assert_eq!(error.index, 0);
+            assert_eq!(error.position, 49);
+            assert_eq!(error.name_raw, None);
+        }
+        error => panic!("{:?}", error),
+    }
+
+    // The name check skips the broken local header, which fails the operations that need it
+    let mut broken = before;
+    broken[0] = 0;
+    let mut zip = InputZIPArchive::new(Cursor::new(broken))?;
+    assert!(zip.find_name_mismatches()?.is_empty());
+    let error = zip
+        .is_aligned(Some(4))
+        .err()
+        .ok_or(anyhow::anyhow!("the local file header is broken"))?;
+    assert!(error.to_string().starts_with(
+        r#"failed to read the local file header of entry #0 ("\x83e\x83X\x83g.txt") at position 0: "#
+    ));
+    Ok(())
+}
+
+#[test]
+fn malformed_archive_test() -> anyhow::Result<()> {
+    // The comment of the central directory (at 49) runs into the EOCD
+    let mut broken = std::fs::read("tests/assets/before.zip")?;
+    broken[49 + 32] = 10;
+    let mut reader = Cursor::new(broken);
+    let eocd = zip_structs::zip_eocd::ZipEOCD::from_reader(&mut reader)?;
+    assert!(read_central_directory(&mut reader, &eocd).is_err());
+    assert!(verify_archive_structure(&mut reader).is_err());
+
+    // Found by fuzzing: a local header whose ZIP64 compressed size is 2^64 - 1
+    let bytes = std::fs::read("tests/assets/overflowing_zip64_size.zip")?;
+    if let Ok(mut zip) = InputZIPArchive::new(Cursor::new(bytes.clone())) {
+        let _ = zip.find_orphan_local_headers();
+    }
+    let _ = fix_zip_bytes(&bytes, &FixOptions::default());
+    Ok(())
+}
+
+#[test]
+fn oem_cp_feature_test() {
+    let oem_cp = cfg!(feature = "oem-cp");
+    assert_eq!(
+        list_encodings()
+            .iter()
+            .any(|encoding| encoding.name == "CP437"),
+        oem_cp
+    );
+    assert_eq!(
+        <dyn IDecoder>::from_encoding_name("cp437").is_some(),
+        oem_cp
+    );
+    assert_eq!(
+        <dyn IEncoder>::from_encoding_name("cp437").is_some(),
+        oem_cp
+    );
+    // encoding_rs has CP866 by itself
+    assert!(search_encodings("cp866")
+        .iter()
+        .any(|encoding| encoding.group == EncodingGroup::Cyrillic));
+    assert!(<dyn IDecoder>::from_encoding_name("cp866").is_some());
+}
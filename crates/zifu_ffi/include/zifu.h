@@ -0,0 +1,74 @@
+/*
+ * C ABI of zifu_core (ZIP File Names to UTF-8)
+ *
+ * All strings are UTF-8 and NUL-terminated.
+ * Functions returning `int` return ZIFU_OK or ZIFU_ERROR; the message is available from zifu_last_error().
+ */
+#ifndef ZIFU_H
+#define ZIFU_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define ZIFU_ABI_VERSION 1
+
+#define ZIFU_OK 0
+#define ZIFU_ERROR (-1)
+
+/* Opaque handle of an input archive */
+typedef struct ZifuArchive ZifuArchive;
+
+typedef struct ZifuDiagnosis {
+    /* All the file names can be extracted without garbling in all the environments */
+    bool is_universal;
+    /* Has implicitly-encoded (i.e. not UTF-8 flagged) non-ASCII file names */
+    bool has_implicit_non_ascii_names;
+    /* Has explicitly UTF-8 file names not in NFC (e.g. HFS+ NFD) */
+    bool has_non_nfc_explicit_utf8_names;
+} ZifuDiagnosis;
+
+typedef enum ZifuEncodingType {
+    ZIFU_EXPLICIT_REGULAR_UTF8 = 0,
+    ZIFU_EXPLICIT_IRREGULAR_UTF8 = 1,
+    ZIFU_IMPLICIT_ASCII = 2,
+    ZIFU_IMPLICIT_NON_ASCII = 3,
+} ZifuEncodingType;
+
+/* `name` is valid only during the call */
+typedef void (*ZifuNameCallback)(void *user_data, size_t index, const char *name,
+                                 ZifuEncodingType encoding_type);
+
+/* Returns ZIFU_ABI_VERSION of the loaded library */
+uint32_t zifu_abi_version(void);
+
+/* Returns the message of the last error on this thread (NULL if none); owned by the library */
+const char *zifu_last_error(void);
+
+/* Open an archive (NULL on failure); close it with zifu_archive_free() */
+ZifuArchive *zifu_archive_open_path(const char *path);
+/* The data is copied */
+ZifuArchive *zifu_archive_open_buffer(const uint8_t *data, size_t len);
+void zifu_archive_free(ZifuArchive *archive);
+
+int zifu_archive_diagnose(const ZifuArchive *archive, ZifuDiagnosis *out);
+/* 0 on errors (see zifu_last_error()) */
+size_t zifu_archive_entry_count(const ZifuArchive *archive);
+/* `encoding` is an encoding label (e.g. "sjis", "cp437") or NULL for that of the current locale */
+int zifu_archive_list_names(const ZifuArchive *archive, const char *encoding,
+                            ZifuNameCallback callback, void *user_data);
+
+/* Converts the file names into UTF-8 in memory; fails without changes if they can't be decoded */
+int zifu_archive_convert(ZifuArchive *archive, const char *encoding);
+/* Writes the archive; the partially written file is removed on failure */
+int zifu_archive_write_to_path(ZifuArchive *archive, const char *path);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ZIFU_H */
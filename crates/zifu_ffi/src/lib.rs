@@ -0,0 +1,350 @@
+//! C ABI of `zifu_core` (see `include/zifu.h`)
+//!
+//! Functions never unwind into C: errors (and panics) are reported by return values,
+//! and the message is available from `zifu_last_error` on the same thread.
+
+#![allow(clippy::needless_return)]
+
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Seek};
+use std::os::raw::{c_char, c_int, c_void};
+
+use anyhow::anyhow;
+use zifu_core::filename_decoder::IDecoder;
+use zifu_core::{FileNameEncodingType, InputZIPArchive, OutputOptions};
+
+/// Version of the ABI; incremented when the signatures or the layouts change incompatibly
+pub const ZIFU_ABI_VERSION: u32 = 1;
+
+/// Return value of functions: succeeded
+pub const ZIFU_OK: c_int = 0;
+/// Return value of functions: failed (see `zifu_last_error`)
+pub const ZIFU_ERROR: c_int = -1;
+
+trait ReadSeek: Read + Seek {}
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Opaque handle of an input archive
+pub struct ZifuArchive {
+    archive: InputZIPArchive<Box<dyn ReadSeek>>,
+}
+
+/// Result of `zifu_archive_diagnose`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ZifuDiagnosis {
+    /// `true` if all the file names can be extracted without garbling in all the environments
+    pub is_universal: bool,
+    /// `true` if the archive has implicitly-encoded (i.e. not UTF-8 flagged) non-ASCII file names
+    pub has_implicit_non_ascii_names: bool,
+    /// `true` if the archive has explicitly UTF-8 file names not in NFC (e.g. HFS+ NFD)
+    pub has_non_nfc_explicit_utf8_names: bool,
+}
+
+/// Encoding type of a file name passed to `ZifuNameCallback` (same as `FileNameEncodingType`)
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZifuEncodingType {
+    ExplicitRegularUtf8 = 0,
+    ExplicitIrregularUtf8 = 1,
+    ImplicitAscii = 2,
+    ImplicitNonAscii = 3,
+}
+
+impl From<&FileNameEncodingType> for ZifuEncodingType {
+    fn from(encoding_type: &FileNameEncodingType) -> Self {
+        return match encoding_type {
+            FileNameEncodingType::ExplicitRegularUTF8 => ZifuEncodingType::ExplicitRegularUtf8,
+            FileNameEncodingType::ExplicitIrregularUTF8 => ZifuEncodingType::ExplicitIrregularUtf8,
+            FileNameEncodingType::ImplicitASCII => ZifuEncodingType::ImplicitAscii,
+            FileNameEncodingType::ImplicitNonASCII => ZifuEncodingType::ImplicitNonAscii,
+        };
+    }
+}
+
+/// Callback of `zifu_archive_list_names` invoked for each entry (`name` is valid only during the call)
+pub type ZifuNameCallback = extern "C" fn(
+    user_data: *mut c_void,
+    index: usize,
+    name: *const c_char,
+    encoding_type: ZifuEncodingType,
+);
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    // Interior NULs would truncate the message
+    let message = CString::new(message.replace('\0', "\\0")).unwrap_or_default();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, stores its error (or panic) for `zifu_last_error`, and returns `None` on failure
+fn guard<T, F: FnOnce() -> anyhow::Result<T>>(f: F) -> Option<T> {
+    return match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            None
+        }
+        Err(_) => {
+            set_last_error("internal error (panic) in zifu".to_string());
+            None
+        }
+    };
+}
+
+/// Converts a C string into `&str` (`None` for `NULL`)
+unsafe fn str_from_ptr<'a>(ptr: *const c_char, name: &str) -> anyhow::Result<Option<&'a str>> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    return CStr::from_ptr(ptr)
+        .to_str()
+        .map(Some)
+        .map_err(|_| anyhow!("{} is not valid UTF-8", name));
+}
+
+/// Returns the decoder for the encoding label (`None` means that of the current locale)
+fn legacy_decoder(encoding: Option<&str>) -> anyhow::Result<Box<dyn IDecoder>> {
+    return match encoding {
+        Some(name) => <dyn IDecoder>::from_encoding_name(name)
+            .ok_or_else(|| anyhow!("unknown encoding name: {}", name)),
+        None => Ok(<dyn IDecoder>::native_oem_encoding()),
+    };
+}
+
+fn open(reader: Box<dyn ReadSeek>) -> *mut ZifuArchive {
+    return guard(|| {
+        let archive = InputZIPArchive::new(reader)?;
+        return Ok(Box::into_raw(Box::new(ZifuArchive { archive })));
+    })
+    .unwrap_or(std::ptr::null_mut());
+}
+
+/// Returns `ZIFU_ABI_VERSION` of the loaded library
+#[no_mangle]
+pub extern "C" fn zifu_abi_version() -> u32 {
+    return ZIFU_ABI_VERSION;
+}
+
+/// Returns the message of the last error on this thread (`NULL` if none)
+///
+/// The string is owned by the library and valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn zifu_last_error() -> *const c_char {
+    return LAST_ERROR.with(|last_error| {
+        last_error
+            .borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    });
+}
+
+/// Opens the archive at `path` and returns its handle (`NULL` on failure)
+///
+/// # Safety
+///
+/// `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_open_path(path: *const c_char) -> *mut ZifuArchive {
+    let file = guard(|| {
+        let path = str_from_ptr(path, "path")?.ok_or_else(|| anyhow!("path is NULL"))?;
+        return Ok(File::open(path)?);
+    });
+    return match file {
+        Some(file) => open(Box::new(BufReader::new(file))),
+        None => std::ptr::null_mut(),
+    };
+}
+
+/// Opens the archive in memory and returns its handle (`NULL` on failure)
+///
+/// The data is copied, so the buffer can be freed after the call.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_open_buffer(data: *const u8, len: usize) -> *mut ZifuArchive {
+    if data.is_null() {
+        set_last_error("data is NULL".to_string());
+        return std::ptr::null_mut();
+    }
+    let data = std::slice::from_raw_parts(data, len).to_vec();
+    return open(Box::new(Cursor::new(data)));
+}
+
+/// Closes the archive (`NULL` is ignored)
+///
+/// # Safety
+///
+/// `archive` must be returned by `zifu_archive_open_*` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_free(archive: *mut ZifuArchive) {
+    if !archive.is_null() {
+        drop(Box::from_raw(archive));
+    }
+}
+
+/// Diagnoses the encodings of the file names into `out`
+///
+/// # Safety
+///
+/// `archive` must be a valid handle and `out` must point to a writable `ZifuDiagnosis`.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_diagnose(
+    archive: *const ZifuArchive,
+    out: *mut ZifuDiagnosis,
+) -> c_int {
+    if archive.is_null() || out.is_null() {
+        set_last_error("archive or out is NULL".to_string());
+        return ZIFU_ERROR;
+    }
+    let archive = &(*archive).archive;
+    let diagnosis = match guard(|| Ok(archive.diagnose_file_name_encoding())) {
+        Some(diagnosis) => diagnosis,
+        None => return ZIFU_ERROR,
+    };
+    *out = ZifuDiagnosis {
+        is_universal: diagnosis.is_universal_archive(),
+        has_implicit_non_ascii_names: diagnosis.has_implicit_non_ascii_names,
+        has_non_nfc_explicit_utf8_names: diagnosis.has_non_nfc_explicit_utf8_names,
+    };
+    return ZIFU_OK;
+}
+
+/// Returns the number of the entries (0 on errors)
+///
+/// # Safety
+///
+/// `archive` must be a valid handle.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_entry_count(archive: *const ZifuArchive) -> usize {
+    if archive.is_null() {
+        return 0;
+    }
+    let archive = &(*archive).archive;
+    return guard(|| Ok(archive.get_file_names_list(&*<dyn IDecoder>::utf8()).len())).unwrap_or(0);
+}
+
+/// Calls `callback` for each entry with its file name decoded in `encoding`
+///
+/// # Safety
+///
+/// `archive` must be a valid handle and `encoding` must be a NUL-terminated string or `NULL` (the encoding of the current locale).
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_list_names(
+    archive: *const ZifuArchive,
+    encoding: *const c_char,
+    callback: ZifuNameCallback,
+    user_data: *mut c_void,
+) -> c_int {
+    if archive.is_null() {
+        set_last_error("archive is NULL".to_string());
+        return ZIFU_ERROR;
+    }
+    let archive = &(*archive).archive;
+    let names = guard(|| {
+        let decoder = legacy_decoder(str_from_ptr(encoding, "encoding")?)?;
+        return Ok(archive.get_file_names_list(&*decoder));
+    });
+    let names = match names {
+        Some(names) => names,
+        None => return ZIFU_ERROR,
+    };
+    for (index, entry) in names.iter().enumerate() {
+        let name = CString::new(entry.name.replace('\0', "\\0")).unwrap_or_default();
+        callback(
+            user_data,
+            index,
+            name.as_ptr(),
+            (&entry.encoding_type).into(),
+        );
+    }
+    return ZIFU_OK;
+}
+
+/// Converts the file names into UTF-8 (explicitly flagged) in memory
+///
+/// Implicitly-encoded names are decoded in `encoding` (`NULL` means that of the current locale
+/// falling back to its ANSI code page and CP437).
+/// Fails without changing anything if some names can't be decoded in it or UTF-8.
+///
+/// # Safety
+///
+/// `archive` must be a valid handle and `encoding` must be a NUL-terminated string or `NULL`.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_convert(
+    archive: *mut ZifuArchive,
+    encoding: *const c_char,
+) -> c_int {
+    if archive.is_null() {
+        set_last_error("archive is NULL".to_string());
+        return ZIFU_ERROR;
+    }
+    let archive = &mut (*archive).archive;
+    let result = guard(|| {
+        archive.read_local_header_flags();
+        let legacy_decoders = match str_from_ptr(encoding, "encoding")? {
+            Some(name) => vec![legacy_decoder(Some(name))?],
+            None => <dyn IDecoder>::native_fallback_chain(),
+        };
+        let legacy_decoder = &legacy_decoders[0];
+        let ascii_decoder = <dyn IDecoder>::ascii();
+        let utf8_decoder = <dyn IDecoder>::utf8();
+        // Same order as the CLI without `-u`
+        let mut decoders_list = vec![&*ascii_decoder];
+        decoders_list.extend(legacy_decoders.iter().map(|decoder| &**decoder));
+        decoders_list.push(&*utf8_decoder);
+        let index = archive
+            .get_filename_decoder_index(&decoders_list)
+            .ok_or_else(|| {
+                anyhow!(
+                    "file names & comments are not encoded in UTF-8 or {}",
+                    legacy_decoder.encoding_name()
+                )
+            })?;
+        archive.convert_central_directory_file_names(decoders_list[index]);
+        return Ok(());
+    });
+    return result.map_or(ZIFU_ERROR, |_| ZIFU_OK);
+}
+
+/// Writes the archive (with the converted names) to `path`
+///
+/// The partially written file is removed on failure.
+///
+/// # Safety
+///
+/// `archive` must be a valid handle and `path` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn zifu_archive_write_to_path(
+    archive: *mut ZifuArchive,
+    path: *const c_char,
+) -> c_int {
+    if archive.is_null() {
+        set_last_error("archive is NULL".to_string());
+        return ZIFU_ERROR;
+    }
+    let archive = &mut (*archive).archive;
+    let result = guard(|| {
+        let path = str_from_ptr(path, "path")?.ok_or_else(|| anyhow!("path is NULL"))?;
+        let mut output = BufWriter::new(File::create(path)?);
+        let result = archive
+            .output_archive_with_options(&mut output, &OutputOptions::default())
+            .and_then(|_| {
+                output.into_inner().map_err(|e| e.into_error())?;
+                return Ok(());
+            });
+        if result.is_err() {
+            // Never leave the partially written archive
+            let _ = std::fs::remove_file(path);
+        }
+        return result;
+    });
+    return result.map_or(ZIFU_ERROR, |_| ZIFU_OK);
+}
@@ -0,0 +1,111 @@
+#![allow(clippy::needless_return)]
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+
+use tempfile::tempdir;
+use zifu_ffi::*;
+
+extern "C" fn collect_name(
+    user_data: *mut c_void,
+    index: usize,
+    name: *const c_char,
+    encoding_type: ZifuEncodingType,
+) {
+    let names = unsafe { &mut *(user_data as *mut Vec<(usize, String, ZifuEncodingType)>) };
+    let name = unsafe { CStr::from_ptr(name) }
+        .to_str()
+        .unwrap()
+        .to_string();
+    names.push((index, name, encoding_type));
+}
+
+fn list_names(
+    archive: *const ZifuArchive,
+    encoding: &str,
+) -> Vec<(usize, String, ZifuEncodingType)> {
+    let encoding = CString::new(encoding).unwrap();
+    let mut names = Vec::<(usize, String, ZifuEncodingType)>::new();
+    let result = unsafe {
+        zifu_archive_list_names(
+            archive,
+            encoding.as_ptr(),
+            collect_name,
+            &mut names as *mut _ as *mut c_void,
+        )
+    };
+    assert_eq!(result, ZIFU_OK);
+    return names;
+}
+
+fn last_error() -> String {
+    return unsafe { CStr::from_ptr(zifu_last_error()) }
+        .to_string_lossy()
+        .into_owned();
+}
+
+#[test]
+fn convert_via_ffi_test() -> anyhow::Result<()> {
+    assert_eq!(zifu_abi_version(), ZIFU_ABI_VERSION);
+    let path = CString::new("../zifu_core/tests/assets/before.zip")?;
+    let archive = unsafe { zifu_archive_open_path(path.as_ptr()) };
+    assert!(!archive.is_null(), "{}", last_error());
+
+    let mut diagnosis = ZifuDiagnosis::default();
+    assert_eq!(
+        unsafe { zifu_archive_diagnose(archive, &mut diagnosis) },
+        ZIFU_OK
+    );
+    assert!(!diagnosis.is_universal);
+    assert!(diagnosis.has_implicit_non_ascii_names);
+    assert_eq!(unsafe { zifu_archive_entry_count(archive) }, 1);
+    assert_eq!(
+        list_names(archive, "sjis"),
+        vec![(
+            0,
+            "テスト.txt".to_string(),
+            ZifuEncodingType::ImplicitNonAscii
+        )]
+    );
+
+    let unknown = CString::new("no-such-encoding")?;
+    assert_eq!(
+        unsafe { zifu_archive_convert(archive, unknown.as_ptr()) },
+        ZIFU_ERROR
+    );
+    assert!(last_error().contains("no-such-encoding"));
+    let sjis = CString::new("sjis")?;
+    assert_eq!(
+        unsafe { zifu_archive_convert(archive, sjis.as_ptr()) },
+        ZIFU_OK
+    );
+
+    let dir = tempdir()?;
+    let output_path = dir.path().join("after.zip");
+    let output = CString::new(output_path.to_str().unwrap())?;
+    assert_eq!(
+        unsafe { zifu_archive_write_to_path(archive, output.as_ptr()) },
+        ZIFU_OK
+    );
+    unsafe { zifu_archive_free(archive) };
+    assert_eq!(
+        std::fs::read(&output_path)?,
+        std::fs::read("../zifu_core/tests/assets/after.zip")?
+    );
+
+    // Open the result from memory
+    let data = std::fs::read(&output_path)?;
+    let archive = unsafe { zifu_archive_open_buffer(data.as_ptr(), data.len()) };
+    assert!(!archive.is_null(), "{}", last_error());
+    assert_eq!(
+        unsafe { zifu_archive_diagnose(archive, &mut diagnosis) },
+        ZIFU_OK
+    );
+    assert!(diagnosis.is_universal);
+    unsafe { zifu_archive_free(archive) };
+
+    let garbage = b"not a zip archive";
+    let archive = unsafe { zifu_archive_open_buffer(garbage.as_ptr(), garbage.len()) };
+    assert!(archive.is_null());
+    assert!(!last_error().is_empty());
+    Ok(())
+}